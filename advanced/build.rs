@@ -18,10 +18,7 @@ fn main() {
         .arg("rev-parse")
         .arg("HEAD")
         .output()
-        .and_then(|p| {
-            let out = String::from_utf8(p.stdout).unwrap_or("Invalid utf8 string".to_string());
-            Ok(out)
-        })
+        .map(|p| String::from_utf8(p.stdout).unwrap_or_else(|_| "Invalid utf8 string".to_string()))
         .unwrap();
     //get compile datetime
     let compile_time = Utc::now();
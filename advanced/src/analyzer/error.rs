@@ -0,0 +1,79 @@
+#![allow(dead_code)]
+use crate::router::RouterError;
+
+pub type AnalyzerResult<T> = std::result::Result<T, AnalyzerError>;
+
+#[derive(Debug, Clone)]
+pub enum AnalyzerError {
+    ParseErr(String),
+    EmptyStatement,
+    MultiStatementNotSupported,
+    NoDatabaseSelected,
+    ShardKeyNotFound(String),
+    ScatterNotSupported(String),
+    TablesNotCoLocated(String, String),
+    NotJoinedOnShardKey(String), //a sharded table which is not joined on its shard key.
+    CrossTenant(String),         //the tenant of a db the statement names, not that of the user.
+    InformationSchemaNotSupported, //the proxy answers information_schema from its catalog only.
+    Router(RouterError),
+}
+
+impl From<RouterError> for AnalyzerError {
+    fn from(e: RouterError) -> Self {
+        AnalyzerError::Router(e)
+    }
+}
+
+impl std::fmt::Display for AnalyzerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AnalyzerError::ParseErr(s) => write!(f, "AnalyzerError::ParseErr: {}", s),
+            AnalyzerError::EmptyStatement => write!(f, "AnalyzerError::EmptyStatement"),
+            AnalyzerError::MultiStatementNotSupported => {
+                write!(f, "AnalyzerError::MultiStatementNotSupported")
+            }
+            AnalyzerError::NoDatabaseSelected => write!(f, "AnalyzerError::NoDatabaseSelected"),
+            AnalyzerError::ShardKeyNotFound(t) => {
+                write!(f, "AnalyzerError::ShardKeyNotFound: table {}", t)
+            }
+            AnalyzerError::ScatterNotSupported(s) => {
+                write!(f, "AnalyzerError::ScatterNotSupported: {}", s)
+            }
+            AnalyzerError::TablesNotCoLocated(a, b) => {
+                write!(f, "AnalyzerError::TablesNotCoLocated: {} and {}", a, b)
+            }
+            AnalyzerError::NotJoinedOnShardKey(t) => {
+                write!(f, "AnalyzerError::NotJoinedOnShardKey: table {}", t)
+            }
+            AnalyzerError::CrossTenant(t) => {
+                write!(
+                    f,
+                    "AnalyzerError::CrossTenant: access denied to tenant {:?}",
+                    t
+                )
+            }
+            AnalyzerError::InformationSchemaNotSupported => {
+                write!(f, "AnalyzerError::InformationSchemaNotSupported")
+            }
+            AnalyzerError::Router(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AnalyzerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AnalyzerError::ParseErr(..) => None,
+            AnalyzerError::EmptyStatement => None,
+            AnalyzerError::MultiStatementNotSupported => None,
+            AnalyzerError::NoDatabaseSelected => None,
+            AnalyzerError::ShardKeyNotFound(..) => None,
+            AnalyzerError::ScatterNotSupported(..) => None,
+            AnalyzerError::TablesNotCoLocated(..) => None,
+            AnalyzerError::NotJoinedOnShardKey(..) => None,
+            AnalyzerError::CrossTenant(..) => None,
+            AnalyzerError::InformationSchemaNotSupported => None,
+            AnalyzerError::Router(e) => e.source(),
+        }
+    }
+}
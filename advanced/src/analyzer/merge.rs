@@ -0,0 +1,366 @@
+#![allow(dead_code)]
+use super::error::{AnalyzerError, AnalyzerResult};
+use super::sql;
+use sqlparser::ast::{
+    Expr, Function, FunctionArg, FunctionArgExpr, Ident, ObjectName, SelectItem, SetExpr,
+    Statement, Value,
+};
+
+//a column of the merged result set, hidden columns are appended to the select list
+//only for merging and stripped before the result is sent to the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnRef {
+    Visible(usize),
+    Hidden(usize),
+}
+
+impl ColumnRef {
+    //position in the rows returned by the shards, which have `column_count` columns.
+    #[inline]
+    pub fn index(&self, column_count: usize, hidden_count: usize) -> usize {
+        match self {
+            ColumnRef::Visible(i) => *i,
+            ColumnRef::Hidden(i) => column_count - hidden_count + i,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateKind {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg, //sent to the shards as SUM and a hidden COUNT.
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Aggregate {
+    pub kind: AggregateKind,
+    pub column: ColumnRef,
+    pub count_column: Option<ColumnRef>, //the hidden COUNT of an AVG.
+}
+
+//how the result sets of one select scattered on every physical table are merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeSpec {
+    pub order_by: Vec<(ColumnRef, bool)>, //(column, asc)
+    pub group_by: Vec<ColumnRef>,
+    pub aggregates: Vec<Aggregate>,
+    pub limit: Option<u64>,
+    pub offset: u64,
+    pub hidden_count: usize,
+}
+
+impl MergeSpec {
+    #[inline]
+    pub fn is_aggregate(&self) -> bool {
+        !self.aggregates.is_empty() || !self.group_by.is_empty()
+    }
+}
+
+//rewrite a select for the shards and tell how to merge what they return:
+//AVG is split into SUM and COUNT, LIMIT m OFFSET n becomes LIMIT m + n, and columns needed
+//by ORDER BY or GROUP BY but not selected are appended.
+pub fn rewrite_for_scatter(stmt: &mut Statement) -> AnalyzerResult<MergeSpec> {
+    let query = match stmt {
+        Statement::Query(q) => q,
+        _ => return Err(not_supported("only SELECT can be sent to all shards")),
+    };
+    if query.fetch.is_some() {
+        return Err(not_supported("FETCH"));
+    }
+    let select = match &mut *query.body {
+        SetExpr::Select(s) => s,
+        _ => return Err(not_supported("UNION")),
+    };
+    if select.distinct {
+        return Err(not_supported("DISTINCT"));
+    }
+    if select.having.is_some() {
+        return Err(not_supported("HAVING"));
+    }
+    let mut spec = MergeSpec::default();
+    let mut hidden: Vec<Expr> = Vec::new();
+
+    //the select list as the client wrote it, None for the wildcards.
+    let originals: Vec<Option<(Expr, Option<Ident>)>> = select
+        .projection
+        .iter()
+        .map(|item| match item {
+            SelectItem::UnnamedExpr(e) => Some((e.clone(), None)),
+            SelectItem::ExprWithAlias { expr, alias } => Some((expr.clone(), Some(alias.clone()))),
+            _ => None,
+        })
+        .collect();
+    let has_wildcard = originals.iter().any(|o| o.is_none());
+
+    //aggregates of the select list.
+    for (i, item) in select.projection.iter_mut().enumerate() {
+        let (expr, alias) = match item {
+            SelectItem::UnnamedExpr(e) => (e, None),
+            SelectItem::ExprWithAlias { expr, alias } => (expr, Some(alias)),
+            _ => continue,
+        };
+        let kind = match aggregate_kind(expr)? {
+            Some(kind) => kind,
+            None => {
+                if contains_aggregate(expr) {
+                    return Err(not_supported("expression over aggregate functions"));
+                }
+                continue;
+            }
+        };
+        let mut count_column = None;
+        if kind == AggregateKind::Avg {
+            let mut count = expr.clone();
+            //keep the name the client sees.
+            let avg_name = expr.to_string();
+            if let Expr::Function(f) = expr {
+                f.name = ObjectName(vec![Ident::new("SUM")]);
+            }
+            if let Expr::Function(f) = &mut count {
+                f.name = ObjectName(vec![Ident::new("COUNT")]);
+            }
+            count_column = Some(ColumnRef::Hidden(hidden.len()));
+            hidden.push(count);
+            if alias.is_none() {
+                *item = SelectItem::ExprWithAlias {
+                    expr: expr.clone(),
+                    alias: Ident::with_quote('`', avg_name),
+                };
+            }
+        }
+        spec.aggregates.push(Aggregate {
+            kind,
+            column: ColumnRef::Visible(i),
+            count_column,
+        });
+    }
+    if (!spec.aggregates.is_empty() || !select.group_by.is_empty()) && has_wildcard {
+        return Err(not_supported("wildcard with aggregate functions"));
+    }
+
+    for e in select.group_by.iter() {
+        let column = match find_in_projection(&originals, e) {
+            Some(i) => ColumnRef::Visible(i),
+            None => add_hidden(&mut hidden, &mut spec, e)?,
+        };
+        spec.group_by.push(column);
+    }
+    for o in query.order_by.iter() {
+        let column =
+            match order_position(&o.expr).or_else(|| find_in_projection(&originals, &o.expr)) {
+                Some(i) => ColumnRef::Visible(i),
+                None => {
+                    let e = aliased_expr(&originals, &o.expr).unwrap_or(&o.expr);
+                    add_hidden(&mut hidden, &mut spec, e)?
+                }
+            };
+        spec.order_by.push((column, o.asc.unwrap_or(true)));
+    }
+
+    spec.hidden_count = hidden.len();
+    select
+        .projection
+        .extend(hidden.into_iter().map(SelectItem::UnnamedExpr));
+
+    //LIMIT and OFFSET
+    spec.limit = match &query.limit {
+        Some(e) => Some(literal_u64(e)?),
+        None => None,
+    };
+    spec.offset = match &query.offset {
+        Some(o) => literal_u64(&o.value)?,
+        None => 0,
+    };
+    query.offset = None;
+    if spec.is_aggregate() {
+        //every group of every shard is needed.
+        query.limit = None;
+    } else {
+        //no shard limit when it would overflow, every row of every shard is then needed.
+        query.limit = spec
+            .limit
+            .and_then(|limit| limit.checked_add(spec.offset))
+            .map(|limit| Expr::Value(Value::Number(limit.to_string(), false)));
+    }
+    Ok(spec)
+}
+
+fn not_supported(what: &str) -> AnalyzerError {
+    AnalyzerError::ScatterNotSupported(what.to_string())
+}
+
+fn aggregate_kind(expr: &Expr) -> AnalyzerResult<Option<AggregateKind>> {
+    let f = match expr {
+        Expr::Function(f) if f.over.is_none() => f,
+        _ => return Ok(None),
+    };
+    let kind = match function_name(f).as_str() {
+        "COUNT" => AggregateKind::Count,
+        "SUM" => AggregateKind::Sum,
+        "MIN" => AggregateKind::Min,
+        "MAX" => AggregateKind::Max,
+        "AVG" => AggregateKind::Avg,
+        _ => return Ok(None),
+    };
+    if f.distinct && !matches!(kind, AggregateKind::Min | AggregateKind::Max) {
+        return Err(not_supported("DISTINCT in aggregate functions"));
+    }
+    Ok(Some(kind))
+}
+
+fn function_name(f: &Function) -> String {
+    f.name
+        .0
+        .last()
+        .map(|i| i.value.to_uppercase())
+        .unwrap_or_default()
+}
+
+fn contains_aggregate(expr: &Expr) -> bool {
+    match expr {
+        Expr::Function(f) => {
+            matches!(
+                function_name(f).as_str(),
+                "COUNT" | "SUM" | "MIN" | "MAX" | "AVG"
+            ) || f.args.iter().any(|a| match a {
+                FunctionArg::Named {
+                    arg: FunctionArgExpr::Expr(e),
+                    ..
+                }
+                | FunctionArg::Unnamed(FunctionArgExpr::Expr(e)) => contains_aggregate(e),
+                _ => false,
+            })
+        }
+        Expr::BinaryOp { left, right, .. } => contains_aggregate(left) || contains_aggregate(right),
+        Expr::UnaryOp { expr, .. } | Expr::Nested(expr) | Expr::Cast { expr, .. } => {
+            contains_aggregate(expr)
+        }
+        _ => false,
+    }
+}
+
+//the position of the select list an expr of ORDER BY or GROUP BY refers to, by alias or by itself.
+//columns after a wildcard have no position known before the result arrives.
+fn find_in_projection(originals: &[Option<(Expr, Option<Ident>)>], expr: &Expr) -> Option<usize> {
+    let text = expr.to_string();
+    for (i, o) in originals.iter().enumerate() {
+        let (e, alias) = o.as_ref()?;
+        if let (Some(alias), Expr::Identifier(ident)) = (alias, expr) {
+            if alias.value.eq_ignore_ascii_case(&ident.value) {
+                return Some(i);
+            }
+        }
+        if e.to_string().eq_ignore_ascii_case(&text) {
+            return Some(i);
+        }
+        if let Expr::Identifier(ident) = expr {
+            if alias.is_none() && sql::is_column(e, &ident.value) {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+//ORDER BY an alias which can not be found in the select list by position.
+fn aliased_expr<'e>(
+    originals: &'e [Option<(Expr, Option<Ident>)>],
+    expr: &Expr,
+) -> Option<&'e Expr> {
+    let ident = match expr {
+        Expr::Identifier(ident) => ident,
+        _ => return None,
+    };
+    originals
+        .iter()
+        .flatten()
+        .find_map(|(e, alias)| match alias {
+            Some(a) if a.value.eq_ignore_ascii_case(&ident.value) => Some(e),
+            _ => None,
+        })
+}
+
+//ORDER BY 2 refers to the second column.
+fn order_position(expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Value(Value::Number(n, _)) => {
+            n.parse::<usize>().ok().filter(|&n| n > 0).map(|n| n - 1)
+        }
+        _ => None,
+    }
+}
+
+fn add_hidden(
+    hidden: &mut Vec<Expr>,
+    spec: &mut MergeSpec,
+    expr: &Expr,
+) -> AnalyzerResult<ColumnRef> {
+    let column = ColumnRef::Hidden(hidden.len());
+    match aggregate_kind(expr)? {
+        Some(AggregateKind::Avg) => return Err(not_supported("AVG which is not selected")),
+        Some(kind) => spec.aggregates.push(Aggregate {
+            kind,
+            column,
+            count_column: None,
+        }),
+        None if contains_aggregate(expr) => {
+            return Err(not_supported("expression over aggregate functions"))
+        }
+        None => {}
+    }
+    hidden.push(expr.clone());
+    Ok(column)
+}
+
+fn literal_u64(expr: &Expr) -> AnalyzerResult<u64> {
+    sql::literal_of_expr(expr)
+        .and_then(|v| v.parse::<u64>().ok())
+        .ok_or_else(|| not_supported("LIMIT or OFFSET which is not an integer literal"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_select_for_shards() {
+        let mut stmt = sql::parse_one(
+            "SELECT name, AVG(score), COUNT(*) AS c FROM student GROUP BY name ORDER BY c DESC LIMIT 10, 5",
+        )
+        .unwrap();
+        let spec = rewrite_for_scatter(&mut stmt).unwrap();
+        assert_eq!(
+            stmt.to_string(),
+            "SELECT name, SUM(score) AS `AVG(score)`, COUNT(*) AS c, COUNT(score) FROM student GROUP BY name ORDER BY c DESC"
+        );
+        assert_eq!(spec.group_by, vec![ColumnRef::Visible(0)]);
+        assert_eq!(spec.order_by, vec![(ColumnRef::Visible(2), false)]);
+        assert_eq!(spec.aggregates[0].kind, AggregateKind::Avg);
+        assert_eq!(spec.aggregates[0].count_column, Some(ColumnRef::Hidden(0)));
+        assert_eq!(
+            (spec.limit, spec.offset, spec.hidden_count),
+            (Some(5), 10, 1)
+        );
+
+        let mut stmt =
+            sql::parse_one("SELECT * FROM student ORDER BY age LIMIT 3 OFFSET 2").unwrap();
+        let spec = rewrite_for_scatter(&mut stmt).unwrap();
+        assert_eq!(
+            stmt.to_string(),
+            "SELECT *, age FROM student ORDER BY age LIMIT 5"
+        );
+        assert_eq!(spec.order_by, vec![(ColumnRef::Hidden(0), true)]);
+
+        let mut stmt =
+            sql::parse_one("SELECT * FROM student LIMIT 18446744073709551615 OFFSET 1").unwrap();
+        let spec = rewrite_for_scatter(&mut stmt).unwrap();
+        assert_eq!(stmt.to_string(), "SELECT * FROM student");
+        assert_eq!((spec.limit, spec.offset), (Some(u64::MAX), 1));
+
+        let mut stmt = sql::parse_one("SELECT DISTINCT name FROM student").unwrap();
+        assert!(rewrite_for_scatter(&mut stmt).is_err());
+    }
+}
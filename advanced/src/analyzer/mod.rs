@@ -1,2 +1,5 @@
-mod plan;
-mod sql;
+pub mod error;
+pub mod merge;
+pub mod plan;
+pub mod rewrite;
+pub mod sql;
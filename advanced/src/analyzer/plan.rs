@@ -1,8 +1,658 @@
 #![allow(dead_code)]
+use super::error::{AnalyzerError, AnalyzerResult};
+use super::merge::{self, MergeSpec};
+use super::rewrite;
+use super::sql::{self, ShardCondition};
+use crate::router::{Router, RouterError, TableSectionEntry};
+use sqlparser::ast::Statement;
 use std::collections::HashMap;
 
+#[derive(Debug)]
 pub struct Plan {
-    db: String,
-    force_master: bool,
-    cluster_id_vs_sql: HashMap<String, String>, //sql :  generated for each cluster.
+    pub db: String,
+    pub force_master: bool,
+    pub shards: Vec<(String, String)>, //(cluster_id, sql) generated for each physical table.
+    pub merge: Option<MergeSpec>,      //a select on more than one shard.
+}
+
+impl Plan {
+    fn single(db: &str, force_master: bool, cluster_id: String, sql: String) -> Plan {
+        Plan {
+            db: db.to_string(),
+            force_master,
+            shards: vec![(cluster_id, sql)],
+            merge: None,
+        }
+    }
+}
+
+//build the execution plan of one COM_QUERY for the proxy user whose current db is `db`.
+pub fn build_plan(router: &Router, user: &str, db: &str, query: &str) -> AnalyzerResult<Plan> {
+    plan_query(router, user, db, query, None, false)
+}
+
+//a prepared statement is routed by `bound`, which has the parameter values in place of the
+//placeholders. a plan of one shard runs `template` itself, renamed like `bound` would be,
+//so it can be prepared on the backend; more shards run the bound sql.
+pub fn build_prepared_plan(
+    router: &Router,
+    user: &str,
+    db: &str,
+    template: &str,
+    bound: &str,
+) -> AnalyzerResult<Plan> {
+    plan_query(router, user, db, bound, Some(template), false)
+}
+
+//the plan a prepared statement is described by at COM_STMT_PREPARE, before any value is bound:
+//`template` on one physical table, they all have the same columns.
+pub fn build_template_plan(
+    router: &Router,
+    user: &str,
+    db: &str,
+    template: &str,
+) -> AnalyzerResult<Plan> {
+    plan_query(router, user, db, template, None, true)
+}
+
+fn plan_query(
+    router: &Router,
+    user: &str,
+    db: &str,
+    query: &str,
+    template: Option<&str>,
+    any_shard: bool,
+) -> AnalyzerResult<Plan> {
+    let parsed = sql::parse_one(query);
+    let stmt = match parsed {
+        Ok(stmt) => stmt,
+        //statements sqlparser can not understand are sent to the default cluster untouched, but
+        //when tenants share the backends, as the dbs they name can not be checked.
+        Err(AnalyzerError::ParseErr(_)) if !router.is_multi_tenant() => {
+            return default_plan(router, user, db, template.unwrap_or(query))
+        }
+        Err(e) => return Err(e),
+    };
+    let force_master = !sql::is_read_only(&stmt) || sql::has_master_hint(query);
+    let tables = rewrite::collect_tables(&stmt);
+    let named = tables.iter().filter_map(|t| t.db.as_deref());
+    for named_db in named.chain(sql::named_dbs(&stmt).iter().map(String::as_str)) {
+        check_db(router, user, named_db)?;
+    }
+    let first = match tables.first() {
+        Some(t) => t,
+        None => {
+            let mut plan = default_plan(router, user, db, template.unwrap_or(query))?;
+            plan.force_master = force_master;
+            return Ok(plan);
+        }
+    };
+    let db = first.db.as_deref().unwrap_or(db);
+    if db.is_empty() {
+        return Err(AnalyzerError::NoDatabaseSelected);
+    }
+    check_db(router, user, db)?;
+    let db_entry = router.lookup_db(user, db)?;
+    //the sharded tables of the db, tables of other dbs or not sharded are left as they are.
+    let mut sharded: Vec<&TableSectionEntry> = Vec::new();
+    for t in tables
+        .iter()
+        .filter(|t| t.db.as_deref().unwrap_or(db) == db)
+    {
+        match db_entry.lookup_table(&t.table) {
+            Ok(entry) => sharded.push(entry),
+            Err(RouterError::LookupErrTableNotExist) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let primary = match sharded.first() {
+        Some(entry) => *entry,
+        //no sharded table, all of it lives on the first cluster of the db.
+        None => {
+            let cluster_id = first_cluster_id(db_entry.load_cluster_ids())?;
+            return Ok(Plan::single(
+                db,
+                force_master,
+                cluster_id,
+                template.unwrap_or(query).to_string(),
+            ));
+        }
+    };
+    //tables joined together must keep the rows of one shard value on the same backend.
+    for other in sharded.iter().skip(1) {
+        if !primary.is_co_located(other) {
+            return Err(AnalyzerError::TablesNotCoLocated(
+                primary.get_table().to_string(),
+                other.get_table().to_string(),
+            ));
+        }
+    }
+    if matches!(stmt, Statement::Insert { .. }) && !any_shard {
+        return insert_plan(db, &sharded, stmt, template);
+    }
+
+    let keys: Vec<(&str, &str)> = sharded
+        .iter()
+        .map(|t| (t.get_table(), t.get_shard_key()))
+        .collect();
+    //each sharded table takes the suffix of the primary one, so it must be joined on its key.
+    if let Some(t) = sql::unjoined_sharded_table(&stmt, &keys) {
+        return Err(AnalyzerError::NotJoinedOnShardKey(t));
+    }
+    let is_select = matches!(stmt, Statement::Query(_));
+    let condition = sql::find_shard_condition(&stmt, &keys);
+    let mut targets = match condition {
+        _ if any_shard => Vec::new(),
+        Some(ShardCondition::Values(values)) => {
+            let mut targets = Vec::with_capacity(values.len());
+            for v in values.iter() {
+                let path = primary.lookup_one_path(v)?;
+                if !targets.contains(&path) {
+                    targets.push(path);
+                }
+            }
+            targets
+        }
+        Some(ShardCondition::Range(low, high)) => primary.lookup_range_paths(low, high)?,
+        //a select without the shard key runs on every physical table.
+        None if is_select => primary.load_all_path()?,
+        None => {
+            return Err(AnalyzerError::ShardKeyNotFound(
+                primary.get_table().to_string(),
+            ))
+        }
+    };
+    if targets.is_empty() {
+        //no physical table can hold the rows, or any one will do, the first one answers.
+        targets = primary.load_all_path()?;
+        targets.truncate(1);
+    }
+    if targets.len() == 1 {
+        let (cluster_id, physical_table) = targets.remove(0);
+        let mut stmt = match template {
+            Some(t) => sql::parse_one(t)?,
+            None => stmt,
+        };
+        rename_co_located(&mut stmt, db, &sharded, &physical_table);
+        return Ok(Plan::single(
+            db,
+            force_master,
+            cluster_id.to_string(),
+            stmt.to_string(),
+        ));
+    }
+
+    let mut stmt = stmt;
+    let merge = if is_select {
+        Some(merge::rewrite_for_scatter(&mut stmt)?)
+    } else {
+        None
+    };
+    let shards = targets
+        .into_iter()
+        .map(|(cluster_id, physical_table)| {
+            let mut shard_stmt = stmt.clone();
+            rename_co_located(&mut shard_stmt, db, &sharded, &physical_table);
+            (cluster_id.to_string(), shard_stmt.to_string())
+        })
+        .collect();
+    Ok(Plan {
+        db: db.to_string(),
+        force_master,
+        shards,
+        merge,
+    })
+}
+
+//`physical_table` belongs to the first sharded table, the others take the same suffix.
+fn rename_co_located(
+    stmt: &mut Statement,
+    db: &str,
+    sharded: &[&TableSectionEntry],
+    physical_table: &str,
+) {
+    let suffix = physical_table
+        .strip_prefix(sharded[0].get_table())
+        .unwrap_or_default();
+    let renames: HashMap<String, String> = sharded
+        .iter()
+        .map(|t| {
+            (
+                t.get_table().to_string(),
+                format!("{}{}", t.get_table(), suffix),
+            )
+        })
+        .collect();
+    rewrite::rename_tables(stmt, db, &renames);
+}
+
+//a multi-row insert is split into one insert per physical table.
+fn insert_plan(
+    db: &str,
+    sharded: &[&TableSectionEntry],
+    stmt: Statement,
+    template: Option<&str>,
+) -> AnalyzerResult<Plan> {
+    let primary = sharded[0];
+    let values = sql::insert_shard_values(&stmt, primary.get_shard_key())
+        .ok_or_else(|| AnalyzerError::ShardKeyNotFound(primary.get_table().to_string()))?;
+    //(cluster_id, physical table) -> rows, in the order they first show up.
+    let mut targets: Vec<((&str, String), Vec<usize>)> = Vec::new();
+    for (row, v) in values.iter().enumerate() {
+        let path = primary.lookup_one_path(v)?;
+        match targets.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, rows)) => rows.push(row),
+            None => targets.push((path, vec![row])),
+        }
+    }
+    let split = targets.len() > 1;
+    let stmt = match template {
+        Some(t) if !split => sql::parse_one(t)?,
+        _ => stmt,
+    };
+    let shards = targets
+        .into_iter()
+        .map(|((cluster_id, physical_table), rows)| {
+            let mut shard_stmt = stmt.clone();
+            if split {
+                sql::retain_insert_rows(&mut shard_stmt, &rows);
+            }
+            rename_co_located(&mut shard_stmt, db, sharded, &physical_table);
+            (cluster_id.to_string(), shard_stmt.to_string())
+        })
+        .collect();
+    Ok(Plan {
+        db: db.to_string(),
+        force_master: true,
+        shards,
+        merge: None,
+    })
+}
+
+//a db a statement reads or writes must be one of the user's, a db of another tenant is refused.
+//information_schema is not passed to the backends, which hold it for all tenants.
+fn check_db(router: &Router, user: &str, db: &str) -> AnalyzerResult<()> {
+    if sql::is_information_schema(db) {
+        return Err(AnalyzerError::InformationSchemaNotSupported);
+    }
+    if let Some(t) = router.foreign_tenant(user, db) {
+        return Err(AnalyzerError::CrossTenant(t));
+    }
+    router.lookup_db(user, db)?;
+    Ok(())
+}
+
+//route to the first cluster of the current db, or of the first db of the user if none selected.
+//a statement without tables in information_schema runs on the first cluster of the first db.
+fn default_plan(router: &Router, user: &str, db: &str, query: &str) -> AnalyzerResult<Plan> {
+    let home = if db.is_empty() || sql::is_information_schema(db) {
+        router.lookup_default_db(user)?
+    } else {
+        db
+    };
+    let db_entry = router.lookup_db(user, home)?;
+    let cluster_id = first_cluster_id(db_entry.load_cluster_ids())?;
+    let db = if db.is_empty() { home } else { db };
+    Ok(Plan::single(db, true, cluster_id, query.to_string()))
+}
+
+fn first_cluster_id(cluster_ids: &[String]) -> AnalyzerResult<String> {
+    cluster_ids.first().cloned().ok_or(AnalyzerError::Router(
+        RouterError::LookupErrClusterPairsEmpty,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_plan, build_prepared_plan, build_template_plan};
+    use crate::analyzer::error::AnalyzerError;
+    use crate::analyzer::sql::bind_placeholders;
+    use crate::config::Config;
+    use crate::router::build_router_with;
+
+    const CONFIG: &str = r#"
+[proxy]
+listen_addr = "127.0.0.1:9696"
+users = [{ user = "root", pwd = "root" }]
+
+[[node]]
+id = "mysql_1"
+listen_addr = "127.0.0.1:3306"
+user = "root"
+pwd = "root"
+
+[[cluster]]
+id = "cluster_1"
+master_node_id = "mysql_1"
+
+[[cluster]]
+id = "cluster_2"
+master_node_id = "mysql_1"
+
+[[schema]]
+owner = "root"
+[[schema.db]]
+db = "db1"
+cluster_ids = ["cluster_1", "cluster_2"]
+[[schema.db.table]]
+table = "integer_table"
+shard_key = "id"
+shard_type = "integer"
+each_cluster_table_split_count = [3, 9]
+[[schema.db.table]]
+table = "integer_detail"
+shard_key = "table_id"
+shard_type = "integer"
+each_cluster_table_split_count = [3, 9]
+[[schema.db.table]]
+table = "range_table"
+shard_key = "day"
+shard_type = "integer_range"
+integer_range = ["20220101", "20230101", "20230101", "20240101"]
+each_cluster_table_split_count = [1, 4]
+"#;
+
+    fn shards(router: &crate::router::Router, sql: &str) -> Vec<(String, String)> {
+        build_plan(router, "root", "db1", sql).unwrap().shards
+    }
+
+    fn shard(cluster_id: &str, sql: &str) -> (String, String) {
+        (cluster_id.to_string(), sql.to_string())
+    }
+
+    #[test]
+    fn route_by_shard_key() {
+        let cfg: Config = toml::from_str(CONFIG).unwrap();
+        let router = build_router_with(&cfg).unwrap();
+        let plan = build_plan(
+            &router,
+            "root",
+            "db1",
+            "SELECT name FROM integer_table WHERE id = 7 AND name = 'x'",
+        )
+        .unwrap();
+        assert!(!plan.force_master);
+        assert!(plan.merge.is_none());
+        assert_eq!(
+            plan.shards,
+            vec![shard(
+                "cluster_2",
+                "SELECT name FROM integer_table_7 WHERE id = 7 AND name = 'x'"
+            )]
+        );
+
+        let plan = build_plan(&router, "root", "", "SELECT 1").unwrap();
+        assert_eq!(plan.db, "db1");
+        assert_eq!(plan.shards[0].0, "cluster_1");
+
+        let err = build_plan(&router, "root", "db1", "DELETE FROM integer_table").unwrap_err();
+        assert!(matches!(err, AnalyzerError::ShardKeyNotFound(_)));
+
+        let plan = build_plan(&router, "root", "db1", "SELECT name FROM integer_table").unwrap();
+        assert!(plan.merge.is_some());
+        assert_eq!(plan.shards.len(), 12);
+        assert_eq!(
+            plan.shards[11],
+            shard("cluster_2", "SELECT name FROM integer_table_8")
+        );
+    }
+
+    #[test]
+    fn route_by_in_between_and_insert_values() {
+        let cfg: Config = toml::from_str(CONFIG).unwrap();
+        let router = build_router_with(&cfg).unwrap();
+        //7 and 25 meet on integer_table_7 of cluster_2.
+        assert_eq!(
+            shards(&router, "DELETE FROM integer_table WHERE id IN (7, 25, 4)"),
+            vec![
+                shard(
+                    "cluster_2",
+                    "DELETE FROM integer_table_7 WHERE id IN (7, 25, 4)"
+                ),
+                shard(
+                    "cluster_1",
+                    "DELETE FROM integer_table_1 WHERE id IN (7, 25, 4)"
+                ),
+            ]
+        );
+        assert_eq!(
+            shards(
+                &router,
+                "SELECT * FROM integer_table WHERE (id = 7 OR id = 25) AND id IN (25, 8)"
+            ),
+            vec![shard(
+                "cluster_2",
+                "SELECT * FROM integer_table_7 WHERE (id = 7 OR id = 25) AND id IN (25, 8)"
+            )]
+        );
+        //cluster_1 is pruned, 20230105 % 4 = 1 and 20230106 % 4 = 2.
+        assert_eq!(
+            shards(
+                &router,
+                "UPDATE range_table SET n = 1 WHERE day BETWEEN 20230105 AND 20230106"
+            ),
+            vec![
+                shard(
+                    "cluster_2",
+                    "UPDATE range_table_1 SET n = 1 WHERE day BETWEEN 20230105 AND 20230106"
+                ),
+                shard(
+                    "cluster_2",
+                    "UPDATE range_table_2 SET n = 1 WHERE day BETWEEN 20230105 AND 20230106"
+                ),
+            ]
+        );
+        assert_eq!(
+            shards(
+                &router,
+                "SELECT * FROM range_table WHERE day BETWEEN 20220301 AND 20220302"
+            )
+            .len(),
+            1
+        );
+        assert_eq!(
+            shards(
+                &router,
+                "INSERT INTO integer_table (id, name) VALUES (7, 'a'), (4, 'b'), (25, 'c')"
+            ),
+            vec![
+                shard(
+                    "cluster_2",
+                    "INSERT INTO integer_table_7 (id, name) VALUES (7, 'a'), (25, 'c')"
+                ),
+                shard(
+                    "cluster_1",
+                    "INSERT INTO integer_table_1 (id, name) VALUES (4, 'b')"
+                ),
+            ]
+        );
+        let err = build_plan(
+            &router,
+            "root",
+            "db1",
+            "INSERT INTO integer_table VALUES (1)",
+        )
+        .unwrap_err();
+        assert!(matches!(err, AnalyzerError::ShardKeyNotFound(_)));
+    }
+
+    #[test]
+    fn route_co_located_tables() {
+        let cfg: Config = toml::from_str(CONFIG).unwrap();
+        let router = build_router_with(&cfg).unwrap();
+        assert_eq!(
+            shards(
+                &router,
+                "SELECT t.name, d.v FROM db1.integer_table AS t JOIN integer_detail AS d ON t.id = d.table_id WHERE d.table_id = 7 AND EXISTS (SELECT 1 FROM integer_detail AS x WHERE x.table_id = t.id)"
+            ),
+            vec![shard(
+                "cluster_2",
+                "SELECT t.name, d.v FROM integer_table_7 AS t JOIN integer_detail_7 AS d ON t.id = d.table_id WHERE d.table_id = 7 AND EXISTS (SELECT 1 FROM integer_detail_7 AS x WHERE x.table_id = t.id)"
+            )]
+        );
+        //the subquery reads all of integer_detail, not the shard of the outer query.
+        let err = build_plan(
+            &router,
+            "root",
+            "db1",
+            "SELECT name FROM integer_table WHERE id = 7 AND id IN (SELECT table_id FROM integer_detail)",
+        )
+        .unwrap_err();
+        assert!(matches!(err, AnalyzerError::NotJoinedOnShardKey(t) if t == "integer_detail"));
+        let err = build_plan(
+            &router,
+            "root",
+            "db1",
+            "SELECT * FROM integer_table JOIN range_table ON integer_table.id = range_table.day",
+        )
+        .unwrap_err();
+        assert!(matches!(err, AnalyzerError::TablesNotCoLocated(..)));
+    }
+
+    #[test]
+    fn route_by_qualified_shard_key() {
+        let cfg: Config = toml::from_str(CONFIG).unwrap();
+        let router = build_router_with(&cfg).unwrap();
+        //`name` of the other table is no shard key of the primary one, it scatters.
+        let plan = build_plan(
+            &router,
+            "root",
+            "db1",
+            "SELECT * FROM integer_table AS a JOIN plain AS b ON a.name = b.name WHERE b.id = 5",
+        )
+        .unwrap();
+        assert_eq!(plan.shards.len(), 12);
+        let plan = build_plan(
+            &router,
+            "root",
+            "db1",
+            "SELECT * FROM integer_table AS a JOIN plain AS b ON a.name = b.name WHERE a.id = 5",
+        )
+        .unwrap();
+        assert_eq!(plan.shards.len(), 1);
+        //a key of a co-located table which the join equates with the primary one routes.
+        assert_eq!(
+            shards(
+                &router,
+                "SELECT * FROM integer_detail AS d JOIN integer_table AS t ON t.id = d.table_id WHERE t.id = 7"
+            )[0]
+            .0,
+            "cluster_2"
+        );
+        let err = build_plan(
+            &router,
+            "root",
+            "db1",
+            "SELECT * FROM integer_table AS a JOIN integer_detail AS b ON a.name = b.name WHERE b.table_id = 5",
+        )
+        .unwrap_err();
+        assert!(matches!(err, AnalyzerError::NotJoinedOnShardKey(t) if t == "integer_detail"));
+    }
+
+    #[test]
+    fn route_prepared_statement() {
+        let cfg: Config = toml::from_str(CONFIG).unwrap();
+        let router = build_router_with(&cfg).unwrap();
+        let template = "SELECT name FROM integer_table WHERE id = ? AND name <> '?' -- ?";
+        let bound = bind_placeholders(template, &["7".to_string()]);
+        assert_eq!(
+            bound,
+            "SELECT name FROM integer_table WHERE id = 7 AND name <> '?' -- ?"
+        );
+        let plan = build_prepared_plan(&router, "root", "db1", template, &bound).unwrap();
+        assert_eq!(
+            plan.shards,
+            vec![shard(
+                "cluster_2",
+                "SELECT name FROM integer_table_7 WHERE id = ? AND name <> '?'"
+            )]
+        );
+        //more than one shard runs the bound sql.
+        let template = "DELETE FROM integer_table WHERE id IN (?, ?)";
+        let bound = bind_placeholders(template, &["7".to_string(), "4".to_string()]);
+        let plan = build_prepared_plan(&router, "root", "db1", template, &bound).unwrap();
+        assert_eq!(
+            plan.shards[1],
+            shard(
+                "cluster_1",
+                "DELETE FROM integer_table_1 WHERE id IN (7, 4)"
+            )
+        );
+        //before any value is bound it is described by the first physical table.
+        let template = "INSERT INTO integer_table (id, name) VALUES (?, ?)";
+        let plan = build_template_plan(&router, "root", "db1", template).unwrap();
+        assert_eq!(
+            plan.shards,
+            vec![shard(
+                "cluster_1",
+                "INSERT INTO integer_table_0 (id, name) VALUES (?, ?)"
+            )]
+        );
+    }
+
+    #[test]
+    fn refuse_information_schema() {
+        let cfg: Config = toml::from_str(CONFIG).unwrap();
+        let router = build_router_with(&cfg).unwrap();
+        let refused = |db: &str, sql: &str| {
+            matches!(
+                build_plan(&router, "root", db, sql),
+                Err(AnalyzerError::InformationSchemaNotSupported)
+            )
+        };
+        assert!(refused("information_schema", "SELECT * FROM columns"));
+        assert!(refused("db1", "SELECT * FROM information_schema.columns"));
+        assert!(refused(
+            "db1",
+            "SELECT * FROM integer_table AS t JOIN information_schema.tables AS i ON t.id = 1"
+        ));
+        //a statement on no table still runs where the first db is.
+        let plan = build_plan(&router, "root", "information_schema", "SELECT 1").unwrap();
+        assert_eq!(plan.db, "information_schema");
+        assert_eq!(plan.shards, vec![shard("cluster_1", "SELECT 1")]);
+    }
+
+    #[test]
+    fn refuse_foreign_tenant_db() {
+        let tenants = format!(
+            "{}{}",
+            CONFIG.replace(r#"owner = "root""#, r#"owner = "root@t1""#),
+            r#"
+[[schema]]
+owner = "root@t2"
+[[schema.db]]
+db = "secret_db"
+cluster_ids = ["cluster_1"]
+[[schema.db.table]]
+table = "secret"
+shard_key = "id"
+shard_type = "integer"
+each_cluster_table_split_count = [2]
+"#
+        );
+        let cfg: Config = toml::from_str(&tenants).unwrap();
+        let router = build_router_with(&cfg).unwrap();
+        let plan = |sql: &str| build_plan(&router, "root@t1", "db1", sql);
+        let denied =
+            |sql: &str| matches!(plan(sql), Err(AnalyzerError::CrossTenant(t)) if t == "t2");
+        assert!(denied(
+            "SELECT * FROM db1.integer_table AS t JOIN secret_db.secret AS s ON t.id = s.id WHERE t.id = 7"
+        ));
+        assert!(denied(
+            "SELECT * FROM plain WHERE id IN (SELECT id FROM secret_db.secret)"
+        ));
+        assert!(denied("SELECT * FROM secret_db.secret"));
+        assert!(denied("DROP TABLE secret_db.secret"));
+        assert!(denied("EXPLAIN SELECT * FROM secret_db.secret"));
+        //a db nobody has is unknown, sql the parser can not read is refused as its dbs are unknown.
+        assert!(matches!(
+            plan("SELECT * FROM plain JOIN mysql.user"),
+            Err(AnalyzerError::Router(_))
+        ));
+        assert!(matches!(
+            plan("OPTIMIZE TABLE secret_db.secret"),
+            Err(AnalyzerError::ParseErr(_))
+        ));
+        assert_eq!(plan("SELECT * FROM plain").unwrap().shards.len(), 1);
+    }
 }
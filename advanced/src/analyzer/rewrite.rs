@@ -0,0 +1,423 @@
+#![allow(dead_code)]
+use super::sql::TableRef;
+use sqlparser::ast::{
+    Expr, FunctionArg, FunctionArgExpr, Ident, JoinConstraint, JoinOperator, ObjectName, OnInsert,
+    OrderByExpr, Query, Select, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins,
+};
+use std::collections::{HashMap, HashSet};
+
+//a walk over every table name and every table qualifier of a statement, subqueries included.
+trait TableVisitor {
+    //`FROM db.table AS alias`, `INSERT INTO db.table` ...
+    fn visit_table(&mut self, name: &mut ObjectName, alias: Option<&Ident>);
+    //`db.table.column`, `table.*` ..., the first `len` idents are the `db.table` or `table` part.
+    fn visit_qualifier(&mut self, _idents: &mut Vec<Ident>, _len: usize) {}
+}
+
+//all tables a statement reads or writes, in the order they show up.
+pub fn collect_tables(stmt: &Statement) -> Vec<TableRef> {
+    struct Collector {
+        tables: Vec<TableRef>,
+        cte_names: HashSet<String>,
+    }
+    impl TableVisitor for Collector {
+        fn visit_table(&mut self, name: &mut ObjectName, _alias: Option<&Ident>) {
+            if let Some(t) = TableRef::from_object_name(name) {
+                if t.db.is_none() && self.cte_names.contains(&t.table) {
+                    return;
+                }
+                if !self.tables.contains(&t) {
+                    self.tables.push(t);
+                }
+            }
+        }
+    }
+    let mut stmt = stmt.clone();
+    let mut c = Collector {
+        tables: Vec::new(),
+        cte_names: cte_names(&stmt),
+    };
+    walk_statement(&mut stmt, &mut c);
+    c.tables
+}
+
+//rename the logical tables of the current db to physical ones, `renames`: logical -> physical.
+//the db qualifier of a renamed table is dropped, as the backend connection is on that db already.
+//table qualifiers of columns follow, unless the logical name is used as an alias somewhere.
+pub fn rename_tables(stmt: &mut Statement, db: &str, renames: &HashMap<String, String>) {
+    struct AliasCollector {
+        aliases: HashSet<String>,
+    }
+    impl TableVisitor for AliasCollector {
+        fn visit_table(&mut self, _name: &mut ObjectName, alias: Option<&Ident>) {
+            if let Some(a) = alias {
+                self.aliases.insert(a.value.clone());
+            }
+        }
+    }
+    struct Renamer<'r> {
+        db: &'r str,
+        renames: &'r HashMap<String, String>,
+        aliases: HashSet<String>,
+    }
+    impl<'r> Renamer<'r> {
+        //the physical name of `[db.]table` in the first `len` idents, when it is renamed.
+        fn rename(&self, idents: &mut Vec<Ident>, len: usize) {
+            let (db, table) = match len {
+                1 => (None, &idents[0]),
+                2 => (Some(&idents[0]), &idents[1]),
+                _ => return,
+            };
+            if db.map(|d| d.value != self.db).unwrap_or(false) {
+                return;
+            }
+            if let Some(physical) = self.renames.get(&table.value) {
+                idents[len - 1].value = physical.clone();
+                if len == 2 {
+                    idents.remove(0);
+                }
+            }
+        }
+    }
+    impl<'r> TableVisitor for Renamer<'r> {
+        fn visit_table(&mut self, name: &mut ObjectName, _alias: Option<&Ident>) {
+            let len = name.0.len();
+            self.rename(&mut name.0, len);
+        }
+        fn visit_qualifier(&mut self, idents: &mut Vec<Ident>, len: usize) {
+            if len == 1 && self.aliases.contains(&idents[0].value) {
+                return;
+            }
+            self.rename(idents, len);
+        }
+    }
+
+    let mut aliases = AliasCollector {
+        aliases: cte_names(stmt),
+    };
+    walk_statement(stmt, &mut aliases);
+    let mut r = Renamer {
+        db,
+        renames,
+        aliases: aliases.aliases,
+    };
+    walk_statement(stmt, &mut r);
+}
+
+fn cte_names(stmt: &Statement) -> HashSet<String> {
+    match stmt {
+        Statement::Query(q) => q
+            .with
+            .iter()
+            .flat_map(|w| w.cte_tables.iter().map(|c| c.alias.name.value.clone()))
+            .collect(),
+        _ => HashSet::new(),
+    }
+}
+
+fn walk_statement(stmt: &mut Statement, v: &mut dyn TableVisitor) {
+    match stmt {
+        Statement::Query(q) => walk_query(q, v),
+        Statement::Insert {
+            table_name,
+            source,
+            on,
+            ..
+        } => {
+            v.visit_table(table_name, None);
+            walk_query(source, v);
+            if let Some(OnInsert::DuplicateKeyUpdate(assignments)) = on {
+                for a in assignments.iter_mut() {
+                    walk_expr(&mut a.value, v);
+                }
+            }
+        }
+        Statement::Update {
+            table,
+            assignments,
+            from,
+            selection,
+            ..
+        } => {
+            walk_table_with_joins(table, v);
+            if let Some(f) = from {
+                walk_table_with_joins(f, v);
+            }
+            for a in assignments.iter_mut() {
+                if a.id.len() > 1 {
+                    let len = a.id.len() - 1;
+                    v.visit_qualifier(&mut a.id, len);
+                }
+                walk_expr(&mut a.value, v);
+            }
+            if let Some(e) = selection {
+                walk_expr(e, v);
+            }
+        }
+        Statement::Delete {
+            table_name,
+            using,
+            selection,
+            ..
+        } => {
+            walk_table_factor(table_name, v);
+            if let Some(u) = using {
+                walk_table_factor(u, v);
+            }
+            if let Some(e) = selection {
+                walk_expr(e, v);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn walk_query(q: &mut Query, v: &mut dyn TableVisitor) {
+    if let Some(w) = &mut q.with {
+        for cte in w.cte_tables.iter_mut() {
+            walk_query(&mut cte.query, v);
+        }
+    }
+    walk_set_expr(&mut q.body, v);
+    walk_order_by(&mut q.order_by, v);
+}
+
+fn walk_set_expr(body: &mut SetExpr, v: &mut dyn TableVisitor) {
+    match body {
+        SetExpr::Select(s) => walk_select(s, v),
+        SetExpr::Query(q) => walk_query(q, v),
+        SetExpr::SetOperation { left, right, .. } => {
+            walk_set_expr(left, v);
+            walk_set_expr(right, v);
+        }
+        SetExpr::Values(values) => {
+            for row in values.0.iter_mut() {
+                for e in row.iter_mut() {
+                    walk_expr(e, v);
+                }
+            }
+        }
+        SetExpr::Insert(stmt) => walk_statement(stmt, v),
+    }
+}
+
+fn walk_select(s: &mut Select, v: &mut dyn TableVisitor) {
+    for t in s.from.iter_mut() {
+        walk_table_with_joins(t, v);
+    }
+    for item in s.projection.iter_mut() {
+        match item {
+            SelectItem::UnnamedExpr(e) | SelectItem::ExprWithAlias { expr: e, .. } => {
+                walk_expr(e, v)
+            }
+            SelectItem::QualifiedWildcard(name) => {
+                let len = name.0.len();
+                v.visit_qualifier(&mut name.0, len)
+            }
+            SelectItem::Wildcard => {}
+        }
+    }
+    for e in s
+        .selection
+        .iter_mut()
+        .chain(s.group_by.iter_mut())
+        .chain(s.having.iter_mut())
+    {
+        walk_expr(e, v);
+    }
+}
+
+fn walk_order_by(order_by: &mut [OrderByExpr], v: &mut dyn TableVisitor) {
+    for o in order_by.iter_mut() {
+        walk_expr(&mut o.expr, v);
+    }
+}
+
+fn walk_table_with_joins(t: &mut TableWithJoins, v: &mut dyn TableVisitor) {
+    walk_table_factor(&mut t.relation, v);
+    for j in t.joins.iter_mut() {
+        walk_table_factor(&mut j.relation, v);
+        match &mut j.join_operator {
+            JoinOperator::Inner(JoinConstraint::On(e))
+            | JoinOperator::LeftOuter(JoinConstraint::On(e))
+            | JoinOperator::RightOuter(JoinConstraint::On(e))
+            | JoinOperator::FullOuter(JoinConstraint::On(e)) => walk_expr(e, v),
+            _ => {}
+        }
+    }
+}
+
+fn walk_table_factor(t: &mut TableFactor, v: &mut dyn TableVisitor) {
+    match t {
+        TableFactor::Table { name, alias, .. } => {
+            v.visit_table(name, alias.as_ref().map(|a| &a.name))
+        }
+        TableFactor::Derived { subquery, .. } => walk_query(subquery, v),
+        TableFactor::NestedJoin {
+            table_with_joins, ..
+        } => walk_table_with_joins(table_with_joins, v),
+        TableFactor::TableFunction { expr, .. } => walk_expr(expr, v),
+        TableFactor::UNNEST { array_expr, .. } => walk_expr(array_expr, v),
+    }
+}
+
+fn walk_expr(e: &mut Expr, v: &mut dyn TableVisitor) {
+    match e {
+        Expr::CompoundIdentifier(idents) if idents.len() > 1 => {
+            let len = idents.len() - 1;
+            v.visit_qualifier(idents, len);
+        }
+        Expr::Subquery(q) | Expr::ArraySubquery(q) | Expr::Exists { subquery: q, .. } => {
+            walk_query(q, v)
+        }
+        Expr::InSubquery { expr, subquery, .. } => {
+            walk_expr(expr, v);
+            walk_query(subquery, v);
+        }
+        Expr::InList { expr, list, .. } => {
+            walk_expr(expr, v);
+            for x in list.iter_mut() {
+                walk_expr(x, v);
+            }
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            walk_expr(expr, v);
+            walk_expr(low, v);
+            walk_expr(high, v);
+        }
+        Expr::BinaryOp { left, right, .. }
+        | Expr::IsDistinctFrom(left, right)
+        | Expr::IsNotDistinctFrom(left, right) => {
+            walk_expr(left, v);
+            walk_expr(right, v);
+        }
+        Expr::Like { expr, pattern, .. }
+        | Expr::ILike { expr, pattern, .. }
+        | Expr::SimilarTo { expr, pattern, .. } => {
+            walk_expr(expr, v);
+            walk_expr(pattern, v);
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::Cast { expr, .. }
+        | Expr::TryCast { expr, .. }
+        | Expr::SafeCast { expr, .. }
+        | Expr::Extract { expr, .. }
+        | Expr::Collate { expr, .. }
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr)
+        | Expr::IsTrue(expr)
+        | Expr::IsNotTrue(expr)
+        | Expr::IsFalse(expr)
+        | Expr::IsNotFalse(expr)
+        | Expr::IsUnknown(expr)
+        | Expr::IsNotUnknown(expr)
+        | Expr::AnyOp(expr)
+        | Expr::AllOp(expr) => walk_expr(expr, v),
+        Expr::Substring {
+            expr,
+            substring_from,
+            substring_for,
+        } => {
+            walk_expr(expr, v);
+            for x in substring_from.iter_mut().chain(substring_for.iter_mut()) {
+                walk_expr(x, v);
+            }
+        }
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            for x in operand.iter_mut().chain(else_result.iter_mut()) {
+                walk_expr(x, v);
+            }
+            for x in conditions.iter_mut().chain(results.iter_mut()) {
+                walk_expr(x, v);
+            }
+        }
+        Expr::Function(f) => {
+            for a in f.args.iter_mut() {
+                match a {
+                    FunctionArg::Named { arg, .. } | FunctionArg::Unnamed(arg) => match arg {
+                        FunctionArgExpr::Expr(x) => walk_expr(x, v),
+                        FunctionArgExpr::QualifiedWildcard(name) => {
+                            let len = name.0.len();
+                            v.visit_qualifier(&mut name.0, len)
+                        }
+                        FunctionArgExpr::Wildcard => {}
+                    },
+                }
+            }
+        }
+        Expr::Tuple(list) => {
+            for x in list.iter_mut() {
+                walk_expr(x, v);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::sql::parse_one;
+
+    fn rewrite(sql: &str, renames: &[(&str, &str)]) -> String {
+        let renames: HashMap<String, String> = renames
+            .iter()
+            .map(|(l, p)| (l.to_string(), p.to_string()))
+            .collect();
+        let mut stmt = parse_one(sql).unwrap();
+        rename_tables(&mut stmt, "db1", &renames);
+        stmt.to_string()
+    }
+
+    #[test]
+    fn rename_logical_tables() {
+        let renames = [("student", "student_3"), ("score", "score_3")];
+        assert_eq!(
+            rewrite(
+                "SELECT db1.student.name, c.total FROM db1.student JOIN score AS c ON student.id = c.student_id WHERE student.id = 3",
+                &renames
+            ),
+            "SELECT student_3.name, c.total FROM student_3 JOIN score_3 AS c ON student_3.id = c.student_id WHERE student_3.id = 3"
+        );
+        //an alias named like a logical table, another db and a subquery.
+        assert_eq!(
+            rewrite(
+                "SELECT score.id FROM student AS score WHERE score.id IN (SELECT student_id FROM score) AND EXISTS (SELECT 1 FROM db2.student)",
+                &renames
+            ),
+            "SELECT score.id FROM student_3 AS score WHERE score.id IN (SELECT student_id FROM score_3) AND EXISTS (SELECT 1 FROM db2.student)"
+        );
+        assert_eq!(
+            rewrite(
+                "UPDATE `student` SET `student`.name = 'x' WHERE id = 3",
+                &renames
+            ),
+            "UPDATE `student_3` SET `student_3`.name = 'x' WHERE id = 3"
+        );
+        let stmt = parse_one(
+            "WITH s AS (SELECT * FROM student) SELECT * FROM s, (SELECT 1 FROM db1.score) AS t",
+        )
+        .unwrap();
+        assert_eq!(
+            collect_tables(&stmt),
+            vec![
+                TableRef {
+                    db: None,
+                    table: "student".to_string()
+                },
+                TableRef {
+                    db: Some("db1".to_string()),
+                    table: "score".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -1 +1,1099 @@
+#![allow(dead_code)]
+use super::error::{AnalyzerError, AnalyzerResult};
+use sqlparser::ast::{
+    BinaryOperator, Expr, FunctionArg, FunctionArgExpr, JoinConstraint, JoinOperator, ObjectName,
+    ObjectType, Query, SchemaName, Select, SelectItem, SetExpr, Statement, TableFactor,
+    TableWithJoins, Value,
+};
+use sqlparser::dialect::MySqlDialect;
+use sqlparser::parser::Parser;
 
+//parse one sql text into one statement, multi statements are not supported now.
+pub fn parse_one(sql: &str) -> AnalyzerResult<Statement> {
+    let mut stmts = Parser::parse_sql(&MySqlDialect {}, sql)
+        .map_err(|e| AnalyzerError::ParseErr(e.to_string()))?;
+    match stmts.len() {
+        0 => Err(AnalyzerError::EmptyStatement),
+        1 => Ok(stmts.remove(0)),
+        _ => Err(AnalyzerError::MultiStatementNotSupported),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableRef {
+    pub db: Option<String>,
+    pub table: String,
+}
+
+impl TableRef {
+    pub fn from_object_name(name: &ObjectName) -> Option<TableRef> {
+        let idents = &name.0;
+        match idents.len() {
+            1 => Some(TableRef {
+                db: None,
+                table: idents[0].value.clone(),
+            }),
+            2 => Some(TableRef {
+                db: Some(idents[0].value.clone()),
+                table: idents[1].value.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+//what the where clause tells about the shard key, None means it may be anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShardCondition {
+    Values(Vec<String>), //`=` or IN
+    Range(u128, u128),   //BETWEEN, both ends included.
+}
+
+impl ShardCondition {
+    //both must hold, an empty intersection keeps the left one as the rows go nowhere anyway.
+    pub fn and(
+        left: Option<ShardCondition>,
+        right: Option<ShardCondition>,
+    ) -> Option<ShardCondition> {
+        use ShardCondition::*;
+        let (l, r) = match (left, right) {
+            (Some(l), Some(r)) => (l, r),
+            (l, None) => return l,
+            (None, r) => return r,
+        };
+        let both = match (&l, &r) {
+            (Values(a), Values(b)) => Values(a.iter().filter(|v| b.contains(v)).cloned().collect()),
+            (Values(vs), Range(lo, hi)) | (Range(lo, hi), Values(vs)) => Values(
+                vs.iter()
+                    .filter(|v| match v.parse::<u128>() {
+                        Ok(x) => *lo <= x && x <= *hi,
+                        Err(_) => true,
+                    })
+                    .cloned()
+                    .collect(),
+            ),
+            (Range(a_lo, a_hi), Range(b_lo, b_hi)) => {
+                Range(std::cmp::max(*a_lo, *b_lo), std::cmp::min(*a_hi, *b_hi))
+            }
+        };
+        match both {
+            Values(vs) if vs.is_empty() => Some(l),
+            Range(lo, hi) if lo > hi => Some(l),
+            both => Some(both),
+        }
+    }
+    //either may hold, so both sides must tell about the shard key.
+    fn or(left: Option<ShardCondition>, right: Option<ShardCondition>) -> Option<ShardCondition> {
+        use ShardCondition::*;
+        match (left?, right?) {
+            (Values(mut a), Values(b)) => {
+                for v in b {
+                    if !a.contains(&v) {
+                        a.push(v);
+                    }
+                }
+                Some(Values(a))
+            }
+            (Range(a_lo, a_hi), Range(b_lo, b_hi)) => {
+                Some(Range(std::cmp::min(a_lo, b_lo), std::cmp::max(a_hi, b_hi)))
+            }
+            (Values(vs), Range(lo, hi)) | (Range(lo, hi), Values(vs)) => {
+                let mut lo = lo;
+                let mut hi = hi;
+                for v in vs.iter() {
+                    let x = v.parse::<u128>().ok()?;
+                    lo = std::cmp::min(lo, x);
+                    hi = std::cmp::max(hi, x);
+                }
+                Some(Range(lo, hi))
+            }
+        }
+    }
+}
+
+//the shard key condition of the where clause of select, update and delete. `sharded` is the
+//(table, shard key) of the sharded tables, the first is the primary one the query routes by.
+//a column counts when it is the key of the primary table or one the joins equate with it.
+pub fn find_shard_condition(stmt: &Statement, sharded: &[(&str, &str)]) -> Option<ShardCondition> {
+    let level = Level::of_statement(stmt)?;
+    let mut levels = vec![Scope::of(&level)];
+    levels[0].seed(sharded);
+    close_keys(&level, &mut levels, sharded);
+    let is_key = |e: &Expr| {
+        resolve(&levels, e).is_some_and(|(l, key)| l == 0 && levels[0].keys.contains(&key))
+    };
+    level
+        .selection
+        .and_then(|e| shard_condition_of_expr(e, &is_key))
+}
+
+//a sharded table which is not joined on its shard key with the primary one, in the same level
+//or as a subquery which does not equate its key with one of the query around it. the rows of it
+//may be on other shards than those the query is sent to, so the query can not run.
+pub fn unjoined_sharded_table(stmt: &Statement, sharded: &[(&str, &str)]) -> Option<String> {
+    let mut levels = Vec::new();
+    match stmt {
+        Statement::Query(q) => check_query(q, sharded, &mut levels),
+        _ => check_level(&Level::of_statement(stmt)?, &[], sharded, &mut levels),
+    }
+}
+
+//one level of a query: its tables, the ON conditions of its joins, its WHERE and the other
+//expressions which may hold subqueries.
+#[derive(Default)]
+struct Level<'a> {
+    factors: Vec<&'a TableFactor>,
+    on: Vec<&'a Expr>,
+    selection: Option<&'a Expr>,
+    exprs: Vec<&'a Expr>,
+}
+
+impl<'a> Level<'a> {
+    fn of_statement(stmt: &'a Statement) -> Option<Level<'a>> {
+        let mut level = Level::default();
+        match stmt {
+            Statement::Query(q) => match &*q.body {
+                SetExpr::Select(s) => return Some(Level::of_select(s)),
+                _ => return None,
+            },
+            Statement::Update {
+                table,
+                assignments,
+                from,
+                selection,
+                ..
+            } => {
+                level.add_joins(table);
+                if let Some(f) = from {
+                    level.add_joins(f);
+                }
+                level.exprs.extend(assignments.iter().map(|a| &a.value));
+                level.selection = selection.as_ref();
+            }
+            Statement::Delete {
+                table_name,
+                using,
+                selection,
+                ..
+            } => {
+                level.add_factor(table_name);
+                if let Some(u) = using {
+                    level.add_factor(u);
+                }
+                level.selection = selection.as_ref();
+            }
+            _ => return None,
+        }
+        Some(level)
+    }
+    fn of_select(s: &'a Select) -> Level<'a> {
+        let mut level = Level::default();
+        for t in s.from.iter() {
+            level.add_joins(t);
+        }
+        for item in s.projection.iter() {
+            if let SelectItem::UnnamedExpr(e) | SelectItem::ExprWithAlias { expr: e, .. } = item {
+                level.exprs.push(e);
+            }
+        }
+        level.exprs.extend(s.group_by.iter().chain(s.having.iter()));
+        level.selection = s.selection.as_ref();
+        level
+    }
+    fn add_joins(&mut self, t: &'a TableWithJoins) {
+        self.add_factor(&t.relation);
+        for j in t.joins.iter() {
+            self.add_factor(&j.relation);
+            match &j.join_operator {
+                JoinOperator::Inner(JoinConstraint::On(e))
+                | JoinOperator::LeftOuter(JoinConstraint::On(e))
+                | JoinOperator::RightOuter(JoinConstraint::On(e))
+                | JoinOperator::FullOuter(JoinConstraint::On(e)) => self.on.push(e),
+                _ => {}
+            }
+        }
+    }
+    fn add_factor(&mut self, t: &'a TableFactor) {
+        match t {
+            TableFactor::NestedJoin {
+                table_with_joins, ..
+            } => self.add_joins(table_with_joins),
+            t => self.factors.push(t),
+        }
+    }
+    //the subqueries right in this level, derived tables included.
+    fn subqueries(&self) -> Vec<&'a Query> {
+        let mut out = Vec::new();
+        for t in self.factors.iter() {
+            if let TableFactor::Derived { subquery, .. } = t {
+                out.push(&**subquery);
+            }
+        }
+        for e in self
+            .on
+            .iter()
+            .chain(self.selection.iter())
+            .chain(self.exprs.iter())
+        {
+            subqueries_of_expr(e, &mut out);
+        }
+        out
+    }
+}
+
+//the tables of a level by their qualifiers, `FROM t AS a` is (a, t), a derived table has no
+//table. `keys` are the (qualifier, column) which hold the shard value the query routes by.
+struct Scope {
+    tables: Vec<(String, Option<String>)>,
+    keys: Vec<(String, String)>,
+}
+
+impl Scope {
+    fn of(level: &Level) -> Scope {
+        let tables = level
+            .factors
+            .iter()
+            .map(|t| match t {
+                TableFactor::Table { name, alias, .. } => {
+                    let table = name.0.last().map(|i| i.value.clone()).unwrap_or_default();
+                    let qualifier = alias
+                        .as_ref()
+                        .map_or(table.clone(), |a| a.name.value.clone());
+                    (qualifier, Some(table))
+                }
+                TableFactor::Derived { alias, .. } => (
+                    alias
+                        .as_ref()
+                        .map(|a| a.name.value.clone())
+                        .unwrap_or_default(),
+                    None,
+                ),
+                _ => (String::new(), None),
+            })
+            .collect();
+        Scope {
+            tables,
+            keys: Vec::new(),
+        }
+    }
+    //the key of the first place of the primary table.
+    fn seed(&mut self, sharded: &[(&str, &str)]) {
+        let (primary, key) = match sharded.first() {
+            Some(p) => *p,
+            None => return,
+        };
+        if let Some((q, _)) = self
+            .tables
+            .iter()
+            .find(|(_, t)| t.as_deref() == Some(primary))
+        {
+            self.keys.push((q.clone(), key.to_ascii_lowercase()));
+        }
+    }
+    //the (qualifier, column) of a column of this level which is the shard key of its table.
+    fn shard_key(&self, q: &str, column: &str, sharded: &[(&str, &str)]) -> bool {
+        self.tables.iter().any(|(tq, t)| {
+            tq == q
+                && sharded
+                    .iter()
+                    .any(|(st, key)| t.as_deref() == Some(*st) && key.eq_ignore_ascii_case(column))
+        })
+    }
+}
+
+//the level and the (qualifier, column) of a column.
+type Column = (usize, (String, String));
+
+//a column is looked up from the innermost level out, one without a qualifier is only known in
+//a level of one table.
+fn resolve(levels: &[Scope], e: &Expr) -> Option<Column> {
+    let last = levels.len().checked_sub(1)?;
+    match e {
+        Expr::Identifier(c) => match levels[last].tables.as_slice() {
+            [(q, _)] => Some((last, (q.clone(), c.value.to_ascii_lowercase()))),
+            _ => None,
+        },
+        Expr::CompoundIdentifier(idents) if idents.len() >= 2 => {
+            let q = &idents[idents.len() - 2].value;
+            let column = idents[idents.len() - 1].value.to_ascii_lowercase();
+            (0..=last)
+                .rev()
+                .find(|l| levels[*l].tables.iter().any(|(tq, _)| tq == q))
+                .map(|l| (l, (q.clone(), column)))
+        }
+        Expr::Nested(e) => resolve(levels, e),
+        _ => None,
+    }
+}
+
+//add the shard keys of the innermost level which an `=` of its joins or its where equates with
+//a key, of this level or of one around it.
+fn close_keys(level: &Level, levels: &mut [Scope], sharded: &[(&str, &str)]) {
+    let mut conjuncts = Vec::new();
+    for e in level.on.iter().chain(level.selection.iter()) {
+        and_conjuncts(e, &mut conjuncts);
+    }
+    let pairs: Vec<(Column, Column)> = conjuncts
+        .into_iter()
+        .filter_map(|e| match e {
+            Expr::BinaryOp {
+                left,
+                op: BinaryOperator::Eq,
+                right,
+            } => Some((resolve(levels, left)?, resolve(levels, right)?)),
+            _ => None,
+        })
+        .collect();
+    let last = levels.len() - 1;
+    loop {
+        let mut added = false;
+        for (l, r) in pairs.iter() {
+            for ((al, a), (bl, b)) in [(l, r), (r, l)] {
+                if levels[*al].keys.contains(a)
+                    && *bl == last
+                    && !levels[last].keys.contains(b)
+                    && levels[last].shard_key(&b.0, &b.1, sharded)
+                {
+                    levels[last].keys.push(b.clone());
+                    added = true;
+                }
+            }
+        }
+        if !added {
+            return;
+        }
+    }
+}
+
+fn check_query(q: &Query, sharded: &[(&str, &str)], levels: &mut Vec<Scope>) -> Option<String> {
+    let ctes: Vec<&Query> = q
+        .with
+        .iter()
+        .flat_map(|w| w.cte_tables.iter().map(|c| &*c.query))
+        .collect();
+    check_set_expr(&q.body, &ctes, sharded, levels)
+}
+
+fn check_set_expr(
+    body: &SetExpr,
+    ctes: &[&Query],
+    sharded: &[(&str, &str)],
+    levels: &mut Vec<Scope>,
+) -> Option<String> {
+    match body {
+        SetExpr::Select(s) => check_level(&Level::of_select(s), ctes, sharded, levels),
+        SetExpr::Query(q) => check_query(q, sharded, levels),
+        SetExpr::SetOperation { left, right, .. } => check_set_expr(left, ctes, sharded, levels)
+            .or_else(|| check_set_expr(right, ctes, sharded, levels)),
+        _ => None,
+    }
+}
+
+//a level under no keyed level routes by the primary table when it has it, a level under or
+//with keys must have the shard key of each of its sharded tables keyed.
+fn check_level(
+    level: &Level,
+    ctes: &[&Query],
+    sharded: &[(&str, &str)],
+    levels: &mut Vec<Scope>,
+) -> Option<String> {
+    let keyed_around = levels.iter().any(|s| !s.keys.is_empty());
+    let mut scope = Scope::of(level);
+    if !keyed_around {
+        scope.seed(sharded);
+    }
+    levels.push(scope);
+    close_keys(level, levels, sharded);
+    let scope = &levels[levels.len() - 1];
+    let mut found = None;
+    if keyed_around || !scope.keys.is_empty() {
+        found = scope.tables.iter().find_map(|(q, t)| {
+            let t = t.as_deref()?;
+            let (_, key) = sharded.iter().find(|(st, _)| *st == t)?;
+            let keyed = scope
+                .keys
+                .iter()
+                .any(|(kq, kc)| kq == q && key.eq_ignore_ascii_case(kc));
+            (!keyed).then(|| t.to_string())
+        });
+    }
+    if found.is_none() {
+        found = level
+            .subqueries()
+            .into_iter()
+            .chain(ctes.iter().copied())
+            .find_map(|q| check_query(q, sharded, levels));
+    }
+    levels.pop();
+    found
+}
+
+fn and_conjuncts<'a>(e: &'a Expr, out: &mut Vec<&'a Expr>) {
+    match e {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            and_conjuncts(left, out);
+            and_conjuncts(right, out);
+        }
+        Expr::Nested(e) => and_conjuncts(e, out),
+        e => out.push(e),
+    }
+}
+
+//the subqueries of an expression, not those nested in them.
+fn subqueries_of_expr<'a>(e: &'a Expr, out: &mut Vec<&'a Query>) {
+    match e {
+        Expr::Subquery(q) | Expr::ArraySubquery(q) | Expr::Exists { subquery: q, .. } => {
+            out.push(q)
+        }
+        Expr::InSubquery { expr, subquery, .. } => {
+            subqueries_of_expr(expr, out);
+            out.push(subquery);
+        }
+        Expr::BinaryOp { left, right, .. }
+        | Expr::IsDistinctFrom(left, right)
+        | Expr::IsNotDistinctFrom(left, right)
+        | Expr::Like {
+            expr: left,
+            pattern: right,
+            ..
+        } => {
+            subqueries_of_expr(left, out);
+            subqueries_of_expr(right, out);
+        }
+        Expr::InList { expr, list, .. } => {
+            subqueries_of_expr(expr, out);
+            for x in list.iter() {
+                subqueries_of_expr(x, out);
+            }
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => {
+            for x in [expr, low, high] {
+                subqueries_of_expr(x, out);
+            }
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::Cast { expr, .. }
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr)
+        | Expr::AnyOp(expr)
+        | Expr::AllOp(expr) => subqueries_of_expr(expr, out),
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            for x in operand
+                .iter()
+                .chain(else_result.iter())
+                .map(|b| &**b)
+                .chain(conditions.iter())
+                .chain(results.iter())
+            {
+                subqueries_of_expr(x, out);
+            }
+        }
+        Expr::Function(f) => {
+            for a in f.args.iter() {
+                if let FunctionArg::Named {
+                    arg: FunctionArgExpr::Expr(x),
+                    ..
+                }
+                | FunctionArg::Unnamed(FunctionArgExpr::Expr(x)) = a
+                {
+                    subqueries_of_expr(x, out);
+                }
+            }
+        }
+        Expr::Tuple(list) => {
+            for x in list.iter() {
+                subqueries_of_expr(x, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn shard_condition_of_expr(expr: &Expr, is_key: &dyn Fn(&Expr) -> bool) -> Option<ShardCondition> {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => ShardCondition::and(
+            shard_condition_of_expr(left, is_key),
+            shard_condition_of_expr(right, is_key),
+        ),
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Or,
+            right,
+        } => ShardCondition::or(
+            shard_condition_of_expr(left, is_key),
+            shard_condition_of_expr(right, is_key),
+        ),
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Eq,
+            right,
+        } => {
+            if is_key(left) {
+                literal_of_expr(right).map(|v| ShardCondition::Values(vec![v]))
+            } else if is_key(right) {
+                literal_of_expr(left).map(|v| ShardCondition::Values(vec![v]))
+            } else {
+                None
+            }
+        }
+        Expr::InList {
+            expr,
+            list,
+            negated: false,
+        } if is_key(expr) => {
+            let mut values: Vec<String> = Vec::with_capacity(list.len());
+            for e in list.iter() {
+                let v = literal_of_expr(e)?;
+                if !values.contains(&v) {
+                    values.push(v);
+                }
+            }
+            Some(ShardCondition::Values(values))
+        }
+        Expr::Between {
+            expr,
+            negated: false,
+            low,
+            high,
+        } if is_key(expr) => {
+            let low = literal_of_expr(low)?.parse::<u128>().ok()?;
+            let high = literal_of_expr(high)?.parse::<u128>().ok()?;
+            Some(ShardCondition::Range(low, high))
+        }
+        Expr::Nested(e) => shard_condition_of_expr(e, is_key),
+        _ => None,
+    }
+}
+
+//the shard value of each row of `INSERT INTO t (..) VALUES (..), (..)`, None if any row has none.
+pub fn insert_shard_values(stmt: &Statement, shard_key: &str) -> Option<Vec<String>> {
+    let (columns, source) = match stmt {
+        Statement::Insert {
+            columns, source, ..
+        } => (columns, source),
+        _ => return None,
+    };
+    let pos = columns
+        .iter()
+        .position(|c| c.value.eq_ignore_ascii_case(shard_key))?;
+    match &*source.body {
+        SetExpr::Values(values) => values
+            .0
+            .iter()
+            .map(|row| row.get(pos).and_then(literal_of_expr))
+            .collect(),
+        _ => None,
+    }
+}
+
+//keep only the rows of the given positions in `INSERT ... VALUES`.
+pub fn retain_insert_rows(stmt: &mut Statement, rows: &[usize]) {
+    if let Statement::Insert { source, .. } = stmt {
+        if let SetExpr::Values(values) = &mut *source.body {
+            let all = std::mem::take(&mut values.0);
+            values.0 = all
+                .into_iter()
+                .enumerate()
+                .filter(|(i, _)| rows.contains(i))
+                .map(|(_, row)| row)
+                .collect();
+        }
+    }
+}
+
+pub fn is_column(expr: &Expr, column: &str) -> bool {
+    match expr {
+        Expr::Identifier(i) => i.value.eq_ignore_ascii_case(column),
+        Expr::CompoundIdentifier(idents) => idents
+            .last()
+            .map(|i| i.value.eq_ignore_ascii_case(column))
+            .unwrap_or(false),
+        Expr::Nested(e) => is_column(e, column),
+        _ => false,
+    }
+}
+
+//the text of a number or string literal, which is the form the router takes shard values in.
+pub fn literal_of_expr(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Value(Value::Number(n, _)) => Some(n.clone()),
+        Expr::Value(Value::SingleQuotedString(s)) => Some(s.clone()),
+        Expr::Value(Value::DoubleQuotedString(s)) => Some(s.clone()),
+        Expr::Nested(e) => literal_of_expr(e),
+        _ => None,
+    }
+}
+
+pub fn is_read_only(stmt: &Statement) -> bool {
+    matches!(
+        stmt,
+        Statement::Query(_)
+            | Statement::ShowTables { .. }
+            | Statement::ShowColumns { .. }
+            | Statement::ShowCreate { .. }
+            | Statement::ShowVariables { .. }
+            | Statement::ShowVariable { .. }
+            | Statement::ExplainTable { .. }
+            | Statement::Explain { .. }
+    )
+}
+
+//the dbs named by a statement whose tables are not collected for routing: the qualifier of a
+//ddl or SHOW on a table, the db of CREATE/DROP DATABASE and SHOW TABLES, and those of the
+//statement an EXPLAIN is of.
+pub fn named_dbs(stmt: &Statement) -> Vec<String> {
+    let qualifier = |name: &ObjectName| match name.0.as_slice() {
+        [db, _] => Some(db.value.clone()),
+        _ => None,
+    };
+    let db = |name: &ObjectName| name.0.first().map(|i| i.value.clone());
+    match stmt {
+        Statement::CreateTable { name, .. }
+        | Statement::CreateView { name, .. }
+        | Statement::AlterTable { name, .. }
+        | Statement::Truncate {
+            table_name: name, ..
+        }
+        | Statement::Analyze {
+            table_name: name, ..
+        }
+        | Statement::CreateIndex {
+            table_name: name, ..
+        }
+        | Statement::ShowColumns {
+            table_name: name, ..
+        }
+        | Statement::ShowCreate { obj_name: name, .. }
+        | Statement::ExplainTable {
+            table_name: name, ..
+        } => qualifier(name).into_iter().collect(),
+        Statement::Drop {
+            object_type: ObjectType::Schema,
+            names,
+            ..
+        } => names.iter().filter_map(db).collect(),
+        Statement::Drop { names, .. } => names.iter().filter_map(qualifier).collect(),
+        Statement::CreateDatabase { db_name, .. }
+        | Statement::CreateSchema {
+            schema_name: SchemaName::Simple(db_name) | SchemaName::NamedAuthorization(db_name, _),
+            ..
+        } => db(db_name).into_iter().collect(),
+        Statement::ShowTables {
+            db_name: Some(db), ..
+        } => vec![db.value.clone()],
+        Statement::Explain { statement, .. } => {
+            let tables = super::rewrite::collect_tables(statement);
+            let mut dbs: Vec<String> = tables.into_iter().filter_map(|t| t.db).collect();
+            dbs.extend(named_dbs(statement));
+            dbs
+        }
+        _ => Vec::new(),
+    }
+}
+
+//a leading `/*+ master */` sends a read to the master, e.g. to read what was just written.
+pub fn has_master_hint(sql: &str) -> bool {
+    let rest = match sql.trim_start().strip_prefix("/*+") {
+        Some(rest) => rest,
+        None => return false,
+    };
+    match rest.find("*/") {
+        Some(end) => rest[..end].trim().eq_ignore_ascii_case("master"),
+        None => false,
+    }
+}
+
+//the byte offsets of the `?` placeholders, the ones in quotes or comments are not counted.
+pub fn placeholder_offsets(sql: &str) -> Vec<usize> {
+    let bytes = sql.as_bytes();
+    let mut offsets = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'?' => offsets.push(pos),
+            q @ (b'\'' | b'"' | b'`') => {
+                pos += 1;
+                while pos < bytes.len() && bytes[pos] != q {
+                    if bytes[pos] == b'\\' && q != b'`' {
+                        pos += 1;
+                    }
+                    pos += 1;
+                }
+            }
+            b'#' => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            }
+            b'-' if bytes[pos..].starts_with(b"-- ") => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+            }
+            b'/' if bytes[pos..].starts_with(b"/*") => {
+                pos += 2;
+                while pos < bytes.len() && !bytes[pos..].starts_with(b"*/") {
+                    pos += 1;
+                }
+                pos += 1;
+            }
+            _ => {}
+        }
+        pos += 1;
+    }
+    offsets
+}
+
+//replace the placeholders with the literals in order, the sql must have as many placeholders.
+pub fn bind_placeholders(sql: &str, literals: &[String]) -> String {
+    let mut bound =
+        String::with_capacity(sql.len() + literals.iter().map(|l| l.len()).sum::<usize>());
+    let mut last = 0;
+    for (offset, literal) in placeholder_offsets(sql).into_iter().zip(literals.iter()) {
+        bound.push_str(&sql[last..offset]);
+        bound.push_str(literal);
+        last = offset + 1;
+    }
+    bound.push_str(&sql[last..]);
+    bound
+}
+
+//the statements which control the transaction of the session, the proxy handles them itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxnStatement {
+    Begin(String), //BEGIN or START TRANSACTION, the text is sent to each backend as it is.
+    Commit,
+    Rollback,
+    SetAutocommit(bool),
+    Savepoint(String),
+    RollbackToSavepoint(String),
+    ReleaseSavepoint(String),
+}
+
+pub fn txn_statement(sql: &str) -> Option<TxnStatement> {
+    let text = sql.trim().trim_end_matches(';').trim_end();
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|w| w.to_ascii_uppercase())
+        .collect();
+    let words: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+    //the savepoint name is the last word, in its own case.
+    let name = || -> String {
+        let last = text.split_whitespace().last().unwrap_or_default();
+        last.trim_matches('`').to_string()
+    };
+    match words.as_slice() {
+        ["BEGIN"] | ["BEGIN", "WORK"] => Some(TxnStatement::Begin(text.to_string())),
+        ["START", "TRANSACTION", ..] => Some(TxnStatement::Begin(text.to_string())),
+        ["COMMIT"] | ["COMMIT", "WORK"] => Some(TxnStatement::Commit),
+        ["ROLLBACK"] | ["ROLLBACK", "WORK"] => Some(TxnStatement::Rollback),
+        ["SAVEPOINT", _] => Some(TxnStatement::Savepoint(name())),
+        ["RELEASE", "SAVEPOINT", _] => Some(TxnStatement::ReleaseSavepoint(name())),
+        ["ROLLBACK", "TO", _]
+        | ["ROLLBACK", "WORK", "TO", _]
+        | ["ROLLBACK", "TO", "SAVEPOINT", _]
+        | ["ROLLBACK", "WORK", "TO", "SAVEPOINT", _] => {
+            Some(TxnStatement::RollbackToSavepoint(name()))
+        }
+        ["SET", ..] => {
+            //SET autocommit = 0, SET @@session.autocommit=ON ...
+            let assignment: String = text[3..]
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>()
+                .to_ascii_lowercase();
+            let assignment = ["@@session.", "@@local.", "@@", "session", "local"]
+                .iter()
+                .find_map(|p| assignment.strip_prefix(p))
+                .unwrap_or(&assignment);
+            match assignment.strip_prefix("autocommit=")? {
+                "1" | "on" | "true" => Some(TxnStatement::SetAutocommit(true)),
+                "0" | "off" | "false" => Some(TxnStatement::SetAutocommit(false)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+//the db of `USE db`, which the proxy answers like COM_INIT_DB.
+pub fn use_statement(sql: &str) -> Option<String> {
+    let text = sql.trim().trim_end_matches(';').trim_end();
+    let mut words = text.split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some(w), Some(db), None) if w.eq_ignore_ascii_case("USE") => {
+            Some(db.trim_matches('`').to_string())
+        }
+        _ => None,
+    }
+}
+
+//the statements on the schema which the proxy answers with its logical view, the db and the
+//table of the router instead of the physical ones of the backends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatalogStatement {
+    ShowDatabases(Option<String>), //the LIKE pattern.
+    ShowTables {
+        db: Option<String>,
+        full: bool,
+        like: Option<String>,
+    },
+    ShowCreateTable {
+        db: Option<String>,
+        table: String,
+    },
+    //DESCRIBE t, SHOW [FULL] COLUMNS FROM t.
+    ShowColumns {
+        db: Option<String>,
+        table: String,
+        full: bool,
+    },
+    //a select of information_schema.tables, or of `tables` in the db information_schema.
+    InformationSchemaTables {
+        query: Box<Query>,
+        qualified: bool,
+    },
+}
+
+//information_schema is on every backend, the proxy does not route it by the router.
+pub fn is_information_schema(db: &str) -> bool {
+    db.eq_ignore_ascii_case("information_schema")
+}
+
+pub fn catalog_statement(sql: &str) -> Option<CatalogStatement> {
+    let text = sql.trim().trim_end_matches(';').trim_end();
+    let verb = text.split_whitespace().next()?;
+    if verb.eq_ignore_ascii_case("SELECT") {
+        return information_schema_tables(text);
+    }
+    let verbs = ["SHOW", "DESCRIBE", "DESC", "EXPLAIN"];
+    if !verbs.iter().any(|v| verb.eq_ignore_ascii_case(v)) {
+        return None;
+    }
+    let raw: Vec<&str> = text.split_whitespace().collect();
+    let words: Vec<String> = raw.iter().map(|w| w.to_ascii_uppercase()).collect();
+    let words: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+    let unquote = |w: &str| w.trim_matches('`').to_string();
+    //`LIKE 'pattern'` at the end, its pattern is in its own case.
+    let like = |at: usize| -> Option<Option<String>> {
+        match words.get(at..)? {
+            [] => Some(None),
+            ["LIKE", _] => Some(Some(
+                raw[at + 1]
+                    .trim_matches(|c| c == '\'' || c == '"')
+                    .to_string(),
+            )),
+            _ => None,
+        }
+    };
+    match words.as_slice() {
+        ["SHOW", "DATABASES" | "SCHEMAS", ..] => like(2).map(CatalogStatement::ShowDatabases),
+        ["SHOW", "FULL", "TABLES", ..] | ["SHOW", "TABLES", ..] => {
+            let full = words[1] == "FULL";
+            let at = if full { 3 } else { 2 };
+            let (db, at) = match words.get(at) {
+                Some(&"FROM") | Some(&"IN") => (Some(unquote(raw.get(at + 1)?)), at + 2),
+                _ => (None, at),
+            };
+            like(at).map(|like| CatalogStatement::ShowTables { db, full, like })
+        }
+        ["SHOW", "CREATE", "TABLE", _] => {
+            let (db, table) = split_name(raw[3]);
+            Some(CatalogStatement::ShowCreateTable { db, table })
+        }
+        ["DESCRIBE" | "DESC" | "EXPLAIN", _] => {
+            let (db, table) = split_name(raw[1]);
+            Some(CatalogStatement::ShowColumns {
+                db,
+                table,
+                full: false,
+            })
+        }
+        ["SHOW", "FULL", "COLUMNS" | "FIELDS", "FROM" | "IN", _, ..]
+        | ["SHOW", "COLUMNS" | "FIELDS", "FROM" | "IN", _, ..] => {
+            let full = words[1] == "FULL";
+            let at = if full { 4 } else { 3 };
+            let (db, table) = split_name(raw[at]);
+            let db = match words.get(at + 1..)? {
+                [] => db,
+                ["FROM" | "IN", _] => Some(unquote(raw[at + 2])),
+                _ => return None,
+            };
+            Some(CatalogStatement::ShowColumns { db, table, full })
+        }
+        _ => None,
+    }
+}
+
+//a select whose FROM is information_schema.tables alone, it is only parsed when it names tables.
+fn information_schema_tables(sql: &str) -> Option<CatalogStatement> {
+    if !sql
+        .as_bytes()
+        .windows(6)
+        .any(|w| w.eq_ignore_ascii_case(b"tables"))
+    {
+        return None;
+    }
+    let query = match parse_one(sql).ok()? {
+        Statement::Query(query) => query,
+        _ => return None,
+    };
+    let from = match query.body.as_ref() {
+        SetExpr::Select(select) => match select.from.as_slice() {
+            [from] if from.joins.is_empty() => &from.relation,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let qualified = match from {
+        TableFactor::Table { name, .. } => match name.0.as_slice() {
+            [db, table]
+                if is_information_schema(&db.value)
+                    && table.value.eq_ignore_ascii_case("tables") =>
+            {
+                true
+            }
+            [table] if table.value.eq_ignore_ascii_case("tables") => false,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(CatalogStatement::InformationSchemaTables { query, qualified })
+}
+
+//`db`.`table` or table.
+fn split_name(name: &str) -> (Option<String>, String) {
+    let unquote = |w: &str| w.trim_matches('`').to_string();
+    match name.split_once("`.`").or_else(|| name.split_once('.')) {
+        Some((db, table)) => (Some(unquote(db)), unquote(table)),
+        None => (None, unquote(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        catalog_statement, has_master_hint, txn_statement, use_statement, CatalogStatement,
+        TxnStatement,
+    };
+
+    #[test]
+    fn classify_txn_statements() {
+        assert_eq!(
+            txn_statement("start transaction read only;"),
+            Some(TxnStatement::Begin(
+                "start transaction read only".to_string()
+            ))
+        );
+        assert_eq!(txn_statement(" COMMIT work "), Some(TxnStatement::Commit));
+        assert_eq!(
+            txn_statement("SET @@session.autocommit = OFF"),
+            Some(TxnStatement::SetAutocommit(false))
+        );
+        assert_eq!(
+            txn_statement("set autocommit=1"),
+            Some(TxnStatement::SetAutocommit(true))
+        );
+        assert_eq!(txn_statement("SET autocommit=1, names utf8"), None);
+        assert_eq!(
+            txn_statement("rollback work to savepoint `Sp1`"),
+            Some(TxnStatement::RollbackToSavepoint("Sp1".to_string()))
+        );
+        assert_eq!(
+            txn_statement("RELEASE SAVEPOINT sp1"),
+            Some(TxnStatement::ReleaseSavepoint("sp1".to_string()))
+        );
+        assert_eq!(txn_statement("SELECT 1"), None);
+    }
+
+    #[test]
+    fn classify_catalog_statements() {
+        assert_eq!(
+            catalog_statement("show schemas like 'db%';"),
+            Some(CatalogStatement::ShowDatabases(Some("db%".to_string())))
+        );
+        assert_eq!(
+            catalog_statement("SHOW FULL TABLES FROM `db1` LIKE 'Stu%'"),
+            Some(CatalogStatement::ShowTables {
+                db: Some("db1".to_string()),
+                full: true,
+                like: Some("Stu%".to_string()),
+            })
+        );
+        assert_eq!(
+            catalog_statement("show create table `db1`.`student`"),
+            Some(CatalogStatement::ShowCreateTable {
+                db: Some("db1".to_string()),
+                table: "student".to_string(),
+            })
+        );
+        assert_eq!(
+            catalog_statement("desc student"),
+            Some(CatalogStatement::ShowColumns {
+                db: None,
+                table: "student".to_string(),
+                full: false,
+            })
+        );
+        assert_eq!(
+            catalog_statement("SHOW FULL COLUMNS FROM student IN db1"),
+            Some(CatalogStatement::ShowColumns {
+                db: Some("db1".to_string()),
+                table: "student".to_string(),
+                full: true,
+            })
+        );
+        let tables = |sql: &str| match catalog_statement(sql) {
+            Some(CatalogStatement::InformationSchemaTables { qualified, .. }) => Some(qualified),
+            _ => None,
+        };
+        assert_eq!(
+            tables("select table_name from INFORMATION_SCHEMA.`TABLES` t"),
+            Some(true)
+        );
+        assert_eq!(tables("SELECT * FROM tables"), Some(false));
+        assert_eq!(tables("select * from information_schema.tablespaces"), None);
+        assert_eq!(
+            tables("select 'information_schema.tables' from information_schema.columns"),
+            None
+        );
+        assert_eq!(
+            tables("select * from t where x in (select 1 from information_schema.tables)"),
+            None
+        );
+        assert_eq!(catalog_statement("explain select * from student"), None);
+        assert_eq!(catalog_statement("show tables where x"), None);
+    }
+
+    #[test]
+    fn detect_use_statement() {
+        assert_eq!(use_statement("use `db1`;"), Some("db1".to_string()));
+        assert_eq!(use_statement(" USE db2 "), Some("db2".to_string()));
+        assert_eq!(use_statement("use"), None);
+        assert_eq!(use_statement("select use from t"), None);
+    }
+
+    #[test]
+    fn detect_master_hint() {
+        assert!(has_master_hint(" /*+ MASTER */ select * from t"));
+        assert!(has_master_hint("/*+master*/select 1"));
+        assert!(!has_master_hint("/* master */ select 1"));
+        assert!(!has_master_hint("select /*+ master */ 1"));
+    }
+}
@@ -1,7 +1,13 @@
 #![allow(dead_code)]
-use super::error::BackendResult;
-use crate::mysql::{constants, packetio, utils};
+use super::error::{BackendError, BackendResult};
+use crate::mysql::constants::command;
+use crate::mysql::{constants, packet, packetio, utils};
+use byteorder::{ByteOrder, LittleEndian as LE, WriteBytesExt};
+use mysql_common::{crypto, scramble};
+use regex::bytes::Regex;
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream; //should async???
 
 #[derive(Debug)]
@@ -19,16 +25,24 @@ pub struct P2MConn {
     cluster_id: String,
     pub node_id: String,
     db: String,
+    //(db, sql) -> statement id, the statements prepared on this conn.
+    stmts: HashMap<(String, String), u32>,
+    created_at: Instant,
+    last_used: Instant, //the time it went back to the pool.
     //--
     quited: AtomicBool,
 }
 
+//the statements prepared on one conn are closed all together beyond this.
+const MAX_PREPARED_STMTS: usize = 1024;
+
 impl P2MConn {
     pub async fn build_conn(
         tcp: TcpStream,
         mysql_user: String,
         mysql_pwd: String,
         mysql_addr: String,
+        db: String,
         cluster_id: String,
         node_id: String,
     ) -> BackendResult<P2MConn> {
@@ -38,7 +52,6 @@ impl P2MConn {
         let salt: Vec<u8> = utils::random_salt(20)?;
         let collation_id: u8 = constants::UTF8MB4_GENERAL_CI;
         let status = constants::StatusFlags::SERVER_STATUS_AUTOCOMMIT;
-        let db: String = String::new();
         Ok(P2MConn {
             pkg,
             conn_id,
@@ -52,37 +65,359 @@ impl P2MConn {
             node_id,
             cluster_id,
             db,
+            stmts: HashMap::new(),
+            created_at: Instant::now(),
+            last_used: Instant::now(),
             quited: AtomicBool::new(false),
         })
     }
-    pub async fn ping(&self) -> BackendResult<()> {
+    pub async fn ping(&mut self) -> BackendResult<()> {
         //1. send mysql ping command
+        self.write_command(command::COM_PING, &[]).await?;
         //2. wait read result
-        unimplemented!();
+        self.read_ok().await?;
+        Ok(())
     }
-    pub async fn close(&self) {
-        //pool.recycle(this);
-        unimplemented!();
+    //quits politely, the backend is told by COM_QUIT before the stream is shut down.
+    pub async fn close(mut self) {
+        if self.quited.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        if let Err(e) = self.write_command(command::COM_QUIT, &[]).await {
+            log::info!(
+                "quit mysql conn failed, node: {}, error: {}",
+                &self.node_id,
+                e
+            );
+        }
+        if let Err(e) = self.pkg.shutdown().await {
+            log::info!(
+                "shutdown mysql conn failed, node: {}, error: {}",
+                &self.node_id,
+                e
+            );
+        }
     }
     //真正的关闭网络链接。
+    //only the first call does the work, drop calls it again.
     #[allow(unused_must_use)]
     pub fn quit(&self) {
-        let result =
-            self.quited
-                .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed);
-        let result = match result {
-            Ok(r) => r,
-            Err(error) => {
-                panic!("Compare and exchange error: {:?}", error)
-            }
-        };
-        if !result {
+        if self
+            .quited
+            .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
             self.pkg.quit();
         }
     }
 
+    //proxy to mysql server handshake, the proxy plays the client here.
+    //https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_connection_phase.html
     pub async fn handshake(&mut self) -> BackendResult<()> {
-        unimplemented!();
+        self.pkg.reset_seq();
+        let plugin = self.read_initial_handshake().await?;
+        let auth = self.auth_response(&plugin)?;
+        self.write_handshake_response(&plugin, &auth).await?;
+        self.read_auth_result(plugin).await?;
+        self.pkg.reset_seq();
+        Ok(())
+    }
+
+    //https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_connection_phase_packets_protocol_handshake_v10.html
+    async fn read_initial_handshake(&mut self) -> BackendResult<String> {
+        let data = self.pkg.read_packet().await?;
+        if data.first() == Some(&constants::ERR_PACKET_HEADER_MARK) {
+            return Err(BackendError::ServerErr(packet::ErrPacket::parse(&data)?));
+        }
+        if data.first() != Some(&constants::MIN_PROTOCOL_VERSION) {
+            return Err(BackendError::HandshakeErrProtocolVersion(
+                data.first().copied().unwrap_or(0),
+            ));
+        }
+        let mut pos: usize = 1;
+        //server version[00]
+        pos += data[pos..]
+            .iter()
+            .position(|&x| x == 0)
+            .ok_or(BackendError::HandshakeErrMalformed)?
+            + 1;
+        //connection id, auth-plugin-data-part-1, filler [00], the lower 2 bytes of the capability
+        if data.len() < pos + 4 + 8 + 1 + 2 {
+            return Err(BackendError::HandshakeErrMalformed);
+        }
+        self.conn_id = LE::read_u32(&data[pos..]);
+        pos += 4;
+        let mut salt = data[pos..pos + 8].to_vec();
+        pos += 8 + 1;
+        let mut server_capability = LE::read_u16(&data[pos..]) as u32;
+        pos += 2;
+        let mut plugin = constants::AUTH_NATIVE_PASSWORD.to_string();
+        //charset, status, the upper 2 bytes of the capability, auth data length, reserved 10 [00]
+        if data.len() >= pos + 1 + 2 + 2 + 1 + 10 {
+            pos += 1;
+            self.status = constants::StatusFlags::from_bits_truncate(LE::read_u16(&data[pos..]));
+            pos += 2;
+            server_capability |= (LE::read_u16(&data[pos..]) as u32) << 16;
+            pos += 2;
+            let auth_data_len = data[pos] as usize;
+            pos += 1 + 10;
+            let server_capability =
+                constants::CapabilityFlags::from_bits_truncate(server_capability);
+            if server_capability.contains(constants::CapabilityFlags::CLIENT_SECURE_CONNECTION) {
+                //auth-plugin-data-part-2, at least 13 bytes, the last one is [00]
+                let part2_len = std::cmp::max(13, auth_data_len.saturating_sub(8));
+                let end = std::cmp::min(pos + part2_len, data.len());
+                salt.extend(data[pos..end].iter().take_while(|&&x| x != 0));
+                pos = end;
+            }
+            if server_capability.contains(constants::CapabilityFlags::CLIENT_PLUGIN_AUTH) {
+                //some servers do not terminate the plugin name with [00]
+                plugin = String::from_utf8_lossy(&data[pos..])
+                    .trim_end_matches('\0')
+                    .to_string();
+            }
+        }
+        self.salt = salt;
+        self.capability = (constants::get_default_capability_flags()
+            | constants::CapabilityFlags::CLIENT_PLUGIN_AUTH
+            | constants::CapabilityFlags::CLIENT_MULTI_RESULTS)
+            & constants::CapabilityFlags::from_bits_truncate(server_capability);
+        if self.db.is_empty() {
+            self.capability
+                .remove(constants::CapabilityFlags::CLIENT_CONNECT_WITH_DB);
+        }
+        log::info!(
+            "mysql initial handshake, node: {}, conn_id: {}, plugin: {}",
+            &self.node_id,
+            self.conn_id,
+            &plugin
+        );
+        Ok(plugin)
+    }
+
+    //scramble the password with the salt in the way the plugin asks for.
+    fn auth_response(&self, plugin: &str) -> BackendResult<Vec<u8>> {
+        if plugin == constants::AUTH_NATIVE_PASSWORD {
+            let salt = &self.salt[..std::cmp::min(20, self.salt.len())];
+            Ok(utils::scramble_password(salt, &self.mysql_pwd).unwrap_or_default())
+        } else if plugin == constants::AUTH_CACHING_SHA2_PASSWORD {
+            Ok(
+                scramble::scramble_sha256(&self.salt, self.mysql_pwd.as_bytes())
+                    .map(|s| s.to_vec())
+                    .unwrap_or_default(),
+            )
+        } else {
+            Err(BackendError::AuthErrUnsupportedPlugin(plugin.to_string()))
+        }
+    }
+
+    //https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_connection_phase_packets_protocol_handshake_response.html
+    async fn write_handshake_response(&mut self, plugin: &str, auth: &[u8]) -> BackendResult<()> {
+        let mut data: Vec<u8> = Vec::new();
+        data.write_u32::<LE>(self.capability.bits())?;
+        data.write_u32::<LE>(constants::MAX_PAYLOAD_LEN as u32)?;
+        data.push(self.collation_id);
+        //reserved 23[00]
+        data.extend_from_slice(&[0u8; 23]);
+        //user name[00]
+        data.extend_from_slice(self.mysql_user.as_bytes());
+        data.push(0u8);
+        //auth length and auth
+        data.push(auth.len() as u8);
+        data.extend_from_slice(auth);
+        //init with db[00]
+        if self
+            .capability
+            .contains(constants::CapabilityFlags::CLIENT_CONNECT_WITH_DB)
+        {
+            data.extend_from_slice(self.db.as_bytes());
+            data.push(0u8);
+        }
+        if self
+            .capability
+            .contains(constants::CapabilityFlags::CLIENT_PLUGIN_AUTH)
+        {
+            data.extend_from_slice(plugin.as_bytes());
+            data.push(0u8);
+        }
+        self.pkg.write_packet(&mut data).await?;
+        Ok(())
+    }
+
+    //OK, ERR, auth switch request or auth more data, until OK or ERR.
+    async fn read_auth_result(&mut self, mut plugin: String) -> BackendResult<()> {
+        loop {
+            let data = self.pkg.read_packet().await?;
+            match data.first() {
+                Some(&h) if h == constants::OK_PACKET_HEADER_MARK => {
+                    packet::OkPacket::parse(&data)?;
+                    return Ok(());
+                }
+                Some(&h) if h == constants::ERR_PACKET_HEADER_MARK => {
+                    return Err(BackendError::ServerErr(packet::ErrPacket::parse(&data)?));
+                }
+                //https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_connection_phase_packets_protocol_auth_switch_request.html
+                Some(&h) if h == constants::AUTH_SWITCH_REQUEST_MARK => {
+                    let name_len = data[1..]
+                        .iter()
+                        .position(|&x| x == 0)
+                        .ok_or(BackendError::HandshakeErrMalformed)?;
+                    plugin = String::from_utf8_lossy(&data[1..1 + name_len]).to_string();
+                    self.salt = data[2 + name_len..]
+                        .iter()
+                        .take_while(|&&x| x != 0)
+                        .copied()
+                        .collect();
+                    let mut auth = self.auth_response(&plugin)?;
+                    self.pkg.write_packet(&mut auth).await?;
+                }
+                Some(&h) if h == constants::AUTH_MORE_DATA_MARK => {
+                    if plugin != constants::AUTH_CACHING_SHA2_PASSWORD || data.len() < 2 {
+                        return Err(BackendError::HandshakeErrMalformed);
+                    }
+                    match data[1] {
+                        //the OK packet follows.
+                        m if m == constants::CACHING_SHA2_FAST_AUTH_SUCCESS => continue,
+                        m if m == constants::CACHING_SHA2_PERFORM_FULL_AUTH => {
+                            self.caching_sha2_full_auth().await?
+                        }
+                        _ => return Err(BackendError::HandshakeErrMalformed),
+                    }
+                }
+                _ => return Err(BackendError::HandshakeErrMalformed),
+            }
+        }
+    }
+
+    //no tls between proxy and mysql, so the password is encrypted with the rsa public key of the server.
+    async fn caching_sha2_full_auth(&mut self) -> BackendResult<()> {
+        self.pkg
+            .write_packet(&mut [constants::CACHING_SHA2_REQUEST_PUBLIC_KEY])
+            .await?;
+        let data = self.pkg.read_packet().await?;
+        if data.first() != Some(&constants::AUTH_MORE_DATA_MARK) || self.salt.is_empty() {
+            return Err(BackendError::HandshakeErrMalformed);
+        }
+        let mut pwd = self.mysql_pwd.as_bytes().to_vec();
+        pwd.push(0u8);
+        for (i, b) in pwd.iter_mut().enumerate() {
+            *b ^= self.salt[i % self.salt.len()];
+        }
+        //crypto::encrypt panics on a key it can not parse, or one too short for the password.
+        match rsa_key_octets(&data[1..]) {
+            Some(k) if k >= pwd.len() + RSA_OAEP_OVERHEAD => {}
+            _ => return Err(BackendError::AuthErrPublicKeyILL),
+        }
+        let mut encrypted = crypto::encrypt(&pwd, &data[1..]);
+        self.pkg.write_packet(&mut encrypted).await?;
+        Ok(())
+    }
+
+    //send one command packet, a command always starts a new sequence.
+    pub async fn write_command(&mut self, cmd: u8, arg: &[u8]) -> BackendResult<()> {
+        self.pkg.reset_seq();
+        let mut data: Vec<u8> = Vec::with_capacity(arg.len() + 1);
+        data.push(cmd);
+        data.extend_from_slice(arg);
+        self.pkg.write_packet(&mut data).await?;
+        Ok(())
+    }
+    #[inline]
+    pub async fn read_packet(&mut self) -> BackendResult<Vec<u8>> {
+        Ok(self.pkg.read_packet().await?)
+    }
+    //read an OK packet, an ERR packet from mysql is returned as BackendError::ServerErr.
+    pub async fn read_ok(&mut self) -> BackendResult<packet::OkPacket> {
+        let data = self.read_packet().await?;
+        match data.first() {
+            Some(&h) if h == constants::ERR_PACKET_HEADER_MARK => {
+                Err(BackendError::ServerErr(packet::ErrPacket::parse(&data)?))
+            }
+            _ => Ok(packet::OkPacket::parse(&data)?),
+        }
+    }
+    #[inline]
+    pub fn cluster_id(&self) -> &str {
+        &self.cluster_id
+    }
+    #[inline]
+    pub fn touch(&mut self) {
+        self.last_used = Instant::now();
+    }
+    #[inline]
+    pub fn idle_time(&self) -> Duration {
+        self.last_used.elapsed()
+    }
+    #[inline]
+    pub fn lifetime(&self) -> Duration {
+        self.created_at.elapsed()
+    }
+    //run a statement which returns no rows, such as COMMIT.
+    pub async fn query_ok(&mut self, sql: &str) -> BackendResult<packet::OkPacket> {
+        self.write_command(command::COM_QUERY, sql.as_bytes())
+            .await?;
+        self.read_ok().await
+    }
+    //switch the default db of the conn by COM_INIT_DB when it differs.
+    pub async fn use_db(&mut self, db: &str) -> BackendResult<()> {
+        if db.is_empty() || self.db == db {
+            return Ok(());
+        }
+        self.write_command(command::COM_INIT_DB, db.as_bytes())
+            .await?;
+        self.read_ok().await?;
+        self.db = db.to_string();
+        Ok(())
+    }
+    //the id of `sql` prepared on this conn, it is prepared by COM_STMT_PREPARE the first time.
+    //https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_stmt_prepare.html
+    pub async fn prepare(&mut self, sql: &str) -> BackendResult<u32> {
+        let key = (self.db.clone(), sql.to_string());
+        match self.stmts.get(&key) {
+            Some(id) => Ok(*id),
+            None => Ok(self.describe(sql).await?.0),
+        }
+    }
+    //prepare `sql` by COM_STMT_PREPARE, returning its id and its column definitions. the id is
+    //kept for later executes, a statement prepared before is closed in favour of the new one.
+    pub async fn describe(&mut self, sql: &str) -> BackendResult<(u32, Vec<Vec<u8>>)> {
+        if self.stmts.len() >= MAX_PREPARED_STMTS {
+            self.close_stmts().await?;
+        }
+        self.write_command(command::COM_STMT_PREPARE, sql.as_bytes())
+            .await?;
+        let data = self.read_packet().await?;
+        if data.first() == Some(&constants::ERR_PACKET_HEADER_MARK) {
+            return Err(BackendError::ServerErr(packet::ErrPacket::parse(&data)?));
+        }
+        let ok = packet::StmtPrepareOk::parse(&data)?;
+        //the parameter and column definitions, each followed by an EOF.
+        if ok.param_count > 0 {
+            for _ in 0..=ok.param_count {
+                self.read_packet().await?;
+            }
+        }
+        let mut columns = Vec::with_capacity(ok.column_count as usize);
+        for _ in 0..ok.column_count {
+            columns.push(self.read_packet().await?);
+        }
+        if ok.column_count > 0 {
+            self.read_packet().await?;
+        }
+        let key = (self.db.clone(), sql.to_string());
+        if let Some(old) = self.stmts.insert(key, ok.stmt_id) {
+            self.write_command(command::COM_STMT_CLOSE, &old.to_le_bytes())
+                .await?;
+        }
+        Ok((ok.stmt_id, columns))
+    }
+    //COM_STMT_CLOSE has no response.
+    async fn close_stmts(&mut self) -> BackendResult<()> {
+        for (_, id) in std::mem::take(&mut self.stmts) {
+            self.write_command(command::COM_STMT_CLOSE, &id.to_le_bytes())
+                .await?;
+        }
+        Ok(())
     }
 }
 
@@ -91,3 +426,324 @@ impl std::ops::Drop for P2MConn {
         self.quit();
     }
 }
+
+//the octets oaep padding with sha1 adds to an encrypted block.
+const RSA_OAEP_OVERHEAD: usize = 2 * 20 + 2;
+
+//the octets of the modulus of a pem rsa public key, None for a key crypto::encrypt can not parse.
+//the key is walked the same way mysql_common does it, but every length is checked on the way.
+fn rsa_key_octets(pem: &[u8]) -> Option<usize> {
+    let pkcs1 =
+        Regex::new("-----BEGIN RSA PUBLIC KEY-----([^-]*)-----END RSA PUBLIC KEY-----").ok()?;
+    let pkcs8 = Regex::new("-----BEGIN PUBLIC KEY-----([^-]*)-----END PUBLIC KEY-----").ok()?;
+    let (body, is_pkcs1) = match pkcs1.captures(pem) {
+        Some(c) => (c.get(1)?.as_bytes(), true),
+        None => (pkcs8.captures(pem)?.get(1)?.as_bytes(), false),
+    };
+    let body: Vec<u8> = body
+        .iter()
+        .filter(|b| !b" \n\t\r\x0b\x0c".contains(b))
+        .cloned()
+        .collect();
+    let der = base64::decode_config(&body, base64::STANDARD).ok()?;
+    let mut key = der.as_slice();
+    if !is_pkcs1 {
+        //SEQUENCE { SEQUENCE algorithm, BIT STRING pkcs1 key }
+        let (info, _) = der_field(key, 0x30)?;
+        let (_, info) = der_field(info, 0x30)?;
+        match der_field(info, 0x03)?.0.split_first() {
+            Some((0, pkcs1_key)) => key = pkcs1_key,
+            _ => return None,
+        }
+    }
+    //SEQUENCE { INTEGER modulus, INTEGER exponent }
+    let (fields, _) = der_field(key, 0x30)?;
+    let (modulus, fields) = der_field(fields, 0x02)?;
+    der_field(fields, 0x02)?;
+    let bits = match modulus.iter().position(|b| *b != 0) {
+        Some(i) => (modulus.len() - i) * 8 - modulus[i].leading_zeros() as usize,
+        None => 0,
+    };
+    Some((bits + 6) >> 3)
+}
+
+//a der field of the tag: (the content, the rest).
+fn der_field(der: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    let (&t, der) = der.split_first()?;
+    let (&first, mut der) = der.split_first()?;
+    if t != tag {
+        return None;
+    }
+    let mut len = first as usize;
+    if first & 0x80 > 0 {
+        let n = (first & 0x7f) as usize;
+        if der.len() < n {
+            return None;
+        }
+        len = der[..n]
+            .iter()
+            .try_fold(0usize, |l, b| l.checked_mul(256)?.checked_add(*b as usize))?;
+        der = &der[n..];
+    }
+    if der.len() < len {
+        return None;
+    }
+    Some(der.split_at(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::P2MConn;
+    use crate::backend::error::BackendError;
+    use crate::mysql::constants::{self, command, CapabilityFlags, ColumnType};
+    use crate::mysql::packet::ColumnDefinition;
+    use crate::mysql::{packet, packetio, utils};
+    use byteorder::{ByteOrder, LittleEndian as LE};
+    use mysql_common::scramble;
+    use tokio::net::{TcpListener, TcpStream};
+
+    const SALT: &[u8; 20] = b"abcdefghij0123456789";
+    const SWITCH_SALT: &[u8; 20] = b"ABCDEFGHIJ9876543210";
+
+    fn initial_handshake(plugin: &str) -> Vec<u8> {
+        let capability = constants::get_default_capability_flags()
+            | CapabilityFlags::CLIENT_PLUGIN_AUTH
+            | CapabilityFlags::CLIENT_MULTI_RESULTS;
+        let mut data = vec![constants::MIN_PROTOCOL_VERSION];
+        data.extend_from_slice(b"8.0.30\0");
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(&SALT[..8]);
+        data.push(0);
+        data.extend_from_slice(&(capability.bits() as u16).to_le_bytes());
+        data.push(constants::UTF8MB4_GENERAL_CI);
+        data.extend_from_slice(
+            &constants::StatusFlags::SERVER_STATUS_AUTOCOMMIT
+                .bits()
+                .to_le_bytes(),
+        );
+        data.extend_from_slice(&((capability.bits() >> 16) as u16).to_le_bytes());
+        data.push(21);
+        data.extend_from_slice(&[0u8; 10]);
+        data.extend_from_slice(&SALT[8..]);
+        data.push(0);
+        data.extend_from_slice(plugin.as_bytes());
+        data.push(0);
+        data
+    }
+
+    //user, auth response, db and plugin of a handshake response.
+    fn parse_response(data: &[u8]) -> (String, Vec<u8>, String, String) {
+        let capability = CapabilityFlags::from_bits_truncate(LE::read_u32(data));
+        let mut fields = data[32..].split(|&x| x == 0);
+        let user = String::from_utf8_lossy(fields.next().unwrap()).to_string();
+        let pos = 32 + user.len() + 1;
+        let auth_len = data[pos] as usize;
+        let auth = data[pos + 1..pos + 1 + auth_len].to_vec();
+        let mut rest = data[pos + 1 + auth_len..].split(|&x| x == 0);
+        let mut db = String::new();
+        if capability.contains(CapabilityFlags::CLIENT_CONNECT_WITH_DB) {
+            db = String::from_utf8_lossy(rest.next().unwrap()).to_string();
+        }
+        let plugin = String::from_utf8_lossy(rest.next().unwrap()).to_string();
+        (user, auth, db, plugin)
+    }
+
+    async fn write_ok(pkg: &mut packetio::PacketIO) {
+        let mut ok = vec![constants::OK_PACKET_HEADER_MARK, 0, 0, 2, 0, 0, 0];
+        pkg.write_packet(&mut ok).await.unwrap();
+    }
+
+    //a fake mysql server which accepts one conn and serves one handshake and one COM_PING.
+    async fn fake_mysql(plugin: &'static str, switch: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            if let Some(mut pkg) = accept(&listener, plugin, switch).await {
+                let ping = pkg.read_packet().await.unwrap();
+                assert_eq!(ping, vec![command::COM_PING]);
+                write_ok(&mut pkg).await;
+            }
+        });
+        addr
+    }
+
+    //accept one conn and serve its handshake, None when the password is refused.
+    async fn accept(
+        listener: &TcpListener,
+        plugin: &'static str,
+        switch: bool,
+    ) -> Option<packetio::PacketIO> {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut pkg = packetio::PacketIO::new(tcp);
+        pkg.write_packet(&mut initial_handshake(plugin))
+            .await
+            .unwrap();
+        let (user, auth, db, client_plugin) = parse_response(&pkg.read_packet().await.unwrap());
+        assert_eq!(user, "root");
+        assert_eq!(db, "db1");
+        assert_eq!(client_plugin, plugin);
+        if switch {
+            let mut req = vec![constants::AUTH_SWITCH_REQUEST_MARK];
+            req.extend_from_slice(constants::AUTH_NATIVE_PASSWORD.as_bytes());
+            req.push(0);
+            req.extend_from_slice(SWITCH_SALT);
+            req.push(0);
+            pkg.write_packet(&mut req).await.unwrap();
+            let auth = pkg.read_packet().await.unwrap();
+            assert_eq!(auth, utils::scramble_password(SWITCH_SALT, "pwd").unwrap());
+            write_ok(&mut pkg).await;
+        } else if plugin == constants::AUTH_CACHING_SHA2_PASSWORD {
+            assert_eq!(
+                auth,
+                scramble::scramble_sha256(SALT, b"pwd").unwrap().to_vec()
+            );
+            let mut fast = vec![
+                constants::AUTH_MORE_DATA_MARK,
+                constants::CACHING_SHA2_FAST_AUTH_SUCCESS,
+            ];
+            pkg.write_packet(&mut fast).await.unwrap();
+            write_ok(&mut pkg).await;
+        } else if auth == utils::scramble_password(SALT, "pwd").unwrap() {
+            write_ok(&mut pkg).await;
+        } else {
+            let mut err = packet::ErrPacket::new(1045, "Access denied".to_string()).to_bits();
+            pkg.write_packet(&mut err).await.unwrap();
+            return None;
+        }
+        pkg.reset_seq();
+        Some(pkg)
+    }
+
+    async fn connect(addr: &str, pwd: &str) -> Result<P2MConn, BackendError> {
+        let tcp = TcpStream::connect(addr).await?;
+        let mut conn = P2MConn::build_conn(
+            tcp,
+            "root".to_string(),
+            pwd.to_string(),
+            addr.to_string(),
+            "db1".to_string(),
+            "cluster_1".to_string(),
+            "mysql_1".to_string(),
+        )
+        .await?;
+        conn.handshake().await?;
+        Ok(conn)
+    }
+
+    #[tokio::test]
+    async fn handshake_and_ping() {
+        for (plugin, switch) in [
+            (constants::AUTH_NATIVE_PASSWORD, false),
+            (constants::AUTH_CACHING_SHA2_PASSWORD, false),
+            (constants::AUTH_CACHING_SHA2_PASSWORD, true),
+        ] {
+            let addr = fake_mysql(plugin, switch).await;
+            let mut conn = connect(&addr, "pwd").await.unwrap();
+            assert_eq!(conn.conn_id, 7);
+            conn.ping().await.unwrap();
+        }
+
+        let addr = fake_mysql(constants::AUTH_NATIVE_PASSWORD, false).await;
+        let err = connect(&addr, "bad").await.unwrap_err();
+        assert!(matches!(err, BackendError::ServerErr(p) if p.err_code() == 1045));
+    }
+
+    //the public key of mysql_common's own tests, 2048 bits.
+    const PUBLIC_KEY: &[u8] = br"-----BEGIN PUBLIC KEY-----
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAxSKOcxiet8lLMn8ImyUE
+bGGKob5EdRz/4wdiw12ED0GfKKTKhVnodFCfm1mdy7bKOX5QxL9skrvYodpW43eR
+R5bfOzIgy1qIB8RYb6qOXRBw1oA4snBDqtUjDv/lbHLJN+IbzM4oU+e3Lt9rXyLX
+VY289ewONPweXHqSCnTL91w+wkU1peIFV2QhZ+upUCdCtwOn5hnJPNgxtbklFoya
+C8W3Z7Xx7He2QDJsEWAqX197efw0L6j8X8Tyd8Uwb7zUB1tfMGhHfm9EwejPAtzx
+4GztQNtNMtGS2oGZLQBLV9hib4dDL92iiZeckg2LAf4GsJofLLR8mcHCRoqVbQJ1
+YQIDAQAB
+-----END PUBLIC KEY-----";
+
+    //a fake mysql server whose cache misses the password, so it hands out its public key.
+    async fn full_auth_mysql(key: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut pkg = packetio::PacketIO::new(tcp);
+            let mut handshake = initial_handshake(constants::AUTH_CACHING_SHA2_PASSWORD);
+            pkg.write_packet(&mut handshake).await.unwrap();
+            pkg.read_packet().await.unwrap();
+            let mut full = vec![
+                constants::AUTH_MORE_DATA_MARK,
+                constants::CACHING_SHA2_PERFORM_FULL_AUTH,
+            ];
+            pkg.write_packet(&mut full).await.unwrap();
+            let req = pkg.read_packet().await.unwrap();
+            assert_eq!(req, vec![constants::CACHING_SHA2_REQUEST_PUBLIC_KEY]);
+            let mut data = vec![constants::AUTH_MORE_DATA_MARK];
+            data.extend_from_slice(key);
+            pkg.write_packet(&mut data).await.unwrap();
+            //the password encrypted by the key, or nothing when the key is refused.
+            if let Ok(encrypted) = pkg.read_packet().await {
+                assert_eq!(encrypted.len(), 256);
+                write_ok(&mut pkg).await;
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn caching_sha2_full_auth() {
+        let addr = full_auth_mysql(PUBLIC_KEY).await;
+        connect(&addr, "pwd").await.unwrap();
+
+        let short = "-----BEGIN PUBLIC KEY-----\nMIIBIjAN\n-----END PUBLIC KEY-----";
+        let huge_len =
+            "-----BEGIN RSA PUBLIC KEY-----\nMIn/////////////\n-----END RSA PUBLIC KEY-----";
+        for key in [
+            &b"not a key"[..],
+            b"-----BEGIN PUBLIC KEY-----\n!!\n-----END PUBLIC KEY-----",
+            short.as_bytes(),
+            huge_len.as_bytes(),
+        ] {
+            let addr = full_auth_mysql(key).await;
+            let err = connect(&addr, "pwd").await.unwrap_err();
+            assert!(matches!(err, BackendError::AuthErrPublicKeyILL));
+        }
+        //a key too short for the password.
+        let addr = full_auth_mysql(PUBLIC_KEY).await;
+        let err = connect(&addr, &"p".repeat(256)).await.unwrap_err();
+        assert!(matches!(err, BackendError::AuthErrPublicKeyILL));
+    }
+
+    #[tokio::test]
+    async fn describe_and_cache_stmt() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let mut id = ColumnDefinition::new("id", ColumnType::MYSQL_TYPE_LONGLONG);
+        id.org_table = "t_1".to_string();
+        let column = id.to_bits();
+        let served = column.clone();
+        tokio::spawn(async move {
+            let mut pkg = accept(&listener, constants::AUTH_NATIVE_PASSWORD, false)
+                .await
+                .unwrap();
+            let prepare = pkg.read_packet().await.unwrap();
+            assert_eq!(prepare[0], command::COM_STMT_PREPARE);
+            assert_eq!(&prepare[1..], b"SELECT id FROM t_1 WHERE id = ?");
+            let ok = packet::StmtPrepareOk {
+                stmt_id: 3,
+                column_count: 1,
+                param_count: 1,
+                warnings: 0,
+            };
+            let eof = || packet::EofPacket::new(0, constants::StatusFlags::empty()).to_bits();
+            let param = ColumnDefinition::new("?", ColumnType::MYSQL_TYPE_LONGLONG).to_bits();
+            for mut data in [ok.to_bits(), param, eof(), served, eof()] {
+                pkg.write_packet(&mut data).await.unwrap();
+            }
+            //a second prepare of the same sql would find no answer here.
+            let _ = pkg.read_packet().await;
+        });
+        let mut conn = connect(&addr, "pwd").await.unwrap();
+        let sql = "SELECT id FROM t_1 WHERE id = ?";
+        assert_eq!(conn.describe(sql).await.unwrap(), (3, vec![column]));
+        assert_eq!(conn.prepare(sql).await.unwrap(), 3);
+    }
+}
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 use crate::mysql::errors::MySQLError;
+use crate::mysql::packet::ErrPacket;
 
 pub type BackendResult<T> = std::result::Result<T, BackendError>;
 
@@ -8,12 +9,21 @@ pub enum BackendError {
     InnerErrPipeEmpty,
     InnerErrOfflineOrQuit,
     InnerErrGreaterThenMaxConnCount,
+    InnerErrWaitQueueFull,
     PoolErrClusterIdNotFound(String),
     PoolErrNodeNotFound(String),
     PoolErrConnGrowFailed(String),
     PoolErrConnGrowGiveup(String),
+    PoolErrWeightILL(String),
+    PoolErrWaitTimeout(String),
     IO(std::io::Error),
     Mysql(MySQLError),
+    ServerErr(ErrPacket),
+    HandshakeErrMalformed,
+    HandshakeErrProtocolVersion(u8),
+    AuthErrUnsupportedPlugin(String),
+    AuthErrPublicKeyILL,
+    RelayErrLocalInfile,
 }
 
 impl From<std::io::Error> for BackendError {
@@ -35,9 +45,18 @@ impl std::error::Error for BackendError {
             BackendError::InnerErrOfflineOrQuit => None,
             BackendError::PoolErrConnGrowFailed(..) => None,
             BackendError::PoolErrConnGrowGiveup(..) => None,
+            BackendError::PoolErrWeightILL(..) => None,
             BackendError::InnerErrGreaterThenMaxConnCount => None,
+            BackendError::InnerErrWaitQueueFull => None,
+            BackendError::PoolErrWaitTimeout(..) => None,
             BackendError::IO(e) => e.source(),
             BackendError::Mysql(e) => e.source(),
+            BackendError::ServerErr(..) => None,
+            BackendError::HandshakeErrMalformed => None,
+            BackendError::HandshakeErrProtocolVersion(..) => None,
+            BackendError::AuthErrUnsupportedPlugin(..) => None,
+            BackendError::AuthErrPublicKeyILL => None,
+            BackendError::RelayErrLocalInfile => None,
         }
     }
 }
@@ -54,11 +73,35 @@ impl std::fmt::Display for BackendError {
                 write!(f, "node conn grow failed! node_id: {:?}", id)
             }
             BackendError::PoolErrConnGrowGiveup(id) => write!(f, "node: {:?} give up grow!", id),
+            BackendError::PoolErrWeightILL(msg) => write!(f, "ill read write weight: {}", msg),
+            BackendError::PoolErrWaitTimeout(id) => {
+                write!(f, "node: {:?} wait for a free conn timeout!", id)
+            }
             BackendError::InnerErrGreaterThenMaxConnCount => {
                 write!(f, "total conn count >= max conn limit!")
             }
+            BackendError::InnerErrWaitQueueFull => write!(f, "too many waiting for a free conn!"),
             BackendError::IO(e) => e.fmt(f),
             BackendError::Mysql(e) => e.fmt(f),
+            BackendError::ServerErr(p) => write!(
+                f,
+                "mysql server error: {}, {}",
+                p.err_code(),
+                String::from_utf8_lossy(p.err_msg())
+            ),
+            BackendError::HandshakeErrMalformed => write!(f, "malformed mysql handshake packet!"),
+            BackendError::HandshakeErrProtocolVersion(v) => {
+                write!(f, "unsupported mysql protocol version: {}", v)
+            }
+            BackendError::AuthErrUnsupportedPlugin(name) => {
+                write!(f, "unsupported mysql auth plugin: {:?}", name)
+            }
+            BackendError::AuthErrPublicKeyILL => {
+                write!(f, "ill rsa public key of mysql server for the password!")
+            }
+            BackendError::RelayErrLocalInfile => {
+                write!(f, "LOAD DATA LOCAL INFILE is not supported!")
+            }
         }
     }
 }
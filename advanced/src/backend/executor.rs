@@ -2,3 +2,176 @@
     为每一个mysql command and sql statement 相应执行入口。
     dispatcher 调用analyer{sql}  / router, 生成Plan, 然后统一交给executor执行。
 */
+#![allow(dead_code)]
+use super::conn::P2MConn;
+use super::error::{BackendError, BackendResult};
+use crate::mysql::constants::{self, command, StatusFlags};
+use crate::mysql::{packet, packetio, utils};
+
+//run one COM_QUERY on the backend conn and stream the whole response to the client as it is read.
+//https://dev.mysql.com/doc/internals/en/com-query-response.html
+pub async fn relay_query(
+    conn: &mut P2MConn,
+    sql: &str,
+    client: &mut packetio::PacketIO,
+) -> BackendResult<StatusFlags> {
+    relay_command(conn, command::COM_QUERY, sql.as_bytes(), client).await
+}
+
+//run a command as the client sent it, whose response is an OK, an ERR or result sets.
+pub async fn relay_command(
+    conn: &mut P2MConn,
+    cmd: u8,
+    arg: &[u8],
+    client: &mut packetio::PacketIO,
+) -> BackendResult<StatusFlags> {
+    conn.write_command(cmd, arg).await?;
+    relay_response(conn, client).await
+}
+
+//run a command whose response is one packet, such as the OK or ERR of COM_INIT_DB or the
+//string of COM_STATISTICS. the header byte of the packet is returned.
+pub async fn relay_one(
+    conn: &mut P2MConn,
+    cmd: u8,
+    arg: &[u8],
+    client: &mut packetio::PacketIO,
+) -> BackendResult<u8> {
+    conn.write_command(cmd, arg).await?;
+    let mut data = conn.read_packet().await?;
+    client.write_packet(&mut data).await?;
+    Ok(data[0])
+}
+
+//COM_STMT_PREPARE as the client sent it: the statement id of the backend goes to the client.
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_stmt_prepare.html
+pub async fn relay_prepare(
+    conn: &mut P2MConn,
+    sql: &str,
+    client: &mut packetio::PacketIO,
+) -> BackendResult<()> {
+    conn.write_command(command::COM_STMT_PREPARE, sql.as_bytes())
+        .await?;
+    let mut data = conn.read_packet().await?;
+    client.write_packet(&mut data).await?;
+    if data[0] == constants::ERR_PACKET_HEADER_MARK {
+        return Ok(());
+    }
+    let ok = packet::StmtPrepareOk::parse(&data)?;
+    //the parameter and column definitions, each followed by an EOF.
+    for count in [ok.param_count, ok.column_count] {
+        if count > 0 {
+            for _ in 0..=count {
+                let mut data = conn.read_packet().await?;
+                client.write_packet(&mut data).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+//COM_FIELD_LIST: column definitions till an EOF, or an ERR.
+pub async fn relay_field_list(
+    conn: &mut P2MConn,
+    arg: &[u8],
+    client: &mut packetio::PacketIO,
+) -> BackendResult<()> {
+    relay_field_list_with(conn, arg, client, |c| c).await
+}
+
+//COM_FIELD_LIST whose column definitions each go through `column` on their way to the client.
+pub async fn relay_field_list_with(
+    conn: &mut P2MConn,
+    arg: &[u8],
+    client: &mut packetio::PacketIO,
+    column: impl Fn(Vec<u8>) -> Vec<u8>,
+) -> BackendResult<()> {
+    conn.write_command(command::COM_FIELD_LIST, arg).await?;
+    loop {
+        let data = conn.read_packet().await?;
+        let end = packet::is_eof_packet(&data) || data[0] == constants::ERR_PACKET_HEADER_MARK;
+        let mut data = if end { data } else { column(data) };
+        client.write_packet(&mut data).await?;
+        if end {
+            return Ok(());
+        }
+    }
+}
+
+//run a prepared statement on the backend conn, it is prepared there first if it has not been.
+//`exec` comes from the client, its statement id is replaced with the one of the backend.
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_stmt_execute_response.html
+pub async fn relay_execute(
+    conn: &mut P2MConn,
+    sql: &str,
+    exec: &packet::StmtExecute,
+    long_data: &[(u16, Vec<u8>)],
+    client: &mut packetio::PacketIO,
+) -> BackendResult<StatusFlags> {
+    let stmt_id = conn.prepare(sql).await?;
+    for (param_id, data) in long_data.iter() {
+        let mut arg: Vec<u8> = Vec::with_capacity(6 + data.len());
+        arg.extend_from_slice(&stmt_id.to_le_bytes());
+        arg.extend_from_slice(&param_id.to_le_bytes());
+        arg.extend_from_slice(data);
+        conn.write_command(command::COM_STMT_SEND_LONG_DATA, &arg)
+            .await?;
+    }
+    let mut exec = exec.clone();
+    exec.stmt_id = stmt_id;
+    //no cursor is opened, the rows follow right away as SERVER_STATUS_CURSOR_EXISTS is not set.
+    exec.flags = 0;
+    conn.write_command(command::COM_STMT_EXECUTE, &exec.to_bits())
+        .await?;
+    relay_response(conn, client).await
+}
+
+//an OK, an ERR or result sets of a text or binary protocol, as many as the status tells.
+async fn relay_response(
+    conn: &mut P2MConn,
+    client: &mut packetio::PacketIO,
+) -> BackendResult<StatusFlags> {
+    loop {
+        let mut data = conn.read_packet().await?;
+        //LOCAL INFILE request, the proxy never lends its file system to a backend. it is not
+        //relayed, the caller answers the client with an ERR and the conn which waits for the file
+        //is discarded.
+        if data.first() == Some(&constants::LOCAL_INFILE_HEADER_MARK) {
+            return Err(BackendError::RelayErrLocalInfile);
+        }
+        client.write_packet(&mut data).await?;
+        let status = match data[0] {
+            h if h == constants::OK_PACKET_HEADER_MARK => packet::OkPacket::parse(&data)?.status(),
+            h if h == constants::ERR_PACKET_HEADER_MARK => return Ok(StatusFlags::empty()),
+            _ => relay_result_set(conn, client, &data).await?,
+        };
+        if !status.contains(StatusFlags::SERVER_MORE_RESULTS_EXISTS) {
+            return Ok(status);
+        }
+    }
+}
+
+//column definitions, EOF, rows, then EOF or ERR; the first packet is the column count.
+async fn relay_result_set(
+    conn: &mut P2MConn,
+    client: &mut packetio::PacketIO,
+    column_count: &[u8],
+) -> BackendResult<StatusFlags> {
+    let (_, count) = utils::read_length_encoded_int(column_count);
+    for _ in 0..count {
+        let mut column = conn.read_packet().await?;
+        client.write_packet(&mut column).await?;
+    }
+    let mut eof = conn.read_packet().await?;
+    client.write_packet(&mut eof).await?;
+    loop {
+        let mut row = conn.read_packet().await?;
+        client.write_packet(&mut row).await?;
+        if packet::is_eof_packet(&row) {
+            return Ok(packet::EofPacket::parse(&row)?.status());
+        }
+        if row[0] == constants::ERR_PACKET_HEADER_MARK {
+            return Ok(StatusFlags::empty());
+        }
+    }
+}
@@ -0,0 +1,672 @@
+/*
+    scatter-gather: 一条 select 在所有物理表上并发执行, 再把各个结果集合并成一个返回给client。
+*/
+#![allow(dead_code)]
+use super::conn::P2MConn;
+use super::error::{BackendError, BackendResult};
+use super::pool::P2MConnPool;
+use crate::analyzer::merge::{AggregateKind, ColumnRef, MergeSpec};
+use crate::mysql::constants::{self, command, ColumnFlags, ColumnType, StatusFlags};
+use crate::mysql::packet::{self, BinaryRow, BinaryValue, ColumnDefinition, EofPacket, TextRow};
+use crate::mysql::{packetio, utils};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub struct ResultSet {
+    pub columns: Vec<ColumnDefinition>,
+    pub rows: Vec<TextRow>,
+    pub status: StatusFlags,
+    pub ok: Option<packet::OkPacket>, //the statement returns no rows.
+}
+
+impl ResultSet {
+    pub fn new(status: StatusFlags) -> ResultSet {
+        ResultSet {
+            columns: Vec::new(),
+            rows: Vec::new(),
+            status,
+            ok: None,
+        }
+    }
+}
+
+//run the (cluster_id, sql) list concurrently, the result sets keep the order of the list.
+pub async fn scatter_query(
+    pool: &Arc<P2MConnPool>,
+    db: &str,
+    shards: &[(String, String)],
+    force_master: bool,
+) -> BackendResult<Vec<ResultSet>> {
+    let mut tasks = Vec::with_capacity(shards.len());
+    for (cluster_id, sql) in shards.iter() {
+        let pool = pool.clone();
+        let db = db.to_string();
+        let cluster_id = cluster_id.clone();
+        let sql = sql.clone();
+        tasks.push(tokio::spawn(async move {
+            let mut conn = pool.get_conn(&cluster_id, force_master).await?;
+            let rc = match conn.use_db(&db).await {
+                Ok(_) => read_result_set(&mut conn, &sql).await,
+                Err(e) => Err(e),
+            };
+            match rc {
+                Ok(rs) => {
+                    pool.recycle(conn).await;
+                    Ok(rs)
+                }
+                Err(e) => {
+                    pool.discard(conn).await;
+                    Err(e)
+                }
+            }
+        }));
+    }
+    let mut sets = Vec::with_capacity(tasks.len());
+    let mut first_err = None;
+    //wait all of them even if one fails, so that every conn goes back to the pool.
+    for t in tasks {
+        match t.await {
+            Ok(Ok(rs)) => sets.push(rs),
+            Ok(Err(e)) => {
+                first_err.get_or_insert(e);
+            }
+            Err(e) => {
+                first_err.get_or_insert(BackendError::IO(e.into()));
+            }
+        }
+    }
+    match first_err {
+        Some(e) => Err(e),
+        None => Ok(sets),
+    }
+}
+
+//run one COM_QUERY and read the whole text result set, an ERR packet is returned as ServerErr.
+pub async fn read_result_set(conn: &mut P2MConn, sql: &str) -> BackendResult<ResultSet> {
+    conn.write_command(command::COM_QUERY, sql.as_bytes())
+        .await?;
+    let data = conn.read_packet().await?;
+    match data[0] {
+        h if h == constants::ERR_PACKET_HEADER_MARK => {
+            return Err(BackendError::ServerErr(packet::ErrPacket::parse(&data)?))
+        }
+        h if h == constants::OK_PACKET_HEADER_MARK => {
+            let ok = packet::OkPacket::parse(&data)?;
+            let mut rs = ResultSet::new(ok.status());
+            rs.ok = Some(ok);
+            return Ok(rs);
+        }
+        _ => {}
+    }
+    let (_, count) = utils::read_length_encoded_int(&data);
+    let mut rs = ResultSet::new(StatusFlags::empty());
+    for _ in 0..count {
+        rs.columns
+            .push(ColumnDefinition::parse(&conn.read_packet().await?)?);
+    }
+    EofPacket::parse(&conn.read_packet().await?)?;
+    loop {
+        let data = conn.read_packet().await?;
+        if packet::is_eof_packet(&data) {
+            rs.status = EofPacket::parse(&data)?.status();
+            return Ok(rs);
+        }
+        if data[0] == constants::ERR_PACKET_HEADER_MARK {
+            return Err(BackendError::ServerErr(packet::ErrPacket::parse(&data)?));
+        }
+        rs.rows.push(TextRow::parse(&data, rs.columns.len())?);
+    }
+}
+
+//write a text result set to the client, without CLIENT_DEPRECATE_EOF.
+pub async fn write_result_set(
+    client: &mut packetio::PacketIO,
+    rs: &ResultSet,
+) -> BackendResult<()> {
+    write_result_set_as(client, rs, false).await
+}
+
+//write the result set in the binary protocol, as the answer of COM_STMT_EXECUTE.
+pub async fn write_binary_result_set(
+    client: &mut packetio::PacketIO,
+    rs: &ResultSet,
+) -> BackendResult<()> {
+    write_result_set_as(client, rs, true).await
+}
+
+async fn write_result_set_as(
+    client: &mut packetio::PacketIO,
+    rs: &ResultSet,
+    binary: bool,
+) -> BackendResult<()> {
+    let mut count = utils::write_length_encoded_int(rs.columns.len() as u64);
+    client.write_packet(&mut count).await?;
+    for c in rs.columns.iter() {
+        client.write_packet(&mut c.to_bits()).await?;
+    }
+    let status = rs.status & !StatusFlags::SERVER_MORE_RESULTS_EXISTS;
+    let mut eof = EofPacket::new(0, status).to_bits();
+    client.write_packet(&mut eof).await?;
+    for r in rs.rows.iter() {
+        let mut data = if binary {
+            let values = r
+                .values
+                .iter()
+                .zip(rs.columns.iter())
+                .map(|(v, c)| BinaryValue::from_text(v.as_deref(), c))
+                .collect();
+            BinaryRow::new(values).to_bits(&rs.columns)
+        } else {
+            r.to_bits()
+        };
+        client.write_packet(&mut data).await?;
+    }
+    client.write_packet(&mut eof).await?;
+    Ok(())
+}
+
+//merge the result sets of all shards into one, as if the select ran on one table.
+pub fn merge(spec: &MergeSpec, mut sets: Vec<ResultSet>) -> ResultSet {
+    let mut merged = match sets.iter().position(|rs| !rs.columns.is_empty()) {
+        Some(i) => ResultSet {
+            columns: sets[i].columns.clone(),
+            rows: Vec::new(),
+            status: sets[i].status,
+            ok: None,
+        },
+        None => return sets.pop().unwrap_or(ResultSet::new(StatusFlags::empty())),
+    };
+    let column_count = merged.columns.len();
+    let hidden_count = std::cmp::min(spec.hidden_count, column_count);
+    let index = |c: &ColumnRef| c.index(column_count, hidden_count);
+    let order_by: Vec<(usize, Collation, bool)> = spec
+        .order_by
+        .iter()
+        .map(|(c, asc)| (index(c), collation(&merged.columns[index(c)]), *asc))
+        .collect();
+
+    let mut rows = if spec.is_aggregate() {
+        let mut rows = aggregate(spec, &merged.columns, hidden_count, sets);
+        rows.sort_by(|a, b| compare_rows(a, b, &order_by));
+        rows
+    } else if order_by.is_empty() {
+        sets.into_iter().flat_map(|rs| rs.rows).collect()
+    } else {
+        k_way_merge(sets, &order_by)
+    };
+
+    let offset = std::cmp::min(spec.offset as usize, rows.len());
+    rows.drain(..offset);
+    if let Some(limit) = spec.limit {
+        rows.truncate(limit as usize);
+    }
+    for r in rows.iter_mut() {
+        r.values.truncate(column_count - hidden_count);
+    }
+    merged.columns.truncate(column_count - hidden_count);
+    merged.rows = rows;
+    merged
+}
+
+//the OK packets of a write on many shards as one: affected rows are summed up.
+pub fn merge_ok(sets: &[ResultSet]) -> packet::OkPacket {
+    let oks: Vec<&packet::OkPacket> = sets.iter().filter_map(|rs| rs.ok.as_ref()).collect();
+    let status = oks
+        .first()
+        .map(|ok| ok.status())
+        .unwrap_or(StatusFlags::SERVER_STATUS_AUTOCOMMIT);
+    packet::OkPacket::new(
+        oks.iter().map(|ok| ok.affected_rows()).sum(),
+        oks.iter()
+            .map(|ok| ok.last_insert_id())
+            .find(|&id| id > 0)
+            .unwrap_or(0),
+        status,
+        oks.iter()
+            .map(|ok| ok.warnings())
+            .fold(0u16, u16::saturating_add),
+    )
+}
+
+//each shard returns its rows sorted already, so only the heads of the shards are compared.
+fn k_way_merge(sets: Vec<ResultSet>, order_by: &[(usize, Collation, bool)]) -> Vec<TextRow> {
+    struct Head<'o> {
+        row: TextRow,
+        shard: usize,
+        order_by: &'o [(usize, Collation, bool)],
+    }
+    impl<'o> PartialEq for Head<'o> {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+    impl<'o> Eq for Head<'o> {}
+    impl<'o> PartialOrd for Head<'o> {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    impl<'o> Ord for Head<'o> {
+        //BinaryHeap pops the max, the smallest row must be the max here.
+        //ties are broken by shard order, which keeps the merge stable.
+        fn cmp(&self, other: &Self) -> Ordering {
+            compare_rows(&self.row, &other.row, self.order_by)
+                .then(self.shard.cmp(&other.shard))
+                .reverse()
+        }
+    }
+
+    let total = sets.iter().map(|rs| rs.rows.len()).sum();
+    let mut iters: Vec<_> = sets.into_iter().map(|rs| rs.rows.into_iter()).collect();
+    let mut heap = BinaryHeap::with_capacity(iters.len());
+    for (shard, it) in iters.iter_mut().enumerate() {
+        if let Some(row) = it.next() {
+            heap.push(Head {
+                row,
+                shard,
+                order_by,
+            });
+        }
+    }
+    let mut rows = Vec::with_capacity(total);
+    while let Some(head) = heap.pop() {
+        if let Some(row) = iters[head.shard].next() {
+            heap.push(Head {
+                row,
+                shard: head.shard,
+                order_by,
+            });
+        }
+        rows.push(head.row);
+    }
+    rows
+}
+
+//group the rows of all shards by the GROUP BY columns and combine the aggregates of each group.
+fn aggregate(
+    spec: &MergeSpec,
+    columns: &[ColumnDefinition],
+    hidden_count: usize,
+    sets: Vec<ResultSet>,
+) -> Vec<TextRow> {
+    let column_count = columns.len();
+    let index = |c: &ColumnRef| c.index(column_count, hidden_count);
+    let group_by: Vec<usize> = spec.group_by.iter().map(index).collect();
+    let mut groups: Vec<TextRow> = Vec::new();
+    let mut group_pos: HashMap<Vec<Option<Vec<u8>>>, usize> = HashMap::new();
+    for row in sets.into_iter().flat_map(|rs| rs.rows) {
+        let key: Vec<Option<Vec<u8>>> = group_by.iter().map(|&i| row.values[i].clone()).collect();
+        let pos = match group_pos.get(&key) {
+            Some(&pos) => pos,
+            None => {
+                group_pos.insert(key, groups.len());
+                groups.push(row);
+                continue;
+            }
+        };
+        let acc = &mut groups[pos];
+        for a in spec.aggregates.iter() {
+            let i = index(&a.column);
+            let v = &row.values[i];
+            let t = collation(&columns[i]);
+            acc.values[i] = match a.kind {
+                AggregateKind::Count | AggregateKind::Sum | AggregateKind::Avg => {
+                    add_values(&acc.values[i], v)
+                }
+                AggregateKind::Min => min_max(&acc.values[i], v, t, Ordering::Less),
+                AggregateKind::Max => min_max(&acc.values[i], v, t, Ordering::Greater),
+            };
+            if let Some(c) = &a.count_column {
+                let ci = index(c);
+                acc.values[ci] = add_values(&acc.values[ci], &row.values[ci]);
+            }
+        }
+    }
+    //AVG = SUM / COUNT
+    for acc in groups.iter_mut() {
+        for a in spec.aggregates.iter() {
+            if let (AggregateKind::Avg, Some(c)) = (a.kind, &a.count_column) {
+                let i = index(&a.column);
+                acc.values[i] = div_values(&acc.values[i], &acc.values[index(c)]);
+            }
+        }
+    }
+    groups
+}
+
+fn compare_rows(a: &TextRow, b: &TextRow, order_by: &[(usize, Collation, bool)]) -> Ordering {
+    for (i, t, asc) in order_by.iter() {
+        let o = compare_values(&a.values[*i], &b.values[*i], *t);
+        if o != Ordering::Equal {
+            return if *asc { o } else { o.reverse() };
+        }
+    }
+    Ordering::Equal
+}
+
+//how the values of a column compare, as the shards sorted them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Collation {
+    Numeric,
+    //binary strings and strings of a _bin collation.
+    Binary,
+    //strings of a _ci collation, which is the default of the backends.
+    CaseInsensitive,
+}
+
+fn collation(c: &ColumnDefinition) -> Collation {
+    if is_numeric(c.column_type) {
+        Collation::Numeric
+    } else if c.flags.contains(ColumnFlags::BINARY_FLAG) {
+        Collation::Binary
+    } else {
+        Collation::CaseInsensitive
+    }
+}
+
+//NULL is the smallest as mysql sorts, numbers compare by value, binary strings by bytes and
+//other strings with their case folded as utf8mb4_general_ci does. other _ci collations are
+//followed only as far as the case: accents and the weights of utf8mb4_0900_ai_ci are not, so
+//a scatter ORDER BY on such strings may merge the rows of the shards out of their order.
+fn compare_values(a: &Option<Vec<u8>>, b: &Option<Vec<u8>>, t: Collation) -> Ordering {
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(x), Some(y)) => match t {
+            Collation::Numeric => match (Decimal::parse(x), Decimal::parse(y)) {
+                (Some(dx), Some(dy)) => dx.cmp(&dy),
+                _ => to_f64(x).partial_cmp(&to_f64(y)).unwrap_or(Ordering::Equal),
+            },
+            Collation::Binary => x.cmp(y),
+            Collation::CaseInsensitive => {
+                let fold = |v: &[u8]| String::from_utf8_lossy(v).to_uppercase();
+                fold(x).cmp(&fold(y))
+            }
+        },
+    }
+}
+
+fn is_numeric(t: ColumnType) -> bool {
+    matches!(
+        t,
+        ColumnType::MYSQL_TYPE_DECIMAL
+            | ColumnType::MYSQL_TYPE_NEWDECIMAL
+            | ColumnType::MYSQL_TYPE_TINY
+            | ColumnType::MYSQL_TYPE_SHORT
+            | ColumnType::MYSQL_TYPE_LONG
+            | ColumnType::MYSQL_TYPE_INT24
+            | ColumnType::MYSQL_TYPE_LONGLONG
+            | ColumnType::MYSQL_TYPE_FLOAT
+            | ColumnType::MYSQL_TYPE_DOUBLE
+            | ColumnType::MYSQL_TYPE_YEAR
+    )
+}
+
+fn min_max(
+    acc: &Option<Vec<u8>>,
+    v: &Option<Vec<u8>>,
+    t: Collation,
+    want: Ordering,
+) -> Option<Vec<u8>> {
+    match (acc, v) {
+        //aggregates skip NULL
+        (None, _) => v.clone(),
+        (_, None) => acc.clone(),
+        _ if compare_values(v, acc, t) == want => v.clone(),
+        _ => acc.clone(),
+    }
+}
+
+fn add_values(a: &Option<Vec<u8>>, b: &Option<Vec<u8>>) -> Option<Vec<u8>> {
+    match (a, b) {
+        (None, _) => b.clone(),
+        (_, None) => a.clone(),
+        (Some(x), Some(y)) => match (Decimal::parse(x), Decimal::parse(y)) {
+            (Some(dx), Some(dy)) => dx.add(&dy).map(|d| d.to_string().into_bytes()),
+            _ => None,
+        }
+        .or_else(|| Some((to_f64(x) + to_f64(y)).to_string().into_bytes())),
+    }
+}
+
+//mysql keeps 4 more digits after the point for AVG.
+fn div_values(sum: &Option<Vec<u8>>, count: &Option<Vec<u8>>) -> Option<Vec<u8>> {
+    let (sum, count) = match (sum, count) {
+        (Some(s), Some(c)) => (s, c),
+        _ => return None,
+    };
+    let count = Decimal::parse(count).filter(|c| c.scale == 0 && c.units > 0)?;
+    match Decimal::parse(sum).and_then(|s| s.div(count.units, 4)) {
+        Some(d) => Some(d.to_string().into_bytes()),
+        None => Some((to_f64(sum) / count.units as f64).to_string().into_bytes()),
+    }
+}
+
+fn to_f64(v: &[u8]) -> f64 {
+    std::str::from_utf8(v)
+        .ok()
+        .and_then(|s| s.trim().parse::<f64>().ok())
+        .unwrap_or(0f64)
+}
+
+//fixed point number in the text form mysql sends, which sums DECIMAL columns exactly.
+#[derive(Debug, Clone, Copy)]
+struct Decimal {
+    units: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    fn parse(v: &[u8]) -> Option<Decimal> {
+        let s = std::str::from_utf8(v).ok()?.trim();
+        let (int_part, frac_part) = match s.split_once('.') {
+            Some((i, f)) => (i, f),
+            None => (s, ""),
+        };
+        if frac_part.len() > 30 || !frac_part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let negative = int_part.starts_with('-');
+        let digits = int_part.trim_start_matches(['-', '+']);
+        if !digits.bytes().all(|b| b.is_ascii_digit())
+            || (digits.is_empty() && frac_part.is_empty())
+        {
+            return None;
+        }
+        let units: i128 = format!("{}{}", digits, frac_part).parse().ok()?;
+        Some(Decimal {
+            units: if negative { -units } else { units },
+            scale: frac_part.len() as u32,
+        })
+    }
+    fn rescale(&self, scale: u32) -> Option<i128> {
+        self.units
+            .checked_mul(10i128.checked_pow(scale - self.scale)?)
+    }
+    fn add(&self, other: &Decimal) -> Option<Decimal> {
+        let scale = std::cmp::max(self.scale, other.scale);
+        Some(Decimal {
+            units: self.rescale(scale)?.checked_add(other.rescale(scale)?)?,
+            scale,
+        })
+    }
+    //divide and round half up with `more` digits after the point added.
+    fn div(&self, divisor: i128, more: u32) -> Option<Decimal> {
+        let scale = self.scale + more;
+        let units = self.rescale(scale)?;
+        let q = units / divisor;
+        let r = units % divisor;
+        let q = if r.abs() * 2 >= divisor {
+            q + units.signum()
+        } else {
+            q
+        };
+        Some(Decimal { units: q, scale })
+    }
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let scale = std::cmp::max(self.scale, other.scale);
+        match (self.rescale(scale), other.rescale(scale)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => (self.units as f64 / 10f64.powi(self.scale as i32))
+                .partial_cmp(&(other.units as f64 / 10f64.powi(other.scale as i32)))
+                .unwrap_or(Ordering::Equal),
+        }
+    }
+}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = format!(
+            "{:0>width$}",
+            self.units.unsigned_abs(),
+            width = self.scale as usize + 1
+        );
+        let (int_part, frac_part) = digits.split_at(digits.len() - self.scale as usize);
+        let sign = if self.units < 0 { "-" } else { "" };
+        if frac_part.is_empty() {
+            write!(f, "{}{}", sign, int_part)
+        } else {
+            write!(f, "{}{}.{}", sign, int_part, frac_part)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::merge::rewrite_for_scatter;
+    use crate::analyzer::sql::parse_one;
+
+    fn rs(columns: &[(&str, ColumnType)], rows: &[&[Option<&str>]]) -> ResultSet {
+        ResultSet {
+            columns: columns
+                .iter()
+                .map(|(n, t)| ColumnDefinition::new(n, *t))
+                .collect(),
+            rows: rows
+                .iter()
+                .map(|r| TextRow::new(r.iter().map(|v| v.map(|s| s.as_bytes().to_vec())).collect()))
+                .collect(),
+            status: StatusFlags::SERVER_STATUS_AUTOCOMMIT,
+            ok: None,
+        }
+    }
+
+    fn texts(rs: &ResultSet) -> Vec<Vec<Option<String>>> {
+        rs.rows
+            .iter()
+            .map(|r| {
+                r.values
+                    .iter()
+                    .map(|v| v.as_ref().map(|b| String::from_utf8_lossy(b).to_string()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merge_shard_results() {
+        //ORDER BY a hidden column, LIMIT 2 OFFSET 1
+        let mut stmt = parse_one("SELECT name FROM student ORDER BY age DESC LIMIT 1, 2").unwrap();
+        let spec = rewrite_for_scatter(&mut stmt).unwrap();
+        let cols = [
+            ("name", ColumnType::MYSQL_TYPE_VAR_STRING),
+            ("age", ColumnType::MYSQL_TYPE_LONG),
+        ];
+        let merged = merge(
+            &spec,
+            vec![
+                rs(&cols, &[&[Some("a"), Some("30")], &[Some("b"), Some("9")]]),
+                rs(&cols, &[&[Some("c"), Some("12")], &[Some("d"), None]]),
+            ],
+        );
+        assert_eq!(merged.columns.len(), 1);
+        assert_eq!(
+            texts(&merged),
+            vec![vec![Some("c".to_string())], vec![Some("b".to_string())]]
+        );
+
+        //GROUP BY with COUNT/SUM/MIN/MAX/AVG
+        let mut stmt = parse_one(
+            "SELECT class, COUNT(*), SUM(score), MIN(score), MAX(score), AVG(score) FROM student GROUP BY class ORDER BY class",
+        )
+        .unwrap();
+        let spec = rewrite_for_scatter(&mut stmt).unwrap();
+        let cols = [
+            ("class", ColumnType::MYSQL_TYPE_VAR_STRING),
+            ("COUNT(*)", ColumnType::MYSQL_TYPE_LONGLONG),
+            ("SUM(score)", ColumnType::MYSQL_TYPE_NEWDECIMAL),
+            ("MIN(score)", ColumnType::MYSQL_TYPE_NEWDECIMAL),
+            ("MAX(score)", ColumnType::MYSQL_TYPE_NEWDECIMAL),
+            ("AVG(score)", ColumnType::MYSQL_TYPE_NEWDECIMAL),
+            ("COUNT(score)", ColumnType::MYSQL_TYPE_LONGLONG),
+        ];
+        let row = |c, n, s, min, max, cnt| {
+            [
+                Some(c),
+                Some(n),
+                Some(s),
+                Some(min),
+                Some(max),
+                Some(s),
+                Some(cnt),
+            ]
+        };
+        let merged = merge(
+            &spec,
+            vec![
+                rs(
+                    &cols,
+                    &[
+                        &row("b", "1", "9.5", "9.5", "9.5", "1"),
+                        &row("a", "2", "3.1", "1.0", "2.1", "2"),
+                    ],
+                ),
+                rs(&cols, &[&row("a", "1", "10", "10", "10", "1")]),
+            ],
+        );
+        let s = |v: &str| Some(v.to_string());
+        assert_eq!(
+            texts(&merged),
+            vec![
+                vec![s("a"), s("3"), s("13.1"), s("1.0"), s("10"), s("4.36667")],
+                vec![s("b"), s("1"), s("9.5"), s("9.5"), s("9.5"), s("9.50000")],
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_strings_as_collated() {
+        //the shards sorted by utf8mb4_general_ci, so the case does not count
+        let mut stmt = parse_one("SELECT name FROM student ORDER BY name").unwrap();
+        let spec = rewrite_for_scatter(&mut stmt).unwrap();
+        let cols = [("name", ColumnType::MYSQL_TYPE_VAR_STRING)];
+        let sets = || {
+            vec![
+                rs(&cols, &[&[Some("apple")], &[Some("Banana")]]),
+                rs(&cols, &[&[Some("Apricot")], &[Some("cherry")]]),
+            ]
+        };
+        let names = |v: &[&str]| -> Vec<Vec<Option<String>>> {
+            v.iter().map(|n| vec![Some(n.to_string())]).collect()
+        };
+        assert_eq!(
+            texts(&merge(&spec, sets())),
+            names(&["apple", "Apricot", "Banana", "cherry"])
+        );
+
+        //a _bin collation sorts by bytes
+        let mut binary = sets();
+        for rs in binary.iter_mut() {
+            rs.columns[0].flags |= ColumnFlags::BINARY_FLAG;
+            rs.rows.sort_by(|a, b| a.values[0].cmp(&b.values[0]));
+        }
+        assert_eq!(
+            texts(&merge(&spec, binary)),
+            names(&["Apricot", "Banana", "apple", "cherry"])
+        );
+    }
+}
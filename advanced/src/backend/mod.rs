@@ -1,5 +1,6 @@
-mod conn;
+pub mod conn;
 mod constants;
 pub mod error;
 pub mod executor;
-mod pool;
+pub mod merger;
+pub mod pool;
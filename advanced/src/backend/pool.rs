@@ -2,23 +2,72 @@
 use super::conn::P2MConn;
 use super::error::{BackendError, BackendResult};
 use std::collections::{HashMap, LinkedList};
-use std::sync::Arc;
 
+pub mod balancer;
+pub mod checker;
 pub mod node_cfg;
 pub mod node_chan;
+pub mod node_line;
 pub mod node_mu;
+use crate::config::config_model::ReadWriteWeight;
+use crate::config::Config;
+use balancer::{Balance, Balancer};
 use dashmap::DashMap;
-use node_mu::node;
+use node_cfg::NodeCfg;
+use node_line::NodeLine;
 
-struct P2MConnPool {
+#[derive(Debug)]
+pub struct P2MConnPool {
     //static const  relationship data
-    node_conns: DashMap<String, Arc<node::NodePipeLine>>,
-    cluster_id_node_ids: HashMap<String, Vec<String>>, //Attention: the index:0 is always master node id forever!
+    node_conns: DashMap<String, NodeLine>,
+    balancers: HashMap<String, Balancer>, //the master and the read nodes of each cluster.
 }
 
 impl P2MConnPool {
-    pub async fn build_pool() -> BackendResult<P2MConnPool> {
-        unimplemented!();
+    pub async fn build_pool_with(cfg: &Config) -> BackendResult<P2MConnPool> {
+        let node_configs = cfg.load_db_node_config();
+        let node_pool = cfg.query_node_pool();
+        let node_conns: DashMap<String, NodeLine> = DashMap::new();
+        let mut balancers: HashMap<String, Balancer> = HashMap::new();
+        for (c_id, cluster) in cfg.load_db_cluster_config() {
+            let mut node_ids = vec![cluster.master_node_id.clone()];
+            node_ids.extend(cluster.slave_node_ids.clone().unwrap_or_default());
+            let mut weights = Vec::with_capacity(node_ids.len());
+            for n_id in node_ids.iter() {
+                let node_config = node_configs
+                    .get(n_id)
+                    .ok_or_else(|| BackendError::PoolErrNodeNotFound(n_id.to_string()))?;
+                let weight = match node_config.weight.as_deref() {
+                    None => ReadWriteWeight { read: 1, write: 1 },
+                    Some(w) => ReadWriteWeight::parse(w).map_err(|e| {
+                        BackendError::PoolErrWeightILL(format!("node: {}, {}", n_id, e))
+                    })?,
+                };
+                weights.push((n_id.to_string(), weight));
+                let node_line = NodeLine::new(NodeCfg::new(node_config, &c_id), node_pool).await;
+                node_line.init().await;
+                node_conns.insert(n_id.to_string(), node_line);
+            }
+            let balance = Balance::parse(cluster.balance.as_deref()).ok_or_else(|| {
+                BackendError::PoolErrWeightILL(format!(
+                    "cluster: {}, balance: {:?}",
+                    c_id, cluster.balance
+                ))
+            })?;
+            balancers.insert(c_id, Balancer::new(&weights, balance));
+        }
+        Ok(P2MConnPool {
+            node_conns,
+            balancers,
+        })
+    }
+    pub async fn get_conn(&self, cluster_id: &str, force_master: bool) -> BackendResult<P2MConn> {
+        let mut conns = self
+            .get_conns(&[cluster_id.to_string()], force_master)
+            .await?;
+        conns
+            .pop_front()
+            .ok_or_else(|| BackendError::PoolErrClusterIdNotFound(cluster_id.to_string()))
     }
     pub async fn get_conns(
         &self,
@@ -27,60 +76,63 @@ impl P2MConnPool {
     ) -> BackendResult<LinkedList<P2MConn>> {
         let mut v: LinkedList<P2MConn> = LinkedList::new();
         for c_id in cluster_ids.iter() {
-            let nodes = self
-                .cluster_id_node_ids
+            let balancer = self
+                .balancers
                 .get(c_id)
                 .ok_or_else(|| BackendError::PoolErrClusterIdNotFound(c_id.to_string()))?;
-            let node_line = if force_master {
-                self.node_conns
-                    .get(&nodes[0])
-                    .ok_or_else(|| BackendError::PoolErrNodeNotFound(nodes[0].to_string()))?
-            } else if nodes.len() > 1 {
-                let n_id = &nodes[0]; //rand to choose one in nodes.
-                let n_line = self
-                    .node_conns
-                    .get(n_id)
-                    .ok_or_else(|| BackendError::PoolErrNodeNotFound(n_id.to_string()))?;
-                if n_line.is_offline().await {
-                    self.node_conns
-                        .get(&nodes[0])
-                        .ok_or_else(|| BackendError::PoolErrNodeNotFound(nodes[0].to_string()))?
-                } else {
-                    n_line
-                }
+            //writes go to the master, reads to an online node of read weight.
+            let n_id = if force_master {
+                balancer.master()
             } else {
-                self.node_conns
-                    .get(&nodes[0])
-                    .ok_or_else(|| BackendError::PoolErrNodeNotFound(nodes[0].to_string()))?
+                let mut online = Vec::new();
+                for r_id in balancer.readers() {
+                    online.push(match self.node_line(r_id) {
+                        Some(n) => !n.is_offline().await,
+                        None => false,
+                    });
+                }
+                balancer.pick_reader(&online)
             };
+            let node_line = self
+                .node_line(n_id)
+                .ok_or_else(|| BackendError::PoolErrNodeNotFound(n_id.to_string()))?;
             v.push_back(node_line.get_conn().await?);
         }
         Ok(v)
     }
     pub async fn recycle(&self, conn: P2MConn) {
-        if let Some(n) = self.node_conns.get(&conn.node_id) {
+        if let Some(n) = self.node_line(&conn.node_id) {
             n.recycle(conn).await;
         }
     }
+    //the conn is broken or in an unknown state, never lend it again.
+    pub async fn discard(&self, conn: P2MConn) {
+        if let Some(n) = self.node_line(&conn.node_id) {
+            n.discard(conn).await;
+        }
+    }
     pub async fn reonline_node(&self, node_id: &str) -> BackendResult<()> {
-        self.node_conns
-            .get(node_id)
+        self.node_line(node_id)
             .ok_or_else(|| BackendError::PoolErrNodeNotFound(node_id.to_string()))?
             .reonline()
             .await?;
         Ok(())
     }
     pub async fn offline_node(&self, node_id: &str) -> BackendResult<usize> {
-        self.node_conns
-            .get(node_id)
+        self.node_line(node_id)
             .ok_or_else(|| BackendError::PoolErrNodeNotFound(node_id.to_string()))?
             .offline()
             .await
     }
     #[allow(unused_must_use)]
     pub async fn quit(&self) {
-        for n in self.node_conns.iter() {
+        let lines: Vec<NodeLine> = self.node_conns.iter().map(|n| n.clone()).collect();
+        for n in lines {
             n.quit().await;
         }
     }
+    //a line is cloned out of the map, a shard guard of it must not be held across an await.
+    fn node_line(&self, node_id: &str) -> Option<NodeLine> {
+        self.node_conns.get(node_id).map(|n| n.clone())
+    }
 }
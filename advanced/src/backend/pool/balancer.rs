@@ -0,0 +1,106 @@
+#![allow(dead_code)]
+//read/write splitting of one cluster: writes go to the master, reads spread over the nodes
+//of read weight, the master included when it has one.
+use crate::config::config_model::ReadWriteWeight;
+use rand::{thread_rng, Rng};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Balance {
+    Random,
+    RoundRobin,
+}
+
+impl Balance {
+    pub fn parse(balance: Option<&str>) -> Option<Balance> {
+        match balance.map(str::trim) {
+            None | Some("random") => Some(Balance::Random),
+            Some("round_robin") => Some(Balance::RoundRobin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Balancer {
+    master: String,
+    readers: Vec<(String, u32)>, //(node id, read weight), only the ones of weight > 0.
+    balance: Balance,
+    cursor: AtomicUsize,
+}
+
+impl Balancer {
+    //`nodes` are the master then the slaves with their weights.
+    pub fn new(nodes: &[(String, ReadWriteWeight)], balance: Balance) -> Balancer {
+        Balancer {
+            master: nodes.first().map(|n| n.0.clone()).unwrap_or_default(),
+            readers: nodes
+                .iter()
+                .filter(|(_, w)| w.read > 0)
+                .map(|(id, w)| (id.clone(), w.read))
+                .collect(),
+            balance,
+            cursor: AtomicUsize::new(0),
+        }
+    }
+    #[inline]
+    pub fn master(&self) -> &str {
+        &self.master
+    }
+    #[inline]
+    pub fn readers(&self) -> impl Iterator<Item = &str> {
+        self.readers.iter().map(|(id, _)| id.as_str())
+    }
+    //one of the readers by weight, `online` tells which of them can be picked, in their order.
+    //the master takes the read when no reader is online.
+    pub fn pick_reader(&self, online: &[bool]) -> &str {
+        let candidates: Vec<&(String, u32)> = self
+            .readers
+            .iter()
+            .zip(online.iter())
+            .filter(|(_, on)| **on)
+            .map(|(r, _)| r)
+            .collect();
+        let total: u64 = candidates.iter().map(|(_, w)| *w as u64).sum();
+        if total == 0 {
+            return &self.master;
+        }
+        let mut point = match self.balance {
+            Balance::Random => thread_rng().gen_range(0..total),
+            Balance::RoundRobin => self.cursor.fetch_add(1, Ordering::Relaxed) as u64 % total,
+        };
+        for (id, w) in candidates {
+            if point < *w as u64 {
+                return id;
+            }
+            point -= *w as u64;
+        }
+        &self.master
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Balance, Balancer};
+    use crate::config::config_model::ReadWriteWeight;
+
+    #[test]
+    fn pick_reader_by_weight() {
+        let nodes: Vec<(String, ReadWriteWeight)> =
+            [("m", "r0w10"), ("s1", "r1w0"), ("s2", "r3w0")]
+                .iter()
+                .map(|(id, w)| (id.to_string(), ReadWriteWeight::parse(w).unwrap()))
+                .collect();
+        let b = Balancer::new(&nodes, Balance::RoundRobin);
+        assert_eq!(b.master(), "m");
+        assert_eq!(b.readers().collect::<Vec<_>>(), vec!["s1", "s2"]);
+        let picked: Vec<&str> = (0..4).map(|_| b.pick_reader(&[true, true])).collect();
+        assert_eq!(picked, vec!["s1", "s2", "s2", "s2"]);
+        //offline readers are skipped, then the master takes the reads.
+        assert_eq!(b.pick_reader(&[true, false]), "s1");
+        assert_eq!(b.pick_reader(&[false, false]), "m");
+
+        let b = Balancer::new(&nodes, Balance::Random);
+        assert!((0..16).all(|_| b.pick_reader(&[false, true]) == "s2"));
+    }
+}
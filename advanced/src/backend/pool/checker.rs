@@ -1,35 +1,35 @@
-use super::node::NodePipeLine;
+//the health and shrink checks shared by both kinds of node line.
+use super::node_cfg::NodeCfg;
+use super::node_line::NodeLine;
 use crate::backend::conn::P2MConn;
 use crate::backend::error::BackendResult;
 use log::info;
 use std::collections::LinkedList;
-use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio::task::JoinHandle;
 use tokio::{task, time};
 
-pub async fn loop_check(receiver: &Arc<NodePipeLine>) {
-    let self_shared = receiver.clone();
+pub async fn loop_check(receiver: NodeLine) {
     task::spawn(async move {
         loop {
-            if self_shared.is_quit().await {
+            if receiver.is_quit().await {
                 return;
             }
-            shrink_check(&self_shared).await;
-            health_check(&self_shared).await;
-            time::sleep(Duration::from_secs(self_shared.cfg.time_to_check_interval)).await;
+            receiver.shrink().await;
+            health_check(&receiver).await;
+            time::sleep(Duration::from_secs(receiver.cfg().time_to_check_interval)).await;
         }
     });
 }
 #[allow(unused_must_use)]
-async fn health_check(receiver: &Arc<NodePipeLine>) {
+async fn health_check(receiver: &NodeLine) {
     let self_shared = receiver.clone();
     task::spawn(async move {
         //1. ping
-        if let Ok(c) = self_shared.get_conn().await {
+        if let Ok(mut c) = self_shared.get_conn().await {
             let mut ping_tick: u8 = 0;
-            while ping_tick < self_shared.cfg.ping_retry_count {
+            while ping_tick < self_shared.cfg().ping_retry_count {
                 ping_tick += 1;
                 let p_r = c.ping().await;
                 if p_r.is_ok() {
@@ -38,20 +38,21 @@ async fn health_check(receiver: &Arc<NodePipeLine>) {
                     self_shared.recycle(c).await;
                     return;
                 }
-                time::sleep(Duration::from_secs(self_shared.cfg.ping_retry_interval)).await;
+                time::sleep(Duration::from_secs(self_shared.cfg().ping_retry_interval)).await;
             }
             self_shared.discard(c).await;
         }
         //2. reconnect
         let mut reconnect_tick: u8 = 0;
-        while reconnect_tick < self_shared.cfg.reconnect_retry_count {
+        while reconnect_tick < self_shared.cfg().reconnect_retry_count {
             reconnect_tick += 1;
             if let Ok(c) = create_conn(
-                &self_shared.cfg.mysql_user,
-                &self_shared.cfg.mysql_pwd,
-                &self_shared.cfg.mysql_addr,
-                &self_shared.cfg.cluster_id,
-                &self_shared.cfg.node_id,
+                &self_shared.cfg().mysql_user,
+                &self_shared.cfg().mysql_pwd,
+                &self_shared.cfg().mysql_addr,
+                &self_shared.cfg().mysql_db,
+                &self_shared.cfg().cluster_id,
+                &self_shared.cfg().node_id,
             )
             .await
             {
@@ -61,35 +62,23 @@ async fn health_check(receiver: &Arc<NodePipeLine>) {
                 return;
             }
             time::sleep(Duration::from_secs(
-                self_shared.cfg.reconnect_retry_interval,
+                self_shared.cfg().reconnect_retry_interval,
             ))
             .await;
         }
         //Attention: if total conn count  == max conn limit , then give up  offline!!!
         //coz: can not get/create conn to  ping and reconnect to test peer node `s alive!!!
         self_shared
-            .offline_where(self_shared.cfg.max_conns_limit)
+            .offline_where(self_shared.cfg().max_conns_limit)
             .await;
     });
 }
-#[allow(unused_must_use)]
-async fn shrink_check(receiver: &Arc<NodePipeLine>) {
-    receiver
-        .inner
-        .lock()
-        .await
-        .shrink(
-            receiver.cfg.idle_time_to_shrink,
-            receiver.cfg.min_conns_limit,
-            receiver.cfg.shrink_count,
-        )
-        .await;
-}
 //Must be No Lock!!!
 async fn create_conn(
     user: &str,
     pwd: &str,
     addr: &str,
+    db: &str,
     c_id: &str,
     n_id: &str,
 ) -> BackendResult<P2MConn> {
@@ -101,6 +90,7 @@ async fn create_conn(
         user.to_string(),
         pwd.to_string(),
         addr.to_string(),
+        db.to_string(),
         c_id.to_string(),
         n_id.to_string(),
     )
@@ -111,22 +101,23 @@ async fn create_conn(
     Ok(con_wrap)
 }
 //Must be No Lock!!!
-pub async fn grow(receiver: &Arc<NodePipeLine>, size: u16) -> LinkedList<P2MConn> {
+pub async fn grow(cfg: &NodeCfg, size: u16) -> LinkedList<P2MConn> {
     let mut conns: LinkedList<P2MConn> = LinkedList::new();
     let mut tasks: Vec<JoinHandle<BackendResult<P2MConn>>> = Vec::new();
     for _ in 0..size {
-        let user = receiver.cfg.mysql_user.clone();
-        let pwd = receiver.cfg.mysql_pwd.clone();
-        let addr = receiver.cfg.mysql_addr.clone();
-        let c_id = receiver.cfg.cluster_id.clone();
-        let n_id = receiver.cfg.node_id.clone();
+        let user = cfg.mysql_user.clone();
+        let pwd = cfg.mysql_pwd.clone();
+        let addr = cfg.mysql_addr.clone();
+        let db = cfg.mysql_db.clone();
+        let c_id = cfg.cluster_id.clone();
+        let n_id = cfg.node_id.clone();
         tasks.push(task::spawn(async move {
-            create_conn(&user, &pwd, &addr, &c_id, &n_id).await
+            create_conn(&user, &pwd, &addr, &db, &c_id, &n_id).await
         }));
     }
     for t in tasks {
         match t.await {
-            Ok(c) => conns.push_back(c.unwrap()),
+            Ok(Ok(c)) => conns.push_back(c),
             e => info!("create new mysql conn failed: {:?}", e),
         }
     }
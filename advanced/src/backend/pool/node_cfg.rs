@@ -1,18 +1,63 @@
+use crate::backend::constants::node;
+use crate::config::DBNodeConfig;
+
 #[derive(Debug)]
 pub struct NodeCfg {
     pub mysql_user: String,
     pub mysql_pwd: String,
     pub mysql_addr: String,
+    pub mysql_db: String,
     pub cluster_id: String,
     pub node_id: String,
     pub max_conns_limit: u64,
     pub min_conns_limit: u16,
     pub grow_count: u16,
     pub shrink_count: u16,
-    pub idle_time_to_shrink: u64,
+    pub idle_time_to_shrink: u64, //time unit: second
+    pub max_lifetime: u64,        //time unit: second, zero value is for unlimited.
+    pub wait_timeout: u64,        //time unit: millisecond
+    pub wait_queue_limit: usize,
     pub time_to_check_interval: u64,
     pub ping_retry_count: u8,
     pub ping_retry_interval: u64, //time unit: second
     pub reconnect_retry_count: u8,
     pub reconnect_retry_interval: u64, //time unit: second
 }
+
+impl NodeCfg {
+    pub fn new(node_config: &DBNodeConfig, cluster_id: &str) -> NodeCfg {
+        let max_conns_limit = match node_config.max_conns_limit {
+            Some(limit) if limit > 0 => limit,
+            _ => node::MAX_CONN_COUNT_LIMIT,
+        };
+        let min_conns_limit = node_config
+            .min_conns_limit
+            .unwrap_or(node::MIN_CONN_COUNT_LIMIT)
+            .min(max_conns_limit.min(u16::MAX as u64) as u16);
+        NodeCfg {
+            mysql_user: node_config.user.clone(),
+            mysql_pwd: node_config.pwd.clone(),
+            mysql_addr: node_config.listen_addr.clone(),
+            mysql_db: node_config.db.clone().unwrap_or_default(),
+            cluster_id: cluster_id.to_string(),
+            node_id: node_config.id.clone(),
+            max_conns_limit,
+            min_conns_limit,
+            grow_count: node::GROW_COUNT,
+            shrink_count: node::SHRINK_COUNT,
+            idle_time_to_shrink: node_config
+                .idle_timeout
+                .unwrap_or(node::IDLE_TIME_TO_SHRINK_THRESHOLD),
+            max_lifetime: node_config.max_lifetime.unwrap_or(node::MAX_LIFETIME),
+            wait_timeout: node_config.wait_timeout.unwrap_or(node::WAIT_TIMEOUT),
+            wait_queue_limit: node_config
+                .wait_queue_limit
+                .unwrap_or(node::WAIT_QUEUE_LIMIT),
+            time_to_check_interval: node::TO_CHECK_TIME_INTERVAL,
+            ping_retry_count: node::PING_RETRY_COUNT,
+            ping_retry_interval: node::PING_RETRY_MIN_INTERVAL as u64,
+            reconnect_retry_count: node::RECONNECT_RETRY_COUNT,
+            reconnect_retry_interval: node::RECONNECT_RETRY_MIN_INTERVAL as u64,
+        }
+    }
+}
@@ -1,82 +1,352 @@
-use std::collections::LinkedList;
-use std::sync::Arc;
-//use async_std::prelude::*;
-//use async_std::sync::channel;
+#![allow(dead_code)]
+//the node line as a pump station: one task owns the cache and the wait queue, the borrowers
+//and the lenders only send it messages, so no lock is taken for a lend or a recycle.
 use crate::backend::conn::P2MConn;
-use crate::backend::error::BackendResult;
+use crate::backend::error::{BackendError, BackendResult};
+use crate::backend::pool::checker::grow;
 use crate::backend::pool::node_cfg::NodeCfg;
+use std::collections::{LinkedList, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot};
+use tokio::{task, time};
+
+type Reply<T> = oneshot::Sender<BackendResult<T>>;
+
+#[derive(Debug)]
+enum Msg {
+    Lend(Reply<P2MConn>),
+    Recycle(P2MConn),
+    Discard(P2MConn),
+    Takeup(P2MConn),
+    Grown(u64, LinkedList<P2MConn>), //(count asked for, conns made)
+    Reonline(Reply<()>),
+    Reonlined(LinkedList<P2MConn>, Reply<()>),
+    Offline(Option<u64>, Reply<usize>), //offline, only below the max conn count if given.
+    Quit(Reply<usize>),
+    Shrink(oneshot::Sender<usize>),
+}
 
 #[derive(Debug)]
 pub struct NodePipeLine {
-    //dynamic data
-    cache: LinkedList<P2MConn>, //pop/push
-    // a mpmc channel PIPE
-    recent_request_time: u64,
-    total_conn_count: u64,
-    offline: bool,
-    quit: bool,
+    pipe: mpsc::UnboundedSender<Msg>,
+    offline: AtomicBool,
+    quit: AtomicBool,
     //static config data
     pub cfg: NodeCfg,
 }
 
+//the dynamic data, only the pump station touches it.
+struct Station {
+    //lent from the back and recycled to the back, so the front ones are idle the longest.
+    cache: LinkedList<P2MConn>,
+    waiters: VecDeque<Reply<P2MConn>>, //first in, first served.
+    recent_request_time: u64,          //time unit: second since the unix epoch
+    total_conn_count: u64,
+    growing: u64, //conns being made, they count against the max limit.
+}
+
 impl NodePipeLine {
+    #[inline]
+    pub async fn new(cfg: NodeCfg) -> Arc<Self> {
+        let (pipe, rx) = mpsc::unbounded_channel();
+        let line = Arc::new(NodePipeLine {
+            pipe,
+            offline: AtomicBool::new(false),
+            quit: AtomicBool::new(false),
+            cfg,
+        });
+        task::spawn(line.clone().pump_station(rx));
+        line
+    }
+    //a full pool puts the borrower in line, it fails after the wait timeout.
     #[inline]
     pub async fn get_conn(self: &Arc<Self>) -> BackendResult<P2MConn> {
-        //update recent_request_time with now timestamp
-        //block read on channel PIPE
-        unimplemented!();
+        if self.is_offline().await {
+            return Err(BackendError::InnerErrOfflineOrQuit);
+        }
+        let (tx, mut rx) = oneshot::channel();
+        self.send(Msg::Lend(tx))?;
+        match time::timeout(Duration::from_millis(self.cfg.wait_timeout), &mut rx).await {
+            Ok(got) => got.map_err(|_| BackendError::InnerErrOfflineOrQuit)?,
+            Err(_) => {
+                //whatever was handed over meanwhile is still ours.
+                rx.close();
+                match rx.try_recv() {
+                    Ok(got) => got,
+                    Err(_) => Err(BackendError::PoolErrWaitTimeout(self.cfg.node_id.clone())),
+                }
+            }
+        }
+    }
+    #[inline]
+    #[allow(unused_must_use)]
+    pub async fn recycle(self: &Arc<Self>, conn: P2MConn) {
+        self.send(Msg::Recycle(conn));
+    }
+    #[inline]
+    #[allow(unused_must_use)]
+    pub async fn discard(self: &Arc<Self>, conn: P2MConn) {
+        self.send(Msg::Discard(conn));
+    }
+    #[inline]
+    #[allow(unused_must_use)]
+    pub async fn takeup(self: &Arc<Self>, conn: P2MConn) {
+        self.send(Msg::Takeup(conn));
+    }
+    #[inline]
+    pub async fn reonline(self: &Arc<Self>) -> BackendResult<()> {
+        self.call(Msg::Reonline).await
+    }
+    #[inline]
+    pub async fn offline(self: &Arc<Self>) -> BackendResult<usize> {
+        self.call(|tx| Msg::Offline(None, tx)).await
+    }
+    #[inline]
+    pub async fn offline_where(self: &Arc<Self>, max: u64) -> BackendResult<usize> {
+        self.call(|tx| Msg::Offline(Some(max), tx)).await
+    }
+    #[inline]
+    pub async fn is_offline(self: &Arc<Self>) -> bool {
+        self.offline.load(Ordering::Acquire)
+    }
+    #[inline]
+    pub async fn is_quit(self: &Arc<Self>) -> bool {
+        self.quit.load(Ordering::Acquire)
     }
     #[inline]
-    pub async fn recycle(self: &Arc<Self>) -> BackendResult<P2MConn> {
-        //async write to channel PIPE
-        unimplemented!();
+    pub async fn quit(self: &Arc<Self>) -> BackendResult<usize> {
+        self.call(Msg::Quit).await
     }
     #[inline]
-    pub async fn discard(self: &Arc<Self>) -> BackendResult<P2MConn> {
-        //1. close conn
-        //2. update total_conn_count by atomic
-        unimplemented!();
-    }
-    pub async fn pump_station(self: &Arc<Self>) {
-        /*loop {
-            select {
-                case1 read channel PIPE {
-                    //sync way
-                    push the conn to cache
+    pub async fn shrink(self: &Arc<Self>) -> usize {
+        let (tx, rx) = oneshot::channel();
+        if self.send(Msg::Shrink(tx)).is_err() {
+            return 0;
+        }
+        rx.await.unwrap_or(0)
+    }
+
+    fn send(&self, msg: Msg) -> BackendResult<()> {
+        self.pipe
+            .send(msg)
+            .map_err(|_| BackendError::InnerErrOfflineOrQuit)
+    }
+    async fn call<T>(&self, msg: impl FnOnce(Reply<T>) -> Msg) -> BackendResult<T> {
+        let (tx, rx) = oneshot::channel();
+        self.send(msg(tx))?;
+        rx.await.map_err(|_| BackendError::InnerErrOfflineOrQuit)?
+    }
+
+    //the only owner of the station, it stops once the line quits.
+    async fn pump_station(self: Arc<Self>, mut rx: mpsc::UnboundedReceiver<Msg>) {
+        let mut st = Station {
+            cache: LinkedList::new(),
+            waiters: VecDeque::new(),
+            recent_request_time: 0,
+            total_conn_count: 0,
+            growing: 0,
+        };
+        while let Some(msg) = rx.recv().await {
+            match msg {
+                Msg::Lend(reply) => self.lend(&mut st, reply),
+                Msg::Recycle(conn) => {
+                    let max_lifetime = Duration::from_secs(self.cfg.max_lifetime);
+                    if self.is_offline().await
+                        || (!max_lifetime.is_zero() && conn.lifetime() >= max_lifetime)
+                    {
+                        self.drop_conn(&mut st, conn);
+                    } else {
+                        self.hand_over(&mut st, conn);
+                    }
                 }
-                case2 can write to channel PIPE {
-                    //sync way
-                    if cache is not empty {
-                        pop a conn from the cache
-                        send the conn to channel PIPE
-                    } else if total_conn_count < max_conn_limit {
-                        create some new conn
-                        push them into cache
-                        send one into channel PIPE
+                Msg::Discard(conn) => self.drop_conn(&mut st, conn),
+                Msg::Takeup(conn) => {
+                    if self.is_offline().await {
+                        conn.quit();
+                    } else {
+                        st.total_conn_count += 1;
+                        self.hand_over(&mut st, conn);
                     }
                 }
-                case3 a interval task{
-                    //sync way
-                    shrink check
-                    if recent_request_time - now() > shrink time threshold  {
-                        if channel PIPE len() + cache len() > min conn limit && reach on 2/3 of the total_conn_count {
-                            start to shrink shrink_count_limit conn
-
+                Msg::Grown(asked, conns) => {
+                    st.growing -= asked;
+                    if self.is_offline().await {
+                        conns.iter().for_each(P2MConn::quit);
+                        continue;
+                    }
+                    let made = conns.len();
+                    st.total_conn_count += made as u64;
+                    for conn in conns {
+                        self.hand_over(&mut st, conn);
+                    }
+                    //nothing to wait for when no conn can be made and none is lent.
+                    if made == 0 && st.growing == 0 && st.total_conn_count == 0 {
+                        for w in st.waiters.drain(..) {
+                            let err = BackendError::PoolErrConnGrowFailed(self.cfg.node_id.clone());
+                            let _ = w.send(Err(err));
                         }
                     }
                 }
-                case4 a interval task {
-                    //async way
-                    node health check , online/offline
-                    first pingpong
-                    second reconnect
-                    create new conn
-                    retry some times
-                    finally to be considered offline
+                Msg::Reonline(reply) => {
+                    if self.is_quit().await {
+                        let _ = reply.send(Err(BackendError::InnerErrOfflineOrQuit));
+                    } else if !self.is_offline().await {
+                        let err = BackendError::PoolErrConnGrowGiveup(self.cfg.node_id.clone());
+                        let _ = reply.send(Err(err));
+                    } else {
+                        let line = self.clone();
+                        task::spawn(async move {
+                            let conns = grow(&line.cfg, line.cfg.grow_count).await;
+                            let _ = line.send(Msg::Reonlined(conns, reply));
+                        });
+                    }
+                }
+                Msg::Reonlined(conns, reply) => {
+                    if self.is_quit().await {
+                        conns.iter().for_each(P2MConn::quit);
+                        let _ = reply.send(Err(BackendError::InnerErrOfflineOrQuit));
+                    } else if conns.is_empty() {
+                        let err = BackendError::PoolErrConnGrowFailed(self.cfg.node_id.clone());
+                        let _ = reply.send(Err(err));
+                    } else {
+                        self.clean_cache(&mut st);
+                        st.total_conn_count += conns.len() as u64;
+                        self.offline.store(false, Ordering::Release);
+                        for conn in conns {
+                            self.hand_over(&mut st, conn);
+                        }
+                        let _ = reply.send(Ok(()));
+                    }
+                }
+                Msg::Offline(Some(max), reply) if st.total_conn_count >= max => {
+                    let _ = reply.send(Err(BackendError::InnerErrGreaterThenMaxConnCount));
+                }
+                Msg::Offline(_, reply) => {
+                    self.offline.store(true, Ordering::Release);
+                    let _ = reply.send(Ok(self.clean_cache(&mut st)));
                 }
+                Msg::Quit(reply) => {
+                    self.quit.store(true, Ordering::Release);
+                    self.offline.store(true, Ordering::Release);
+                    let _ = reply.send(Ok(self.clean_cache(&mut st)));
+                    return;
+                }
+                Msg::Shrink(reply) => {
+                    let _ = reply.send(self.shrink_station(&mut st));
+                }
+            }
+        }
+    }
 
+    //a cached conn, or a place in the wait queue while new conns are made below the max.
+    fn lend(self: &Arc<Self>, st: &mut Station, reply: Reply<P2MConn>) {
+        if self.offline.load(Ordering::Acquire) {
+            let _ = reply.send(Err(BackendError::InnerErrOfflineOrQuit));
+            return;
+        }
+        st.recent_request_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        if let Some(conn) = st.cache.pop_back() {
+            if let Err(Ok(conn)) = reply.send(Ok(conn)) {
+                st.cache.push_back(conn);
+            }
+            return;
+        }
+        st.waiters.retain(|w| !w.is_closed());
+        if st.waiters.len() >= self.cfg.wait_queue_limit {
+            let _ = reply.send(Err(BackendError::InnerErrWaitQueueFull));
+            return;
+        }
+        st.waiters.push_back(reply);
+        let room = self
+            .cfg
+            .max_conns_limit
+            .saturating_sub(st.total_conn_count + st.growing);
+        let short = (st.waiters.len() as u64).saturating_sub(st.growing);
+        if room > 0 && short > 0 {
+            self.grow_async(st, room.min((self.cfg.grow_count as u64).max(short)));
+        }
+    }
+    //conns are made out of the station, they come back by a message.
+    fn grow_async(self: &Arc<Self>, st: &mut Station, count: u64) {
+        st.growing += count;
+        let line = self.clone();
+        task::spawn(async move {
+            let conns = grow(&line.cfg, count as u16).await;
+            let _ = line.send(Msg::Grown(count, conns));
+        });
+    }
+    //to the first waiter still waiting, or to the cache.
+    fn hand_over(&self, st: &mut Station, mut conn: P2MConn) {
+        conn.touch();
+        while let Some(w) = st.waiters.pop_front() {
+            match w.send(Ok(conn)) {
+                Ok(_) => return,
+                Err(back) => conn = back.expect("the conn sent comes back"),
+            }
+        }
+        st.cache.push_back(conn);
+        if st.total_conn_count > self.cfg.max_conns_limit {
+            if let Some(conn) = st.cache.pop_front() {
+                conn.quit();
+                st.total_conn_count -= 1;
+            }
+        }
+    }
+    //the slot of a dropped conn is used at once when someone waits.
+    fn drop_conn(self: &Arc<Self>, st: &mut Station, conn: P2MConn) {
+        conn.quit();
+        st.total_conn_count -= 1;
+        st.waiters.retain(|w| !w.is_closed());
+        if st.waiters.len() as u64 > st.growing && !self.offline.load(Ordering::Acquire) {
+            self.grow_async(st, 1);
+        }
+    }
+    fn clean_cache(&self, st: &mut Station) -> usize {
+        for w in st.waiters.drain(..) {
+            let _ = w.send(Err(BackendError::InnerErrOfflineOrQuit));
+        }
+        let c_size = st.cache.len();
+        while let Some(conn) = st.cache.pop_front() {
+            conn.quit();
+            st.total_conn_count -= 1;
+        }
+        c_size
+    }
+    //the same as the shrink of node_mu: the expired conns, then the idle ones down to min.
+    fn shrink_station(&self, st: &mut Station) -> usize {
+        let max_lifetime = Duration::from_secs(self.cfg.max_lifetime);
+        let idle_time = Duration::from_secs(self.cfg.idle_time_to_shrink);
+        let mut kept = LinkedList::new();
+        let mut closed = 0;
+        while let Some(conn) = st.cache.pop_front() {
+            if !max_lifetime.is_zero() && conn.lifetime() >= max_lifetime {
+                conn.quit();
+                st.total_conn_count -= 1;
+                closed += 1;
+            } else {
+                kept.push_back(conn);
+            }
+        }
+        st.cache = kept;
+        let mut shrunk: u16 = 0;
+        while shrunk < self.cfg.shrink_count
+            && st.total_conn_count > self.cfg.min_conns_limit as u64
+        {
+            match st.cache.front() {
+                Some(conn) if conn.idle_time() >= idle_time => {}
+                _ => break,
+            }
+            if let Some(conn) = st.cache.pop_front() {
+                conn.quit();
+                st.total_conn_count -= 1;
+                shrunk += 1;
             }
-        }*/
-        unimplemented!();
+        }
+        closed + shrunk as usize
     }
 }
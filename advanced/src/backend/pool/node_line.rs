@@ -0,0 +1,191 @@
+#![allow(dead_code)]
+//the line of conns to one node, either kind behind the same calls, chosen by `node_pool`.
+use super::checker::loop_check;
+use super::node_cfg::NodeCfg;
+use super::{node_chan, node_mu};
+use crate::backend::conn::P2MConn;
+use crate::backend::error::BackendResult;
+use crate::config::NodePool;
+use std::sync::Arc;
+use tokio::task;
+
+#[derive(Debug, Clone)]
+pub enum NodeLine {
+    Mu(Arc<node_mu::node::NodePipeLine>),
+    Chan(Arc<node_chan::node::NodePipeLine>),
+}
+
+macro_rules! each_line {
+    ($self:ident, $line:ident => $call:expr) => {
+        match $self {
+            NodeLine::Mu($line) => $call,
+            NodeLine::Chan($line) => $call,
+        }
+    };
+}
+
+impl NodeLine {
+    pub async fn new(cfg: NodeCfg, kind: NodePool) -> NodeLine {
+        match kind {
+            NodePool::Mutex => NodeLine::Mu(node_mu::node::NodePipeLine::new(cfg).await),
+            NodePool::Channel => NodeLine::Chan(node_chan::node::NodePipeLine::new(cfg).await),
+        }
+    }
+    //the first conns are made in the background, then the checks run every interval.
+    #[allow(unused_must_use)]
+    pub async fn init(&self) {
+        let self_shared = self.clone();
+        task::spawn(async move {
+            self_shared.reonline().await;
+        });
+        loop_check(self.clone()).await;
+    }
+    #[inline]
+    pub fn cfg(&self) -> &NodeCfg {
+        each_line!(self, l => &l.cfg)
+    }
+    #[inline]
+    pub async fn get_conn(&self) -> BackendResult<P2MConn> {
+        each_line!(self, l => l.get_conn().await)
+    }
+    #[inline]
+    pub async fn recycle(&self, conn: P2MConn) {
+        each_line!(self, l => l.recycle(conn).await)
+    }
+    #[inline]
+    pub async fn discard(&self, conn: P2MConn) {
+        each_line!(self, l => l.discard(conn).await)
+    }
+    #[inline]
+    pub async fn takeup(&self, conn: P2MConn) {
+        each_line!(self, l => l.takeup(conn).await)
+    }
+    #[inline]
+    pub async fn reonline(&self) -> BackendResult<()> {
+        each_line!(self, l => l.reonline().await)
+    }
+    #[inline]
+    pub async fn offline(&self) -> BackendResult<usize> {
+        each_line!(self, l => l.offline().await)
+    }
+    #[inline]
+    pub async fn offline_where(&self, max: u64) -> BackendResult<usize> {
+        each_line!(self, l => l.offline_where(max).await)
+    }
+    #[inline]
+    pub async fn is_offline(&self) -> bool {
+        each_line!(self, l => l.is_offline().await)
+    }
+    #[inline]
+    pub async fn is_quit(&self) -> bool {
+        each_line!(self, l => l.is_quit().await)
+    }
+    #[inline]
+    pub async fn quit(&self) -> BackendResult<usize> {
+        each_line!(self, l => l.quit().await)
+    }
+    #[inline]
+    pub async fn shrink(&self) -> usize {
+        each_line!(self, l => l.shrink().await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeLine;
+    use crate::backend::conn::P2MConn;
+    use crate::backend::pool::node_cfg::NodeCfg;
+    use crate::config::NodePool;
+    use std::time::{Duration, Instant};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::task;
+
+    fn cfg(max_conns_limit: u64) -> NodeCfg {
+        NodeCfg {
+            mysql_user: "root".to_string(),
+            mysql_pwd: "pwd".to_string(),
+            mysql_addr: "127.0.0.1:0".to_string(),
+            mysql_db: "db1".to_string(),
+            cluster_id: "cluster_1".to_string(),
+            node_id: "mysql_1".to_string(),
+            max_conns_limit,
+            min_conns_limit: 0,
+            grow_count: 0,
+            shrink_count: 0,
+            idle_time_to_shrink: 0,
+            max_lifetime: 0,
+            wait_timeout: 10_000,
+            wait_queue_limit: 100_000,
+            time_to_check_interval: 60,
+            ping_retry_count: 0,
+            ping_retry_interval: 0,
+            reconnect_retry_count: 0,
+            reconnect_retry_interval: 0,
+        }
+    }
+
+    //a full line of `conns`, which `tasks` borrowers lend and recycle `rounds` times each.
+    async fn lend_and_recycle(kind: NodePool, conns: u64, tasks: usize, rounds: usize) -> Duration {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let line = NodeLine::new(cfg(conns), kind).await;
+        for _ in 0..conns {
+            let tcp = TcpStream::connect(&addr).await.unwrap();
+            let conn = P2MConn::build_conn(
+                tcp,
+                "root".to_string(),
+                "pwd".to_string(),
+                addr.clone(),
+                "db1".to_string(),
+                "cluster_1".to_string(),
+                "mysql_1".to_string(),
+            )
+            .await
+            .unwrap();
+            line.takeup(conn).await;
+        }
+        let start = Instant::now();
+        let borrowers: Vec<_> = (0..tasks)
+            .map(|_| {
+                let line = line.clone();
+                task::spawn(async move {
+                    for _ in 0..rounds {
+                        let conn = line.get_conn().await.unwrap();
+                        task::yield_now().await;
+                        line.recycle(conn).await;
+                    }
+                })
+            })
+            .collect();
+        for b in borrowers {
+            b.await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        assert_eq!(line.quit().await.unwrap(), conns as usize);
+        elapsed
+    }
+
+    #[tokio::test]
+    async fn both_lines_share_their_conns() {
+        for kind in [NodePool::Mutex, NodePool::Channel] {
+            lend_and_recycle(kind, 2, 8, 50).await;
+        }
+    }
+
+    //cargo test --release bench_node_lines -- --ignored --nocapture
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    #[ignore]
+    async fn bench_node_lines() {
+        for kind in [NodePool::Mutex, NodePool::Channel] {
+            let (conns, tasks, rounds) = (32, 1024, 1000);
+            let elapsed = lend_and_recycle(kind, conns, tasks, rounds).await;
+            println!(
+                "{:?}: {} conns, {} tasks, {:.0} lends/s",
+                kind,
+                conns,
+                tasks,
+                (tasks * rounds) as f64 / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
@@ -1,13 +1,27 @@
 #![allow(dead_code)]
 use crate::backend::conn::P2MConn;
 use crate::backend::error::{BackendError, BackendResult};
-use std::collections::LinkedList;
+use std::collections::{LinkedList, VecDeque};
 use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+
+//a borrower waits in line for a conn when the pool is full, it gets either a recycled conn
+//or none, which hands it the slot of a discarded conn to grow a new one itself.
+pub type Waiter = oneshot::Sender<Option<P2MConn>>;
+
+#[allow(clippy::large_enum_variant)] //lent right away, boxing it would cost each lend.
+pub enum Lend {
+    Ready(P2MConn),
+    Wait(oneshot::Receiver<Option<P2MConn>>),
+}
 
 #[derive(Debug)]
 pub struct InnerLine {
-    cache: LinkedList<P2MConn>, //pop/push
-    recent_request_time: u64,
+    //lent from the back and recycled to the back, so the front ones are idle the longest.
+    cache: LinkedList<P2MConn>,
+    waiters: VecDeque<Waiter>, //first in, first served.
+    recent_request_time: u64,  //time unit: second since the unix epoch
     total_conn_count: u64,
     offline: bool,
     quit: bool,
@@ -17,6 +31,7 @@ impl InnerLine {
     pub async fn new() -> InnerLine {
         InnerLine {
             cache: LinkedList::new(),
+            waiters: VecDeque::new(),
             recent_request_time: 0,
             total_conn_count: 0,
             offline: false,
@@ -25,7 +40,10 @@ impl InnerLine {
     }
     #[inline]
     pub async fn update_time_stamp(&mut self) {
-        self.recent_request_time = 0; //now
+        self.recent_request_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
     }
     #[inline]
     pub async fn is_offline(&self) -> bool {
@@ -54,8 +72,14 @@ impl InnerLine {
         self.offline = true;
         self.clean_cache().await
     }
+    //a cached conn, a new one while below `max`, or else a place in the wait queue.
     #[inline]
-    pub async fn lend_with<F>(&mut self, max: u64, grow: F) -> BackendResult<P2MConn>
+    pub async fn lend_with<F>(
+        &mut self,
+        max: u64,
+        max_waiters: usize,
+        grow: F,
+    ) -> BackendResult<Lend>
     where
         F: Future<Output = LinkedList<P2MConn>>,
     {
@@ -63,21 +87,25 @@ impl InnerLine {
             return Err(BackendError::InnerErrOfflineOrQuit);
         }
         self.update_time_stamp().await;
-        let rc = self
-            .cache
-            .pop_front()
-            .ok_or_else(|| BackendError::InnerErrPipeEmpty);
-        if rc.is_ok() {
-            rc
-        } else if self.total_conn_count < max {
+        if let Some(conn) = self.cache.pop_back() {
+            return Ok(Lend::Ready(conn));
+        }
+        if self.total_conn_count < max {
             let mut conn_list = grow.await;
             self.takeup_batch(&mut conn_list).await;
-            self.cache
-                .pop_front()
-                .ok_or_else(|| BackendError::InnerErrPipeEmpty)
-        } else {
-            Err(BackendError::InnerErrPipeEmpty)
+            return self
+                .cache
+                .pop_back()
+                .map(Lend::Ready)
+                .ok_or(BackendError::InnerErrPipeEmpty);
         }
+        self.waiters.retain(|w| !w.is_closed());
+        if self.waiters.len() >= max_waiters {
+            return Err(BackendError::InnerErrWaitQueueFull);
+        }
+        let (tx, rx) = oneshot::channel();
+        self.waiters.push_back(tx);
+        Ok(Lend::Wait(rx))
     }
     #[inline]
     #[allow(unused_must_use)]
@@ -98,45 +126,91 @@ impl InnerLine {
         self.update_time_stamp().await;
         self.clean_cache().await;
         self.takeup_batch(&mut conns).await;
+        self.offline = false;
         Ok(())
     }
+    //close the cached conns older than `max_lifetime`, then the ones idle for `idle_time`
+    //from the idlest on, at most `shrink_c` of them and never below `min`.
     #[inline]
     #[allow(unused_must_use)]
-    pub async fn shrink(&mut self, _idle_time: u64, min: u16, shrink_c: u16) {
-        if self.total_conn_count <= min as u64 {
-            return;
+    pub async fn shrink(
+        &mut self,
+        idle_time: Duration,
+        min: u16,
+        shrink_c: u16,
+        max_lifetime: Duration,
+    ) -> usize {
+        let mut kept = LinkedList::new();
+        let mut closed = 0;
+        while let Some(conn) = self.cache.pop_front() {
+            if !max_lifetime.is_zero() && conn.lifetime() >= max_lifetime {
+                conn.close().await;
+                self.total_conn_count -= 1;
+                closed += 1;
+            } else {
+                kept.push_back(conn);
+            }
         }
-        //Todo: design a shrink algorithm
-        self.eliminate(shrink_c as u64).await;
+        self.cache = kept;
+        let mut shrunk: u16 = 0;
+        while shrunk < shrink_c && self.total_conn_count > min as u64 {
+            match self.cache.front() {
+                Some(conn) if conn.idle_time() >= idle_time => {}
+                _ => break,
+            }
+            if let Some(conn) = self.cache.pop_front() {
+                conn.close().await;
+                self.total_conn_count -= 1;
+                shrunk += 1;
+            }
+        }
+        closed + shrunk as usize
     }
 
     #[inline]
     #[allow(unused_must_use)]
     pub async fn clean_cache(&mut self) -> BackendResult<usize> {
+        //the waiters are told to give up by dropping their senders.
+        self.waiters.clear();
         let c_size = self.cache.len();
         for _ in 0..c_size {
             self.cache
                 .pop_front()
-                .ok_or_else(|| BackendError::InnerErrPipeEmpty)?
-                .quit();
+                .ok_or(BackendError::InnerErrPipeEmpty)?
+                .close()
+                .await;
             self.total_conn_count -= 1;
         }
         Ok(c_size)
     }
+    //the slot of a discarded conn goes to the first waiter to grow a new one.
     #[inline]
     #[allow(unused_must_use)]
     pub async fn discard(&mut self, conn: P2MConn) {
         conn.quit();
+        self.release_slot().await;
+    }
+    #[inline]
+    pub async fn release_slot(&mut self) {
+        while let Some(w) = self.waiters.pop_front() {
+            if w.send(None).is_ok() {
+                return;
+            }
+        }
         self.total_conn_count -= 1;
     }
     #[inline]
     #[allow(unused_must_use)]
-    pub async fn recycle(&mut self, max: u64, conn: P2MConn) {
+    pub async fn recycle(&mut self, max: u64, max_lifetime: Duration, conn: P2MConn) {
         if self.is_offline().await | self.is_quit().await {
             self.discard(conn).await;
             return;
         }
-        self.cache.push_back(conn);
+        if !max_lifetime.is_zero() && conn.lifetime() >= max_lifetime {
+            self.discard(conn).await;
+            return;
+        }
+        self.hand_over(conn);
         if self.total_conn_count > max {
             self.eliminate(self.total_conn_count - max).await;
         }
@@ -149,11 +223,22 @@ impl InnerLine {
             return;
         }
         self.total_conn_count += 1;
-        self.cache.push_back(conn);
+        self.hand_over(conn);
         if self.total_conn_count > max {
             self.eliminate(self.total_conn_count - max).await;
         }
     }
+    //to the first waiter still waiting, or to the cache.
+    fn hand_over(&mut self, mut conn: P2MConn) {
+        conn.touch();
+        while let Some(w) = self.waiters.pop_front() {
+            match w.send(Some(conn)) {
+                Ok(_) => return,
+                Err(back) => conn = back.expect("the conn sent comes back"),
+            }
+        }
+        self.cache.push_back(conn);
+    }
     #[inline]
     async fn takeup_batch(&mut self, conns: &mut LinkedList<P2MConn>) {
         self.total_conn_count += conns.len() as u64;
@@ -165,10 +250,80 @@ impl InnerLine {
         for _ in 0..count {
             self.cache
                 .pop_front()
-                .ok_or_else(|| BackendError::InnerErrPipeEmpty)?
-                .quit();
+                .ok_or(BackendError::InnerErrPipeEmpty)?
+                .close()
+                .await;
             self.total_conn_count -= 1;
         }
         Ok(count)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{InnerLine, Lend};
+    use crate::backend::conn::P2MConn;
+    use std::collections::LinkedList;
+    use std::time::Duration;
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn conns(n: usize) -> LinkedList<P2MConn> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let mut conns = LinkedList::new();
+        for _ in 0..n {
+            let tcp = TcpStream::connect(&addr).await.unwrap();
+            let conn = P2MConn::build_conn(
+                tcp,
+                "root".to_string(),
+                "pwd".to_string(),
+                addr.clone(),
+                "db1".to_string(),
+                "cluster_1".to_string(),
+                "mysql_1".to_string(),
+            )
+            .await
+            .unwrap();
+            conns.push_back(conn);
+        }
+        conns
+    }
+
+    #[tokio::test]
+    async fn wait_in_line_and_shrink() {
+        let mut line = InnerLine::new().await;
+        let forever = Duration::ZERO;
+        let first = match line.lend_with(2, 1, conns(2)).await.unwrap() {
+            Lend::Ready(c) => c,
+            Lend::Wait(_) => panic!("the pool grows below max"),
+        };
+        let second = match line.lend_with(2, 1, conns(1)).await.unwrap() {
+            Lend::Ready(c) => c,
+            Lend::Wait(_) => panic!("one conn is cached"),
+        };
+        let mut rx = match line.lend_with(2, 1, conns(1)).await.unwrap() {
+            Lend::Wait(rx) => rx,
+            Lend::Ready(_) => panic!("the pool is full"),
+        };
+        assert!(line.lend_with(2, 1, conns(1)).await.is_err());
+
+        //the waiter gets the recycled conn, then the slot of the discarded one.
+        line.recycle(2, forever, first).await;
+        let got = rx.try_recv().unwrap().unwrap();
+        let mut rx = match line.lend_with(2, 1, conns(1)).await.unwrap() {
+            Lend::Wait(rx) => rx,
+            Lend::Ready(_) => panic!("the pool is full"),
+        };
+        line.discard(second).await;
+        assert!(rx.try_recv().unwrap().is_none());
+        assert_eq!(line.total_conn_count, 2);
+
+        //the slot is released unused, then idle conns shrink down to min.
+        line.release_slot().await;
+        line.recycle(2, forever, got).await;
+        assert_eq!(line.shrink(Duration::from_secs(60), 0, 8, forever).await, 0);
+        assert_eq!(line.shrink(Duration::ZERO, 1, 8, forever).await, 0);
+        assert_eq!(line.shrink(Duration::ZERO, 0, 8, forever).await, 1);
+        assert_eq!(line.total_conn_count, 0);
+    }
+}
@@ -1,3 +1,2 @@
-pub mod checker;
 pub mod inner;
 pub mod node;
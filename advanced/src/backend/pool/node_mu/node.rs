@@ -1,11 +1,12 @@
-use super::checker::{grow, loop_check};
-use super::inner::InnerLine;
+use super::inner::{InnerLine, Lend};
 use crate::backend::conn::P2MConn;
-use crate::backend::error::BackendResult;
+use crate::backend::error::{BackendError, BackendResult};
+use crate::backend::pool::checker::grow;
 use crate::backend::pool::node_cfg::NodeCfg;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tokio::task;
+use tokio::time;
 
 #[derive(Debug)]
 pub struct NodePipeLine {
@@ -23,23 +24,49 @@ impl NodePipeLine {
             cfg,
         })
     }
-    #[inline]
-    #[allow(unused_must_use)]
-    pub async fn init(self: &Arc<Self>) {
-        let self_shared = self.clone();
-        task::spawn(async move {
-            self_shared.reonline().await;
-        });
-        loop_check(&self).await;
-    }
-    //fast fail!!!
+    //a full pool puts the borrower in line, it fails after the wait timeout.
     #[inline]
     pub async fn get_conn(self: &Arc<Self>) -> BackendResult<P2MConn> {
-        self.inner
+        let lend = self
+            .inner
             .lock()
             .await
-            .lend_with(self.cfg.max_conns_limit, grow(&self, self.cfg.grow_count))
-            .await
+            .lend_with(
+                self.cfg.max_conns_limit,
+                self.cfg.wait_queue_limit,
+                grow(&self.cfg, self.cfg.grow_count),
+            )
+            .await?;
+        let mut rx = match lend {
+            Lend::Ready(conn) => return Ok(conn),
+            Lend::Wait(rx) => rx,
+        };
+        let got = match time::timeout(Duration::from_millis(self.cfg.wait_timeout), &mut rx).await {
+            Ok(got) => got.map_err(|_| BackendError::InnerErrOfflineOrQuit)?,
+            Err(_) => {
+                //whatever was handed over meanwhile is still ours.
+                rx.close();
+                match rx.try_recv() {
+                    Ok(Some(conn)) => return Ok(conn),
+                    Ok(None) => self.inner.lock().await.release_slot().await,
+                    Err(_) => {}
+                }
+                return Err(BackendError::PoolErrWaitTimeout(self.cfg.node_id.clone()));
+            }
+        };
+        match got {
+            Some(conn) => Ok(conn),
+            //the slot of a discarded conn, which is counted already.
+            None => match grow(&self.cfg, 1).await.pop_front() {
+                Some(conn) => Ok(conn),
+                None => {
+                    self.inner.lock().await.release_slot().await;
+                    Err(BackendError::PoolErrConnGrowFailed(
+                        self.cfg.node_id.clone(),
+                    ))
+                }
+            },
+        }
     }
     #[inline]
     #[allow(unused_must_use)]
@@ -47,7 +74,11 @@ impl NodePipeLine {
         self.inner
             .lock()
             .await
-            .recycle(self.cfg.max_conns_limit, conn)
+            .recycle(
+                self.cfg.max_conns_limit,
+                Duration::from_secs(self.cfg.max_lifetime),
+                conn,
+            )
             .await;
     }
     #[inline]
@@ -55,7 +86,7 @@ impl NodePipeLine {
         self.inner
             .lock()
             .await
-            .reonline_with(&self.cfg.node_id, grow(&self, self.cfg.grow_count))
+            .reonline_with(&self.cfg.node_id, grow(&self.cfg, self.cfg.grow_count))
             .await
     }
     #[inline]
@@ -90,4 +121,17 @@ impl NodePipeLine {
             .takeup(self.cfg.max_conns_limit, conn)
             .await;
     }
+    #[inline]
+    pub async fn shrink(self: &Arc<Self>) -> usize {
+        self.inner
+            .lock()
+            .await
+            .shrink(
+                Duration::from_secs(self.cfg.idle_time_to_shrink),
+                self.cfg.min_conns_limit,
+                self.cfg.shrink_count,
+                Duration::from_secs(self.cfg.max_lifetime),
+            )
+            .await
+    }
 }
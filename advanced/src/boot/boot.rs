@@ -1,44 +1,35 @@
 use std::error::Error;
+use std::sync::Arc;
 
 use crate::boot::discovery::{Discovery, DiscoveryProvider};
-use crate::config::Tenant;
 use crate::security;
 use crate::security::tenant::TenantManager;
 
 pub fn bootstrap(config: String) -> Result<DiscoveryProvider, Box<dyn Error>> {
     let mut provider = DiscoveryProvider::new(config);
-    match provider.init() {
-        Some(err) => return Err(err),
-        None => {}
-    };
-    let clusters = match provider.list_clusters() {
-        Ok(cluster) => cluster,
-        Err(err) => return Err(err),
-    };
+    if let Some(err) = provider.init() {
+        return Err(err);
+    }
+    let tenant_manager = security::TenantManagerProvider::new();
+    let clusters = provider.list_clusters()?;
     for cluster in clusters {
-        let _cluster = match provider.cluster(cluster){
+        let cluster = match provider.cluster(cluster) {
             Ok(c) => c,
             Err(_) => continue,
         };
+        tenant_manager.put_cluster(cluster.tenant, cluster.name);
     }
-    let tenants = match provider.list_tenants() {
-        Ok(tenants) => tenants,
-        Err(err) => return Err(err),
-    };
+    let tenants = provider.list_tenants()?;
 
     for item in tenants {
         let tenant = match provider.tenant(item.clone()) {
             Ok(tenant) => tenant,
-            Err(err) => continue,
+            Err(_) => continue,
         };
-        let tenant_provider = security::TenantManagerProvider::new();
         for user in tenant.users {
-            tenant_provider.put_user(item.clone(), user);
+            tenant_manager.put_user(item.clone(), user);
         }
-    };
+    }
+    provider.tenant_manager = Some(Arc::new(tenant_manager));
     Ok(provider)
 }
-
-fn build_namespace() {
-    todo!()
-}
@@ -1,13 +1,18 @@
 use std::error::Error;
 use std::fs::File;
 
-use serde::Deserialize;
 use crate::boot::error::BootstrapError;
+use serde::Deserialize;
 
 use crate::config::{
     Center, Configuration, DataSourceCluster, Filter, Group, Listener, Node, Tenant,
 };
+use crate::proto::rule::VTable;
+use crate::security::TenantManagerProvider;
+use std::sync::Arc;
 
+//the lookups of the discovery API, the proxy boots through only some of them.
+#[allow(dead_code)]
 pub trait Discovery {
     // Init init discovery
     fn init(&mut self) -> Option<Box<dyn Error>>;
@@ -48,8 +53,7 @@ pub trait Discovery {
     fn list_tables(&self, cluster: String) -> Result<Vec<String>, Box<dyn Error>>;
 
     // GetTable returns the table info.
-    // TODO return Result<rule.VTable, Error>;
-    fn table(&self, cluster: String, table: String) -> Result<Vec<String>, Box<dyn Error>>;
+    fn table(&self, cluster: String, table: String) -> Result<VTable, Box<dyn Error>>;
 
     // GetConfigCenter
     fn config_center(self) -> Result<Center, Box<dyn Error>>;
@@ -65,6 +69,7 @@ pub struct DiscoveryProvider {
     pub path: String,
     pub options: Option<BootOptions>,
     pub center: Option<Center>,
+    pub tenant_manager: Option<Arc<TenantManagerProvider>>, //the logins of every tenant, once booted.
 }
 
 impl DiscoveryProvider {
@@ -73,6 +78,7 @@ impl DiscoveryProvider {
             path,
             options: None,
             center: None,
+            tenant_manager: None,
         }
     }
     fn load_boot_options(&mut self) -> Option<Box<dyn Error>> {
@@ -91,8 +97,11 @@ impl DiscoveryProvider {
     }
 
     fn init_config_center(&mut self) -> Option<Box<dyn Error>> {
-        let center = Center::new();
-        let center = match center {
+        let options = match &self.options {
+            Some(options) => &options.config,
+            None => return Some("no bootstrap options loaded".into()),
+        };
+        let center = match Center::new(options) {
             Ok(data) => data,
             Err(err) => return Some(err),
         };
@@ -117,12 +126,8 @@ impl DiscoveryProvider {
 impl Discovery for DiscoveryProvider {
     fn init(&mut self) -> Option<Box<dyn Error>> {
         match self.load_boot_options() {
-            Some(err) => {
-                return Some(err)
-            }
-            None => {
-                self.init_config_center()
-            }
+            Some(err) => Some(err),
+            None => self.init_config_center(),
         }
     }
 
@@ -144,17 +149,17 @@ impl Discovery for DiscoveryProvider {
     }
 
     fn list_listeners(&self) -> Result<Vec<Listener>, Box<dyn Error>> {
-        return match self.load().data.listeners {
+        match self.load().data.listeners {
             Some(listener) => Ok(listener),
             None => Ok(Vec::new()),
-        };
+        }
     }
 
     fn list_filters(&self) -> Result<Vec<Filter>, Box<dyn Error>> {
-        return match self.load().data.filters {
+        match self.load().data.filters {
             Some(filters) => Ok(filters),
             None => Ok(Vec::new()),
-        };
+        }
     }
 
     fn list_clusters(&self) -> Result<Vec<String>, Box<dyn Error>> {
@@ -177,11 +182,7 @@ impl Discovery for DiscoveryProvider {
     }
 
     fn list_groups(&self, cluster: String) -> Result<Vec<String>, Box<dyn Error>> {
-        let cluster = self.cluster(cluster);
-        let cluster = match cluster {
-            Ok(cluster) => cluster,
-            Err(err) => return Err(err),
-        };
+        let cluster = self.cluster(cluster)?;
 
         Ok(cluster
             .groups
@@ -191,11 +192,7 @@ impl Discovery for DiscoveryProvider {
     }
 
     fn list_nodes(&self, cluster: String, group: String) -> Result<Vec<String>, Box<dyn Error>> {
-        let cluster = self.cluster(cluster);
-        let cluster = match cluster {
-            Ok(cluster) => cluster,
-            Err(err) => return Err(err),
-        };
+        let cluster = self.cluster(cluster)?;
         let group: Option<Group> = cluster.groups.into_iter().find(|g| g.name.eq(&group));
         let result = Vec::new();
         if group.is_none() {
@@ -215,11 +212,7 @@ impl Discovery for DiscoveryProvider {
         group: String,
         node: String,
     ) -> Result<Option<Node>, Box<dyn Error>> {
-        let cluster = self.cluster(cluster);
-        let cluster = match cluster {
-            Ok(cluster) => cluster,
-            Err(err) => return Err(err),
-        };
+        let cluster = self.cluster(cluster)?;
         let group: Option<Group> = cluster.groups.into_iter().find(|g| g.name.eq(&group));
         if group.is_none() {
             return Ok(None);
@@ -227,12 +220,28 @@ impl Discovery for DiscoveryProvider {
         Ok(group.unwrap().nodes.into_iter().find(|n| n.name.eq(&node)))
     }
 
+    //the sharded tables are named `schema.table` in the rules, the schema is the cluster.
     fn list_tables(&self, cluster: String) -> Result<Vec<String>, Box<dyn Error>> {
-        todo!()
+        Ok(self
+            .load()
+            .data
+            .sharding_rule
+            .tables
+            .iter()
+            .filter_map(|t| t.name.split_once('.'))
+            .filter(|(schema, _)| *schema == cluster)
+            .map(|(_, name)| name.to_string())
+            .collect())
     }
 
-    fn table(&self, cluster: String, table: String) -> Result<Vec<String>, Box<dyn Error>> {
-        todo!()
+    fn table(&self, cluster: String, table: String) -> Result<VTable, Box<dyn Error>> {
+        let name = format!("{}.{}", cluster, table);
+        for each in self.load().data.sharding_rule.tables {
+            if each.name == name {
+                return Ok(VTable::new(&each)?);
+            }
+        }
+        Err(Box::new(BootstrapError::TableNotExist(name)))
     }
 
     fn config_center(self) -> Result<Center, Box<dyn Error>> {
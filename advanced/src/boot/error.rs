@@ -1,15 +1,26 @@
 #![allow(dead_code)]
 #[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum BootstrapError {
     DataSourceClusterNotExist(String),
     TenantNotExist(String),
+    TableNotExist(String),
 }
 
 impl std::fmt::Display for BootstrapError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            BootstrapError::DataSourceClusterNotExist(cluster) => write!(f, "BootstrapError::DataSourceClusterNotExist::clusster:{}", cluster),
-            BootstrapError::TenantNotExist(tenant) => write!(f, "BootstrapError::TenantNotExist::Tenant:{}", tenant),
+            BootstrapError::DataSourceClusterNotExist(cluster) => write!(
+                f,
+                "BootstrapError::DataSourceClusterNotExist::clusster:{}",
+                cluster
+            ),
+            BootstrapError::TenantNotExist(tenant) => {
+                write!(f, "BootstrapError::TenantNotExist::Tenant:{}", tenant)
+            }
+            BootstrapError::TableNotExist(table) => {
+                write!(f, "BootstrapError::TableNotExist::Table:{}", table)
+            }
         }
     }
 }
@@ -19,6 +30,7 @@ impl std::error::Error for BootstrapError {
         match self {
             BootstrapError::DataSourceClusterNotExist(..) => None,
             BootstrapError::TenantNotExist(..) => None,
+            BootstrapError::TableNotExist(..) => None,
         }
     }
 }
@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod boot;
 pub mod discovery;
-mod error;
\ No newline at end of file
+mod error;
@@ -7,7 +7,7 @@ pub fn init() -> Command<'static> {
         .subcommand(
             clap::command!("start")
                 .arg(
-                    clap::arg!(--"c" <PATH>)
+                    clap::arg!(--"c" <PATH> "the config file")
                         .required(false)
                         .allow_invalid_utf8(true),
                 )
@@ -17,15 +17,59 @@ pub fn init() -> Command<'static> {
         )
         .subcommand(
             clap::command!("import")
+                .arg(clap::arg!(--"c" <PATH> "the config file, toml or yaml"))
                 .arg(
-                    clap::arg!(--"c" <PATH>)
+                    clap::arg!(--"bootstrap" <PATH> "the bootstrap options naming the config center")
                         .required(false)
-                        .allow_invalid_utf8(true),
+                        .default_value("src/conf/bootstrap.yaml"),
+                )
+                .version("0.1.0")
+                .help_template("{bin} ({version}) - {usage} {all-args} {about}")
+                .about("Import Proxy config into the config center"),
+        )
+        .subcommand(
+            clap::command!("export")
+                .arg(
+                    clap::arg!(--"c" <PATH> "the config file, the config center when none")
+                        .required(false),
+                )
+                .arg(
+                    clap::arg!(--"bootstrap" <PATH> "the bootstrap options naming the config center")
+                        .required(false)
+                        .default_value("src/conf/bootstrap.yaml"),
+                )
+                .arg(
+                    clap::arg!(--"format" <FORMAT> "yaml or toml")
+                        .required(false)
+                        .default_value("yaml"),
                 )
+                .arg(clap::arg!(--"o" <PATH> "the file to write, stdout when none").required(false))
                 .version("0.1.0")
                 .help_template("{bin} ({version}) - {usage} {all-args} {about}")
-                .about("Import Proxy config"),
-        ).help_expected(true);
+                .about("Export the effective Proxy config as yaml or toml"),
+        )
+        .subcommand(
+            clap::command!("validate")
+                .arg(clap::arg!(--"c" <PATH> "the config file, toml or a yaml ConfigMap"))
+                .version("0.1.0")
+                .help_template("{bin} ({version}) - {usage} {all-args} {about}")
+                .about("Report every problem of a config file"),
+        )
+        .subcommand(
+            clap::command!("reshard")
+                .arg(clap::arg!(--"old" <PATH> "the schema config the rows are sharded by now"))
+                .arg(clap::arg!(--"new" <PATH> "the schema config to shard the rows by"))
+                .arg(clap::arg!(--"dry-run" "only report how many rows would move per target"))
+                .arg(
+                    clap::arg!(--"batch" <N> "distinct shard values read per query")
+                        .required(false)
+                        .default_value("1000"),
+                )
+                .version("0.1.0")
+                .help_template("{bin} ({version}) - {usage} {all-args} {about}")
+                .about("Reshard tables from an old schema config to a new one"),
+        )
+        .help_expected(true);
 
     command
 }
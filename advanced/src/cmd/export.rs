@@ -0,0 +1,28 @@
+//export: the effective config, of a file or of the config center of the bootstrap options, written
+//as yaml or toml to stdout or a file.
+use crate::cmd::import::open_center;
+use crate::config::{export, load_checked, Format};
+use std::error::Error;
+
+pub fn run(
+    path: Option<&str>,
+    bootstrap: &str,
+    format: &str,
+    out: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let format = match format.trim() {
+        "yaml" | "yml" => Format::Yaml,
+        "toml" => Format::Toml,
+        other => return Err(format!("unknown format {}, yaml or toml", other).into()),
+    };
+    let cfg = match path {
+        Some(path) => load_checked(path)?,
+        None => open_center(bootstrap)?.load()?,
+    };
+    let text = export(&cfg, format)?;
+    match out {
+        Some(out) => std::fs::write(out, text)?,
+        None => print!("{}", text),
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+//import: check a config file and put it into the config center of the bootstrap options, from
+//which every proxy started with them loads it.
+use crate::boot::discovery::BootOptions;
+use crate::config::center;
+use crate::config::{ConfigCenter, Format};
+use std::error::Error;
+use std::sync::Arc;
+
+pub const DEFAULT_BOOTSTRAP: &str = "src/conf/bootstrap.yaml";
+
+//the center the bootstrap options at `bootstrap` name.
+pub fn open_center(bootstrap: &str) -> Result<Arc<dyn ConfigCenter>, Box<dyn Error>> {
+    let options: BootOptions = serde_yaml::from_reader(std::fs::File::open(bootstrap)?)?;
+    Ok(center::open(&options.config)?)
+}
+
+pub fn run(path: &str, bootstrap: &str) -> Result<(), Box<dyn Error>> {
+    let center = open_center(bootstrap)?;
+    let text = std::fs::read_to_string(path)?;
+    if let Err(e) = center.import(&text, Format::of(path), path) {
+        for line in e.lines() {
+            println!("{}", line);
+        }
+        return Err(Box::new(e));
+    }
+    println!("{}: imported into {}", path, center.name());
+    Ok(())
+}
@@ -1,3 +1,6 @@
 pub mod cmds;
+pub mod export;
+pub mod import;
+pub mod reshard;
 pub mod start;
-pub mod tools;
+pub mod validate;
@@ -0,0 +1,234 @@
+#![allow(dead_code)]
+//reshard: which rows of the sharded tables move when the schema config changes, e.g. when a table
+//outgrows its each_cluster_table_split_count. only the dry run is supported for now, it scans the
+//shard values of every physical table of the old config and counts the rows each (cluster, table)
+//of the new config would take over, nothing is copied.
+use crate::backend::error::BackendResult;
+use crate::backend::merger::read_result_set;
+use crate::backend::pool::P2MConnPool;
+use crate::config::load_config_from;
+use crate::mysql::utils::quote_sql_string;
+use crate::router::{build_router_with, TableSectionEntry};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+//distinct shard values read per query.
+pub const DEFAULT_BATCH: u64 = 1000;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TableMoves {
+    pub rows: u64,
+    pub moved: u64,
+    pub unroutable: u64, //null shard values, or ones the new rules reject.
+    pub targets: BTreeMap<(String, String), u64>, //(cluster_id, table) -> rows moved to it.
+}
+
+impl TableMoves {
+    //`count` rows of the shard value `value` which are at `from` now.
+    pub fn add(&mut self, new: &TableSectionEntry, from: (&str, &str), value: &str, count: u64) {
+        self.rows += count;
+        let (c_id, table) = match new.lookup_one_path(value) {
+            Ok(path) => path,
+            Err(_) => {
+                self.unroutable += count;
+                return;
+            }
+        };
+        if (c_id, table.as_str()) != from {
+            self.moved += count;
+            *self.targets.entry((c_id.to_string(), table)).or_insert(0) += count;
+        }
+    }
+}
+
+pub async fn run(
+    old_path: &str,
+    new_path: &str,
+    dry_run: bool,
+    batch: u64,
+) -> Result<(), Box<dyn Error>> {
+    if !dry_run {
+        return Err("copying rows is not supported yet, run reshard with --dry-run".into());
+    }
+    let old_cfg = load_config_from(old_path)?;
+    let new_cfg = load_config_from(new_path)?;
+    let old_router = build_router_with(&old_cfg)?;
+    let new_router = build_router_with(&new_cfg)?;
+    let pool = P2MConnPool::build_pool_with(&old_cfg).await?;
+    let rc = async {
+        for (user, db, old) in old_router.list_tables() {
+            let name = format!("{}.{}.{}", user, db, old.get_table());
+            let new = match new_router
+                .lookup_db(user, db)
+                .and_then(|d| d.lookup_table(old.get_table()))
+            {
+                Ok(new) => new,
+                Err(_) => {
+                    println!("{}: not in the new config, skipped", name);
+                    continue;
+                }
+            };
+            let mut moves = TableMoves::default();
+            for (c_id, table) in old.load_all_path()? {
+                scan(
+                    &pool,
+                    db,
+                    (c_id, &table),
+                    old.get_shard_key(),
+                    batch,
+                    new,
+                    &mut moves,
+                )
+                .await?;
+            }
+            println!(
+                "{}: {} rows, {} to move, {} unroutable",
+                name, moves.rows, moves.moved, moves.unroutable
+            );
+            for ((c_id, table), count) in moves.targets.iter() {
+                println!("    -> {}.{}: {}", c_id, table, count);
+            }
+        }
+        Ok::<(), Box<dyn Error>>(())
+    }
+    .await;
+    pool.quit().await;
+    rc
+}
+
+//tally the rows of one physical table, a batch of distinct shard values with their counts at a
+//time in the order of the values, then the rows without one.
+async fn scan(
+    pool: &P2MConnPool,
+    db: &str,
+    from: (&str, &str),
+    shard_key: &str,
+    batch: u64,
+    new: &TableSectionEntry,
+    moves: &mut TableMoves,
+) -> BackendResult<()> {
+    let mut conn = pool.get_conn(from.0, false).await?;
+    let rc = async {
+        conn.use_db(db).await?;
+        let (key, table) = (quote(shard_key), quote(from.1));
+        let mut last: Option<Vec<u8>> = None;
+        loop {
+            let after = match &last {
+                Some(v) => format!(" AND {} > {}", key, quote_sql_string(v)),
+                None => String::new(),
+            };
+            let sql = format!(
+                "SELECT {k}, COUNT(*) FROM {t} WHERE {k} IS NOT NULL{a} GROUP BY {k} ORDER BY {k} LIMIT {b}",
+                k = key,
+                t = table,
+                a = after,
+                b = batch.max(1)
+            );
+            let rs = read_result_set(&mut conn, &sql).await?;
+            for row in rs.rows.iter() {
+                if let (Some(Some(value)), Some(Some(count))) = (row.values.first(), row.values.get(1)) {
+                    let count = String::from_utf8_lossy(count).parse::<u64>().unwrap_or(0);
+                    moves.add(new, from, &String::from_utf8_lossy(value), count);
+                }
+            }
+            if (rs.rows.len() as u64) < batch.max(1) {
+                break;
+            }
+            last = rs.rows.last().and_then(|r| r.values[0].clone());
+        }
+        let sql = format!("SELECT COUNT(*) FROM {} WHERE {} IS NULL", table, key);
+        let rs = read_result_set(&mut conn, &sql).await?;
+        let nulls = rs
+            .rows
+            .first()
+            .and_then(|r| r.values.first().cloned().flatten())
+            .map(|c| String::from_utf8_lossy(&c).parse::<u64>().unwrap_or(0))
+            .unwrap_or(0);
+        moves.rows += nulls;
+        moves.unroutable += nulls;
+        Ok(())
+    }
+    .await;
+    match rc {
+        Ok(()) => pool.recycle(conn).await,
+        Err(_) => pool.discard(conn).await,
+    }
+    rc
+}
+
+fn quote(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TableMoves;
+    use crate::config::Config;
+    use crate::router::build_router_with;
+
+    fn config(split: &str) -> String {
+        format!(
+            r#"
+[proxy]
+listen_addr = "127.0.0.1:9696"
+users = [{{ user = "root", pwd = "root" }}]
+
+[[node]]
+id = "mysql_1"
+listen_addr = "127.0.0.1:3306"
+user = "root"
+pwd = "root"
+
+[[cluster]]
+id = "cluster_1"
+master_node_id = "mysql_1"
+
+[[cluster]]
+id = "cluster_2"
+master_node_id = "mysql_1"
+
+[[schema]]
+owner = "root"
+[[schema.db]]
+db = "db1"
+cluster_ids = ["cluster_1", "cluster_2"]
+[[schema.db.table]]
+table = "integer_table"
+shard_key = "id"
+shard_type = "integer"
+each_cluster_table_split_count = {}
+"#,
+            split
+        )
+    }
+
+    #[test]
+    fn count_moved_rows() {
+        let old_cfg: Config = toml::from_str(&config("[2, 2]")).unwrap();
+        let new_cfg: Config = toml::from_str(&config("[4, 4]")).unwrap();
+        let old_router = build_router_with(&old_cfg).unwrap();
+        let new_router = build_router_with(&new_cfg).unwrap();
+        let (_, db, old) = old_router.list_tables()[0];
+        let new = new_router
+            .lookup_db("root", db)
+            .unwrap()
+            .lookup_table(old.get_table())
+            .unwrap();
+
+        let mut moves = TableMoves::default();
+        for id in 0..8 {
+            let (c_id, table) = old.lookup_one_path(&id.to_string()).unwrap();
+            moves.add(new, (c_id, &table), &id.to_string(), 10);
+        }
+        moves.add(new, ("cluster_1", "integer_table_0"), "x", 1);
+        //2, 3, 6 and 7 stay on their clusters, but move to the tables _2 and _3.
+        assert_eq!((moves.rows, moves.moved, moves.unroutable), (81, 40, 1));
+        assert_eq!(
+            moves
+                .targets
+                .get(&("cluster_1".to_string(), "integer_table_2".to_string())),
+            Some(&20)
+        );
+        assert_eq!(moves.targets.len(), 2);
+    }
+}
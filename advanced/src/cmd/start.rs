@@ -1,52 +1,49 @@
 use crate::boot::boot;
-use crate::boot::discovery::{Discovery, DiscoveryProvider};
-use crate::config::Filter;
+use crate::boot::discovery::Discovery;
 use crate::executor::redirect::RedirectExecutor;
+use crate::frontend::executor::ShardingExecutor;
 use crate::mysql::server::Listener;
-use crate::proto::interface::{Executor, FilterFactory};
+use crate::proxy::reload::{Reloader, WATCH_INTERVAL};
 use crate::server::server::Server;
+use std::error::Error;
 
-pub fn run(config: String) {
-    //TODO default config.
-    let provider = match boot::bootstrap(config) {
-        Ok(provider) => provider,
-        Err(_) => {
-            // TODO log
-            return;
-        }
-    };
-    let filters = provider.list_filters();
-    if filters.is_err() {
-        // TODO log
-        return;
-    }
-    for each in filters.unwrap() {
-        let factory = each.factory(each.name.clone());
-        if factory.is_none() {
-            panic!()
-        }
-        let factory = factory.unwrap();
-        let filter: Filter = match factory.new_filter(each.config.clone()) {
-            Ok(filter) => filter,
-            Err(err) => panic!(),
-        };
-        filter.register(filter.name.clone());
-    }
-
-    let listeners_config = match provider.list_listeners() {
-        Ok(listeners) => listeners,
-        Err(err) => {
-            // TODO log
-            return;
-        }
-    };
+//boot from the bootstrap options: the config of the center they name drives the executor of each
+//mysql listener of it, the sharding one unless the listener names another.
+pub async fn run(config: String) -> Result<(), Box<dyn Error>> {
+    let provider = boot::bootstrap(config)?;
+    let listeners_config = provider.list_listeners()?;
+    let center = provider.config_center()?;
+    let reloader = Reloader::new(center.center().clone(), &center.load_config()?)
+        .await
+        .map_err(|e| e.to_string())?;
+    log::info!("filters: {:?}", reloader.snapshot().filters.names());
+    reloader.watch(WATCH_INTERVAL);
 
     let mut listeners: Vec<Box<dyn crate::proto::interface::Listener>> = Vec::new();
     for config in listeners_config {
-        let executor = RedirectExecutor::new();
-        let listener = Listener::new(executor, config);
-        listeners.push(Box::new(listener));
+        if !config.protocol_type.eq_ignore_ascii_case("mysql") {
+            log::warn!(
+                "listener of protocol {} not supported",
+                config.protocol_type
+            );
+            continue;
+        }
+        match config.executor.as_deref().map(str::trim) {
+            None | Some("sharding") => {
+                let executor = ShardingExecutor::new(reloader.clone());
+                listeners.push(Box::new(Listener::new(executor, config)));
+            }
+            Some("redirect") => {
+                let executor = RedirectExecutor::new(reloader.clone());
+                listeners.push(Box::new(Listener::new(executor, config)));
+            }
+            Some(other) => return Err(format!("unknown executor {}", other).into()),
+        }
+    }
+    if listeners.is_empty() {
+        return Err("no mysql listener".into());
     }
     let server = Server::new(listeners);
-    server.start();
+    server.start().await?;
+    Ok(())
 }
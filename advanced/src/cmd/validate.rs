@@ -0,0 +1,20 @@
+//validate: load a config file as the proxy would, and print each of its problems as
+//`file: field: why`.
+use crate::config::load_checked;
+use std::error::Error;
+
+pub fn run(path: &str) -> Result<(), Box<dyn Error>> {
+    match load_checked(path) {
+        Ok(_) => {
+            println!("{}: ok", path);
+            Ok(())
+        }
+        Err(e) => {
+            let lines = e.lines();
+            for line in lines.iter() {
+                println!("{}", line);
+            }
+            Err(format!("{} problems in {}", lines.len(), path).into())
+        }
+    }
+}
@@ -0,0 +1,146 @@
+#![allow(dead_code)]
+//a directory of config fragments, such as `00-proxy.yaml`, `10-nodes.yaml`, `20-schema.yaml`, read
+//in the order of their names and merged into one config: tables merge key by key, lists are
+//joined, any other value is the one of the last fragment. only the fragments of the format of the
+//center are read. they are edited in place, an import goes to a file or an etcd center.
+use super::ConfigCenter;
+use crate::config::configer::Format;
+use crate::config::error::{ConfigError, ConfigResult};
+use serde_yaml::Value;
+
+#[derive(Debug, Clone)]
+pub struct DirCenter {
+    path: String,
+    format: Format,
+}
+
+impl DirCenter {
+    pub fn new(path: String, format: Format) -> DirCenter {
+        DirCenter { path, format }
+    }
+    //the fragments in the order they are merged.
+    fn fragments(&self) -> ConfigResult<Vec<std::path::PathBuf>> {
+        let io_err = |e| ConfigError::IO(self.path.clone(), e);
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(&self.path).map_err(io_err)? {
+            let path = entry.map_err(io_err)?.path();
+            if path.is_file() && path.to_str().map(Format::of) == Some(self.format) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+impl ConfigCenter for DirCenter {
+    fn name(&self) -> &str {
+        &self.path
+    }
+    fn format(&self) -> Format {
+        self.format
+    }
+    fn read(&self) -> ConfigResult<String> {
+        let mut merged: Option<Value> = None;
+        for path in self.fragments()? {
+            let origin = path.display().to_string();
+            let text =
+                std::fs::read_to_string(&path).map_err(|e| ConfigError::IO(origin.clone(), e))?;
+            let value: Value = match self.format {
+                Format::Yaml => serde_yaml::from_str(&text).map_err(|e| e.to_string()),
+                Format::Toml => toml::from_str::<toml::Value>(&text)
+                    .map_err(|e| e.to_string())
+                    .and_then(|v| serde_yaml::to_value(v).map_err(|e| e.to_string())),
+            }
+            .map_err(|why| ConfigError::Parse(origin, why))?;
+            merged = Some(match merged {
+                Some(into) => merge(into, value),
+                None => value,
+            });
+        }
+        let merged = merged.ok_or_else(|| {
+            ConfigError::Center(self.path.clone(), "no config fragment".to_string())
+        })?;
+        let ser_err = |e: String| ConfigError::Parse(self.path.clone(), e);
+        match self.format {
+            Format::Yaml => serde_yaml::to_string(&merged).map_err(|e| ser_err(e.to_string())),
+            Format::Toml => toml::Value::try_from(&merged)
+                .and_then(|v| toml::to_string_pretty(&v))
+                .map_err(|e| ser_err(e.to_string())),
+        }
+    }
+    fn write(&self, _text: &str) -> ConfigResult<()> {
+        Err(ConfigError::Center(
+            self.path.clone(),
+            "the fragments of a dir are edited in place".to_string(),
+        ))
+    }
+    //the name, modified time and size of each fragment.
+    fn revision(&self) -> ConfigResult<Option<String>> {
+        let mut revision = String::new();
+        for path in self.fragments()? {
+            let m = std::fs::metadata(&path)
+                .map_err(|e| ConfigError::IO(path.display().to_string(), e))?;
+            revision.push_str(&format!(
+                "{}@{:?}/{};",
+                path.display(),
+                m.modified().ok(),
+                m.len()
+            ));
+        }
+        Ok(Some(revision).filter(|r| !r.is_empty()))
+    }
+}
+
+fn merge(into: Value, from: Value) -> Value {
+    match (into, from) {
+        (Value::Mapping(mut into), Value::Mapping(from)) => {
+            for (k, v) in from {
+                let v = match into.remove(&k) {
+                    Some(old) => merge(old, v),
+                    None => v,
+                };
+                into.insert(k, v);
+            }
+            Value::Mapping(into)
+        }
+        (Value::Sequence(mut into), Value::Sequence(from)) => {
+            into.extend(from);
+            Value::Sequence(into)
+        }
+        (_, from) => from,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DirCenter;
+    use crate::config::center::ConfigCenter;
+    use crate::config::configer::Format;
+
+    #[test]
+    fn merge_fragments() {
+        let dir = std::env::temp_dir().join(format!("center_dir_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = std::fs::read_to_string("src/etc/config.toml").unwrap();
+        //the schema in a fragment of its own, read after the rest.
+        let at = text.find("\n[[schema]]").unwrap();
+        std::fs::write(dir.join("20-schema.toml"), &text[at..]).unwrap();
+        std::fs::write(dir.join("10-proxy.toml"), &text[..at]).unwrap();
+        std::fs::write(dir.join("notes.yaml"), "not: read").unwrap();
+
+        let center = DirCenter::new(dir.to_str().unwrap().to_string(), Format::Toml);
+        let cfg = center.load().unwrap();
+        assert!(!cfg.schema.is_empty() && !cfg.node.is_empty());
+        let before = center.revision().unwrap();
+        assert!(before.is_some());
+        std::fs::write(dir.join("30-more.toml"), "[global]\nlog_level = \"info\"\n").unwrap();
+        assert_ne!(center.revision().unwrap(), before);
+        assert_eq!(
+            center.load().unwrap().query_log_level(),
+            Some(log::LevelFilter::Info)
+        );
+        assert!(center.write("").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
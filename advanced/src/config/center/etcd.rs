@@ -0,0 +1,290 @@
+#![allow(dead_code)]
+//a key of etcd v3, through the json gateway of its kv api over plain http: `/v3/kv/range` reads
+//the key and its mod_revision, `/v3/kv/put` writes it. keys and values are base64 in the json.
+//`endpoints` are tried in order until one answers.
+use super::ConfigCenter;
+use crate::config::configer::Format;
+use crate::config::error::{ConfigError, ConfigResult};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+pub const DEFAULT_ENDPOINT: &str = "http://localhost:2379";
+pub const DEFAULT_KEY: &str = "/mydbproxy/config";
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct EtcdCenter {
+    endpoints: Vec<String>, //host:port
+    key: String,
+    format: Format,
+}
+
+impl EtcdCenter {
+    //`endpoints` such as `http://10.0.0.1:2379,http://10.0.0.2:2379`.
+    pub fn new(endpoints: &str, key: String, format: Format) -> ConfigResult<EtcdCenter> {
+        let mut hosts = Vec::new();
+        for endpoint in endpoints
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+        {
+            match endpoint.strip_prefix("http://") {
+                Some(host) => hosts.push(host.trim_end_matches('/').to_string()),
+                None => {
+                    return Err(ConfigError::Center(
+                        "etcd".to_string(),
+                        format!("endpoint {} is not http://host:port", endpoint),
+                    ))
+                }
+            }
+        }
+        if hosts.is_empty() {
+            return Err(ConfigError::Center(
+                "etcd".to_string(),
+                "no endpoints".to_string(),
+            ));
+        }
+        Ok(EtcdCenter {
+            endpoints: hosts,
+            key,
+            format,
+        })
+    }
+    fn err(&self, why: impl Into<String>) -> ConfigError {
+        ConfigError::Center(format!("etcd {}", self.key), why.into())
+    }
+    //post `body` to `path` of the first endpoint which answers, the json of the answer.
+    fn call(&self, path: &str, body: serde_json::Value) -> ConfigResult<serde_json::Value> {
+        let body = body.to_string();
+        let mut last = String::new();
+        for host in self.endpoints.iter() {
+            match post(host, path, &body) {
+                Ok((200, answer)) => {
+                    return serde_json::from_slice(&answer).map_err(|e| self.err(e.to_string()))
+                }
+                Ok((status, answer)) => {
+                    return Err(self.err(format!(
+                        "{} {}: {}",
+                        path,
+                        status,
+                        String::from_utf8_lossy(&answer)
+                    )))
+                }
+                Err(e) => last = format!("{}: {}", host, e),
+            }
+        }
+        Err(self.err(last))
+    }
+    //the value of the key and its mod_revision.
+    fn range(&self) -> ConfigResult<Option<(String, String)>> {
+        let answer = self.call(
+            "/v3/kv/range",
+            serde_json::json!({ "key": base64::encode(&self.key) }),
+        )?;
+        let kv = match answer.get("kvs").and_then(|kvs| kvs.get(0)) {
+            Some(kv) => kv,
+            None => return Ok(None),
+        };
+        let value = kv.get("value").and_then(|v| v.as_str()).unwrap_or("");
+        let value = base64::decode(value)
+            .ok()
+            .and_then(|v| String::from_utf8(v).ok())
+            .ok_or_else(|| self.err("the value is not base64 utf8"))?;
+        //int64 is a string in the json of etcd.
+        let revision = match kv.get("mod_revision") {
+            Some(serde_json::Value::String(r)) => r.clone(),
+            Some(r) => r.to_string(),
+            None => String::new(),
+        };
+        Ok(Some((value, revision)))
+    }
+}
+
+impl ConfigCenter for EtcdCenter {
+    fn name(&self) -> &str {
+        &self.key
+    }
+    fn format(&self) -> Format {
+        self.format
+    }
+    fn read(&self) -> ConfigResult<String> {
+        self.range()?
+            .map(|(value, _)| value)
+            .ok_or_else(|| self.err("no such key"))
+    }
+    fn write(&self, text: &str) -> ConfigResult<()> {
+        self.call(
+            "/v3/kv/put",
+            serde_json::json!({
+                "key": base64::encode(&self.key),
+                "value": base64::encode(text),
+            }),
+        )
+        .map(|_| ())
+    }
+    fn revision(&self) -> ConfigResult<Option<String>> {
+        Ok(self.range()?.map(|(_, revision)| revision))
+    }
+}
+
+//one http/1.1 request on its own conn, the status and body of the answer.
+fn post(host: &str, path: &str, body: &str) -> std::io::Result<(u16, Vec<u8>)> {
+    let mut stream = TcpStream::connect(host)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path,
+        host,
+        body.len(),
+        body
+    )?;
+    let mut answer = Vec::new();
+    stream.read_to_end(&mut answer)?;
+
+    let bad = |why: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, why.to_string());
+    let end = answer
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| bad("no end of the http head"))?;
+    let head = String::from_utf8_lossy(&answer[..end]).to_string();
+    let body = answer.split_off(end + 4);
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| bad("no http status"))?;
+    let chunked = head.lines().any(|l| {
+        let l = l.to_ascii_lowercase();
+        l.starts_with("transfer-encoding:") && l.contains("chunked")
+    });
+    if chunked {
+        return dechunk(&body)
+            .map(|b| (status, b))
+            .ok_or_else(|| bad("bad chunk"));
+    }
+    Ok((status, body))
+}
+
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let eol = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..eol]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(out);
+        }
+        let data = body.get(eol + 2..eol + 2 + size)?;
+        out.extend_from_slice(data);
+        body = body.get(eol + 4 + size..)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EtcdCenter;
+    use crate::config::center::{watch, ConfigCenter};
+    use crate::config::configer::{export, load_checked, Format};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    //a stand-in for etcd: range and put of the json gateway, on one key space in memory.
+    fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let mut kvs: HashMap<String, (String, i64)> = HashMap::new();
+            let mut revision = 1;
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let (mut path, mut len) = (String::new(), 0);
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if line.starts_with("POST") {
+                        path = line.split_whitespace().nth(1).unwrap().to_string();
+                    }
+                    if let Some(l) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        len = l.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                let req: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                let key = req["key"].as_str().unwrap().to_string();
+                let answer = match path.as_str() {
+                    "/v3/kv/put" => {
+                        revision += 1;
+                        let value = req["value"].as_str().unwrap().to_string();
+                        kvs.insert(key, (value, revision));
+                        serde_json::json!({ "header": { "revision": revision.to_string() } })
+                    }
+                    _ => match kvs.get(&key) {
+                        Some((value, rev)) => serde_json::json!({
+                            "kvs": [{ "key": key, "value": value, "mod_revision": rev.to_string() }],
+                            "count": "1",
+                        }),
+                        None => serde_json::json!({ "header": {} }),
+                    },
+                }
+                .to_string();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+                    answer.len(),
+                    answer
+                )
+                .unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn etcd_share_and_watch() {
+        let endpoints = format!("http://127.0.0.1:1,http://{}", serve());
+        let center: Arc<dyn ConfigCenter> = Arc::new(
+            EtcdCenter::new(&endpoints, "/proxy/config".to_string(), Format::Yaml).unwrap(),
+        );
+        assert_eq!(center.revision().unwrap(), None);
+        assert!(center.read().is_err());
+
+        let text = std::fs::read_to_string("src/etc/config.toml").unwrap();
+        let c = center.clone();
+        tokio::task::spawn_blocking(move || c.import(&text, Format::Toml, "config.toml"))
+            .await
+            .unwrap()
+            .unwrap();
+        let original = load_checked("src/etc/config.toml").unwrap();
+        assert_eq!(
+            export(&center.load().unwrap(), Format::Toml).unwrap(),
+            export(&original, Format::Toml).unwrap()
+        );
+
+        //a second proxy sees the change of the first.
+        let mut changes = watch(center.clone(), Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(*changes.borrow(), 0);
+        let c = center.clone();
+        let yaml = export(&original, Format::Yaml).unwrap();
+        tokio::task::spawn_blocking(move || c.write(&yaml))
+            .await
+            .unwrap()
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), changes.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(*changes.borrow(), 1);
+        assert!(EtcdCenter::new("https://etcd:2379", "k".to_string(), Format::Yaml).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+#![allow(dead_code)]
+use super::ConfigCenter;
+use crate::config::configer::Format;
+use crate::config::error::{ConfigError, ConfigResult};
+
+//the config of the default file center.
+pub const DEFAULT_FILE: &str = "src/conf/config.yaml";
+
+//a file, in the format of its extension, `path` of the options.
+#[derive(Debug, Clone)]
+pub struct FileCenter {
+    path: String,
+}
+
+impl FileCenter {
+    pub fn new(path: String) -> FileCenter {
+        FileCenter { path }
+    }
+}
+
+impl ConfigCenter for FileCenter {
+    fn name(&self) -> &str {
+        &self.path
+    }
+    fn format(&self) -> Format {
+        Format::of(&self.path)
+    }
+    fn read(&self) -> ConfigResult<String> {
+        std::fs::read_to_string(&self.path).map_err(|e| ConfigError::IO(self.path.clone(), e))
+    }
+    //the file is replaced whole, a proxy watching it never reads half of it.
+    fn write(&self, text: &str) -> ConfigResult<()> {
+        let tmp = format!("{}.tmp", self.path);
+        std::fs::write(&tmp, text)
+            .and_then(|_| std::fs::rename(&tmp, &self.path))
+            .map_err(|e| ConfigError::IO(self.path.clone(), e))
+    }
+    //the modified time and size of the file.
+    fn revision(&self) -> ConfigResult<Option<String>> {
+        match std::fs::metadata(&self.path) {
+            Ok(m) => Ok(Some(format!("{:?}/{}", m.modified().ok(), m.len()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(ConfigError::IO(self.path.clone(), e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileCenter;
+    use crate::config::center::ConfigCenter;
+    use crate::config::configer::{export, load_checked, Format};
+
+    #[test]
+    fn import_and_export() {
+        let path = std::env::temp_dir().join(format!("center_{}.yaml", std::process::id()));
+        let center = FileCenter::new(path.to_str().unwrap().to_string());
+        let text = std::fs::read_to_string("src/etc/config.toml").unwrap();
+        center
+            .import(&text, Format::Toml, "src/etc/config.toml")
+            .unwrap();
+
+        let original = load_checked("src/etc/config.toml").unwrap();
+        let stored = center.load().unwrap();
+        assert_eq!(
+            export(&original, Format::Toml).unwrap(),
+            export(&stored, Format::Toml).unwrap()
+        );
+        assert!(!center.read().unwrap().contains("null"));
+        assert!(center.import("[proxy]", Format::Toml, "bad").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
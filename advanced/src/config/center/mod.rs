@@ -0,0 +1,110 @@
+#![allow(dead_code)]
+//the config centers: where a config lives so that many proxies load the same one, and see it
+//change. a center is chosen by the `config` section of the bootstrap options, `name` is one of
+//`file`, `dir` or `etcd` and `options` are its own.
+mod dir;
+mod etcd;
+mod file;
+
+use super::config::ConfigOptions;
+use super::configer::{export, parse_checked, Config, Format};
+use super::error::{ConfigError, ConfigResult};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch as notify;
+
+pub use dir::DirCenter;
+pub use etcd::EtcdCenter;
+pub use file::FileCenter;
+
+pub trait ConfigCenter: std::fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+    //what the config is written in in this center.
+    fn format(&self) -> Format;
+    fn read(&self) -> ConfigResult<String>;
+    fn write(&self, text: &str) -> ConfigResult<()>;
+    //a mark of the stored config which changes when it does, none when there is no config yet.
+    fn revision(&self) -> ConfigResult<Option<String>>;
+    //the stored config, checked.
+    fn load(&self) -> ConfigResult<Config> {
+        parse_checked(&self.read()?, self.format(), self.name())
+    }
+    //store a config read from `origin` in `format`, as it is when the formats are the same.
+    fn import(&self, text: &str, format: Format, origin: &str) -> ConfigResult<Config> {
+        let cfg = parse_checked(text, format, origin)?;
+        if format == self.format() {
+            self.write(text)?;
+        } else {
+            self.write(&export(&cfg, self.format())?)?;
+        }
+        Ok(cfg)
+    }
+}
+
+pub fn open(options: &ConfigOptions) -> ConfigResult<Arc<dyn ConfigCenter>> {
+    let option = |key: &str| options.options.get(key).map(|v| v.trim().to_string());
+    let format = |default: Format| match option("format").as_deref() {
+        Some("toml") => Ok(Format::Toml),
+        Some("yaml") | Some("yml") => Ok(Format::Yaml),
+        None => Ok(default),
+        Some(other) => Err(ConfigError::Center(
+            options.store_name.clone(),
+            format!("unknown format {}, yaml or toml", other),
+        )),
+    };
+    match options.store_name.trim() {
+        "file" => Ok(Arc::new(FileCenter::new(
+            option("path").unwrap_or_else(|| file::DEFAULT_FILE.to_string()),
+        ))),
+        "dir" => {
+            let path = option("path")
+                .ok_or_else(|| ConfigError::Center("dir".to_string(), "no path".to_string()))?;
+            Ok(Arc::new(DirCenter::new(path, format(Format::Yaml)?)))
+        }
+        "etcd" => Ok(Arc::new(EtcdCenter::new(
+            option("endpoints")
+                .as_deref()
+                .unwrap_or(etcd::DEFAULT_ENDPOINT),
+            option("key").unwrap_or_else(|| etcd::DEFAULT_KEY.to_string()),
+            format(Format::Yaml)?,
+        )?)),
+        name => Err(ConfigError::Center(
+            name.to_string(),
+            "no such config center".to_string(),
+        )),
+    }
+}
+
+//a version bumped each time the revision of the center changes, checked every `interval`. the
+//first one is of the config as it is now, a center which can not be read is tried again later.
+pub fn watch(center: Arc<dyn ConfigCenter>, interval: Duration) -> notify::Receiver<u64> {
+    let (tx, rx) = notify::channel(0);
+    tokio::spawn(async move {
+        let mut last: Option<Option<String>> = None;
+        let mut tick = tokio::time::interval(interval);
+        loop {
+            tick.tick().await;
+            let c = center.clone();
+            let now = match tokio::task::spawn_blocking(move || c.revision()).await {
+                Ok(Ok(now)) => now,
+                Ok(Err(e)) => {
+                    log::warn!("config center {} not watched: {}", center.name(), e);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            match &last {
+                None => last = Some(now),
+                Some(prev) if *prev != now => {
+                    last = Some(now);
+                    tx.send_modify(|v| *v += 1);
+                }
+                _ => {}
+            }
+            if tx.is_closed() {
+                return;
+            }
+        }
+    });
+    rx
+}
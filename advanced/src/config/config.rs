@@ -1,8 +1,13 @@
+#![allow(dead_code)]
+use crate::config::center::{self, ConfigCenter};
 use crate::config::config_model::Configuration;
+use crate::config::configer::Config;
+use crate::config::error::ConfigError;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::error::Error;
-use std::fs::File;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ConfigOptions {
@@ -13,8 +18,7 @@ pub struct ConfigOptions {
 
 #[derive(Debug, Clone)]
 pub struct Center {
-    initialize: i32,
-    // store_operate: StoreOperate,
+    center: Arc<dyn ConfigCenter>,
     // conf_holder: atomic.Value,
     // lock:         sync.RWMutex
     // observers    []Observer
@@ -22,21 +26,40 @@ pub struct Center {
 }
 
 impl Center {
-    pub fn new() -> Result<Self, Box<dyn Error>> {
-        // TODO
-        let center = Center { initialize: 0 };
-        return Ok(center);
+    pub fn new(options: &ConfigOptions) -> Result<Self, Box<dyn Error>> {
+        let center = Center {
+            center: center::open(options)?,
+        };
+        Ok(center)
+    }
+
+    #[inline]
+    pub fn center(&self) -> &Arc<dyn ConfigCenter> {
+        &self.center
+    }
+
+    //a version bumped each time the config in the center changes.
+    pub fn watch(&self, interval: Duration) -> tokio::sync::watch::Receiver<u64> {
+        center::watch(self.center.clone(), interval)
     }
 
+    //the ConfigMap in the center, as the discovery reads it.
     pub fn load(&self) -> Result<Configuration, Box<dyn Error>> {
-        let file =
-            File::open("src/conf/config.yaml")?;
-        //TODO is valid yaml file.
-        let content = serde_yaml::from_reader(file);
-        let configuration: Configuration = match content {
-            Ok(content) => content,
-            Err(err) => return Err(Box::new(err)),
-        };
+        let content = self.center.read()?;
+        let value: serde_yaml::Value = serde_yaml::from_str(&content)?;
+        if value.get("kind").is_none() {
+            return Err(Box::new(ConfigError::Parse(
+                self.center.name().to_string(),
+                "not a yaml ConfigMap".to_string(),
+            )));
+        }
+        let configuration: Configuration = serde_yaml::from_str(&content)?;
+        configuration.check_rules()?;
         Ok(configuration)
     }
+
+    //the canonical config in the center, checked.
+    pub fn load_config(&self) -> Result<Config, Box<dyn Error>> {
+        Ok(self.center.load()?)
+    }
 }
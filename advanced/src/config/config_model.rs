@@ -1,25 +1,248 @@
-use regex::{Captures, Regex};
+use regex::Regex;
 use std::collections::HashMap;
 use std::error::Error;
-use std::fs::File;
 
-use crate::proto::interface::FilterFactory;
-use serde::Deserialize;
-
-pub enum ProtocolType {
-    MySQL(u8),
-    HTTP(u8),
-}
+use super::configer::{Config, DBClusterConfig, DBNodeConfig, ProxyConfig, ProxyUser};
+use super::error::Problem;
+use super::schema::{DBSectionConfig, DBShardSchemaConfig, TableSectionConfig};
+use crate::proto::interface::{FilterFactory, QueryFilter};
+use crate::proto::rule::VTable;
+use crate::security::tenant::qualify;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
 pub struct Configuration {
-    pub kind: String,
-    #[serde(rename(serialize = "api_version", deserialize = "apiVersion"))]
-    pub api_version: String,
-    pub metadata: HashMap<String, String>,
     pub data: Data,
 }
 
+impl Configuration {
+    //every sharding rule compiles, so a bad expression fails the load, never a query.
+    pub fn check_rules(&self) -> Result<(), Box<dyn Error>> {
+        for table in self.data.sharding_rule.tables.iter() {
+            VTable::new(table)?;
+        }
+        Ok(())
+    }
+    //the canonical config of this ConfigMap. a group is a cluster of its nodes, the first node
+    //which takes writes is the master. each user of a tenant owns, as `user@tenant`, a db for each
+    //cluster of the tenant, with the tables `cluster.table` of the sharding rule.
+    pub fn to_config(&self) -> Result<Config, Vec<Problem>> {
+        let mut problems = Vec::new();
+        let data = &self.data;
+        let listen_addr = match data
+            .listeners
+            .iter()
+            .flatten()
+            .find(|l| l.protocol_type.eq_ignore_ascii_case("mysql"))
+        {
+            Some(l) => format!("{}:{}", l.socket_address.address, l.socket_address.port),
+            None => {
+                problems.push(Problem::new("data.listeners", "no mysql listener"));
+                String::new()
+            }
+        };
+        let users: Vec<ProxyUser> = data
+            .tenants
+            .iter()
+            .flat_map(|t| {
+                t.users.iter().map(move |u| ProxyUser {
+                    user: u.username.clone(),
+                    pwd: u.password.clone(),
+                    tenant: Some(t.name.clone()),
+                })
+            })
+            .collect();
+
+        let mut node = Vec::new();
+        let mut cluster = Vec::new();
+        for (i, c) in data.clusters.iter().enumerate() {
+            for (j, g) in c.groups.iter().enumerate() {
+                let field = format!("data.clusters[{}].groups[{}]", i, j);
+                let mut master = None;
+                let mut slaves = Vec::new();
+                for (k, n) in g.nodes.iter().enumerate() {
+                    match n.read_write_weight() {
+                        Ok(w) if w.write > 0 && master.is_none() => master = Some(n.name.clone()),
+                        Ok(_) => slaves.push(n.name.clone()),
+                        Err(e) => problems.push(Problem::new(
+                            format!("{}.nodes[{}].weight", field, k),
+                            e.to_string(),
+                        )),
+                    }
+                    node.push(DBNodeConfig {
+                        id: n.name.clone(),
+                        listen_addr: format!("{}:{}", n.host, n.port),
+                        user: n.username.clone(),
+                        pwd: n.password.clone(),
+                        db: Some(n.database.clone()),
+                        max_conns_limit: Some(c.conn_props.max_capacity as u64),
+                        weight: Some(n.weight.clone()),
+                        min_conns_limit: Some(c.conn_props.capacity.min(u16::MAX as u32) as u16),
+                        idle_timeout: Some(c.conn_props.idle_timeout as u64),
+                        max_lifetime: None,
+                        wait_timeout: None,
+                        wait_queue_limit: None,
+                    });
+                }
+                match master {
+                    Some(master_node_id) => cluster.push(DBClusterConfig {
+                        id: g.name.clone(),
+                        master_node_id,
+                        slave_node_ids: Some(slaves),
+                        balance: None,
+                        sql_max_limit: Some(c.sql_max_limit.max(0) as u64).filter(|n| *n > 0),
+                    }),
+                    None => problems.push(Problem::new(
+                        format!("{}.nodes", field),
+                        "no node takes writes",
+                    )),
+                }
+            }
+        }
+
+        let mut dbs: Vec<DBSectionConfig> = data
+            .clusters
+            .iter()
+            .map(|c| DBSectionConfig {
+                db: c.name.clone(),
+                cluster_ids: c.groups.iter().map(|g| g.name.clone()).collect(),
+                table: Vec::new(),
+            })
+            .collect();
+        for (t, table) in data.sharding_rule.tables.iter().enumerate() {
+            let field = format!("data.sharding_rule.tables[{}]", t);
+            let db = table
+                .name
+                .split_once('.')
+                .and_then(|(schema, _)| dbs.iter_mut().find(|db| db.db == schema));
+            let db = match db {
+                Some(db) => db,
+                None => {
+                    problems.push(Problem::new(
+                        format!("{}.name", field),
+                        format!("{:?} is not `cluster.table` of a cluster", table.name),
+                    ));
+                    continue;
+                }
+            };
+            match table_section(table, &db.cluster_ids, &field) {
+                Ok(section) => db.table.push(section),
+                Err(p) => problems.push(p),
+            }
+        }
+        let schema = data
+            .tenants
+            .iter()
+            .flat_map(|t| {
+                let db: Vec<DBSectionConfig> = data
+                    .clusters
+                    .iter()
+                    .zip(dbs.iter())
+                    .filter(|(c, db)| c.tenant == t.name && !db.table.is_empty())
+                    .map(|(_, db)| db.clone())
+                    .collect();
+                t.users.iter().map(move |u| DBShardSchemaConfig {
+                    owner: qualify(&u.username, &t.name),
+                    db: db.clone(),
+                })
+            })
+            .filter(|s| !s.db.is_empty())
+            .collect();
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+        Ok(Config {
+            global: None,
+            proxy: ProxyConfig {
+                listen_addr,
+                charset: None,
+                users,
+                time_to_no_alive: None,
+                distributed_txn: None,
+                node_pool: None,
+                tenant: None,
+            },
+            web: None,
+            node,
+            cluster,
+            schema,
+            filter: data.filters.clone(),
+        })
+    }
+}
+
+//the table section of a sharded table, whose db_pattern names the groups of its cluster in order
+//and whose tables spread over them evenly. the table rule numbers the table across the groups,
+//the db rule, if any, numbers the group.
+fn table_section(
+    table: &Table,
+    group_names: &[String],
+    field: &str,
+) -> Result<TableSectionConfig, Problem> {
+    let vt = VTable::new(table).map_err(|e| Problem::new(field, e.to_string()))?;
+    let tbl_rule = match vt.tbl_rules() {
+        [rule] => rule,
+        _ => {
+            return Err(Problem::new(
+                format!("{}.tbl_rules", field),
+                "one rule is needed",
+            ))
+        }
+    };
+    let db_rule = match vt.db_rules() {
+        [] => None,
+        [rule] if rule.column.eq_ignore_ascii_case(&tbl_rule.column) => Some(rule),
+        _ => {
+            return Err(Problem::new(
+                format!("{}.db_rules", field),
+                "none, or one on the column of the table rule",
+            ))
+        }
+    };
+    let topology = vt.topology();
+    let dbs = topology.dbs().names();
+    if dbs != group_names {
+        return Err(Problem::new(
+            format!("{}.topology.db_pattern", field),
+            format!("names {:?}, the groups are {:?}", dbs, group_names),
+        ));
+    }
+    let tables = topology.tables();
+    if tables.len() % dbs.len() != 0 {
+        return Err(Problem::new(
+            format!("{}.topology.tbl_pattern", field),
+            format!(
+                "{} tables do not spread evenly over {} dbs",
+                tables.len(),
+                dbs.len()
+            ),
+        ));
+    }
+    let per_db = tables.len() / dbs.len();
+    let offset = |expr: &str, start: i128| match start {
+        0 => format!("({})", expr),
+        _ => format!("(({}) - {})", expr, start),
+    };
+    let table_number = offset(tbl_rule.expr.source(), tables.number_at(0).unwrap_or(0));
+    let cluster_expr = match db_rule {
+        Some(rule) => offset(rule.expr.source(), topology.dbs().number_at(0).unwrap_or(0)),
+        None => format!("{} / {}", table_number, per_db),
+    };
+    Ok(TableSectionConfig {
+        table: vt.name().to_string(),
+        shard_key: tbl_rule.column.clone(),
+        shard_type: None,
+        cluster_expr: Some(cluster_expr),
+        table_expr: Some(format!("{} % $tables", table_number)),
+        hash: vt.attributes().get("hash").cloned(),
+        vnodes: None,
+        each_cluster_table_split_count: vec![per_db.min(u16::MAX as usize) as u16; dbs.len()],
+        integer_range: None,
+        table_pattern: Some(table.topology.tbl_pattern.clone()),
+    })
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct SocketAddress {
     pub address: String,
@@ -35,19 +258,22 @@ pub struct Data {
     pub sharding_rule: ShardingRule,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Filter {
-    pub name: String,
-    pub config: String,
+    pub name: String,   //the filter of the registry, see crate::filter.
+    pub config: String, //given to its factory as it is.
 }
 
 impl Filter {
-    pub fn factory(&self, name: String) -> Option<Box<dyn FilterFactory>> {
-        todo!()
+    pub fn factory(&self) -> Option<Arc<dyn FilterFactory>> {
+        crate::filter::factory(&self.name)
     }
 
-    pub fn register(&self, name: String) {
-        todo!()
+    pub fn build(&self) -> Result<Box<dyn QueryFilter>, Box<dyn Error>> {
+        let factory = self
+            .factory()
+            .ok_or_else(|| format!("no filter {:?}", self.name))?;
+        factory.new_filter(self.config.clone())
     }
 }
 
@@ -60,8 +286,6 @@ pub struct Tenant {
 #[derive(Debug, Deserialize)]
 pub struct DataSourceCluster {
     pub name: String,
-    #[serde(rename(serialize = "data_source_type", deserialize = "type"))]
-    pub data_source_type: String,
     pub sql_max_limit: i32,
     pub tenant: String,
     pub conn_props: ConnProp,
@@ -89,26 +313,12 @@ pub struct Node {
     pub username: String,
     pub password: String,
     pub database: String,
-    pub conn_props: HashMap<String, String>,
     pub weight: String,
-    pub labels: HashMap<String, String>,
 }
 
 impl Node {
     pub fn read_write_weight(&self) -> Result<ReadWriteWeight, Box<dyn Error>> {
-        let weight_regex = Regex::new(r"^[rR]([0-9]+)[wW]([0-9]+)$").unwrap();
-        let result: Vec<Captures> = weight_regex.captures_iter(&self.weight).collect();
-        if result.is_empty() {
-            // TODO error
-        }
-        let captures = &result[0];
-        if captures.len() != 3 {
-            // TODO err
-        }
-        let read = captures[1].to_string().parse::<u32>().unwrap();
-        let write = captures[2].to_string().parse::<u32>().unwrap();
-        let result = ReadWriteWeight { read, write };
-        Ok(result)
+        ReadWriteWeight::parse(&self.weight)
     }
 }
 
@@ -122,6 +332,7 @@ pub struct Listener {
     pub protocol_type: String,
     pub socket_address: SocketAddress,
     pub server_version: String,
+    pub executor: Option<String>, //"sharding", the default, or "redirect".
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -153,31 +364,34 @@ pub struct Topology {
     pub tbl_pattern: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ReadWriteWeight {
     pub read: u32,
     pub write: u32,
 }
 
+impl ReadWriteWeight {
+    //`r10w10`: the share of reads and writes a node takes in its group.
+    pub fn parse(weight: &str) -> Result<ReadWriteWeight, Box<dyn Error>> {
+        let weight_regex = Regex::new(r"^[rR]([0-9]+)[wW]([0-9]+)$").unwrap();
+        let captures = weight_regex
+            .captures(weight.trim())
+            .ok_or_else(|| format!("illegal read write weight: {:?}", weight))?;
+        let read = captures[1].parse::<u32>()?;
+        let write = captures[2].parse::<u32>()?;
+        Ok(ReadWriteWeight { read, write })
+    }
+}
+
+//the ConfigMap of a file, the proxy reads it through its config center.
+#[cfg(test)]
 pub fn load(config_path: String) -> Result<Configuration, Box<dyn Error>> {
-    let file = match File::open(config_path.clone()) {
-        Ok(file) => file,
-        Err(err) => {
-            panic!(
-                "Open config file is error: {:?}, config path: {:?}",
-                err, config_path
-            )
-        }
-    };
-    let content = serde_yaml::from_reader(file);
-    let config: Configuration = match content {
-        Ok(content) => content,
-        Err(err) => {
-            panic!(
-                "Read config content is error: {:?}, config path: {:?}",
-                err, config_path
-            )
-        }
-    };
+    use super::error::ConfigError;
+    let content = std::fs::read_to_string(&config_path)
+        .map_err(|e| ConfigError::IO(config_path.clone(), e))?;
+    let config: Configuration = serde_yaml::from_str(&content)
+        .map_err(|e| ConfigError::Parse(config_path.clone(), e.to_string()))?;
+    config.check_rules()?;
     Ok(config)
 }
 
@@ -185,16 +399,14 @@ pub fn load(config_path: String) -> Result<Configuration, Box<dyn Error>> {
 
 #[cfg(test)]
 mod tests {
-    use log::error;
     use regex::{Captures, Regex};
 
-    use crate::config::config_model::load;
+    use crate::config::config_model::{load, ReadWriteWeight};
+    use crate::router::build_router_with;
 
     #[test]
     fn load_config() {
-        let config = load(String::from(
-            "src/config/config.yaml",
-        ));
+        let config = load(String::from("src/config/config.yaml"));
         match config {
             Ok(content) => {
                 println!("Load config content is: {:?}", content);
@@ -203,6 +415,23 @@ mod tests {
                 println!("Load config is err: {:?}", e);
             }
         }
+        //the same routes through the canonical config.
+        let cfg = load(String::from("src/conf/config.yaml"))
+            .unwrap()
+            .to_config()
+            .unwrap();
+        assert!(cfg.validate().is_empty());
+        let router = build_router_with(&cfg).unwrap();
+        let table = router
+            .lookup_db("admin@arana", "employees")
+            .unwrap()
+            .lookup_table("student")
+            .unwrap();
+        assert_eq!(
+            table.lookup_one_path("77").unwrap(),
+            ("employees_0000", "student_0013".to_string())
+        );
+        assert_eq!(table.load_all_path().unwrap().len(), 32);
     }
 
     #[test]
@@ -214,5 +443,8 @@ mod tests {
             println!("Month: {} Day: {}", &cap[2], &cap[1]);
         }
         println!("Load config is err: {:?}", result);
+        let weight = ReadWriteWeight::parse("R3w5").unwrap();
+        assert_eq!((weight.read, weight.write), (3, 5));
+        assert!(ReadWriteWeight::parse("w5").is_err());
     }
 }
@@ -1,10 +1,14 @@
 #![allow(dead_code)]
+//the canonical config the proxy runs with, read from TOML, or from a YAML ConfigMap through
+//config_model, and checked as a whole before it is used.
+use super::config_model::{Configuration, Filter, ReadWriteWeight, User};
+use super::error::{ConfigError, ConfigResult, Problem};
 use super::schema::DBShardSchemaConfig;
-use serde::Deserialize;
-use std::collections::HashMap;
+use crate::backend::pool::balancer::Balance;
+use crate::security::tenant::{qualify, DEFAULT_TENANT};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::fs::File;
-use std::io::prelude::*;
 
 pub mod constants {
     //min unit : second
@@ -12,7 +16,7 @@ pub mod constants {
 }
 /// This is what we're going to decode into. Each field is optional, meaning
 /// that it doesn't have to be present in TOML.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     pub global: Option<GlobalConfig>,
     pub proxy: ProxyConfig,
@@ -20,70 +24,366 @@ pub struct Config {
     pub node: Vec<DBNodeConfig>,
     pub cluster: Vec<DBClusterConfig>,
     pub schema: Vec<DBShardSchemaConfig>,
+    pub filter: Option<Vec<Filter>>, //run around each query in their order, see crate::filter.
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GlobalConfig {
-    log_path: Option<String>,
-    log_level: Option<String>,
-    log_slow_query_time: Option<u64>,
+    pub(super) log_path: Option<String>,
+    pub(super) log_level: Option<String>,
+    pub(super) log_slow_query_time: Option<u64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ProxyConfig {
-    listen_addr: String,
-    charset: Option<String>,
-    users: Vec<ProxyUser>,
-    time_to_no_alive: Option<u64>, //none or zero value is for unlimited.
+    pub(super) listen_addr: String,
+    pub(super) charset: Option<String>,
+    pub(super) users: Vec<ProxyUser>,
+    pub(super) time_to_no_alive: Option<u64>, //none or zero value is for unlimited.
+    pub(super) distributed_txn: Option<String>, //"reject" or "xa", none is for "reject".
+    pub(super) node_pool: Option<String>,     //"mutex" or "channel", none is for "mutex".
+    pub(super) tenant: Option<String>, //the tenant of a login without `@tenant`, the listener is bound to it.
+}
+
+//what a transaction does when it touches a second cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistributedTxn {
+    Reject, //the statement fails, the transaction stays on its first cluster.
+    Xa,     //every transaction runs as a MySQL XA transaction, committed in two phases.
 }
 
-#[derive(Debug, Deserialize)]
+//how the conns of a node are pooled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodePool {
+    Mutex,   //the cache is shared under an async mutex.
+    Channel, //one task owns the cache, lends and recycles are messages to it.
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ProxyUser {
-    user: String,
-    pwd: String,
+    pub(super) user: String,
+    pub(super) pwd: String,
+    pub(super) tenant: Option<String>, //the user logs in as `user@tenant`, owns schemas as it too.
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct WebConfig {
     listen_addr: String,
     web_user: String,
     web_pwd: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct DBNodeConfig {
-    id: String,
-    listen_addr: String,
-    user: String,
-    pwd: String,
-    max_conns_limit: Option<u64>, //none or zero value is for unlimited.
+    pub id: String,
+    pub listen_addr: String,
+    pub user: String,
+    pub pwd: String,
+    pub db: Option<String>,           //default db selected once connected.
+    pub max_conns_limit: Option<u64>, //none or zero value is for unlimited.
+    pub weight: Option<String>,       //`r10w10`, none is for r1w1.
+    pub min_conns_limit: Option<u16>,
+    pub idle_timeout: Option<u64>, //time unit: second, idle conns beyond min are closed after it.
+    pub max_lifetime: Option<u64>, //time unit: second, zero value is for unlimited.
+    pub wait_timeout: Option<u64>, //time unit: millisecond, to wait for a conn of a full pool.
+    pub wait_queue_limit: Option<usize>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct DBClusterConfig {
-    id: String,
-    master_node_id: String,
-    slave_node_ids: Option<Vec<String>>,
+    pub id: String,
+    pub master_node_id: String,
+    pub slave_node_ids: Option<Vec<String>>,
+    pub balance: Option<String>, //"random" or "round_robin" among the read nodes, none is for random.
+    pub sql_max_limit: Option<u64>, //rows a select reads of the cluster with the limit filter.
 }
 
 //fn definition start here.
 pub fn load_config() -> Result<Config, Box<dyn Error>> {
     let args: Vec<_> = std::env::args().collect();
     println!("The command argument is {:?}", args);
+    let path = config_path().ok_or("Please at least give me the config file path.")?;
+    load_config_from(&path)
+}
+
+//the config file: `--c <PATH>` of the sub command, or the first argument.
+pub fn config_path() -> Option<String> {
+    let args: Vec<_> = std::env::args().collect();
+    args.iter()
+        .position(|a| a == "--c")
+        .and_then(|pos| args.get(pos + 1))
+        .or_else(|| args.get(1))
+        .cloned()
+}
+
+pub fn load_config_from(path: &str) -> Result<Config, Box<dyn Error>> {
+    Ok(load_checked(path)?)
+}
+
+//the file formats the canonical config is read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Yaml, //a ConfigMap of config_model.
+}
+
+impl Format {
+    //`.yaml` or `.yml` is YAML, anything else TOML.
+    pub fn of(path: &str) -> Format {
+        match std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+        {
+            Some("yaml") | Some("yml") => Format::Yaml,
+            _ => Format::Toml,
+        }
+    }
+}
 
-    //1.find and read the config file.
-    let config_path = std::env::args()
-        .nth(1)
-        .expect("Please at least give me the config file path.");
-    let mut f = File::open(config_path).unwrap();
-    let mut contents = String::new();
-    f.read_to_string(&mut contents).unwrap();
-    //2. parse the toml
-    let cfg: Config = toml::de::from_str(&contents).unwrap();
+//a YAML file is a ConfigMap, or the canonical config which has a proxy, not the bootstrap
+//options of boot::discovery.
+pub fn is_config_map(path: &str) -> bool {
+    Format::of(path) == Format::Yaml
+        && std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_yaml::from_str::<serde_yaml::Value>(&s).ok())
+            .map(|v| v.get("kind").is_some() || v.get("proxy").is_some())
+            .unwrap_or(false)
+}
+
+//read the config at `path` in its format, with every problem of it as the error.
+pub fn load_checked(path: &str) -> ConfigResult<Config> {
+    let contents =
+        std::fs::read_to_string(path).map_err(|e| ConfigError::IO(path.to_string(), e))?;
+    parse_checked(&contents, Format::of(path), path)
+}
+
+//the config in `text` read from `origin`, in YAML either a ConfigMap or the canonical config.
+pub fn parse_checked(text: &str, format: Format, origin: &str) -> ConfigResult<Config> {
+    let parse_err = |why: String| ConfigError::Parse(origin.to_string(), why);
+    let cfg: Config = match format {
+        Format::Toml => toml::de::from_str(text).map_err(|e| parse_err(e.to_string()))?,
+        Format::Yaml => {
+            let value: serde_yaml::Value =
+                serde_yaml::from_str(text).map_err(|e| parse_err(e.to_string()))?;
+            if value.get("kind").is_some() {
+                //from the text, a plain scalar such as `true` reads as a string field there.
+                let model: Configuration =
+                    serde_yaml::from_str(text).map_err(|e| parse_err(e.to_string()))?;
+                model
+                    .to_config()
+                    .map_err(|problems| ConfigError::Invalid(origin.to_string(), problems))?
+            } else {
+                serde_yaml::from_value(value).map_err(|e| parse_err(e.to_string()))?
+            }
+        }
+    };
+    let problems = cfg.validate();
+    if !problems.is_empty() {
+        return Err(ConfigError::Invalid(origin.to_string(), problems));
+    }
     Ok(cfg)
 }
 
+//the config written in `format`, the fields without a value left out.
+pub fn export(cfg: &Config, format: Format) -> ConfigResult<String> {
+    let ser_err = ConfigError::Export;
+    match format {
+        //a toml table is written with its plain values first, as a toml value does.
+        Format::Toml => toml::Value::try_from(cfg)
+            .and_then(|v| toml::to_string_pretty(&v))
+            .map_err(|e| ser_err(e.to_string())),
+        Format::Yaml => {
+            let mut value = serde_yaml::to_value(cfg).map_err(|e| ser_err(e.to_string()))?;
+            strip_nulls(&mut value);
+            serde_yaml::to_string(&value).map_err(|e| ser_err(e.to_string()))
+        }
+    }
+}
+
+fn strip_nulls(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(m) => {
+            m.retain(|_, v| !v.is_null());
+            m.iter_mut().for_each(|(_, v)| strip_nulls(v));
+        }
+        serde_yaml::Value::Sequence(s) => s.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+//`host:port`
+fn check_addr(addr: &str) -> Result<(), String> {
+    match addr.trim().rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        _ => Err(format!("{:?} is not host:port", addr)),
+    }
+}
+
 impl Config {
+    //every problem of the config, at the fields of the canonical model.
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let log_level = self.global.as_ref().and_then(|g| g.log_level.as_deref());
+        if let Some(level) = log_level {
+            if !["error", "warn", "info", "debug", "trace"].contains(&level.trim()) {
+                problems.push(Problem::new(
+                    "global.log_level",
+                    format!("unknown {:?}", level),
+                ));
+            }
+        }
+        let proxy = &self.proxy;
+        if let Err(why) = check_addr(&proxy.listen_addr) {
+            problems.push(Problem::new("proxy.listen_addr", why));
+        }
+        if proxy.users.is_empty() {
+            problems.push(Problem::new("proxy.users", "no user"));
+        }
+        let mut users = HashSet::new();
+        for (i, u) in proxy.users.iter().enumerate() {
+            if u.user.trim().is_empty() || u.user.contains('@') {
+                problems.push(Problem::new(
+                    format!("proxy.users[{}].user", i),
+                    format!(
+                        "{:?} is empty or has `@`, the tenant goes in tenant",
+                        u.user
+                    ),
+                ));
+            } else if !users.insert((u.user.trim(), u.tenant.as_deref())) {
+                problems.push(Problem::new(
+                    format!("proxy.users[{}].user", i),
+                    format!("{:?} again", u.user),
+                ));
+            }
+        }
+        for (i, u) in proxy.users.iter().enumerate() {
+            if let Some(t) = u
+                .tenant
+                .as_deref()
+                .filter(|t| t.trim().is_empty() || t.contains('@'))
+            {
+                problems.push(Problem::new(
+                    format!("proxy.users[{}].tenant", i),
+                    format!("{:?} is empty or has `@`", t),
+                ));
+            }
+        }
+        if let Some(t) = proxy.tenant.as_deref() {
+            if !users.iter().any(|(_, ut)| *ut == Some(t)) {
+                problems.push(Problem::new("proxy.tenant", format!("no user of {:?}", t)));
+            }
+        }
+        if let Some(txn) = proxy.distributed_txn.as_deref() {
+            if !["reject", "xa"].contains(&txn.trim()) {
+                problems.push(Problem::new(
+                    "proxy.distributed_txn",
+                    format!("unknown {:?}", txn),
+                ));
+            }
+        }
+        if let Some(pool) = proxy.node_pool.as_deref() {
+            if !["mutex", "channel"].contains(&pool.trim()) {
+                problems.push(Problem::new(
+                    "proxy.node_pool",
+                    format!("unknown {:?}", pool),
+                ));
+            }
+        }
+
+        let mut nodes = HashSet::new();
+        for (i, n) in self.node.iter().enumerate() {
+            if n.id.trim().is_empty() || !nodes.insert(n.id.as_str()) {
+                problems.push(Problem::new(
+                    format!("node[{}].id", i),
+                    format!("{:?} is empty or again", n.id),
+                ));
+            }
+            if let Err(why) = check_addr(&n.listen_addr) {
+                problems.push(Problem::new(format!("node[{}].listen_addr", i), why));
+            }
+            if let Some(Err(e)) = n.weight.as_deref().map(ReadWriteWeight::parse) {
+                problems.push(Problem::new(format!("node[{}].weight", i), e.to_string()));
+            }
+        }
+        let mut clusters = HashSet::new();
+        for (i, c) in self.cluster.iter().enumerate() {
+            if c.id.trim().is_empty() || !clusters.insert(c.id.as_str()) {
+                problems.push(Problem::new(
+                    format!("cluster[{}].id", i),
+                    format!("{:?} is empty or again", c.id),
+                ));
+            }
+            if !nodes.contains(c.master_node_id.as_str()) {
+                problems.push(Problem::new(
+                    format!("cluster[{}].master_node_id", i),
+                    format!("no node {:?}", c.master_node_id),
+                ));
+            }
+            for (j, s) in c.slave_node_ids.iter().flatten().enumerate() {
+                if !nodes.contains(s.as_str()) {
+                    problems.push(Problem::new(
+                        format!("cluster[{}].slave_node_ids[{}]", i, j),
+                        format!("no node {:?}", s),
+                    ));
+                }
+            }
+            if Balance::parse(c.balance.as_deref()).is_none() {
+                problems.push(Problem::new(
+                    format!("cluster[{}].balance", i),
+                    format!("unknown {:?}", c.balance),
+                ));
+            }
+        }
+
+        for (i, f) in self.filter.iter().flatten().enumerate() {
+            if let Err(e) = f.build() {
+                problems.push(Problem::new(format!("filter[{}]", i), e.to_string()));
+            }
+        }
+
+        for (i, schema) in self.schema.iter().enumerate() {
+            //a user of a tenant owns schemas as `user@tenant`.
+            if !users
+                .iter()
+                .any(|(u, t)| qualify(u, t.unwrap_or(DEFAULT_TENANT)) == schema.owner.trim())
+            {
+                problems.push(Problem::new(
+                    format!("schema[{}].owner", i),
+                    format!("no proxy user {:?}", schema.owner),
+                ));
+            }
+            for (j, db) in schema.db.iter().enumerate() {
+                let field = format!("schema[{}].db[{}]", i, j);
+                if db.db.trim().is_empty() {
+                    problems.push(Problem::new(format!("{}.db", field), "empty"));
+                }
+                if db.cluster_ids.is_empty() {
+                    problems.push(Problem::new(format!("{}.cluster_ids", field), "empty"));
+                }
+                for (k, c_id) in db.cluster_ids.iter().enumerate() {
+                    if !clusters.contains(c_id.as_str()) {
+                        problems.push(Problem::new(
+                            format!("{}.cluster_ids[{}]", field, k),
+                            format!("no cluster {:?}", c_id),
+                        ));
+                    }
+                }
+                if db.table.is_empty() {
+                    problems.push(Problem::new(format!("{}.table", field), "no table"));
+                }
+                for (k, table) in db.table.iter().enumerate() {
+                    if let Err(e) = crate::router::build_table(table, &db.cluster_ids) {
+                        problems.push(Problem::new(
+                            format!("{}.table[{}]", field, k),
+                            e.to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+        problems
+    }
     #[inline]
     pub fn query_log_path(&self) -> Option<&str> {
         self.global.as_ref()?.log_path.as_deref()
@@ -104,22 +404,48 @@ impl Config {
             })
     }
     #[inline]
+    pub fn query_distributed_txn(&self) -> DistributedTxn {
+        match self.proxy.distributed_txn.as_deref().map(str::trim) {
+            Some("xa") => DistributedTxn::Xa,
+            _ => DistributedTxn::Reject,
+        }
+    }
+    #[inline]
+    pub fn query_node_pool(&self) -> NodePool {
+        match self.proxy.node_pool.as_deref().map(str::trim) {
+            Some("channel") => NodePool::Channel,
+            _ => NodePool::Mutex,
+        }
+    }
+    #[inline]
     pub fn query_proxy_listen_addr(&self) -> &str {
         &self.proxy.listen_addr
     }
     #[inline]
-    pub fn load_proxy_user_list(&self) -> HashMap<String, String> {
-        let user_map: HashMap<String, String> = self
-            .proxy
+    pub fn query_proxy_tenant(&self) -> Option<&str> {
+        self.proxy.tenant.as_deref().map(str::trim)
+    }
+    //cluster_id -> sql_max_limit, of the clusters which have one.
+    pub fn load_sql_max_limits(&self) -> HashMap<String, u64> {
+        self.cluster
+            .iter()
+            .filter_map(|c| Some((c.id.clone(), c.sql_max_limit.filter(|n| *n > 0)?)))
+            .collect()
+    }
+    //each proxy user with its tenant, the default one for a user of none.
+    pub fn load_tenant_users(&self) -> Vec<(String, User)> {
+        self.proxy
             .users
             .iter()
             .map(|pu| {
-                let user = pu.user.trim().to_string();
-                let pwd = pu.pwd.trim().to_string();
-                (user, pwd)
+                let tenant = pu.tenant.as_deref().unwrap_or(DEFAULT_TENANT);
+                let user = User {
+                    username: pu.user.trim().to_string(),
+                    password: pu.pwd.trim().to_string(),
+                };
+                (tenant.trim().to_string(), user)
             })
-            .collect();
-        user_map
+            .collect()
     }
 
     #[inline]
@@ -141,3 +467,75 @@ impl Config {
         node_map
     }
 } //end of impl Config
+
+#[cfg(test)]
+mod tests {
+    use super::{load_checked, Config};
+
+    #[test]
+    fn validate_configs() {
+        assert!(load_checked("src/etc/config.toml").is_ok());
+        assert!(load_checked("src/conf/config.yaml").is_ok());
+
+        let cfg: Config = toml::from_str(
+            r#"
+[proxy]
+listen_addr = "127.0.0.1"
+users = [{ user = "root", pwd = "root" }]
+
+[[node]]
+id = "mysql_1"
+listen_addr = "127.0.0.1:3306"
+user = "root"
+pwd = "root"
+weight = "w1"
+
+[[cluster]]
+id = "cluster_1"
+master_node_id = "mysql_2"
+
+[[schema]]
+owner = "bob"
+[[schema.db]]
+db = "db1"
+cluster_ids = ["cluster_1"]
+[[schema.db.table]]
+table = "t"
+shard_key = "id"
+shard_type = "integer"
+each_cluster_table_split_count = [2, 2]
+[[schema.db.table]]
+table = "hashed"
+shard_key = "id"
+shard_type = "hash"
+each_cluster_table_split_count = [2]
+[[schema.db.table]]
+table = "std_hashed"
+shard_key = "id"
+shard_type = "consistent_hash"
+hash = "std"
+each_cluster_table_split_count = [2]
+"#,
+        )
+        .unwrap();
+        let problems = cfg.validate();
+        let fields: Vec<&str> = problems.iter().map(|p| p.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "proxy.listen_addr",
+                "node[0].weight",
+                "cluster[0].master_node_id",
+                "schema[0].owner",
+                "schema[0].db[0].table[0]",
+                "schema[0].db[0].table[1]",
+            ]
+        );
+        //a table hashing names its hash, a default one could move its rows.
+        assert!(
+            problems[5].why.contains("HashNotNamed"),
+            "{}",
+            problems[5].why
+        );
+    }
+}
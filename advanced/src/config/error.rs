@@ -0,0 +1,81 @@
+#![allow(dead_code)]
+pub type ConfigResult<T> = std::result::Result<T, ConfigError>;
+
+//one problem of a config file: the field it is at, such as `schema[0].db[1].table[2]`, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub field: String,
+    pub why: String,
+}
+
+impl Problem {
+    pub fn new(field: impl Into<String>, why: impl Into<String>) -> Problem {
+        Problem {
+            field: field.into(),
+            why: why.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.why)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    IO(String, std::io::Error),    //(file, error)
+    Parse(String, String),         //(file, why)
+    Invalid(String, Vec<Problem>), //(file, every problem)
+    Center(String, String),        //(center, why)
+    Export(String),
+}
+
+impl ConfigError {
+    //a line for each problem, `file: field: why`.
+    pub fn lines(&self) -> Vec<String> {
+        match self {
+            ConfigError::IO(file, e) => vec![format!("{}: {}", file, e)],
+            ConfigError::Parse(file, why) => vec![format!("{}: {}", file, why)],
+            ConfigError::Invalid(file, problems) => problems
+                .iter()
+                .map(|p| format!("{}: {}", file, p))
+                .collect(),
+            ConfigError::Center(center, why) => vec![format!("center {}: {}", center, why)],
+            ConfigError::Export(why) => vec![format!("export: {}", why)],
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::IO(file, e) => write!(f, "ConfigError::IO: {}: {}", file, e),
+            ConfigError::Parse(file, why) => write!(f, "ConfigError::Parse: {}: {}", file, why),
+            ConfigError::Invalid(file, problems) => {
+                write!(f, "ConfigError::Invalid: {}", file)?;
+                for p in problems.iter() {
+                    write!(f, "; {}", p)?;
+                }
+                Ok(())
+            }
+            ConfigError::Center(center, why) => {
+                write!(f, "ConfigError::Center: {}: {}", center, why)
+            }
+            ConfigError::Export(why) => write!(f, "ConfigError::Export: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::IO(_, e) => Some(e),
+            ConfigError::Parse(..) => None,
+            ConfigError::Invalid(..) => None,
+            ConfigError::Center(..) => None,
+            ConfigError::Export(..) => None,
+        }
+    }
+}
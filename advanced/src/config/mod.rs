@@ -1,14 +1,26 @@
+pub mod center;
+#[allow(clippy::module_inception)]
 pub mod config;
 pub mod config_model;
 mod configer;
+pub mod error;
 mod schema;
 pub mod shortcut;
 
 //pub mod router;
+pub use center::ConfigCenter;
+pub use configer::export;
+pub use configer::is_config_map;
+pub use configer::load_checked;
 pub use configer::load_config;
+pub use configer::load_config_from;
 pub use configer::Config;
 pub use configer::DBNodeConfig;
-pub use shortcut::build_config_shortcut;
+pub use configer::DistributedTxn;
+pub use configer::Format;
+pub use configer::NodePool;
+pub use schema::TableSectionConfig;
+pub use shortcut::build_config_shortcut_with;
 pub use shortcut::ConfigShortcut;
 
 pub use config::Center;
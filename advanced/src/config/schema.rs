@@ -1,23 +1,31 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DBShardSchemaConfig {
     pub owner: String,
     pub db: Vec<DBSectionConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct DBSectionConfig {
     pub db: String,
     pub cluster_ids: Vec<String>,
     pub table: Vec<TableSectionConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct TableSectionConfig {
     pub table: String,
     pub shard_key: String,
-    pub shard_type: String,
+    pub shard_type: Option<String>, //"hash", "consistent_hash", "integer" or "integer_range".
+    //rule expressions, such as `crc32($value) % $clusters`, which override the shard type.
+    pub cluster_expr: Option<String>, //shard value -> cluster position, with $clusters.
+    pub table_expr: Option<String>,   //shard value -> table suffix, with $clusters and $tables.
+    pub hash: Option<String>, //what hash() is: "crc32", "murmur3", "xxhash" or "std", no default.
+    pub vnodes: Option<u32>,  //points of each cluster on the ring of consistent_hash.
     pub each_cluster_table_split_count: Vec<u16>,
+    //the physical tables, such as `student_${0000...0031}`, numbered across the clusters in order,
+    //none is for `{table}_{suffix}` in each cluster.
+    pub table_pattern: Option<String>,
     pub integer_range: Option<Vec<String>>,
 }
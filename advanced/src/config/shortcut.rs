@@ -1,28 +1,26 @@
 #![allow(dead_code)]
-use super::configer::{DBClusterConfig, DBNodeConfig};
+use super::configer::{Config, DBClusterConfig, DBNodeConfig};
 use std::collections::HashMap;
 use std::error::Error;
 
 #[derive(Debug, Clone)]
 pub struct ConfigShortcut {
-    //should use hashmap to replace vec for efficiency!
-    proxy_user_list: HashMap<String, String>,
     node_list: HashMap<String, DBNodeConfig>,
     cluster_list: HashMap<String, DBClusterConfig>,
 }
 
-pub fn build_config_shortcut() -> Result<ConfigShortcut, Box<dyn Error>> {
+pub fn build_config_shortcut_with(cfg: &Config) -> Result<ConfigShortcut, Box<dyn Error>> {
     let csc = ConfigShortcut {
-        proxy_user_list: crate::GLOBAL_CONFIG.load_proxy_user_list(),
-        node_list: crate::GLOBAL_CONFIG.load_db_node_config(),
-        cluster_list: crate::GLOBAL_CONFIG.load_db_cluster_config(),
+        node_list: cfg.load_db_node_config(),
+        cluster_list: cfg.load_db_cluster_config(),
     };
     Ok(csc)
 }
 impl ConfigShortcut {
+    //the conn pool made of these nodes and clusters can serve the other one too.
     #[inline]
-    pub fn check_proxy_user_exists(&self, user: &str) -> Option<(&String, &String)> {
-        self.proxy_user_list.get_key_value(user)
+    pub fn same_backends(&self, other: &ConfigShortcut) -> bool {
+        self.node_list == other.node_list && self.cluster_list == other.cluster_list
     }
 
     #[inline]
@@ -1,11 +1,354 @@
+#![allow(dead_code)]
+//the redirect executor: each session is pinned to one conn of the cluster of its tenant and the
+//packets are relayed both ways as they are, no sql is parsed. only the commands which change the
+//session, COM_INIT_DB, COM_CHANGE_USER and COM_RESET_CONNECTION, are looked into. it suits the
+//databases which are not sharded, and measures what the sharding executor costs.
+use crate::backend::conn::P2MConn;
+use crate::backend::error::{BackendError, BackendResult};
+use crate::backend::executor;
+use crate::frontend::conn::authenticate;
+use crate::frontend::dispatcher::backend_err_packet;
+use crate::frontend::errors::{FrontendError, FrontendResult};
+use crate::mysql::constants::{command, StatusFlags, OK_PACKET_HEADER_MARK};
+use crate::mysql::server::{Login, Session};
+use crate::mysql::{errcode, errors, packet};
 use crate::proto::interface::Executor;
+use crate::proxy::reload::{Reloader, Snapshot};
+use crate::security::tenant::TenantManager;
+use async_trait::async_trait;
+use std::sync::Arc;
 
-pub struct RedirectExecutor {}
+pub struct RedirectExecutor {
+    reloader: Arc<Reloader>,
+}
 
 impl RedirectExecutor {
-    pub fn new() -> Self {
-        RedirectExecutor {}
+    pub fn new(reloader: Arc<Reloader>) -> Self {
+        RedirectExecutor { reloader }
     }
 }
 
-impl Executor for RedirectExecutor {}
+#[derive(Debug)]
+pub struct RedirectConn {
+    session: Session,
+    snapshot: Arc<Snapshot>,
+    proxy_user: String,
+    cluster_id: String,
+    pinned: Option<P2MConn>, //taken on the first command after the login.
+}
+
+impl AsMut<Session> for RedirectConn {
+    fn as_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
+}
+
+impl RedirectConn {
+    //let the login in and choose the cluster of its tenant, the first of them by name when the
+    //tenant has more than one.
+    fn login(&mut self, login: &Login) -> FrontendResult<()> {
+        let (proxy_user, tenant) = authenticate(&self.snapshot, &self.session, login)?;
+        let cluster_id = self
+            .snapshot
+            .tenants
+            .get_clusters(tenant.clone())
+            .and_then(|clusters| clusters.into_iter().next())
+            .ok_or_else(|| {
+                log::info!("no cluster of tenant {:?} to redirect to", &tenant);
+                FrontendError::ProxyAuthDenied
+            })?;
+        self.proxy_user = proxy_user;
+        self.cluster_id = cluster_id;
+        self.session.db = login.db.clone();
+        Ok(())
+    }
+
+    //the pinned conn, one of the pool on the db of the session the first time.
+    async fn take_backend(&mut self) -> BackendResult<P2MConn> {
+        if let Some(conn) = self.pinned.take() {
+            return Ok(conn);
+        }
+        let mut conn = self.snapshot.pool.get_conn(&self.cluster_id, true).await?;
+        if let Err(e) = conn.use_db(&self.session.db).await {
+            self.snapshot.pool.discard(conn).await;
+            return Err(e);
+        }
+        Ok(conn)
+    }
+
+    //pin the conn again, one which failed is closed and the next command takes another.
+    async fn give_backend(&mut self, conn: P2MConn, healthy: bool) {
+        if healthy {
+            self.pinned = Some(conn);
+        } else {
+            self.snapshot.pool.discard(conn).await;
+        }
+    }
+
+    //the conn goes with the session state the client left on it, so it is closed rather than
+    //handed to another session.
+    fn unpin(&mut self) {
+        if let Some(conn) = self.pinned.take() {
+            let pool = self.snapshot.pool.clone();
+            tokio::spawn(async move { pool.discard(conn).await });
+        }
+    }
+
+    //relay a command and its response, whether it was answered by an OK. an error packet answers
+    //it when there is no conn of the cluster to be had, but a command without a response is
+    //dropped then, as the client would read the error as the answer to its next command.
+    async fn relay(&mut self, relay: Relay<'_>) -> FrontendResult<bool> {
+        let silent = matches!(relay, Relay::Silent(..));
+        let mut conn = match self.take_backend().await {
+            Ok(conn) => conn,
+            Err(e) if silent => {
+                log::info!("command dropped, no conn of {}: {}", &self.cluster_id, e);
+                return Ok(false);
+            }
+            Err(e) => {
+                self.session.write_err(backend_err_packet(e)).await?;
+                return Ok(false);
+            }
+        };
+        let client = &mut self.session.pkg;
+        let rc = match relay {
+            Relay::Command(cmd, arg) => executor::relay_command(&mut conn, cmd, arg, client)
+                .await
+                .map(|_| true),
+            Relay::One(cmd, arg) => executor::relay_one(&mut conn, cmd, arg, client)
+                .await
+                .map(|header| header == OK_PACKET_HEADER_MARK),
+            Relay::Prepare(sql) => executor::relay_prepare(&mut conn, sql, client)
+                .await
+                .map(|_| true),
+            Relay::FieldList(arg) => executor::relay_field_list(&mut conn, arg, client)
+                .await
+                .map(|_| true),
+            Relay::Silent(cmd, arg) => conn.write_command(cmd, arg).await.map(|_| true),
+        };
+        let healthy = matches!(rc, Ok(_) | Err(BackendError::ServerErr(_)));
+        self.give_backend(conn, healthy).await;
+        match rc {
+            Err(e) if silent => {
+                log::info!("command dropped on {}: {}", &self.cluster_id, e);
+                Ok(false)
+            }
+            rc => rc.map_err(FrontendError::Backend),
+        }
+    }
+}
+
+//a command as the client sent it, by the kind of its response.
+enum Relay<'a> {
+    Command(u8, &'a [u8]), //an OK, an ERR or result sets.
+    One(u8, &'a [u8]),     //one packet.
+    Prepare(&'a str),
+    FieldList(&'a [u8]),
+    Silent(u8, &'a [u8]), //no response.
+}
+
+#[async_trait]
+impl Executor for RedirectExecutor {
+    type Conn = RedirectConn;
+
+    fn open(&self, session: Session) -> RedirectConn {
+        RedirectConn {
+            session,
+            snapshot: self.reloader.snapshot(),
+            proxy_user: String::new(),
+            cluster_id: String::new(),
+            pinned: None,
+        }
+    }
+
+    async fn login(&self, conn: &mut RedirectConn, login: &Login) -> FrontendResult<()> {
+        conn.login(login)
+    }
+
+    //a db of another tenant is refused, the session takes the db once the backend has it.
+    async fn init_db(&self, conn: &mut RedirectConn, db: &str) -> FrontendResult<()> {
+        if let Some(t) = conn.snapshot.foreign_tenant(&conn.proxy_user, db) {
+            let err = packet::ErrPacket::new(
+                errcode::ER_DBACCESS_DENIED_ERROR,
+                format!("{}", FrontendError::ProxyCrossTenant(t)),
+            );
+            return conn.session.write_err(err).await;
+        }
+        if conn
+            .relay(Relay::One(command::COM_INIT_DB, db.as_bytes()))
+            .await?
+        {
+            conn.session.db = db.to_string();
+        }
+        Ok(())
+    }
+
+    async fn query(&self, conn: &mut RedirectConn, sql: &str) -> FrontendResult<()> {
+        conn.relay(Relay::Command(command::COM_QUERY, sql.as_bytes()))
+            .await
+            .map(|_| ())
+    }
+
+    async fn field_list(
+        &self,
+        conn: &mut RedirectConn,
+        table: &str,
+        wildcard: &str,
+    ) -> FrontendResult<()> {
+        let mut arg = table.as_bytes().to_vec();
+        arg.push(0);
+        arg.extend_from_slice(wildcard.as_bytes());
+        conn.relay(Relay::FieldList(&arg)).await.map(|_| ())
+    }
+
+    async fn prepare(&self, conn: &mut RedirectConn, sql: &str) -> FrontendResult<()> {
+        conn.relay(Relay::Prepare(sql)).await.map(|_| ())
+    }
+
+    async fn execute(&self, conn: &mut RedirectConn, data: &[u8]) -> FrontendResult<()> {
+        conn.relay(Relay::Command(command::COM_STMT_EXECUTE, data))
+            .await
+            .map(|_| ())
+    }
+
+    async fn stmt(&self, conn: &mut RedirectConn, cmd: u8, data: &[u8]) -> FrontendResult<()> {
+        let relay = match cmd {
+            command::COM_STMT_RESET => Relay::One(cmd, data),
+            _ => Relay::Silent(cmd, data),
+        };
+        conn.relay(relay).await.map(|_| ())
+    }
+
+    async fn command(&self, conn: &mut RedirectConn, cmd: u8, data: &[u8]) -> FrontendResult<()> {
+        match cmd {
+            //a login of another tenant may be of another cluster, its conn starts clean.
+            command::COM_CHANGE_USER => {
+                let login = conn.session.parse_change_user(data);
+                if let Err(e) = conn.login(&login) {
+                    let err =
+                        packet::ErrPacket::new(errcode::ER_ACCESS_DENIED_ERROR, e.to_string());
+                    conn.session.write_err(err).await?;
+                    return conn.session.quit();
+                }
+                conn.unpin();
+                conn.session.status = StatusFlags::SERVER_STATUS_AUTOCOMMIT;
+                conn.session.write_ok(None).await
+            }
+            command::COM_RESET_CONNECTION => {
+                if conn.relay(Relay::One(cmd, data)).await? {
+                    conn.session.status = StatusFlags::SERVER_STATUS_AUTOCOMMIT;
+                }
+                Ok(())
+            }
+            command::COM_PING
+            | command::COM_STATISTICS
+            | command::COM_DEBUG
+            | command::COM_SET_OPTION => conn.relay(Relay::One(cmd, data)).await.map(|_| ()),
+            _ => Err(FrontendError::MySQLErr(errors::MySQLError::ErUnknownCmd)),
+        }
+    }
+
+    async fn close(&self, conn: &mut RedirectConn) {
+        conn.unpin();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RedirectExecutor;
+    use crate::config::center::FileCenter;
+    use crate::config::Config;
+    use crate::mysql::constants::command;
+    use crate::mysql::server::tests::{command as run, connect, serve, Echo};
+    use crate::proxy::reload::tests::config;
+    use crate::proxy::reload::Reloader;
+    use mysql_common::scramble;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn redirect_to_tenant_cluster() {
+        //the backend lets root in with `secret` and answers a query with its length.
+        let backend = serve(Echo).await;
+        let cfg: Config = toml::from_str(&format!(
+            r#"
+[proxy]
+listen_addr = "127.0.0.1:9696"
+users = [{{ user = "root", pwd = "root" }}]
+
+[[node]]
+id = "mysql_1"
+listen_addr = "{}"
+user = "root"
+pwd = "secret"
+
+[[cluster]]
+id = "cluster_1"
+master_node_id = "mysql_1"
+
+[[schema]]
+owner = "root"
+[[schema.db]]
+db = "db1"
+cluster_ids = ["cluster_1"]
+[[schema.db.table]]
+table = "integer_table"
+shard_key = "id"
+shard_type = "integer"
+each_cluster_table_split_count = [2]
+"#,
+            backend
+        ))
+        .unwrap();
+        let center = Arc::new(FileCenter::new("redirect.toml".to_string()));
+        let reloader = Reloader::new(center, &cfg).await.unwrap();
+        let proxy = serve(RedirectExecutor::new(reloader.clone())).await;
+
+        let (mut pkg, ok, salt) = connect(&proxy, "root", "root").await;
+        assert_eq!(ok[0], 0x00);
+        let mut query = vec![command::COM_QUERY];
+        query.extend_from_slice(b"select 1");
+        assert_eq!(run(&mut pkg, &query).await[..2], [0x00, 8]);
+        let mut init_db = vec![command::COM_INIT_DB];
+        init_db.extend_from_slice(b"db2");
+        assert_eq!(run(&mut pkg, &init_db).await[0], 0x00);
+        //the backend knows no COM_PING, its error is relayed.
+        assert_eq!(run(&mut pkg, &[command::COM_PING]).await[0], 0xff);
+
+        //the session starts again on a conn of its own.
+        let mut change = vec![command::COM_CHANGE_USER];
+        change.extend_from_slice(b"root\0");
+        let auth = scramble::scramble_native(&salt, b"root").unwrap();
+        change.push(auth.len() as u8);
+        change.extend_from_slice(&auth);
+        change.extend_from_slice(b"db1\0");
+        assert_eq!(run(&mut pkg, &change).await[0], 0x00);
+        assert_eq!(run(&mut pkg, &query).await[..2], [0x00, 8]);
+
+        let mut change = vec![command::COM_CHANGE_USER];
+        change.extend_from_slice(b"nobody\0\0");
+        let refused = run(&mut pkg, &change).await;
+        assert_eq!(u16::from_le_bytes([refused[1], refused[2]]), 1045);
+        reloader.snapshot().pool.quit().await;
+    }
+
+    #[tokio::test]
+    async fn drop_silent_command_without_backend() {
+        let cfg = config("127.0.0.1:1");
+        let center = Arc::new(FileCenter::new("redirect.toml".to_string()));
+        let reloader = Reloader::new(center, &cfg).await.unwrap();
+        let proxy = serve(RedirectExecutor::new(reloader.clone())).await;
+        let (mut pkg, ok, _) = connect(&proxy, "root", "root").await;
+        assert_eq!(ok[0], 0x00);
+
+        //COM_STMT_CLOSE has no response, the next one read is that of the next command.
+        let mut close = vec![command::COM_STMT_CLOSE];
+        close.extend_from_slice(&1u32.to_le_bytes());
+        pkg.reset_seq();
+        pkg.write_packet(&mut close).await.unwrap();
+        let mut change = vec![command::COM_CHANGE_USER];
+        change.extend_from_slice(b"nobody\0\0");
+        let refused = run(&mut pkg, &change).await;
+        assert_eq!(u16::from_le_bytes([refused[1], refused[2]]), 1045);
+        reloader.snapshot().pool.quit().await;
+    }
+}
@@ -0,0 +1,36 @@
+//blacklist: a query matching any of the regexes of the config, one a line, is rejected before
+//it is parsed.
+use super::{config_lines, QueryContext};
+use crate::proto::interface::{FilterFactory, QueryFilter};
+use regex::Regex;
+use std::error::Error;
+
+pub const NAME: &str = "blacklist";
+
+pub struct Factory;
+
+impl FilterFactory for Factory {
+    fn new_filter(&self, config: String) -> Result<Box<dyn QueryFilter>, Box<dyn Error>> {
+        let patterns = config_lines(&config)
+            .map(Regex::new)
+            .collect::<Result<Vec<Regex>, regex::Error>>()?;
+        Ok(Box::new(Blacklist { patterns }))
+    }
+}
+
+#[derive(Debug)]
+struct Blacklist {
+    patterns: Vec<Regex>,
+}
+
+impl QueryFilter for Blacklist {
+    fn name(&self) -> &str {
+        NAME
+    }
+    fn pre_parse(&self, _ctx: &QueryContext, sql: &mut String) -> Result<(), String> {
+        match self.patterns.iter().find(|p| p.is_match(sql)) {
+            Some(p) => Err(format!("the sql matches {}", p.as_str())),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//limit: a select reads at most sql_max_limit rows of each cluster, the limit of the sql on a
+//shard is lowered to it, or added. the config is the limit of the clusters without one, empty
+//for no limit on them.
+use super::QueryContext;
+use crate::analyzer::plan::Plan;
+use crate::proto::interface::{FilterFactory, QueryFilter};
+use sqlparser::ast::{Expr, Statement, Value};
+use sqlparser::dialect::MySqlDialect;
+use sqlparser::parser::Parser;
+use std::error::Error;
+
+pub const NAME: &str = "limit";
+
+pub struct Factory;
+
+impl FilterFactory for Factory {
+    fn new_filter(&self, config: String) -> Result<Box<dyn QueryFilter>, Box<dyn Error>> {
+        let default = match config.trim() {
+            "" => None,
+            n => Some(n.parse::<u64>()?).filter(|n| *n > 0),
+        };
+        Ok(Box::new(Limit { default }))
+    }
+}
+
+#[derive(Debug)]
+struct Limit {
+    default: Option<u64>,
+}
+
+impl QueryFilter for Limit {
+    fn name(&self) -> &str {
+        NAME
+    }
+    fn post_route(&self, ctx: &QueryContext, plan: &mut Plan) -> Result<(), String> {
+        for (cluster_id, sql) in plan.shards.iter_mut() {
+            let max = match ctx.sql_max_limit(cluster_id).or(self.default) {
+                Some(max) => max,
+                None => continue,
+            };
+            if let Some(limited) = limit_select(sql, max) {
+                *sql = limited;
+            }
+        }
+        Ok(())
+    }
+}
+
+//the select with a limit of at most `max`, none when it is not a select or is limited enough.
+fn limit_select(sql: &str, max: u64) -> Option<String> {
+    let mut stmts = Parser::parse_sql(&MySqlDialect {}, sql).ok()?;
+    if stmts.len() != 1 {
+        return None;
+    }
+    let mut query = match stmts.pop()? {
+        Statement::Query(q) => q,
+        _ => return None,
+    };
+    let limited = match &query.limit {
+        None => true,
+        Some(Expr::Value(Value::Number(n, _))) => n.parse::<u64>().is_ok_and(|n| n > max),
+        Some(_) => false,
+    };
+    if !limited {
+        return None;
+    }
+    query.limit = Some(Expr::Value(Value::Number(max.to_string(), false)));
+    Some(query.to_string())
+}
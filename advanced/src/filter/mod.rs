@@ -0,0 +1,216 @@
+#![allow(dead_code)]
+//the filters around a COM_QUERY: each `[[filter]]` of the config, or `data.filters` of a
+//ConfigMap, names a factory of the registry and gives it its `config` string. the filters of a
+//config make one chain which runs their hooks in the order they are listed.
+mod blacklist;
+mod limit;
+mod rewrite;
+mod slowlog;
+
+use crate::analyzer::plan::Plan;
+use crate::config::Filter;
+use crate::proto::interface::{FilterFactory, QueryFilter};
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: RwLock<HashMap<String, Arc<dyn FilterFactory>>> = {
+        let mut m: HashMap<String, Arc<dyn FilterFactory>> = HashMap::new();
+        m.insert(blacklist::NAME.to_string(), Arc::new(blacklist::Factory));
+        m.insert(slowlog::NAME.to_string(), Arc::new(slowlog::Factory));
+        m.insert(limit::NAME.to_string(), Arc::new(limit::Factory));
+        m.insert(rewrite::NAME.to_string(), Arc::new(rewrite::Factory));
+        RwLock::new(m)
+    };
+}
+
+//add or replace the factory of a filter name.
+pub fn register(name: &str, factory: Arc<dyn FilterFactory>) {
+    REGISTRY.write().unwrap().insert(name.to_string(), factory);
+}
+
+pub fn factory(name: &str) -> Option<Arc<dyn FilterFactory>> {
+    REGISTRY.read().unwrap().get(name.trim()).cloned()
+}
+
+//what a filter knows of the query it runs around.
+#[derive(Debug)]
+pub struct QueryContext<'a> {
+    pub user: &'a str,
+    pub db: &'a str,
+    pub sql: &'a str, //as the client sent it.
+    pub started: Instant,
+    limits: &'a HashMap<String, u64>,
+}
+
+impl QueryContext<'_> {
+    //the sql_max_limit of the cluster, rows a select may read from it.
+    #[inline]
+    pub fn sql_max_limit(&self, cluster_id: &str) -> Option<u64> {
+        self.limits.get(cluster_id).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryOutcome {
+    pub shards: usize, //zero when the query did not reach a backend.
+    pub error: Option<String>,
+    pub elapsed: Duration,
+}
+
+//a query a filter stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejected {
+    pub filter: String,
+    pub why: String,
+}
+
+impl std::fmt::Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rejected by filter {}: {}", self.filter, self.why)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FilterChain {
+    filters: Vec<Box<dyn QueryFilter>>,
+    limits: HashMap<String, u64>, //cluster_id -> sql_max_limit
+}
+
+impl FilterChain {
+    //a filter of no factory, or of a config its factory rejects, fails the whole chain.
+    pub fn build(
+        filters: &[Filter],
+        limits: HashMap<String, u64>,
+    ) -> Result<FilterChain, Box<dyn Error>> {
+        let mut chain = FilterChain {
+            filters: Vec::with_capacity(filters.len()),
+            limits,
+        };
+        for f in filters.iter() {
+            chain.filters.push(f.build()?);
+        }
+        Ok(chain)
+    }
+    pub fn names(&self) -> Vec<&str> {
+        self.filters.iter().map(|f| f.name()).collect()
+    }
+    pub fn context<'a>(&'a self, user: &'a str, db: &'a str, sql: &'a str) -> QueryContext<'a> {
+        QueryContext {
+            user,
+            db,
+            sql,
+            started: Instant::now(),
+            limits: &self.limits,
+        }
+    }
+    pub fn pre_parse(&self, ctx: &QueryContext, sql: &mut String) -> Result<(), Rejected> {
+        for f in self.filters.iter() {
+            f.pre_parse(ctx, sql)
+                .map_err(|why| rejected(f.as_ref(), why))?;
+        }
+        Ok(())
+    }
+    pub fn post_route(&self, ctx: &QueryContext, plan: &mut Plan) -> Result<(), Rejected> {
+        for f in self.filters.iter() {
+            f.post_route(ctx, plan)
+                .map_err(|why| rejected(f.as_ref(), why))?;
+        }
+        Ok(())
+    }
+    pub fn post_result(&self, ctx: &QueryContext, outcome: &QueryOutcome) {
+        for f in self.filters.iter() {
+            f.post_result(ctx, outcome);
+        }
+    }
+}
+
+fn rejected(f: &dyn QueryFilter, why: String) -> Rejected {
+    Rejected {
+        filter: f.name().to_string(),
+        why,
+    }
+}
+
+//the lines of a config string, without blank ones and `#` comments.
+fn config_lines(config: &str) -> impl Iterator<Item = &str> {
+    config
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FilterChain, QueryOutcome};
+    use crate::analyzer::plan::build_plan;
+    use crate::config::{Config, Filter};
+    use crate::router::build_router_with;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    fn filter(name: &str, config: &str) -> Filter {
+        Filter {
+            name: name.to_string(),
+            config: config.to_string(),
+        }
+    }
+
+    #[test]
+    fn filter_chain() {
+        let filters = vec![
+            filter("rewrite", r"(?i)\bpeople\b => integer_table"),
+            filter(
+                "blacklist",
+                "(?i)^\\s*delete\\b\n# no truncate either\n(?i)^\\s*truncate\\b",
+            ),
+            filter("limit", "100"),
+            filter("slowlog", "0"),
+        ];
+        let mut limits = HashMap::new();
+        limits.insert("cluster_1".to_string(), 10);
+        let chain = FilterChain::build(&filters, limits).unwrap();
+        assert_eq!(
+            chain.names(),
+            vec!["rewrite", "blacklist", "limit", "slowlog"]
+        );
+        assert!(FilterChain::build(&[filter("nope", "")], HashMap::new()).is_err());
+        assert!(FilterChain::build(&[filter("blacklist", "(")], HashMap::new()).is_err());
+
+        let ctx = chain.context("root", "db1", "");
+        let mut sql = "delete from people".to_string();
+        let rejected = chain.pre_parse(&ctx, &mut sql).unwrap_err();
+        assert_eq!(rejected.filter, "blacklist");
+
+        let mut sql = "select * from people where id = 1".to_string();
+        chain.pre_parse(&ctx, &mut sql).unwrap();
+        assert_eq!(sql, "select * from integer_table where id = 1");
+
+        let text = std::fs::read_to_string("src/etc/config.toml").unwrap();
+        let cfg: Config = toml::from_str(&text).unwrap();
+        let router = build_router_with(&cfg).unwrap();
+        let (user, db, table) = router.list_tables()[0];
+        let mut plan = build_plan(
+            &router,
+            user,
+            db,
+            &format!("SELECT * FROM {} LIMIT 500", table.get_table()),
+        )
+        .unwrap();
+        chain.post_route(&ctx, &mut plan).unwrap();
+        for (c_id, sql) in plan.shards.iter() {
+            let max = if c_id == "cluster_1" { 10 } else { 100 };
+            assert!(sql.ends_with(&format!("LIMIT {}", max)), "{}", sql);
+        }
+        chain.post_result(
+            &ctx,
+            &QueryOutcome {
+                shards: plan.shards.len(),
+                error: None,
+                elapsed: Duration::from_millis(1),
+            },
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//rewrite: each line of the config is `regex => replacement`, applied to the sql in their order
+//before it is parsed. the replacement may refer to the groups of the regex, as `$1`.
+use super::{config_lines, QueryContext};
+use crate::proto::interface::{FilterFactory, QueryFilter};
+use regex::Regex;
+use std::error::Error;
+
+pub const NAME: &str = "rewrite";
+
+pub struct Factory;
+
+impl FilterFactory for Factory {
+    fn new_filter(&self, config: String) -> Result<Box<dyn QueryFilter>, Box<dyn Error>> {
+        let mut rules = Vec::new();
+        for line in config_lines(&config) {
+            let (pattern, replacement) = line
+                .split_once("=>")
+                .ok_or_else(|| format!("{:?} is not `regex => replacement`", line))?;
+            rules.push((Regex::new(pattern.trim())?, replacement.trim().to_string()));
+        }
+        Ok(Box::new(Rewrite { rules }))
+    }
+}
+
+#[derive(Debug)]
+struct Rewrite {
+    rules: Vec<(Regex, String)>,
+}
+
+impl QueryFilter for Rewrite {
+    fn name(&self) -> &str {
+        NAME
+    }
+    fn pre_parse(&self, _ctx: &QueryContext, sql: &mut String) -> Result<(), String> {
+        for (pattern, replacement) in self.rules.iter() {
+            if let std::borrow::Cow::Owned(s) = pattern.replace_all(sql, replacement.as_str()) {
+                *sql = s;
+            }
+        }
+        Ok(())
+    }
+}
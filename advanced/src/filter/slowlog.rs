@@ -0,0 +1,47 @@
+//slowlog: a query which takes at least the milliseconds of the config, 1000 when it is empty,
+//is logged as a warning with its user, db, shards and time.
+use super::{QueryContext, QueryOutcome};
+use crate::proto::interface::{FilterFactory, QueryFilter};
+use std::error::Error;
+use std::time::Duration;
+
+pub const NAME: &str = "slowlog";
+const DEFAULT_THRESHOLD_MS: u64 = 1000;
+
+pub struct Factory;
+
+impl FilterFactory for Factory {
+    fn new_filter(&self, config: String) -> Result<Box<dyn QueryFilter>, Box<dyn Error>> {
+        let ms = match config.trim() {
+            "" => DEFAULT_THRESHOLD_MS,
+            ms => ms.parse::<u64>()?,
+        };
+        Ok(Box::new(SlowLog {
+            threshold: Duration::from_millis(ms),
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct SlowLog {
+    threshold: Duration,
+}
+
+impl QueryFilter for SlowLog {
+    fn name(&self) -> &str {
+        NAME
+    }
+    fn post_result(&self, ctx: &QueryContext, outcome: &QueryOutcome) {
+        if outcome.elapsed >= self.threshold {
+            log::warn!(
+                "slow query: {}ms, user: {}, db: {}, shards: {}, error: {:?}, sql: {}",
+                outcome.elapsed.as_millis(),
+                ctx.user,
+                ctx.db,
+                outcome.shards,
+                outcome.error,
+                ctx.sql
+            );
+        }
+    }
+}
@@ -0,0 +1,909 @@
+//the logical view of the schema: the dbs and the tables of a proxy user come from the router,
+//the columns and the DDL of a sharded table from its template shard, its first physical table,
+//whose name is put back to the logical one.
+use super::conn::C2PConn;
+use super::dispatcher::backend_err_packet;
+use super::errors::{FrontendError, FrontendResult};
+use crate::analyzer::sql::{self, CatalogStatement};
+use crate::backend::error::BackendError;
+use crate::backend::merger::{self, ResultSet};
+use crate::mysql::constants::ColumnType;
+use crate::mysql::packet::{self, ColumnDefinition, TextRow};
+use crate::mysql::{errcode, utils};
+use crate::router::Router;
+use sqlparser::ast::{
+    BinaryOperator, Expr, FunctionArg, FunctionArgExpr, OrderByExpr, Query, Select, SelectItem,
+    SetExpr, UnaryOperator, Value,
+};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+impl C2PConn {
+    //answer a statement on the schema, false when it is not about a sharded table, it runs on
+    //the backend as any other then.
+    pub(super) async fn handle_catalog(&mut self, stmt: CatalogStatement) -> FrontendResult<bool> {
+        let snapshot = self.snapshot.clone();
+        let router = &snapshot.router;
+        let user = self.proxy_user.clone();
+        let rs = match stmt {
+            CatalogStatement::ShowDatabases(like) => {
+                let mut rs = ResultSet::new(self.session.status);
+                rs.columns.push(text_column("Database"));
+                let dbs = std::iter::once("information_schema").chain(router.list_dbs(&user));
+                rs.rows = dbs
+                    .filter(|db| like_match(like.as_deref(), db))
+                    .map(|db| TextRow::new(vec![Some(db.as_bytes().to_vec())]))
+                    .collect();
+                Ok(rs)
+            }
+            //the tables of information_schema are those of its rows in the logical catalog.
+            CatalogStatement::ShowTables { db, full, like }
+                if sql::is_information_schema(db.as_deref().unwrap_or(&self.session.db)) =>
+            {
+                self.logical_information_schema().await.map(|tables| {
+                    let mut rs = ResultSet::new(self.session.status);
+                    rs.columns.push(text_column("Tables_in_information_schema"));
+                    if full {
+                        rs.columns.push(text_column("Table_type"));
+                    }
+                    let position = |name: &str| {
+                        tables
+                            .columns
+                            .iter()
+                            .position(|c| c.name.eq_ignore_ascii_case(name))
+                    };
+                    let pos = [position("TABLE_SCHEMA"), position("TABLE_NAME")];
+                    let kind_pos = position("TABLE_TYPE");
+                    let text = |r: &TextRow, pos: Option<usize>| -> Option<Vec<u8>> {
+                        r.values.get(pos?).cloned().flatten()
+                    };
+                    let mut rows: Vec<TextRow> = tables
+                        .rows
+                        .iter()
+                        .filter(|r| {
+                            text(r, pos[0]).is_some_and(|s| {
+                                sql::is_information_schema(&String::from_utf8_lossy(&s))
+                            })
+                        })
+                        .filter_map(|r| {
+                            let name = text(r, pos[1])?;
+                            if !like_match(like.as_deref(), &String::from_utf8_lossy(&name)) {
+                                return None;
+                            }
+                            let mut values = vec![Some(name)];
+                            if full {
+                                values.push(text(r, kind_pos));
+                            }
+                            Some(TextRow::new(values))
+                        })
+                        .collect();
+                    rows.sort_by(|a, b| a.values.cmp(&b.values));
+                    rs.rows = rows;
+                    rs
+                })
+            }
+            //the tables of the first cluster of the db which are not shards, and the sharded ones.
+            CatalogStatement::ShowTables { db, full, like } => {
+                let db = match self.logical_db(db) {
+                    Ok(db) => db,
+                    Err(err) => return self.session.write_err(err).await.map(|_| true),
+                };
+                let db_entry = match router.lookup_db(&user, &db) {
+                    Ok(entry) => entry,
+                    Err(_) => return Ok(false),
+                };
+                let cluster_id = match db_entry.load_cluster_ids().first() {
+                    Some(c) => c.clone(),
+                    None => return Ok(false),
+                };
+                self.template_result_set(&cluster_id, &db, "SHOW FULL TABLES")
+                    .await
+                    .map(|physical| {
+                        let names = physical_names(router, &user, &cluster_id);
+                        let mut tables: Vec<(Vec<u8>, Option<Vec<u8>>)> = physical
+                            .rows
+                            .into_iter()
+                            .filter_map(|mut r| {
+                                let kind = r.values.get_mut(1).and_then(Option::take);
+                                let name = r.values.into_iter().next()??;
+                                let key = (db.clone(), String::from_utf8_lossy(&name).to_string());
+                                (!names.contains_key(&key)).then_some((name, kind))
+                            })
+                            .collect();
+                        tables.extend(db_entry.list_tables().into_iter().map(|t| {
+                            let name = t.get_table().as_bytes().to_vec();
+                            (name, Some(b"BASE TABLE".to_vec()))
+                        }));
+                        tables.sort();
+                        tables.dedup_by(|a, b| a.0 == b.0);
+                        let mut rs = ResultSet::new(self.session.status);
+                        rs.columns.push(text_column(&format!("Tables_in_{}", db)));
+                        if full {
+                            rs.columns.push(text_column("Table_type"));
+                        }
+                        rs.rows = tables
+                            .into_iter()
+                            .filter(|(name, _)| {
+                                like_match(like.as_deref(), &String::from_utf8_lossy(name))
+                            })
+                            .map(|(name, kind)| {
+                                let mut values = vec![Some(name)];
+                                if full {
+                                    values.push(kind);
+                                }
+                                TextRow::new(values)
+                            })
+                            .collect();
+                        rs
+                    })
+            }
+            CatalogStatement::ShowCreateTable { db, table } => {
+                let db = match self.logical_db(db) {
+                    Ok(db) => db,
+                    Err(err) => return self.session.write_err(err).await.map(|_| true),
+                };
+                let (db, cluster_id, physical) = match self.template_shard(db, &table) {
+                    Some(shard) => shard,
+                    None => return Ok(false),
+                };
+                let sql = format!("SHOW CREATE TABLE `{}`", physical);
+                self.template_result_set(&cluster_id, &db, &sql)
+                    .await
+                    .map(|mut rs| {
+                        let from = format!("`{}`", physical);
+                        let to = format!("`{}`", table);
+                        for r in rs.rows.iter_mut() {
+                            if let Some(Some(name)) = r.values.get_mut(0) {
+                                *name = table.as_bytes().to_vec();
+                            }
+                            if let Some(Some(ddl)) = r.values.get_mut(1) {
+                                let text = String::from_utf8_lossy(ddl).replacen(&from, &to, 1);
+                                *ddl = text.into_bytes();
+                            }
+                        }
+                        rs
+                    })
+            }
+            CatalogStatement::ShowColumns { db, table, full } => {
+                let db = match self.logical_db(db) {
+                    Ok(db) => db,
+                    Err(err) => return self.session.write_err(err).await.map(|_| true),
+                };
+                let (db, cluster_id, physical) = match self.template_shard(db, &table) {
+                    Some(shard) => shard,
+                    None => return Ok(false),
+                };
+                let full = if full { "FULL " } else { "" };
+                let sql = format!("SHOW {}COLUMNS FROM `{}`", full, physical);
+                self.template_result_set(&cluster_id, &db, &sql).await
+            }
+            //the rows come from the backends without the where of the client and are renamed to
+            //the logical tables, then the select is answered over them.
+            CatalogStatement::InformationSchemaTables { query, qualified } => {
+                if !qualified && !sql::is_information_schema(&self.session.db) {
+                    return Ok(false);
+                }
+                let select = match TablesQuery::of(&query) {
+                    Ok(select) => select,
+                    Err(err) => return self.session.write_err(err).await.map(|_| true),
+                };
+                let db = self.session.db.clone();
+                self.logical_information_schema()
+                    .await
+                    .and_then(|rs| select.answer(rs, &db).map_err(BackendError::ServerErr))
+            }
+        };
+        let mut rs = match rs {
+            Ok(rs) => rs,
+            Err(e) => {
+                return self
+                    .session
+                    .write_err(backend_err_packet(e))
+                    .await
+                    .map(|_| true)
+            }
+        };
+        rs.status = self.session.status;
+        merger::write_result_set(&mut self.session.pkg, &rs)
+            .await
+            .map_err(FrontendError::Backend)?;
+        Ok(true)
+    }
+
+    //the db named in the statement or the current one, which the proxy user must have, a db of
+    //another tenant is refused.
+    pub(super) fn logical_db(&self, db: Option<String>) -> Result<String, packet::ErrPacket> {
+        let db = db.unwrap_or_else(|| self.session.db.clone());
+        if db.is_empty() {
+            return Err(packet::ErrPacket::new(
+                errcode::ER_NO_DB_ERROR,
+                "No database selected".to_string(),
+            ));
+        }
+        if let Some(t) = self.snapshot.foreign_tenant(&self.proxy_user, &db) {
+            return Err(packet::ErrPacket::new(
+                errcode::ER_DBACCESS_DENIED_ERROR,
+                format!("{}", FrontendError::ProxyCrossTenant(t)),
+            ));
+        }
+        match self.snapshot.router.lookup_db(&self.proxy_user, &db) {
+            Ok(_) => Ok(db),
+            Err(_) => Err(packet::ErrPacket::new(
+                errcode::ER_BAD_DB_ERROR,
+                format!("Unknown database '{}'", db),
+            )),
+        }
+    }
+
+    //the db, the cluster and the physical name of the template shard of a sharded table.
+    fn template_shard(&self, db: String, table: &str) -> Option<(String, String, String)> {
+        let router = &self.snapshot.router;
+        let table = router
+            .lookup_db(&self.proxy_user, &db)
+            .ok()?
+            .lookup_table(table)
+            .ok()?;
+        let (cluster_id, physical) = table.load_all_path().ok()?.into_iter().next()?;
+        Some((db, cluster_id.to_string(), physical))
+    }
+
+    //the rows of information_schema.tables as the user sees them: those of information_schema,
+    //the tables of the first cluster of each db which are not shards, and each sharded table as
+    //its template shard, named as the logical one.
+    async fn logical_information_schema(&self) -> Result<ResultSet, BackendError> {
+        let router = &self.snapshot.router;
+        let user = &self.proxy_user;
+        //key: (db, physical table) of a shard, value: the cluster and the logical table of the
+        //template shard, none for the other shards.
+        let mut shards: HashMap<(String, String), Option<(String, String)>> = HashMap::new();
+        //the first cluster of each db, where its tables which are not sharded are.
+        let mut homes: HashMap<String, String> = HashMap::new();
+        //each cluster with the dbs to read on it.
+        let mut reads: Vec<(String, Vec<String>)> = Vec::new();
+        let mut read = |cluster: &str, db: &str| match reads.iter_mut().find(|(c, _)| c == cluster)
+        {
+            Some((_, dbs)) if dbs.iter().any(|d| d == db) => {}
+            Some((_, dbs)) => dbs.push(db.to_string()),
+            None => reads.push((cluster.to_string(), vec![db.to_string()])),
+        };
+        for db in router.list_dbs(user) {
+            let db_entry = match router.lookup_db(user, db) {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            if let Some(home) = db_entry.load_cluster_ids().first() {
+                homes.insert(db.to_string(), home.clone());
+                read(home, db);
+            }
+            for table in db_entry.list_tables() {
+                let paths = table.load_all_path().unwrap_or_default();
+                for (i, (cluster_id, physical)) in paths.into_iter().enumerate() {
+                    let template = (i == 0).then(|| {
+                        read(cluster_id, db);
+                        (cluster_id.to_string(), table.get_table().to_string())
+                    });
+                    shards.insert((db.to_string(), physical), template);
+                }
+            }
+        }
+        //information_schema itself is read where the first db is.
+        if let Some((cluster_id, dbs)) = reads.first_mut() {
+            dbs.push("information_schema".to_string());
+            homes.insert("information_schema".to_string(), cluster_id.clone());
+        }
+        let queries: Vec<(String, String)> = reads
+            .into_iter()
+            .map(|(cluster_id, dbs)| {
+                let dbs: Vec<String> = dbs
+                    .iter()
+                    .map(|db| utils::quote_sql_string(db.as_bytes()))
+                    .collect();
+                let sql = format!(
+                    "SELECT * FROM information_schema.tables WHERE TABLE_SCHEMA IN ({})",
+                    dbs.join(", ")
+                );
+                (cluster_id, sql)
+            })
+            .collect();
+        let sets = merger::scatter_query(&self.snapshot.pool, "", &queries, false).await?;
+        let mut rs = ResultSet::new(self.session.status);
+        for ((cluster_id, _), set) in queries.iter().zip(sets) {
+            let position = |name: &str| {
+                set.columns
+                    .iter()
+                    .position(|c| c.name.eq_ignore_ascii_case(name))
+            };
+            let (schema_pos, name_pos) = match (position("TABLE_SCHEMA"), position("TABLE_NAME")) {
+                (Some(s), Some(n)) => (s, n),
+                _ => continue,
+            };
+            if rs.columns.is_empty() {
+                rs.columns = set.columns.clone();
+            }
+            for mut row in set.rows {
+                let text = |pos: usize| -> Option<String> {
+                    let v = row.values.get(pos)?.as_ref()?;
+                    Some(String::from_utf8_lossy(v).to_string())
+                };
+                let key = match (text(schema_pos), text(name_pos)) {
+                    (Some(s), Some(t)) => (s, t),
+                    _ => continue,
+                };
+                let keep = match shards.get(&key) {
+                    Some(Some((c, logical))) if c == cluster_id => {
+                        row.values[name_pos] = Some(logical.as_bytes().to_vec());
+                        true
+                    }
+                    Some(_) => false,
+                    None => homes.get(&key.0) == Some(cluster_id),
+                };
+                if keep {
+                    rs.rows.push(row);
+                }
+            }
+        }
+        Ok(rs)
+    }
+
+    async fn template_result_set(
+        &mut self,
+        cluster_id: &str,
+        db: &str,
+        sql: &str,
+    ) -> Result<ResultSet, BackendError> {
+        let mut conn = self.take_backend(cluster_id, false, db).await?;
+        let rc = merger::read_result_set(&mut conn, sql).await;
+        let healthy = matches!(rc, Ok(_) | Err(BackendError::ServerErr(_)));
+        self.give_backend(conn, healthy).await;
+        rc
+    }
+}
+
+fn text_column(name: &str) -> ColumnDefinition {
+    ColumnDefinition::new(name, ColumnType::MYSQL_TYPE_VAR_STRING)
+}
+
+//key: (db, physical table) of the sharded tables of the user on the cluster, value: the
+//logical name for the first of them there, none for the others.
+fn physical_names(
+    router: &Router,
+    user: &str,
+    cluster_id: &str,
+) -> HashMap<(String, String), Option<String>> {
+    let mut names = HashMap::new();
+    for db in router.list_dbs(user) {
+        let db_entry = match router.lookup_db(user, db) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        for table in db_entry.list_tables() {
+            let paths = table.load_all_path().unwrap_or_default();
+            let on_cluster = paths.into_iter().filter(|(c, _)| *c == cluster_id);
+            for (i, (_, physical)) in on_cluster.enumerate() {
+                let logical = (i == 0).then(|| table.get_table().to_string());
+                names.insert((db.to_string(), physical), logical);
+            }
+        }
+    }
+    names
+}
+
+//the LIKE of SHOW, `%` and `_` are wildcards unless escaped by `\`, case insensitive.
+fn like_match(pattern: Option<&str>, s: &str) -> bool {
+    fn matches(p: &[u8], s: &[u8]) -> bool {
+        match p {
+            [] => s.is_empty(),
+            [b'%', rest @ ..] => (0..=s.len()).any(|i| matches(rest, &s[i..])),
+            [b'_', rest @ ..] => !s.is_empty() && matches(rest, &s[1..]),
+            [b'\\', c, rest @ ..] | [c, rest @ ..] => {
+                s.first().is_some_and(|b| b.eq_ignore_ascii_case(c)) && matches(rest, &s[1..])
+            }
+        }
+    }
+    pattern.is_none_or(|p| matches(p.as_bytes(), s.as_bytes()))
+}
+
+//a select of information_schema.tables the proxy answers over the logical rows: columns or
+//COUNT(*), a where of comparisons with literals or DATABASE(), ORDER BY and LIMIT.
+struct TablesQuery<'a> {
+    select: &'a Select,
+    order_by: &'a [OrderByExpr],
+    limit: Option<usize>,
+    offset: usize,
+}
+
+//an output column: the position of a column of the rows, or COUNT(*).
+enum Output {
+    Column(usize, String),
+    Count(String),
+}
+
+impl<'a> TablesQuery<'a> {
+    fn of(query: &'a Query) -> Result<TablesQuery<'a>, packet::ErrPacket> {
+        let select = match query.body.as_ref() {
+            SetExpr::Select(select) => select,
+            _ => return Err(unsupported()),
+        };
+        let plain = query.with.is_none()
+            && query.fetch.is_none()
+            && query.lock.is_none()
+            && !select.distinct
+            && select.top.is_none()
+            && select.into.is_none()
+            && select.lateral_views.is_empty()
+            && select.group_by.is_empty()
+            && select.cluster_by.is_empty()
+            && select.distribute_by.is_empty()
+            && select.sort_by.is_empty()
+            && select.having.is_none()
+            && select.qualify.is_none();
+        if !plain {
+            return Err(unsupported());
+        }
+        let number = |e: &Expr| match e {
+            Expr::Value(Value::Number(n, _)) => n.parse::<usize>().map_err(|_| unsupported()),
+            _ => Err(unsupported()),
+        };
+        Ok(TablesQuery {
+            select,
+            order_by: &query.order_by,
+            limit: query.limit.as_ref().map(number).transpose()?,
+            offset: match &query.offset {
+                Some(offset) => number(&offset.value)?,
+                None => 0,
+            },
+        })
+    }
+
+    //filter, count, sort, limit then project the rows, `db` is the one of DATABASE().
+    fn answer(&self, mut rs: ResultSet, db: &str) -> Result<ResultSet, packet::ErrPacket> {
+        let outputs = self.outputs(&rs.columns)?;
+        let eval = Eval {
+            columns: &rs.columns,
+            db,
+        };
+        if let Some(selection) = &self.select.selection {
+            let mut rows = Vec::with_capacity(rs.rows.len());
+            for row in std::mem::take(&mut rs.rows) {
+                if eval.truth(selection, &row)? == Some(true) {
+                    rows.push(row);
+                }
+            }
+            rs.rows = rows;
+        }
+        if outputs.iter().any(|o| matches!(o, Output::Count(_))) {
+            let count = rs.rows.len().to_string().into_bytes();
+            rs.columns.clear();
+            rs.rows = vec![TextRow::new(vec![Some(count); outputs.len()])];
+            for o in outputs.iter() {
+                if let Output::Count(name) = o {
+                    rs.columns
+                        .push(ColumnDefinition::new(name, ColumnType::MYSQL_TYPE_LONGLONG));
+                }
+            }
+        } else {
+            let mut keys = Vec::with_capacity(self.order_by.len());
+            for o in self.order_by.iter() {
+                let pos = match &o.expr {
+                    Expr::Value(Value::Number(n, _)) => match n.parse::<usize>() {
+                        Ok(i) if i >= 1 && i <= outputs.len() => match &outputs[i - 1] {
+                            Output::Column(pos, _) => *pos,
+                            Output::Count(_) => return Err(unsupported()),
+                        },
+                        _ => return Err(bad_field(n)),
+                    },
+                    e => eval.column(e)?,
+                };
+                keys.push((pos, o.asc.unwrap_or(true)));
+            }
+            let value = |r: &TextRow, pos: usize| r.values.get(pos).cloned().flatten();
+            rs.rows.sort_by(|a, b| {
+                keys.iter()
+                    .map(|&(pos, asc)| {
+                        let ord = match (value(a, pos), value(b, pos)) {
+                            (Some(x), Some(y)) => {
+                                compare(&String::from_utf8_lossy(&x), &String::from_utf8_lossy(&y))
+                            }
+                            (x, y) => x.is_some().cmp(&y.is_some()),
+                        };
+                        if asc {
+                            ord
+                        } else {
+                            ord.reverse()
+                        }
+                    })
+                    .find(|ord| ord.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+            let columns = std::mem::take(&mut rs.columns);
+            rs.columns = outputs
+                .iter()
+                .map(|o| match o {
+                    Output::Column(pos, name) => ColumnDefinition {
+                        name: name.clone(),
+                        ..columns[*pos].clone()
+                    },
+                    Output::Count(_) => unreachable!(),
+                })
+                .collect();
+            for row in rs.rows.iter_mut() {
+                let mut values = std::mem::take(&mut row.values);
+                row.values = outputs
+                    .iter()
+                    .map(|o| match o {
+                        Output::Column(pos, _) => values.get_mut(*pos).and_then(Option::take),
+                        Output::Count(_) => None,
+                    })
+                    .collect();
+            }
+        }
+        let rows = std::mem::take(&mut rs.rows).into_iter().skip(self.offset);
+        rs.rows = rows.take(self.limit.unwrap_or(usize::MAX)).collect();
+        Ok(rs)
+    }
+
+    //the output columns of the projection, COUNT(*) can not go with a column.
+    fn outputs(&self, columns: &[ColumnDefinition]) -> Result<Vec<Output>, packet::ErrPacket> {
+        let eval = Eval { columns, db: "" };
+        let mut outputs = Vec::new();
+        for item in self.select.projection.iter() {
+            match item {
+                SelectItem::Wildcard | SelectItem::QualifiedWildcard(_) => {
+                    for (pos, c) in columns.iter().enumerate() {
+                        outputs.push(Output::Column(pos, c.name.clone()));
+                    }
+                }
+                SelectItem::UnnamedExpr(e) if is_count_all(e) => {
+                    outputs.push(Output::Count(e.to_string()))
+                }
+                SelectItem::ExprWithAlias { expr, alias } if is_count_all(expr) => {
+                    outputs.push(Output::Count(alias.value.clone()))
+                }
+                SelectItem::UnnamedExpr(e) => {
+                    let name = match e {
+                        Expr::Identifier(id) => id.value.clone(),
+                        Expr::CompoundIdentifier(ids) => ids.last().unwrap().value.clone(),
+                        _ => return Err(unsupported()),
+                    };
+                    outputs.push(Output::Column(eval.column(e)?, name));
+                }
+                SelectItem::ExprWithAlias { expr, alias } => {
+                    outputs.push(Output::Column(eval.column(expr)?, alias.value.clone()))
+                }
+            }
+        }
+        let counts = outputs
+            .iter()
+            .filter(|o| matches!(o, Output::Count(_)))
+            .count();
+        if counts > 0 && counts < outputs.len() {
+            return Err(unsupported());
+        }
+        Ok(outputs)
+    }
+}
+
+//the expressions of a where over the rows of a result set.
+struct Eval<'a> {
+    columns: &'a [ColumnDefinition],
+    db: &'a str,
+}
+
+impl Eval<'_> {
+    //a column by its name, a qualifier is the table of the select.
+    fn column(&self, e: &Expr) -> Result<usize, packet::ErrPacket> {
+        let name = match e {
+            Expr::Identifier(id) => &id.value,
+            Expr::CompoundIdentifier(ids) => &ids.last().unwrap().value,
+            _ => return Err(unsupported()),
+        };
+        self.columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| bad_field(name))
+    }
+
+    fn value(&self, e: &Expr, row: &TextRow) -> Result<Option<String>, packet::ErrPacket> {
+        match e {
+            Expr::Identifier(_) | Expr::CompoundIdentifier(_) => {
+                let v = row.values.get(self.column(e)?).cloned().flatten();
+                Ok(v.map(|v| String::from_utf8_lossy(&v).to_string()))
+            }
+            Expr::Value(Value::SingleQuotedString(s) | Value::DoubleQuotedString(s)) => {
+                Ok(Some(s.clone()))
+            }
+            Expr::Value(Value::Number(n, _)) => Ok(Some(n.clone())),
+            Expr::Value(Value::Null) => Ok(None),
+            Expr::Function(f)
+                if f.args.is_empty()
+                    && ["DATABASE", "SCHEMA"]
+                        .iter()
+                        .any(|n| f.name.to_string().eq_ignore_ascii_case(n)) =>
+            {
+                Ok((!self.db.is_empty()).then(|| self.db.to_string()))
+            }
+            Expr::Nested(e) => self.value(e, row),
+            _ => Err(unsupported()),
+        }
+    }
+
+    //true, false or unknown as sql tells.
+    fn truth(&self, e: &Expr, row: &TextRow) -> Result<Option<bool>, packet::ErrPacket> {
+        Ok(match e {
+            Expr::BinaryOp { left, op, right } => match op {
+                BinaryOperator::And => match (self.truth(left, row)?, self.truth(right, row)?) {
+                    (Some(false), _) | (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                },
+                BinaryOperator::Or => match (self.truth(left, row)?, self.truth(right, row)?) {
+                    (Some(true), _) | (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                },
+                op => {
+                    let (l, r) = match (self.value(left, row)?, self.value(right, row)?) {
+                        (Some(l), Some(r)) => (l, r),
+                        _ => return Ok(None),
+                    };
+                    let ord = compare(&l, &r);
+                    Some(match op {
+                        BinaryOperator::Eq => ord.is_eq(),
+                        BinaryOperator::NotEq => ord.is_ne(),
+                        BinaryOperator::Lt => ord.is_lt(),
+                        BinaryOperator::LtEq => ord.is_le(),
+                        BinaryOperator::Gt => ord.is_gt(),
+                        BinaryOperator::GtEq => ord.is_ge(),
+                        _ => return Err(unsupported()),
+                    })
+                }
+            },
+            Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                expr,
+            } => self.truth(expr, row)?.map(|t| !t),
+            Expr::Nested(e) => self.truth(e, row)?,
+            Expr::IsNull(e) => Some(self.value(e, row)?.is_none()),
+            Expr::IsNotNull(e) => Some(self.value(e, row)?.is_some()),
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let v = match self.value(expr, row)? {
+                    Some(v) => v,
+                    None => return Ok(None),
+                };
+                let mut unknown = false;
+                for item in list.iter() {
+                    match self.value(item, row)? {
+                        Some(i) if compare(&v, &i).is_eq() => return Ok(Some(!negated)),
+                        Some(_) => {}
+                        None => unknown = true,
+                    }
+                }
+                (!unknown).then_some(*negated)
+            }
+            Expr::Like {
+                negated,
+                expr,
+                pattern,
+                escape_char: None,
+            } => match (self.value(expr, row)?, self.value(pattern, row)?) {
+                (Some(v), Some(p)) => Some(like_match(Some(&p), &v) != *negated),
+                _ => None,
+            },
+            Expr::Value(Value::Boolean(b)) => Some(*b),
+            _ => return Err(unsupported()),
+        })
+    }
+}
+
+fn is_count_all(e: &Expr) -> bool {
+    match e {
+        Expr::Function(f) => {
+            f.name.to_string().eq_ignore_ascii_case("COUNT")
+                && !f.distinct
+                && f.over.is_none()
+                && matches!(
+                    f.args.as_slice(),
+                    [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)]
+                )
+        }
+        _ => false,
+    }
+}
+
+//numbers by their value, text case insensitive as the utf8_general_ci of information_schema.
+fn compare(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        _ => a.to_lowercase().cmp(&b.to_lowercase()),
+    }
+}
+
+fn unsupported() -> packet::ErrPacket {
+    packet::ErrPacket::new(
+        errcode::ER_NOT_SUPPORTED_YET,
+        "the proxy answers a select of information_schema.tables by columns, COUNT(*), \
+         comparisons with literals, ORDER BY and LIMIT only"
+            .to_string(),
+    )
+}
+
+fn bad_field(name: &str) -> packet::ErrPacket {
+    packet::ErrPacket::new(
+        errcode::ER_BAD_FIELD_ERROR,
+        format!("Unknown column '{}' in 'field list'", name),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{like_match, TablesQuery};
+    use crate::analyzer::sql::{self, CatalogStatement};
+    use crate::config::center::FileCenter;
+    use crate::frontend::executor::ShardingExecutor;
+    use crate::mysql::server::tests::{connect, query, rows, serve, Answer, Stub};
+    use crate::proxy::reload::tests::config;
+    use crate::proxy::reload::Reloader;
+    use std::sync::Arc;
+
+    #[test]
+    fn match_like_patterns() {
+        assert!(like_match(None, "db1"));
+        assert!(like_match(Some("DB_"), "db1"));
+        assert!(like_match(Some("int%table"), "integer_table"));
+        assert!(!like_match(Some("db"), "db1"));
+        //an escaped wildcard is the char itself.
+        assert!(like_match(Some("integer\\_table"), "integer_table"));
+        assert!(!like_match(Some("integer\\_table"), "integerxtable"));
+        assert!(like_match(Some("100\\%"), "100%"));
+        assert!(!like_match(Some("100\\%"), "1000"));
+    }
+
+    #[test]
+    fn answer_tables_query() {
+        let tables = || {
+            rows(
+                &["TABLE_SCHEMA", "TABLE_NAME", "TABLE_ROWS"],
+                &[
+                    &["db1", "integer_table", "7"],
+                    &["db1", "plain", "5"],
+                    &["information_schema", "TABLES", "0"],
+                ],
+            )
+        };
+        let answer = |text: &str| {
+            let query = match sql::catalog_statement(text) {
+                Some(CatalogStatement::InformationSchemaTables { query, .. }) => query,
+                _ => panic!("{}", text),
+            };
+            TablesQuery::of(&query).and_then(|q| q.answer(tables(), "db1"))
+        };
+        let values = |text: &str| -> Vec<Vec<String>> {
+            answer(text)
+                .unwrap()
+                .rows
+                .into_iter()
+                .map(|r| {
+                    r.values
+                        .into_iter()
+                        .map(|v| String::from_utf8(v.unwrap()).unwrap())
+                        .collect()
+                })
+                .collect()
+        };
+        assert_eq!(
+            values(
+                "SELECT table_name AS t FROM information_schema.tables \
+                 WHERE table_schema = DATABASE() AND table_name LIKE 'int%' ORDER BY 1"
+            ),
+            vec![vec!["integer_table"]]
+        );
+        assert_eq!(
+            values(
+                "SELECT TABLE_NAME, TABLE_ROWS FROM information_schema.TABLES \
+                 WHERE TABLE_SCHEMA IN ('db1') ORDER BY TABLE_ROWS DESC LIMIT 1"
+            ),
+            vec![vec!["integer_table", "7"]]
+        );
+        assert_eq!(
+            values("SELECT COUNT(*) FROM information_schema.tables WHERE NOT table_rows < 5"),
+            vec![vec!["2"]]
+        );
+        let rs = answer("SELECT * FROM information_schema.tables LIMIT 1, 1").unwrap();
+        assert_eq!(rs.columns.len(), 3);
+        assert_eq!(rs.rows.len(), 1);
+        //what the proxy can not answer is refused.
+        let code = |text: &str| answer(text).unwrap_err().err_code();
+        assert_eq!(code("SELECT nope FROM information_schema.tables"), 1054);
+        assert_eq!(
+            code("SELECT table_schema, COUNT(*) FROM information_schema.tables GROUP BY 1"),
+            1235
+        );
+        assert_eq!(
+            code("SELECT UPPER(table_name) FROM information_schema.tables"),
+            1235
+        );
+    }
+
+    #[tokio::test]
+    async fn answer_catalog_over_backend() {
+        let backend = serve(Stub(Box::new(|sql: &str| match sql {
+            "SHOW FULL TABLES" => Answer::Rows(rows(
+                &["Tables_in_db1", "Table_type"],
+                &[
+                    &["integer_table_0", "BASE TABLE"],
+                    &["integer_table_1", "BASE TABLE"],
+                    &["plain", "VIEW"],
+                ],
+            )),
+            "SHOW CREATE TABLE `integer_table_0`" => Answer::Rows(rows(
+                &["Table", "Create Table"],
+                &[&[
+                    "integer_table_0",
+                    "CREATE TABLE `integer_table_0` (\n  `id` int\n)",
+                ]],
+            )),
+            s if s.starts_with("SELECT * FROM information_schema.tables") => Answer::Rows(rows(
+                &["TABLE_SCHEMA", "TABLE_NAME"],
+                &[
+                    &["db1", "integer_table_0"],
+                    &["db1", "integer_table_1"],
+                    &["db1", "plain"],
+                    &["information_schema", "TABLES"],
+                ],
+            )),
+            _ => Answer::Ok,
+        })))
+        .await;
+        let cfg = config(&backend);
+        let center = Arc::new(FileCenter::new("sharding.toml".to_string()));
+        let reloader = Reloader::new(center, &cfg).await.unwrap();
+        let address = serve(ShardingExecutor::new(reloader.clone())).await;
+        let (mut pkg, ok, _) = connect(&address, "root", "root").await;
+        assert_eq!(ok[0], 0x00);
+
+        let values = |rs: crate::backend::merger::ResultSet| -> Vec<Vec<String>> {
+            rs.rows
+                .into_iter()
+                .map(|r| {
+                    r.values
+                        .into_iter()
+                        .map(|v| String::from_utf8(v.unwrap()).unwrap())
+                        .collect()
+                })
+                .collect()
+        };
+        let tables = query(&mut pkg, "SHOW FULL TABLES").await.unwrap();
+        assert_eq!(tables.columns[0].name, "Tables_in_db1");
+        assert_eq!(
+            values(tables),
+            vec![vec!["integer_table", "BASE TABLE"], vec!["plain", "VIEW"]]
+        );
+        let create = query(&mut pkg, "SHOW CREATE TABLE integer_table").await;
+        assert_eq!(
+            values(create.unwrap()),
+            vec![vec![
+                "integer_table",
+                "CREATE TABLE `integer_table` (\n  `id` int\n)"
+            ]]
+        );
+        let logical = query(
+            &mut pkg,
+            "SELECT table_name FROM information_schema.tables \
+             WHERE table_schema = 'db1' AND table_name = 'integer_table'",
+        )
+        .await;
+        assert_eq!(values(logical.unwrap()), vec![vec!["integer_table"]]);
+        let all = query(&mut pkg, "SELECT COUNT(*) FROM information_schema.tables").await;
+        assert_eq!(values(all.unwrap()), vec![vec!["3"]]);
+        //information_schema is answered from the catalog, never passed to the backend.
+        let err = query(&mut pkg, "SELECT * FROM information_schema.columns").await;
+        assert_eq!(err.unwrap_err()[1..3], 1235u16.to_le_bytes());
+        let system = query(&mut pkg, "SHOW TABLES FROM information_schema").await;
+        assert_eq!(values(system.unwrap()), vec![vec!["TABLES"]]);
+        reloader.snapshot().pool.quit().await;
+    }
+}
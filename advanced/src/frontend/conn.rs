@@ -1,333 +1,145 @@
 #![allow(dead_code)]
 
+use crate::analyzer::sql;
+use crate::config::DistributedTxn;
 use crate::frontend::errors::{FrontendError, FrontendResult};
-use crate::mysql::constants::command;
-use crate::mysql::{constants, errors, packet, packetio, utils};
-use crate::router;
-use byteorder::{WriteBytesExt, LE};
-use mysql_common::scramble;
-use std::io;
-use std::io::Cursor;
+use crate::frontend::stmt::Stmt;
+use crate::frontend::txn::Txn;
+use crate::mysql::constants::{command, StatusFlags};
+use crate::mysql::server::{Login, Session};
+use crate::mysql::{errcode, errors, packet};
+use crate::proxy::reload::Snapshot;
+use crate::security::tenant::{qualify, split_login, TenantManager};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::net::TcpStream;
 
-//client to proxy conn abstraction
+//client to proxy conn abstraction, the conn of the sharding executor.
 #[derive(Debug)]
-pub struct C2PConn<'a> {
-    pkg: packetio::PacketIO,
-    conn_id: u32,
-    capability: constants::CapabilityFlags,
-    salt: Vec<u8>, //8 or 20 bytes.
-    collation_id: u8,
-    status: constants::StatusFlags,
+pub struct C2PConn {
+    pub(super) session: Session,
     //---
-    proxy_user: String,
-    db: String,
+    pub(super) proxy_user: String, //the schema owner of the login, `user@tenant` for a tenant.
+    pub(super) tenant: String,
     //---
-    r: Arc<router::Router<'a>>,
+    pub(super) snapshot: Arc<Snapshot>, //the config this conn runs with till its end.
     //---
-    quit_flag: bool,
+    pub(super) stmt_id: u32, //the last statement id handed out.
+    pub(super) stmts: HashMap<u32, Stmt>,
+    //---
+    pub(super) txn: Txn,
+    pub(super) distributed_txn: DistributedTxn,
 }
 
-impl<'a> C2PConn<'a> {
-    async fn write_initial_handshake(&mut self) -> FrontendResult<()> {
-        let mut data: Vec<u8> = Vec::new();
-        //min version 10
-        data.push(constants::MIN_PROTOCOL_VERSION);
-
-        //server version[00]
-        data.extend_from_slice(constants::SERVER_VERSION.as_bytes());
-        data.push(0u8);
-
-        //connection id
-        let mut conn_id_v = Vec::new();
-        conn_id_v.write_u32::<LE>(self.conn_id)?;
-        data.append(&mut conn_id_v);
-
-        //auth-plugin-data-part-1
-        data.extend_from_slice(&self.salt[0..8]);
-        //filter [00]
-        data.push(0u8);
-
-        //the lower 2 bytes of the capability
-        data.push(self.capability.bits() as u8);
-        data.push((self.capability.bits() >> 8) as u8);
-
-        //charset, utf-8 default
-        data.push(self.collation_id);
-
-        //status
-        data.push(self.status.bits() as u8);
-        data.push((self.status.bits() >> 8) as u8);
-
-        //capability flag upper 2 bytes, using default capability here
-        data.push((self.capability.bits() >> 16) as u8);
-        data.push((self.capability.bits() >> 24) as u8);
-        //filter [0x15], for wireshark dump, value is 0x15
-        data.push(0x15); //????
-                         //reserved 10 [00]
-        let reserved_u8 = [0u8; 10];
-        data.extend_from_slice(&reserved_u8);
-        //auth-plugin-data-part-2
-        data.extend_from_slice(&self.salt[8..]);
-        data.push(0u8);
-
-        // let hsp = HandshakePacket::deserialize((), &mut ParseBuf(data.as_mut_slice())).unwrap();
-        // println!("hsf {:?}", hsp);
-
-        //server send first auth packet to client by tcp stream
-        self.pkg
-            .write_packet(&mut data)
-            .await
-            .map_err(|e| FrontendError::MySQLErr(e))
+impl AsMut<Session> for C2PConn {
+    fn as_mut(&mut self) -> &mut Session {
+        &mut self.session
     }
-    // http://hutaow.com/blog/2013/11/06/mysql-protocol-analysis/#41
-    //https://dev.mysql.com/doc/internals/en/connection-phase-packets.html#packet-Protocol::HandshakeResponse
-    //https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_connection_phase_packets_protocol_handshake_response.html
-    async fn read_client_handshake(&mut self) -> FrontendResult<()> {
-        let data = self.pkg.read_packet().await?;
-
-        log::info!("read_client_handshake raw data: {:?}", &data);
+}
 
-        let mut pos: usize = 0;
-        //capability
-        let mut rdr = Cursor::new(&data[..4]);
-        self.capability &= constants::CapabilityFlags::from_bits_truncate(
-            byteorder::ReadBytesExt::read_u32::<LE>(&mut rdr).unwrap_or(0),
-        );
-        if !self
-            .capability
-            .contains(constants::CapabilityFlags::CLIENT_PROTOCOL_41)
-        {
-            log::info!(
-                "Too older than CapabilityFlags::CLIENT_PROTOCOL_41 : {:?}",
-                &self
-            );
-            return Err(FrontendError::ProxyAuthOldInClientProtocol41);
+impl C2PConn {
+    pub fn new(session: Session, snapshot: Arc<Snapshot>) -> C2PConn {
+        C2PConn {
+            session,
+            proxy_user: String::new(),
+            tenant: String::new(),
+            distributed_txn: snapshot.distributed_txn,
+            snapshot,
+            stmt_id: 0,
+            stmts: HashMap::new(),
+            txn: Txn::default(),
         }
-        pos += 4;
-        //skip max packet size
-        pos += 4;
-        //charset, skip, if you want to use another charset, use set names
-        //c.collation = CollationId(data[pos])
-        pos += 1;
-        //skip reserved 23[00]
-        pos += 23;
-        //user name
-        self.proxy_user = data[pos..]
-            .iter()
-            .position(|&x| x == 0)
-            .map(|offset| String::from_utf8_lossy(&data[pos..(pos + offset)]))
-            .unwrap_or_default()
-            .to_string();
-        pos += self.proxy_user.len() + 1;
-        //auth length and auth
-        let auth_len = data[pos] as usize;
-        pos += 1;
-        let auth = data[pos..(pos + auth_len)].to_vec();
-        log::info!("client auth user: {}, auth:{:?}", &self.proxy_user, &auth);
-        //check proxy user exists?
-        let user_pair = crate::SHOTCUT_GLOBAL_CONFIG
-            .check_proxy_user_exists(&self.proxy_user)
-            .ok_or_else(|| {
-                log::info!(
-                    "proxy user do not exist: {}, auth:{:?}",
-                    &self.proxy_user,
-                    &auth
-                );
-                FrontendError::ProxyAuthDenied
-            })?;
-        //check user password?
-        let scramble = scramble::scramble_native(&self.salt, user_pair.1.as_bytes())
-            .unwrap_or_default()
-            .to_vec();
-        //let _scramble = utils::scramble_password(&self.salt, user_pair.1).unwrap_or_default();
-        if scramble != auth {
-            log::info!(
-                "proxy user pwd check failed: {}, auth:{:?}",
-                &self.proxy_user,
-                &auth
+    }
+    pub(super) fn login(&mut self, login: &Login) -> FrontendResult<()> {
+        let (proxy_user, tenant) = authenticate(&self.snapshot, &self.session, login)?;
+        self.proxy_user = proxy_user;
+        self.tenant = tenant;
+        self.session.db = login.db.clone();
+        log::info!("init_with_db: {:?}", &self);
+        Ok(())
+    }
+    //COM_INIT_DB and USE, a db of another tenant is refused, one the router does not know of
+    //the proxy user is unknown.
+    pub(super) async fn handle_init_db(&mut self, db: &str) -> FrontendResult<()> {
+        if let Some(t) = self.snapshot.foreign_tenant(&self.proxy_user, db) {
+            let err = packet::ErrPacket::new(
+                errcode::ER_DBACCESS_DENIED_ERROR,
+                format!("{}", FrontendError::ProxyCrossTenant(t)),
             );
-            return Err(FrontendError::ProxyAuthDenied);
+            return self.session.write_err(err).await;
         }
-        pos += auth_len;
-
-        //init with db
-        let mut init_with_db = String::new();
-        if self
-            .capability
-            .contains(constants::CapabilityFlags::CLIENT_CONNECT_WITH_DB)
+        //information_schema is answered by the proxy from the logical catalog.
+        if !sql::is_information_schema(db)
+            && self
+                .snapshot
+                .router
+                .lookup_db(&self.proxy_user, db)
+                .is_err()
         {
-            let may_be_db = &data[pos..];
-            if !may_be_db.is_empty() {
-                init_with_db = data[pos..]
-                    .iter()
-                    .position(|&x| x == 0)
-                    .map(|offset| String::from_utf8_lossy(&data[pos..(pos + offset)]))
-                    .unwrap_or_default()
-                    .to_string();
-                pos += init_with_db.len() + 1;
-            }
+            let err = packet::ErrPacket::new(
+                errcode::ER_BAD_DB_ERROR,
+                format!("Unknown database '{}'", db),
+            );
+            return self.session.write_err(err).await;
         }
-        self.db = init_with_db;
-        log::info!("init_with_db: {:?}, final pos: {}", &self, pos);
-        Ok(())
+        self.session.db = db.to_string();
+        self.session.write_ok(None).await
     }
-    pub async fn write_err(&mut self, r: packet::ErrPacket) -> FrontendResult<()> {
-        self.pkg
-            .write_packet(r.to_bits().as_mut_slice())
-            .await
-            .map_err(|e| FrontendError::MySQLErr(e))
+    //COM_RESET_CONNECTION: the session starts over as it was after the login, with its db.
+    pub(super) async fn handle_reset_connection(&mut self) -> FrontendResult<()> {
+        if let Some(e) = self.finish_txn(false).await {
+            log::info!("rollback on reset connection failed: {:?}", e);
+        }
+        self.stmts.clear();
+        self.session.status = StatusFlags::SERVER_STATUS_AUTOCOMMIT;
+        self.session.write_ok(None).await
     }
-    //mysql server to client handshake
-    pub async fn s2c_handshake(&mut self) -> FrontendResult<()> {
-        log::info!("handshake : {:?}", &self);
-        self.write_initial_handshake().await?;
-        self.read_client_handshake().await?;
-        self.write_ok(None).await?;
-        self.pkg.reset_seq();
+    pub(super) async fn dispatch_stmt_cmd(&mut self, cmd: u8, data: &[u8]) -> FrontendResult<()> {
+        match cmd {
+            command::COM_STMT_SEND_LONG_DATA => self.handle_stmt_send_long_data(data),
+            command::COM_STMT_CLOSE => self.handle_stmt_close(data),
+            command::COM_STMT_RESET => return self.handle_stmt_reset(data).await,
+            _ => return Err(FrontendError::MySQLErr(errors::MySQLError::ErUnknownCmd)),
+        }
         Ok(())
     }
-    pub async fn build_c2p_conn(
-        tcp: TcpStream,
-        id: u32,
-        r: Arc<router::Router<'a>>,
-    ) -> FrontendResult<C2PConn<'a>> {
-        let pkg = packetio::PacketIO::new(tcp);
-        let conn_id: u32 = id;
-        let capability = constants::get_default_capability_flags();
-        let salt: Vec<u8> = utils::random_salt(20)?;
-        let collation_id: u8 = constants::UTF8MB4_GENERAL_CI;
-        let status = constants::StatusFlags::SERVER_STATUS_AUTOCOMMIT;
-        let proxy_user: String = String::new();
-        let db: String = String::new();
-        Ok(C2PConn {
-            pkg,
-            conn_id,
-            capability,
-            salt,
-            collation_id,
-            status,
-            proxy_user,
-            db,
-            r,
-            quit_flag: false,
-        })
-    }
-    pub async fn run_loop(&mut self) {
-        log::info!("run_loop : {:?}", &self);
-        //println!("global config: {:?}", *crate::GLOBAL_CONFIG);
-        let mut exit_flag = false;
-        log::info!("exit_flag : {}", exit_flag);
-        loop {
-            match self.pkg.read_packet().await {
-                Err(e) => {
-                    match e {
-                        errors::MySQLError::IO(o) if o.kind() == io::ErrorKind::TimedOut => {
-                            exit_flag = true
-                        }
-                        errors::MySQLError::IO(o) if o.kind() == io::ErrorKind::ConnectionReset => {
-                            exit_flag = true
-                        }
-                        errors::MySQLError::IO(o)
-                            if o.kind() == io::ErrorKind::ConnectionAborted =>
-                        {
-                            exit_flag = true
-                        }
-                        errors::MySQLError::IO(o)
-                            if o.kind() == io::ErrorKind::ConnectionRefused =>
-                        {
-                            exit_flag = true
-                        }
-                        errors::MySQLError::IO(o) if o.kind() == io::ErrorKind::NotConnected => {
-                            exit_flag = true
-                        }
-                        errors::MySQLError::IO(o) if o.kind() == io::ErrorKind::BrokenPipe => {
-                            exit_flag = true
-                        }
-                        _ => continue,
-                    }
-                    if exit_flag {
-                        let rc = self.quit();
-                        log::info!("quit mysql connection: {:?}", rc);
-                        return;
-                    }
-                }
-                Ok(ref mut data) => {
-                    log::info!("before dispatch_mysql_cmd data: {:?}", data);
-                    let rc = self.dispatch_mysql_cmd(data).await;
-                    log::info!("dispatch_mysql_cmd  result: {:?}", rc);
-                    if let Err(e) = rc {
-                        //c.writeError(err)
-                        if let FrontendError::IO(o) = e {
-                            let decision = match o.kind() {
-                                io::ErrorKind::TimedOut => true,
-                                io::ErrorKind::ConnectionReset => true,
-                                io::ErrorKind::ConnectionAborted => true,
-                                io::ErrorKind::ConnectionRefused => true,
-                                io::ErrorKind::NotConnected => true,
-                                io::ErrorKind::BrokenPipe => true,
-                                _ => false,
-                            };
-                            if decision {
-                                let rc = self.quit();
-                                log::info!("quit mysql connection: {:?}", rc);
-                            }
-                        }
-                    }
-                    if self.quit_flag {
-                        return;
-                    }
-                }
-            }
-            self.pkg.reset_seq();
-        } //end of loop
-    }
-    //write ok packet to client.
-    pub async fn write_ok(&mut self, r: Option<packet::OkPacket>) -> FrontendResult<()> {
-        let ok_p = if r.is_none() {
-            packet::OkPacket::empty(self.status)
-        } else {
-            r.unwrap_or_else(|| packet::OkPacket::empty(self.status))
-        };
-        let mut data = ok_p.to_bits();
-        self.pkg
-            .write_packet(&mut data)
-            .await
-            .map_err(|e| FrontendError::MySQLErr(e))
-    }
-    pub fn quit(&mut self) -> FrontendResult<()> {
-        self.quit_flag = true;
-        self.pkg.quit().map_err(|e| FrontendError::MySQLErr(e))
-    }
-    pub async fn dispatch_mysql_cmd(&mut self, data: &mut [u8]) -> FrontendResult<()> {
-        log::info!("dispatch_mysql_cmd data: {:?}", data);
-        //data = data[1:]
-        match data[0] {
-            command::COM_QUIT => {
-                // c.handleRollback()
-                self.quit()?;
-                return Ok(());
-            }
-            command::COM_QUERY => {
-                //return c.handleQuery(hack.String(data));
-                self.quit()?;
-                //return Ok(());
+}
 
-                // TODO
-            }
-            command::COM_PING => {
-                //return c.writeOK(nil);
-            }
-            command::COM_INIT_DB => {
-                //return c.handleUseDB(hack.String(data))
-            }
-            command::COM_FIELD_LIST => {}
-            _ => {
-                log::info!("command {:?}not supported now", data[0]);
-                return Err(FrontendError::MySQLErr(errors::MySQLError::ErUnknownCmd));
-            }
-        }
-        Ok(())
+//the schema owner and the tenant of a login, `user@tenant` or `user` of the tenant of the
+//listener, whose password and db are checked.
+pub fn authenticate(
+    snapshot: &Snapshot,
+    session: &Session,
+    login: &Login,
+) -> FrontendResult<(String, String)> {
+    let (user, tenant) =
+        split_login(&login.user, snapshot.bound_tenant.as_deref()).map_err(|t| {
+            log::info!("proxy user of another tenant: {}", &login.user);
+            FrontendError::ProxyCrossTenant(t)
+        })?;
+    //check proxy user exists?
+    let proxy_user = snapshot
+        .tenants
+        .get_user(tenant.clone(), user.clone())
+        .ok_or_else(|| {
+            log::info!(
+                "proxy user do not exist: {}, auth:{:?}",
+                &login.user,
+                &login.auth
+            );
+            FrontendError::ProxyAuthDenied
+        })?;
+    let owner = qualify(&user, &tenant);
+    //check user password?
+    if !session.check_password(&login.auth, &proxy_user.password) {
+        log::info!(
+            "proxy user pwd check failed: {}, auth:{:?}",
+            &owner,
+            &login.auth
+        );
+        return Err(FrontendError::ProxyAuthDenied);
+    }
+    if let Some(t) = snapshot.foreign_tenant(&owner, &login.db) {
+        return Err(FrontendError::ProxyCrossTenant(t));
     }
+    Ok((owner, tenant))
 }
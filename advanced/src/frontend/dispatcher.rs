@@ -1 +1,237 @@
+use super::conn::C2PConn;
+use super::errors::{FrontendError, FrontendResult};
+use super::stmt::{logical_column, logical_names};
+use crate::analyzer::error::AnalyzerError;
+use crate::analyzer::{plan, sql};
+use crate::backend::error::BackendError;
+use crate::backend::{executor, merger};
+use crate::filter::{QueryContext, QueryOutcome};
+use crate::mysql::{errcode, packet};
+use crate::router::RouterError;
 
+impl C2PConn {
+    //COM_QUERY: analyze and route the sql, then run it on the chosen backend, with the filters of
+    //the config around it.
+    pub async fn handle_query(&mut self, sql: &str) -> FrontendResult<()> {
+        log::info!(
+            "handle_query user: {}, db: {}, sql: {}",
+            &self.proxy_user,
+            &self.session.db,
+            sql
+        );
+        let snapshot = self.snapshot.clone();
+        let (user, db) = (self.proxy_user.clone(), self.session.db.clone());
+        let ctx = snapshot.filters.context(&user, &db, sql);
+        let mut sql = sql.to_string();
+        let (rc, shards, error) = match snapshot.filters.pre_parse(&ctx, &mut sql) {
+            Ok(()) => self.run_query(&ctx, &sql).await,
+            Err(r) => {
+                let err = packet::ErrPacket::new(errcode::ER_UNKNOWN_ERROR, format!("{}", r));
+                (self.session.write_err(err).await, 0, Some(r.to_string()))
+            }
+        };
+        let error = error.or_else(|| rc.as_ref().err().map(|e| e.to_string()));
+        let elapsed = ctx.started.elapsed();
+        let outcome = QueryOutcome {
+            shards,
+            error,
+            elapsed,
+        };
+        snapshot.filters.post_result(&ctx, &outcome);
+        rc
+    }
+
+    //the result, the shards the sql ran on and the error the client was answered with.
+    async fn run_query(
+        &mut self,
+        ctx: &QueryContext<'_>,
+        sql: &str,
+    ) -> (FrontendResult<()>, usize, Option<String>) {
+        if let Some(stmt) = sql::txn_statement(sql) {
+            return (self.handle_txn(stmt).await, 0, None);
+        }
+        if let Some(db) = sql::use_statement(sql) {
+            return (self.handle_init_db(&db).await, 0, None);
+        }
+        if let Some(stmt) = sql::catalog_statement(sql) {
+            match self.handle_catalog(stmt).await {
+                Ok(false) => {}
+                rc => return (rc.map(|_| ()), 0, None),
+            }
+        }
+        let mut plan = match plan::build_plan(
+            &self.snapshot.router,
+            &self.proxy_user,
+            &self.session.db,
+            sql,
+        ) {
+            Ok(plan) => plan,
+            Err(e) => return self.answer_err(analyzer_err_packet(&e), 0).await,
+        };
+        let snapshot = self.snapshot.clone();
+        if let Err(r) = snapshot.filters.post_route(ctx, &mut plan) {
+            let err = packet::ErrPacket::new(errcode::ER_UNKNOWN_ERROR, format!("{}", r));
+            return self.answer_err(err, 0).await;
+        }
+        log::info!("handle_query plan: {:?}", &plan);
+        let shards = plan.shards.len();
+        if shards != 1 {
+            return (self.handle_scatter(&plan, false).await, shards, None);
+        }
+        let (cluster_id, backend_sql) = &plan.shards[0];
+        let mut conn = match self
+            .take_backend(cluster_id, plan.force_master, &plan.db)
+            .await
+        {
+            Ok(conn) => conn,
+            Err(e) => return self.answer_err(backend_err_packet(e), 1).await,
+        };
+        match executor::relay_query(&mut conn, backend_sql, &mut self.session.pkg).await {
+            Ok(_) => {
+                self.give_backend(conn, true).await;
+                (Ok(()), 1, None)
+            }
+            Err(e) => {
+                //the response may be half relayed, so the conn can not be trusted anymore.
+                self.give_backend(conn, false).await;
+                (Err(FrontendError::Backend(e)), 1, None)
+            }
+        }
+    }
+
+    //COM_FIELD_LIST: the columns of the first physical table of the logical one, named by the
+    //logical one. a table which is not sharded is on the first cluster of the db.
+    pub(super) async fn handle_field_list(
+        &mut self,
+        table: &str,
+        wildcard: &str,
+    ) -> FrontendResult<()> {
+        let db = match self.logical_db(None) {
+            Ok(db) => db,
+            Err(err) => return self.session.write_err(err).await,
+        };
+        let snapshot = self.snapshot.clone();
+        let db_entry = snapshot
+            .router
+            .lookup_db(&self.proxy_user, &db)
+            .map_err(|e| FrontendError::Analyzer(AnalyzerError::Router(e)))?;
+        let first = match db_entry.lookup_table(table) {
+            Ok(entry) => entry
+                .load_all_path()
+                .map(|paths| paths.into_iter().next())
+                .map_err(AnalyzerError::Router),
+            Err(_) => Ok(db_entry
+                .load_cluster_ids()
+                .first()
+                .map(|c| (c.as_str(), table.to_string()))),
+        };
+        let (cluster_id, physical) = match first {
+            Ok(Some(path)) => path,
+            Ok(None) => {
+                let e = AnalyzerError::Router(RouterError::LookupErrClusterPairsEmpty);
+                return self.session.write_err(analyzer_err_packet(&e)).await;
+            }
+            Err(e) => return self.session.write_err(analyzer_err_packet(&e)).await,
+        };
+        let mut conn = match self.take_backend(cluster_id, false, &db).await {
+            Ok(conn) => conn,
+            Err(e) => return self.session.write_err(backend_err_packet(e)).await,
+        };
+        let names = logical_names(&snapshot.router, &self.proxy_user, &db);
+        let mut arg = physical.into_bytes();
+        arg.push(0);
+        arg.extend_from_slice(wildcard.as_bytes());
+        let rc = executor::relay_field_list_with(&mut conn, &arg, &mut self.session.pkg, |c| {
+            logical_column(c, &names)
+        })
+        .await;
+        self.give_backend(conn, rc.is_ok()).await;
+        rc.map_err(FrontendError::Backend)
+    }
+
+    async fn answer_err(
+        &mut self,
+        err: packet::ErrPacket,
+        shards: usize,
+    ) -> (FrontendResult<()>, usize, Option<String>) {
+        let why = String::from_utf8_lossy(err.err_msg()).to_string();
+        (self.session.write_err(err).await, shards, Some(why))
+    }
+
+    //run on more than one physical table: a select is merged into one result set,
+    //in the binary protocol for a prepared statement, other statements answer one OK packet.
+    pub(super) async fn handle_scatter(
+        &mut self,
+        plan: &plan::Plan,
+        binary: bool,
+    ) -> FrontendResult<()> {
+        let sets = if self.in_txn() {
+            self.scatter_in_txn(plan).await
+        } else {
+            merger::scatter_query(
+                &self.snapshot.pool,
+                &plan.db,
+                &plan.shards,
+                plan.force_master,
+            )
+            .await
+        };
+        let sets = match sets {
+            Ok(sets) => sets,
+            Err(e) => return self.session.write_err(backend_err_packet(e)).await,
+        };
+        match &plan.merge {
+            Some(spec) => {
+                let merged = merger::merge(spec, sets);
+                let written = if binary {
+                    merger::write_binary_result_set(&mut self.session.pkg, &merged).await
+                } else {
+                    merger::write_result_set(&mut self.session.pkg, &merged).await
+                };
+                written.map_err(FrontendError::Backend)
+            }
+            None => self.session.write_ok(Some(merger::merge_ok(&sets))).await,
+        }
+    }
+
+    //in a transaction the shards run one by one on the pinned conns.
+    async fn scatter_in_txn(
+        &mut self,
+        plan: &plan::Plan,
+    ) -> Result<Vec<merger::ResultSet>, BackendError> {
+        let mut sets = Vec::with_capacity(plan.shards.len());
+        for (cluster_id, sql) in plan.shards.iter() {
+            let mut conn = self
+                .take_backend(cluster_id, plan.force_master, &plan.db)
+                .await?;
+            let rc = merger::read_result_set(&mut conn, sql).await;
+            let healthy = matches!(rc, Ok(_) | Err(BackendError::ServerErr(_)));
+            self.give_backend(conn, healthy).await;
+            sets.push(rc?);
+        }
+        Ok(sets)
+    }
+}
+
+pub fn analyzer_err_packet(e: &AnalyzerError) -> packet::ErrPacket {
+    let code = match e {
+        AnalyzerError::ParseErr(..) => errcode::ER_PARSE_ERROR,
+        AnalyzerError::NoDatabaseSelected => errcode::ER_NO_DB_ERROR,
+        AnalyzerError::Router(RouterError::LookupErrDBNotExist) => errcode::ER_BAD_DB_ERROR,
+        AnalyzerError::MultiStatementNotSupported => errcode::ER_NOT_SUPPORTED_YET,
+        AnalyzerError::ScatterNotSupported(..) => errcode::ER_NOT_SUPPORTED_YET,
+        AnalyzerError::TablesNotCoLocated(..) => errcode::ER_NOT_SUPPORTED_YET,
+        AnalyzerError::NotJoinedOnShardKey(..) => errcode::ER_NOT_SUPPORTED_YET,
+        AnalyzerError::CrossTenant(..) => errcode::ER_DBACCESS_DENIED_ERROR,
+        AnalyzerError::InformationSchemaNotSupported => errcode::ER_NOT_SUPPORTED_YET,
+        _ => errcode::ER_UNKNOWN_ERROR,
+    };
+    packet::ErrPacket::new(code, format!("{}", e))
+}
+
+pub fn backend_err_packet(e: BackendError) -> packet::ErrPacket {
+    match e {
+        BackendError::ServerErr(p) => p,
+        e => packet::ErrPacket::new(errcode::ER_UNKNOWN_ERROR, format!("{}", e)),
+    }
+}
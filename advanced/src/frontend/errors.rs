@@ -1,3 +1,5 @@
+use crate::analyzer::error::AnalyzerError;
+use crate::backend::error::BackendError;
 use crate::mysql;
 
 pub type FrontendResult<T> = std::result::Result<T, FrontendError>;
@@ -7,7 +9,10 @@ pub enum FrontendError {
     IO(std::io::Error),
     MySQLErr(mysql::errors::MySQLError),
     ProxyAuthDenied,
+    ProxyCrossTenant(String), //the tenant the login or db is of, not that of the session.
     ProxyAuthOldInClientProtocol41,
+    Analyzer(AnalyzerError),
+    Backend(BackendError),
 }
 
 impl From<std::io::Error> for FrontendError {
@@ -22,13 +27,28 @@ impl From<mysql::errors::MySQLError> for FrontendError {
     }
 }
 
+impl From<AnalyzerError> for FrontendError {
+    fn from(e: AnalyzerError) -> Self {
+        FrontendError::Analyzer(e)
+    }
+}
+
+impl From<BackendError> for FrontendError {
+    fn from(e: BackendError) -> Self {
+        FrontendError::Backend(e)
+    }
+}
+
 impl std::error::Error for FrontendError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             FrontendError::ProxyAuthDenied => None,
+            FrontendError::ProxyCrossTenant(..) => None,
             FrontendError::ProxyAuthOldInClientProtocol41 => None,
             FrontendError::IO(e) => e.source(),
             FrontendError::MySQLErr(e) => e.source(),
+            FrontendError::Analyzer(e) => e.source(),
+            FrontendError::Backend(e) => e.source(),
         }
     }
 }
@@ -36,11 +56,16 @@ impl std::fmt::Display for FrontendError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             FrontendError::ProxyAuthDenied => write!(f, "proxy auth denied!"),
+            FrontendError::ProxyCrossTenant(t) => {
+                write!(f, "access denied to tenant {:?} from this session!", t)
+            }
             FrontendError::ProxyAuthOldInClientProtocol41 => {
                 write!(f, "Too old than CapabilityFlags::CLIENT_PROTOCOL_41!")
             }
             FrontendError::IO(e) => e.fmt(f),
             FrontendError::MySQLErr(e) => e.fmt(f),
+            FrontendError::Analyzer(e) => e.fmt(f),
+            FrontendError::Backend(e) => e.fmt(f),
         }
     }
 }
@@ -0,0 +1,134 @@
+//the executor of the sharding proxy: each conn runs with the snapshot of the config it was
+//accepted with, its commands are routed through the router of it.
+use super::conn::C2PConn;
+use super::errors::{FrontendError, FrontendResult};
+use crate::mysql::constants::command;
+use crate::mysql::errors::MySQLError;
+use crate::mysql::server::{Login, Session};
+use crate::proto::interface::Executor;
+use crate::proxy::reload::Reloader;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Instant;
+
+pub struct ShardingExecutor {
+    reloader: Arc<Reloader>,
+    started: Instant, //the uptime of COM_STATISTICS.
+}
+
+impl ShardingExecutor {
+    pub fn new(reloader: Arc<Reloader>) -> ShardingExecutor {
+        ShardingExecutor {
+            reloader,
+            started: Instant::now(),
+        }
+    }
+}
+
+#[async_trait]
+impl Executor for ShardingExecutor {
+    type Conn = C2PConn;
+
+    fn open(&self, session: Session) -> C2PConn {
+        C2PConn::new(session, self.reloader.snapshot())
+    }
+
+    async fn login(&self, conn: &mut C2PConn, login: &Login) -> FrontendResult<()> {
+        conn.login(login)
+    }
+
+    async fn init_db(&self, conn: &mut C2PConn, db: &str) -> FrontendResult<()> {
+        conn.handle_init_db(db).await
+    }
+
+    async fn query(&self, conn: &mut C2PConn, sql: &str) -> FrontendResult<()> {
+        conn.handle_query(sql).await
+    }
+
+    async fn field_list(
+        &self,
+        conn: &mut C2PConn,
+        table: &str,
+        wildcard: &str,
+    ) -> FrontendResult<()> {
+        conn.handle_field_list(table, wildcard).await
+    }
+
+    async fn prepare(&self, conn: &mut C2PConn, sql: &str) -> FrontendResult<()> {
+        conn.handle_stmt_prepare(sql).await
+    }
+
+    async fn execute(&self, conn: &mut C2PConn, data: &[u8]) -> FrontendResult<()> {
+        conn.handle_stmt_execute(data).await
+    }
+
+    async fn stmt(&self, conn: &mut C2PConn, command: u8, data: &[u8]) -> FrontendResult<()> {
+        conn.dispatch_stmt_cmd(command, data).await
+    }
+
+    async fn command(&self, conn: &mut C2PConn, cmd: u8, _: &[u8]) -> FrontendResult<()> {
+        match cmd {
+            command::COM_PING => conn.session.write_ok(None).await,
+            //a string packet, not an OK.
+            command::COM_STATISTICS => {
+                let mut data = format!(
+                    "Uptime: {}  Config version: {}",
+                    self.started.elapsed().as_secs(),
+                    conn.snapshot.version
+                )
+                .into_bytes();
+                conn.session
+                    .pkg
+                    .write_packet(&mut data)
+                    .await
+                    .map_err(FrontendError::MySQLErr)
+            }
+            command::COM_RESET_CONNECTION => conn.handle_reset_connection().await,
+            _ => Err(FrontendError::MySQLErr(MySQLError::ErUnknownCmd)),
+        }
+    }
+
+    //a transaction the client left open is rolled back.
+    async fn close(&self, conn: &mut C2PConn) {
+        if let Some(e) = conn.finish_txn(false).await {
+            log::info!("rollback on quit failed: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShardingExecutor;
+    use crate::config::center::FileCenter;
+    use crate::mysql::constants::command;
+    use crate::mysql::server::tests::{command as run, connect, serve};
+    use crate::proxy::reload::tests::config;
+    use crate::proxy::reload::Reloader;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn answer_session_commands() {
+        let cfg = config("127.0.0.1:1");
+        let center = Arc::new(FileCenter::new("sharding.toml".to_string()));
+        let reloader = Reloader::new(center, &cfg).await.unwrap();
+        let address = serve(ShardingExecutor::new(reloader.clone())).await;
+
+        let (mut pkg, ok, _) = connect(&address, "root", "root").await;
+        assert_eq!(ok[0], 0x00);
+        let mut init_db = vec![command::COM_INIT_DB];
+        init_db.extend_from_slice(b"db2");
+        let unknown = run(&mut pkg, &init_db).await;
+        assert_eq!(u16::from_le_bytes([unknown[1], unknown[2]]), 1049);
+        let mut use_db = vec![command::COM_QUERY];
+        use_db.extend_from_slice(b"use `db1`");
+        assert_eq!(run(&mut pkg, &use_db).await[0], 0x00);
+        assert_eq!(run(&mut pkg, &[command::COM_PING]).await[0], 0x00);
+        let stats = run(&mut pkg, &[command::COM_STATISTICS]).await;
+        assert!(stats.starts_with(b"Uptime: "));
+        assert_eq!(
+            run(&mut pkg, &[command::COM_RESET_CONNECTION]).await[0],
+            0x00
+        );
+        reloader.snapshot().pool.quit().await;
+    }
+}
@@ -1,3 +1,7 @@
+mod catalog;
 pub mod conn;
-mod dispatcher;
+pub mod dispatcher;
 pub mod errors;
+pub mod executor;
+mod stmt;
+mod txn;
@@ -0,0 +1,346 @@
+#![allow(dead_code)]
+//prepared statements of the client: the proxy keeps them itself and prepares each one lazily on
+//the backend conn it is routed to at execute time. at prepare time it is only described by the
+//template shard, the first physical table, to learn its columns.
+use super::conn::C2PConn;
+use super::dispatcher::{analyzer_err_packet, backend_err_packet};
+use super::errors::{FrontendError, FrontendResult};
+use crate::analyzer::{plan, sql};
+use crate::backend::error::BackendError;
+use crate::backend::executor;
+use crate::mysql::constants::ColumnType;
+use crate::mysql::packet::{self, ColumnDefinition, EofPacket, StmtExecute, StmtPrepareOk};
+use crate::mysql::{errcode, utils};
+use crate::router::Router;
+use byteorder::{ByteOrder, LittleEndian as LE};
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct Stmt {
+    sql: String,
+    param_count: u16,
+    //the types the client bound last time, it sends them again only when they change.
+    param_types: Vec<u8>,
+    //(param id, data) by COM_STMT_SEND_LONG_DATA since the last execute or reset.
+    long_data: Vec<(u16, Vec<u8>)>,
+}
+
+impl C2PConn {
+    //COM_STMT_PREPARE: the statement is registered here, the backend only tells its columns.
+    pub async fn handle_stmt_prepare(&mut self, query: &str) -> FrontendResult<()> {
+        let param_count = sql::placeholder_offsets(query).len();
+        if param_count > u16::MAX as usize {
+            let err = packet::ErrPacket::new(
+                errcode::ER_WRONG_ARGUMENTS,
+                "too many placeholders".to_string(),
+            );
+            return self.session.write_err(err).await;
+        }
+        let columns = match self.describe_stmt(query).await {
+            Ok(columns) => columns,
+            Err(err) => return self.session.write_err(err).await,
+        };
+        self.stmt_id = self.stmt_id.wrapping_add(1);
+        let stmt_id = self.stmt_id;
+        self.stmts.insert(
+            stmt_id,
+            Stmt {
+                sql: query.to_string(),
+                param_count: param_count as u16,
+                param_types: Vec::new(),
+                long_data: Vec::new(),
+            },
+        );
+        let ok = StmtPrepareOk {
+            stmt_id,
+            column_count: columns.len() as u16,
+            param_count: param_count as u16,
+            warnings: 0,
+        };
+        self.session.pkg.write_packet(&mut ok.to_bits()).await?;
+        if param_count > 0 {
+            let param = ColumnDefinition::new("?", ColumnType::MYSQL_TYPE_VAR_STRING);
+            for _ in 0..param_count {
+                self.session.pkg.write_packet(&mut param.to_bits()).await?;
+            }
+            let mut eof = EofPacket::new(0, self.session.status).to_bits();
+            self.session.pkg.write_packet(&mut eof).await?;
+        }
+        if !columns.is_empty() {
+            for mut column in columns {
+                self.session.pkg.write_packet(&mut column).await?;
+            }
+            let mut eof = EofPacket::new(0, self.session.status).to_bits();
+            self.session.pkg.write_packet(&mut eof).await?;
+        }
+        Ok(())
+    }
+
+    //the column definitions of `query` prepared on its template shard, by the logical tables.
+    async fn describe_stmt(&mut self, query: &str) -> Result<Vec<Vec<u8>>, packet::ErrPacket> {
+        let router = &self.snapshot.router;
+        let plan = plan::build_template_plan(router, &self.proxy_user, &self.session.db, query)
+            .map_err(|e| analyzer_err_packet(&e))?;
+        let names = logical_names(router, &self.proxy_user, &plan.db);
+        let (cluster_id, backend_sql) = &plan.shards[0];
+        let mut conn = self
+            .take_backend(cluster_id, plan.force_master, &plan.db)
+            .await
+            .map_err(backend_err_packet)?;
+        let rc = conn.describe(backend_sql).await;
+        let healthy = matches!(rc, Ok(_) | Err(BackendError::ServerErr(_)));
+        self.give_backend(conn, healthy).await;
+        let (_, columns) = rc.map_err(backend_err_packet)?;
+        Ok(columns
+            .into_iter()
+            .map(|c| logical_column(c, &names))
+            .collect())
+    }
+
+    //COM_STMT_EXECUTE: route by the bound values, then run on the backend in binary protocol.
+    pub async fn handle_stmt_execute(&mut self, data: &[u8]) -> FrontendResult<()> {
+        let stmt_id = StmtExecute::parse_stmt_id(data)?;
+        let stmt = match self.stmts.get_mut(&stmt_id) {
+            Some(stmt) => stmt,
+            None => {
+                return self
+                    .session
+                    .write_err(unknown_stmt(stmt_id, "mysqld_stmt_execute"))
+                    .await
+            }
+        };
+        let mut exec = StmtExecute::parse(data, stmt.param_count as usize)?;
+        if exec.param_types.is_empty() {
+            exec.param_types = stmt.param_types.clone();
+        } else {
+            stmt.param_types = exec.param_types.clone();
+        }
+        let long_data = std::mem::take(&mut stmt.long_data);
+        let template = stmt.sql.clone();
+        if exec.param_types.len() != 2 * stmt.param_count as usize {
+            let err = packet::ErrPacket::new(
+                errcode::ER_WRONG_ARGUMENTS,
+                "parameter types are not bound".to_string(),
+            );
+            return self.session.write_err(err).await;
+        }
+        let long_ids: Vec<usize> = long_data.iter().map(|(id, _)| *id as usize).collect();
+        let literals: Option<Vec<String>> = exec
+            .params(&long_ids)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| match p {
+                Some(v) => v.to_sql_literal(),
+                None => Some(
+                    long_data
+                        .iter()
+                        .find(|(id, _)| *id as usize == i)
+                        .map(|(_, d)| utils::quote_sql_string(d))
+                        .unwrap_or_else(|| "NULL".to_string()),
+                ),
+            })
+            .collect();
+        let literals = match literals {
+            Some(literals) => literals,
+            None => {
+                let err = packet::ErrPacket::new(
+                    errcode::ER_WRONG_ARGUMENTS,
+                    "Incorrect arguments to mysqld_stmt_execute".to_string(),
+                );
+                return self.session.write_err(err).await;
+            }
+        };
+        let bound = sql::bind_placeholders(&template, &literals);
+        log::info!(
+            "handle_stmt_execute user: {}, db: {}, sql: {}",
+            &self.proxy_user,
+            &self.session.db,
+            &bound
+        );
+        let plan = match plan::build_prepared_plan(
+            &self.snapshot.router,
+            &self.proxy_user,
+            &self.session.db,
+            &template,
+            &bound,
+        ) {
+            Ok(plan) => plan,
+            Err(e) => return self.session.write_err(analyzer_err_packet(&e)).await,
+        };
+        if plan.shards.len() != 1 {
+            return self.handle_scatter(&plan, true).await;
+        }
+        let (cluster_id, backend_sql) = &plan.shards[0];
+        let mut conn = match self
+            .take_backend(cluster_id, plan.force_master, &plan.db)
+            .await
+        {
+            Ok(conn) => conn,
+            Err(e) => return self.session.write_err(backend_err_packet(e)).await,
+        };
+        match executor::relay_execute(
+            &mut conn,
+            backend_sql,
+            &exec,
+            &long_data,
+            &mut self.session.pkg,
+        )
+        .await
+        {
+            Ok(_) => {
+                self.give_backend(conn, true).await;
+                Ok(())
+            }
+            //the backend refused to prepare it, nothing has been relayed yet.
+            Err(BackendError::ServerErr(p)) => {
+                self.give_backend(conn, true).await;
+                self.session.write_err(p).await
+            }
+            Err(e) => {
+                self.give_backend(conn, false).await;
+                Err(FrontendError::Backend(e))
+            }
+        }
+    }
+
+    //COM_STMT_SEND_LONG_DATA: kept until the next execute, there is no response.
+    pub fn handle_stmt_send_long_data(&mut self, data: &[u8]) {
+        if data.len() < 6 {
+            return;
+        }
+        let stmt_id = LE::read_u32(&data[..4]);
+        let param_id = LE::read_u16(&data[4..6]);
+        if let Some(stmt) = self.stmts.get_mut(&stmt_id) {
+            match stmt.long_data.iter_mut().find(|(id, _)| *id == param_id) {
+                Some((_, d)) => d.extend_from_slice(&data[6..]),
+                None => stmt.long_data.push((param_id, data[6..].to_vec())),
+            }
+        }
+    }
+
+    //COM_STMT_CLOSE: there is no response, the backend statements stay cached on their conns.
+    pub fn handle_stmt_close(&mut self, data: &[u8]) {
+        if data.len() >= 4 {
+            self.stmts.remove(&LE::read_u32(&data[..4]));
+        }
+    }
+
+    //COM_STMT_RESET: drop the long data sent so far.
+    pub async fn handle_stmt_reset(&mut self, data: &[u8]) -> FrontendResult<()> {
+        let stmt_id = StmtExecute::parse_stmt_id(data)?;
+        match self.stmts.get_mut(&stmt_id) {
+            Some(stmt) => {
+                stmt.long_data.clear();
+                self.session.write_ok(None).await
+            }
+            None => {
+                self.session
+                    .write_err(unknown_stmt(stmt_id, "mysqld_stmt_reset"))
+                    .await
+            }
+        }
+    }
+}
+
+fn unknown_stmt(stmt_id: u32, command: &str) -> packet::ErrPacket {
+    packet::ErrPacket::new(
+        errcode::ER_UNKNOWN_STMT_HANDLER,
+        format!(
+            "Unknown prepared statement handler ({}) given to {}",
+            stmt_id, command
+        ),
+    )
+}
+
+//key: a physical table of a sharded table of the db, value: the logical table.
+pub(super) fn logical_names(router: &Router, user: &str, db: &str) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    if let Ok(db_entry) = router.lookup_db(user, db) {
+        for table in db_entry.list_tables() {
+            for (_, physical) in table.load_all_path().unwrap_or_default() {
+                names.insert(physical, table.get_table().to_string());
+            }
+        }
+    }
+    names
+}
+
+//a column definition of a backend with its physical table put back to the logical one, the
+//table keeps an alias the client gave it.
+pub(super) fn logical_column(data: Vec<u8>, names: &HashMap<String, String>) -> Vec<u8> {
+    let mut column = match ColumnDefinition::parse(&data) {
+        Ok(column) => column,
+        Err(_) => return data,
+    };
+    let logical = match names.get(&column.org_table) {
+        Some(logical) => logical,
+        None => return data,
+    };
+    if column.table == column.org_table {
+        column.table = logical.clone();
+    }
+    column.org_table = logical.clone();
+    column.to_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{logical_column, logical_names};
+    use crate::config::Config;
+    use crate::mysql::constants::ColumnType;
+    use crate::mysql::packet::ColumnDefinition;
+    use crate::router::build_router_with;
+
+    const CONFIG: &str = r#"
+[proxy]
+listen_addr = "127.0.0.1:9696"
+users = [{ user = "root", pwd = "root" }]
+
+[[node]]
+id = "mysql_1"
+listen_addr = "127.0.0.1:1"
+user = "root"
+pwd = "root"
+
+[[cluster]]
+id = "cluster_1"
+master_node_id = "mysql_1"
+
+[[schema]]
+owner = "root"
+[[schema.db]]
+db = "db1"
+cluster_ids = ["cluster_1"]
+[[schema.db.table]]
+table = "integer_table"
+shard_key = "id"
+shard_type = "integer"
+each_cluster_table_split_count = [2]
+"#;
+
+    #[test]
+    fn name_columns_by_logical_table() {
+        let cfg: Config = toml::from_str(CONFIG).unwrap();
+        let router = build_router_with(&cfg).unwrap();
+        let names = logical_names(&router, "root", "db1");
+        let column = |table: &str, org_table: &str| {
+            let mut column = ColumnDefinition::new("name", ColumnType::MYSQL_TYPE_VAR_STRING);
+            column.table = table.to_string();
+            column.org_table = org_table.to_string();
+            column.to_bits()
+        };
+        let renamed = logical_column(column("integer_table_1", "integer_table_1"), &names);
+        let renamed = ColumnDefinition::parse(&renamed).unwrap();
+        assert_eq!(
+            (renamed.table.as_str(), renamed.org_table.as_str()),
+            ("integer_table", "integer_table")
+        );
+        //an alias stays, a table which is not sharded keeps its name.
+        let renamed = logical_column(column("t", "integer_table_0"), &names);
+        assert_eq!(renamed, column("t", "integer_table"));
+        assert_eq!(
+            logical_column(column("plain", "plain"), &names),
+            column("plain", "plain")
+        );
+    }
+}
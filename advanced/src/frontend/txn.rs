@@ -0,0 +1,411 @@
+#![allow(dead_code)]
+//the transaction of a client session: each backend conn it touches is pinned to the session
+//until COMMIT or ROLLBACK, which then go to all of them together.
+use super::conn::C2PConn;
+use super::errors::FrontendResult;
+use crate::analyzer::sql::TxnStatement;
+use crate::backend::conn::P2MConn;
+use crate::backend::error::{BackendError, BackendResult};
+use crate::config::DistributedTxn;
+use crate::mysql::constants::StatusFlags;
+use crate::mysql::{errcode, packet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Default)]
+pub struct Txn {
+    begin: Option<String>, //the BEGIN of an open transaction, each conn runs it as it joins.
+    conns: Vec<P2MConn>,   //the pinned conns, one per cluster.
+    savepoints: Vec<String>,
+    xid: Option<String>,  //the global part of the xid of an XA transaction.
+    lost: Option<String>, //a cluster whose pinned conn broke, the transaction can only roll back.
+}
+
+//how a conn came out of the statements run on it by run_all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Health {
+    Sound,
+    Failed, //answered an ERR packet, the conn still takes statements.
+    Broken, //failed other than by an ERR packet, no more statements go to it.
+}
+
+impl C2PConn {
+    //statements join a transaction after BEGIN, or all the time with autocommit off.
+    pub(super) fn in_txn(&self) -> bool {
+        self.txn.begin.is_some()
+            || !self
+                .session
+                .status
+                .contains(StatusFlags::SERVER_STATUS_AUTOCOMMIT)
+    }
+
+    //a conn of the cluster on `db`: the pinned one in a transaction, or one of the pool.
+    pub(super) async fn take_backend(
+        &mut self,
+        cluster_id: &str,
+        force_master: bool,
+        db: &str,
+    ) -> BackendResult<P2MConn> {
+        let mut conn = if !self.in_txn() {
+            self.snapshot
+                .pool
+                .get_conn(cluster_id, force_master)
+                .await?
+        } else {
+            //after a pinned conn is lost only the rollback is left.
+            if let Some(c) = &self.txn.lost {
+                return Err(BackendError::ServerErr(packet::ErrPacket::new(
+                    errcode::ER_UNKNOWN_ERROR,
+                    format!(
+                        "the conn to {} was lost, the transaction can only be rolled back",
+                        c
+                    ),
+                )));
+            }
+            match self
+                .txn
+                .conns
+                .iter()
+                .position(|c| c.cluster_id() == cluster_id)
+            {
+                Some(pos) => self.txn.conns.remove(pos),
+                None => self.join_txn(cluster_id).await?,
+            }
+        };
+        if let Err(e) = conn.use_db(db).await {
+            let healthy = matches!(e, BackendError::ServerErr(_));
+            self.give_backend(conn, healthy).await;
+            return Err(e);
+        }
+        Ok(conn)
+    }
+
+    //hand back a conn of take_backend, one which is not healthy any more is closed.
+    pub(super) async fn give_backend(&mut self, conn: P2MConn, healthy: bool) {
+        if !self.in_txn() {
+            if healthy {
+                self.snapshot.pool.recycle(conn).await;
+            } else {
+                self.snapshot.pool.discard(conn).await;
+            }
+        } else if healthy {
+            self.txn.conns.push(conn);
+        } else {
+            self.txn.lost.get_or_insert(conn.cluster_id().to_string());
+            self.snapshot.pool.discard(conn).await;
+        }
+    }
+
+    //pin a conn of one more cluster, which starts the transaction and its savepoints so far.
+    async fn join_txn(&mut self, cluster_id: &str) -> BackendResult<P2MConn> {
+        let pinned = self
+            .txn
+            .conns
+            .first()
+            .map(|c| c.cluster_id().to_string())
+            .or_else(|| self.txn.lost.clone());
+        if let (Some(first), DistributedTxn::Reject) = (pinned, self.distributed_txn) {
+            return Err(BackendError::ServerErr(packet::ErrPacket::new(
+                errcode::ER_NOT_SUPPORTED_YET,
+                format!(
+                    "a transaction can not span cluster {} and {}, unless distributed_txn = \"xa\"",
+                    first, cluster_id
+                ),
+            )));
+        }
+        let begin = match self.distributed_txn {
+            DistributedTxn::Xa => format!("XA START {}", self.xid(cluster_id)),
+            DistributedTxn::Reject => self.txn.begin.clone().unwrap_or("BEGIN".to_string()),
+        };
+        let mut conn = self.snapshot.pool.get_conn(cluster_id, true).await?;
+        let mut rc = conn.query_ok(&begin).await;
+        for sp in self.txn.savepoints.iter() {
+            if rc.is_ok() {
+                rc = conn.query_ok(&format!("SAVEPOINT {}", quote(sp))).await;
+            }
+        }
+        match rc {
+            Ok(_) => {
+                self.session.status |= StatusFlags::SERVER_STATUS_IN_TRANS;
+                Ok(conn)
+            }
+            Err(e) => {
+                self.snapshot.pool.discard(conn).await;
+                Err(e)
+            }
+        }
+    }
+
+    //`'gtrid','bqual'` of the branch on the cluster, the global part is made at the first branch.
+    fn xid(&mut self, cluster_id: &str) -> String {
+        let conn_id = self.session.conn_id;
+        let gtrid = self.txn.xid.get_or_insert_with(|| {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or_default();
+            format!("sparrow-{}-{}", conn_id, nanos)
+        });
+        format!("'{}','{}'", gtrid, cluster_id)
+    }
+
+    //BEGIN, COMMIT, ROLLBACK, SET autocommit and savepoints.
+    pub async fn handle_txn(&mut self, stmt: TxnStatement) -> FrontendResult<()> {
+        log::info!("handle_txn user: {}, {:?}", &self.proxy_user, &stmt);
+        let err = match stmt {
+            //a new transaction commits the one open.
+            TxnStatement::Begin(sql) => {
+                let err = match self.in_txn() {
+                    true => self.finish_txn(true).await,
+                    false => None,
+                };
+                if err.is_none() {
+                    self.txn.begin = Some(sql);
+                    self.session.status |= StatusFlags::SERVER_STATUS_IN_TRANS;
+                }
+                err
+            }
+            TxnStatement::Commit => self.finish_txn(true).await,
+            TxnStatement::Rollback => self.finish_txn(false).await,
+            TxnStatement::SetAutocommit(true) => {
+                let err = match self
+                    .session
+                    .status
+                    .contains(StatusFlags::SERVER_STATUS_AUTOCOMMIT)
+                {
+                    true => None,
+                    false => self.finish_txn(true).await,
+                };
+                self.session.status |= StatusFlags::SERVER_STATUS_AUTOCOMMIT;
+                err
+            }
+            TxnStatement::SetAutocommit(false) => {
+                self.session
+                    .status
+                    .remove(StatusFlags::SERVER_STATUS_AUTOCOMMIT);
+                None
+            }
+            //out of a transaction a savepoint is gone right away.
+            TxnStatement::Savepoint(_) if !self.in_txn() => None,
+            TxnStatement::Savepoint(name) => {
+                let err = self
+                    .run_on_pinned(&format!("SAVEPOINT {}", quote(&name)))
+                    .await;
+                self.txn.savepoints.retain(|sp| *sp != name);
+                self.txn.savepoints.push(name);
+                err
+            }
+            TxnStatement::RollbackToSavepoint(name) | TxnStatement::ReleaseSavepoint(name)
+                if !self.txn.savepoints.contains(&name) =>
+            {
+                Some(packet::ErrPacket::new(
+                    errcode::ER_SP_DOES_NOT_EXIST,
+                    format!("SAVEPOINT {} does not exist", name),
+                ))
+            }
+            TxnStatement::RollbackToSavepoint(name) => {
+                let err = self
+                    .run_on_pinned(&format!("ROLLBACK TO SAVEPOINT {}", quote(&name)))
+                    .await;
+                let pos = self.txn.savepoints.iter().position(|sp| *sp == name);
+                self.txn
+                    .savepoints
+                    .truncate(pos.map(|p| p + 1).unwrap_or(0));
+                err
+            }
+            TxnStatement::ReleaseSavepoint(name) => {
+                let err = self
+                    .run_on_pinned(&format!("RELEASE SAVEPOINT {}", quote(&name)))
+                    .await;
+                let pos = self.txn.savepoints.iter().position(|sp| *sp == name);
+                self.txn.savepoints.truncate(pos.unwrap_or(0));
+                err
+            }
+        };
+        match err {
+            Some(e) => self.session.write_err(e).await,
+            None => self.session.write_ok(None).await,
+        }
+    }
+
+    //the statement runs on every pinned conn, a broken one is lost to the transaction.
+    async fn run_on_pinned(&mut self, sql: &str) -> Option<packet::ErrPacket> {
+        let mut conns = std::mem::take(&mut self.txn.conns);
+        let mut health = vec![Health::Sound; conns.len()];
+        let err = run_all(&mut conns, &mut health, |_| sql.to_string()).await;
+        for (conn, health) in conns.into_iter().zip(health) {
+            self.give_backend(conn, health != Health::Broken).await;
+        }
+        err
+    }
+
+    //end the transaction on all the pinned conns, which go back to the pool. a conn any of the
+    //ending statements failed on may still be in the transaction or an XA branch, it is closed.
+    //an XA transaction of more than one branch commits in two phases.
+    pub(super) async fn finish_txn(&mut self, commit: bool) -> Option<packet::ErrPacket> {
+        let mut conns = std::mem::take(&mut self.txn.conns);
+        let lost = self.txn.lost.take();
+        let gtrid = self.txn.xid.take();
+        self.txn.begin = None;
+        self.txn.savepoints.clear();
+        self.session
+            .status
+            .remove(StatusFlags::SERVER_STATUS_IN_TRANS);
+
+        let mut health = vec![Health::Sound; conns.len()];
+        let mut err = lost.as_ref().map(|c| {
+            packet::ErrPacket::new(
+                errcode::ER_UNKNOWN_ERROR,
+                format!("the transaction is rolled back, the conn to {} was lost", c),
+            )
+        });
+        let commit = commit && lost.is_none();
+        match gtrid {
+            None => {
+                let sql = if commit { "COMMIT" } else { "ROLLBACK" };
+                err = err.or(run_all(&mut conns, &mut health, |_| sql.to_string()).await);
+            }
+            Some(gtrid) => {
+                let xid = |c: &str| format!("'{}','{}'", gtrid, c);
+                let ended =
+                    run_all(&mut conns, &mut health, |c| format!("XA END {}", xid(c))).await;
+                let one_phase = conns.len() == 1;
+                let prepared = match (commit, &ended, one_phase) {
+                    (true, None, false) => {
+                        run_all(&mut conns, &mut health, |c| {
+                            format!("XA PREPARE {}", xid(c))
+                        })
+                        .await
+                    }
+                    _ => ended,
+                };
+                if commit && prepared.is_none() {
+                    err = run_all(&mut conns, &mut health, |c| match one_phase {
+                        true => format!("XA COMMIT {} ONE PHASE", xid(c)),
+                        false => format!("XA COMMIT {}", xid(c)),
+                    })
+                    .await;
+                } else {
+                    run_all(&mut conns, &mut health, |c| {
+                        format!("XA ROLLBACK {}", xid(c))
+                    })
+                    .await;
+                    if commit {
+                        let cause = prepared
+                            .map(|p| String::from_utf8_lossy(p.err_msg()).to_string())
+                            .unwrap_or_default();
+                        err = Some(packet::ErrPacket::new(
+                            errcode::ER_XA_RBROLLBACK,
+                            format!("XA_RBROLLBACK: the transaction is rolled back: {}", cause),
+                        ));
+                    }
+                }
+            }
+        }
+        for (conn, health) in conns.into_iter().zip(health) {
+            if health == Health::Sound {
+                self.snapshot.pool.recycle(conn).await;
+            } else {
+                self.snapshot.pool.discard(conn).await;
+            }
+        }
+        err
+    }
+}
+
+//run one statement on each conn which is not broken, the first error is returned.
+async fn run_all(
+    conns: &mut [P2MConn],
+    health: &mut [Health],
+    sql_of: impl Fn(&str) -> String,
+) -> Option<packet::ErrPacket> {
+    let mut err = None;
+    for (conn, health) in conns.iter_mut().zip(health.iter_mut()) {
+        if *health == Health::Broken {
+            continue;
+        }
+        let sql = sql_of(conn.cluster_id());
+        match conn.query_ok(&sql).await {
+            Ok(_) => {}
+            Err(BackendError::ServerErr(p)) => {
+                log::info!("{} failed on cluster {}: {:?}", sql, conn.cluster_id(), p);
+                *health = Health::Failed;
+                err.get_or_insert(p);
+            }
+            Err(e) => {
+                log::info!("{} failed on cluster {}: {}", sql, conn.cluster_id(), e);
+                *health = Health::Broken;
+                err.get_or_insert(packet::ErrPacket::new(
+                    errcode::ER_UNKNOWN_ERROR,
+                    format!("{}", e),
+                ));
+            }
+        }
+    }
+    err
+}
+
+fn quote(name: &str) -> String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::center::FileCenter;
+    use crate::frontend::executor::ShardingExecutor;
+    use crate::mysql::server::tests::{connect, query, serve, Answer, Stub};
+    use crate::proxy::reload::tests::config;
+    use crate::proxy::reload::Reloader;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn discard_conns_left_in_txn() {
+        let quits = Arc::new(AtomicUsize::new(0));
+        let counter = quits.clone();
+        let backend = serve(Stub(Box::new(move |sql: &str| match sql {
+            "QUIT" => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Answer::Ok
+            }
+            "COMMIT" => Answer::Err(1180, "Got error 1 during COMMIT"),
+            "SELECT lost" => Answer::Close,
+            _ => Answer::Ok,
+        })))
+        .await;
+        let cfg = config(&backend);
+        let center = Arc::new(FileCenter::new("sharding.toml".to_string()));
+        let reloader = Reloader::new(center, &cfg).await.unwrap();
+        let address = serve(ShardingExecutor::new(reloader.clone())).await;
+        let (mut pkg, ok, _) = connect(&address, "root", "root").await;
+        assert_eq!(ok[0], 0x00);
+        let wait_quits = |n: usize| {
+            let quits = quits.clone();
+            async move {
+                for _ in 0..100 {
+                    if quits.load(Ordering::SeqCst) >= n {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                quits.load(Ordering::SeqCst)
+            }
+        };
+
+        //a conn the COMMIT failed on may still be in the transaction, it is closed.
+        assert_eq!(query(&mut pkg, "BEGIN").await.unwrap_err()[0], 0x00);
+        let insert = query(&mut pkg, "INSERT INTO plain VALUES (1)").await;
+        assert_eq!(insert.unwrap_err()[0], 0x00);
+        assert_eq!(query(&mut pkg, "COMMIT").await.unwrap_err()[0], 0xff);
+        assert_eq!(wait_quits(1).await, 1);
+
+        //after a pinned conn is lost nothing but the rollback runs.
+        assert_eq!(query(&mut pkg, "BEGIN").await.unwrap_err()[0], 0x00);
+        assert_eq!(query(&mut pkg, "SELECT lost").await.unwrap_err()[0], 0xff);
+        let refused = query(&mut pkg, "SELECT 1").await.unwrap_err();
+        assert!(String::from_utf8_lossy(&refused).contains("can only be rolled back"));
+        assert_eq!(query(&mut pkg, "ROLLBACK").await.unwrap_err()[0], 0xff);
+        assert_eq!(query(&mut pkg, "SELECT 1").await.unwrap_err()[0], 0x00);
+        reloader.snapshot().pool.quit().await;
+    }
+}
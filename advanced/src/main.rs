@@ -1,5 +1,4 @@
 use crate::cmd::cmds;
-use std::env;
 use std::error::Error;
 use std::path::PathBuf;
 
@@ -9,6 +8,7 @@ mod boot;
 mod cmd;
 mod config;
 mod executor;
+mod filter;
 mod frontend;
 mod monitor;
 mod mysql;
@@ -23,76 +23,92 @@ lazy_static::lazy_static! {
         static ref  GLOBAL_CONFIG: config::Config = {
             config::load_config().unwrap()
         };
-
-        static ref SHOTCUT_GLOBAL_CONFIG:config::ConfigShortcut = {
-            config::build_config_shortcut().unwrap()
-        };
 }
 
 include!(concat!(env!("OUT_DIR"), "/commit_id.rs"));
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    // setup_logger();
-    // log::info!(
-    //     "Start MySQL proxy, run commit_id: {} compile_time: {}",
-    //     COMMIT_ID,
-    //     COMPILE_TIME
-    // );
-    //proxy::ProxyServer::new().run().await?;
-
     let command = cmds::init();
 
-    println!(
+    eprintln!(
         "The sub command size is: {:?}",
         command.get_subcommands().size_hint()
     );
 
     let matches = command.get_matches();
-    let sub_command = match matches.subcommand() {
-        Some(("start", matches)) => {
-            let config_path = matches.value_of_os("c").map(std::path::PathBuf::from);
-
-            match config_path {
-                None => "error",
-                Some(path) => "ok",
-            };
-
-            matches
-        }
-        Some(("import", matches)) => {
-            let config_path = matches.value_of_os("c").map(std::path::PathBuf::from);
-
-            match config_path {
-                None => "error",
-                Some(path) => "ok",
-            };
-
-            matches
-        }
+    if let Some(("validate", matches)) = matches.subcommand() {
+        return cmd::validate::run(matches.value_of("c").unwrap());
+    }
+    if let Some(("reshard", matches)) = matches.subcommand() {
+        let batch = matches
+            .value_of("batch")
+            .and_then(|b| b.parse::<u64>().ok())
+            .unwrap_or(cmd::reshard::DEFAULT_BATCH);
+        return cmd::reshard::run(
+            matches.value_of("old").unwrap(),
+            matches.value_of("new").unwrap(),
+            matches.is_present("dry-run"),
+            batch,
+        )
+        .await;
+    }
+    if let Some(("import", matches)) = matches.subcommand() {
+        return cmd::import::run(
+            matches.value_of("c").unwrap(),
+            matches.value_of("bootstrap").unwrap(),
+        );
+    }
+    if let Some(("export", matches)) = matches.subcommand() {
+        return cmd::export::run(
+            matches.value_of("c"),
+            matches.value_of("bootstrap").unwrap(),
+            matches.value_of("format").unwrap(),
+            matches.value_of("o"),
+        );
+    }
+    let config_path = match matches.subcommand() {
+        Some(("start", matches)) => matches.value_of_os("c").map(std::path::PathBuf::from),
         _ => unreachable!("clap should ensure we don't get here"),
     };
-    // let config_path = sub_command
-    //     .value_of_os("c")
-    //     .map(std::path::PathBuf::from);
 
-    let config_path = PathBuf::from("src/conf/bootstrap.yaml");
+    //the toml config, or a yaml ConfigMap, drives the sharding proxy, see etc/config.toml.
+    if let Some(path) = config_path.as_ref().and_then(|p| p.to_str()) {
+        if config::Format::of(path) == config::Format::Toml || config::is_config_map(path) {
+            //every problem is told before the global config is touched.
+            if let Err(e) = config::load_checked(path) {
+                for line in e.lines() {
+                    eprintln!("{}", line);
+                }
+                return Err(Box::new(e));
+            }
+            setup_logger();
+            log::info!(
+                "Start MySQL proxy, run commit_id: {} compile_time: {}",
+                COMMIT_ID,
+                COMPILE_TIME
+            );
+            let center = std::sync::Arc::new(config::center::FileCenter::new(path.to_string()));
+            return proxy::ProxyServer::new(center).run().await;
+        }
+    }
+    let config_path = config_path.unwrap_or_else(|| PathBuf::from(cmd::import::DEFAULT_BOOTSTRAP));
 
     println!("The config path is: {:?}", config_path);
 
     println!("The Server start execute start...");
-    cmd::start::run(config_path.as_os_str().to_str().unwrap().to_string());
+    cmd::start::run(config_path.as_os_str().to_str().unwrap().to_string()).await?;
     println!("The Server start execute end...");
 
     Ok(())
 }
 
-// fn setup_logger() {
-//     let logger = femme::pretty::Logger::new();
-//     async_log::Logger::wrap(logger, || /* get the task id here */ 0)
-//         .start(
-//             GLOBAL_CONFIG
-//                 .query_log_level()
-//                 .unwrap_or(log::LevelFilter::Trace),
-//         )
-//         .unwrap();
-// }
+fn setup_logger() {
+    let logger = femme::pretty::Logger::new();
+    async_log::Logger::wrap(logger, || /* get the task id here */ 0)
+        .start(
+            GLOBAL_CONFIG
+                .query_log_level()
+                .unwrap_or(log::LevelFilter::Trace),
+        )
+        .unwrap();
+}
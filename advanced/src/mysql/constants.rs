@@ -24,6 +24,20 @@ pub static ERR_PACKET_HEADER_MARK: u8 = 0xFF;
 //http://hutaow.com/blog/2013/11/06/mysql-protocol-analysis/#41
 //https://dev.mysql.com/doc/internals/en/packet-EOF_Packet.html
 pub static EOF_PACKET_HEADER_MARK: u8 = 0xFE;
+//the response to a LOAD DATA LOCAL INFILE, the server asks the client for a file.
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_query_response_local_infile_request.html
+pub static LOCAL_INFILE_HEADER_MARK: u8 = 0xFB;
+
+//connection phase, proxy as a client of the backend mysql.
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_connection_phase.html
+pub static AUTH_SWITCH_REQUEST_MARK: u8 = 0xFE;
+pub static AUTH_MORE_DATA_MARK: u8 = 0x01;
+pub static AUTH_NATIVE_PASSWORD: &str = "mysql_native_password";
+pub static AUTH_CACHING_SHA2_PASSWORD: &str = "caching_sha2_password";
+//caching_sha2_password: second byte of the more data packet.
+pub static CACHING_SHA2_FAST_AUTH_SUCCESS: u8 = 0x03;
+pub static CACHING_SHA2_PERFORM_FULL_AUTH: u8 = 0x04;
+pub static CACHING_SHA2_REQUEST_PUBLIC_KEY: u8 = 0x02;
 
 bitflags! {
     /// MySql server status flags
@@ -310,6 +324,137 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// MySql column flags
+    pub struct ColumnFlags: u16 {
+        /// Field can't be NULL.
+        const NOT_NULL_FLAG         = 1u16;
+
+        /// Field is part of a primary key.
+        const PRI_KEY_FLAG          = 2u16;
+
+        /// Field is part of a unique key.
+        const UNIQUE_KEY_FLAG       = 4u16;
+
+        /// Field is part of a key.
+        const MULTIPLE_KEY_FLAG     = 8u16;
+
+        /// Field is a blob.
+        const BLOB_FLAG             = 16u16;
+
+        /// Field is unsigned.
+        const UNSIGNED_FLAG         = 32u16;
+
+        /// Field is zerofill.
+        const ZEROFILL_FLAG         = 64u16;
+
+        /// Field is binary.
+        const BINARY_FLAG           = 128u16;
+
+        /// Field is an enum.
+        const ENUM_FLAG             = 256u16;
+
+        /// Field is a autoincrement field.
+        const AUTO_INCREMENT_FLAG   = 512u16;
+
+        /// Field is a timestamp.
+        const TIMESTAMP_FLAG        = 1024u16;
+
+        /// Field is a set.
+        const SET_FLAG              = 2048u16;
+
+        /// Field doesn't have default value.
+        const NO_DEFAULT_VALUE_FLAG = 4096u16;
+
+        /// Field is set to NOW on UPDATE.
+        const ON_UPDATE_NOW_FLAG    = 8192u16;
+
+        /// Field is num (for clients).
+        const NUM_FLAG              = 32768u16;
+    }
+}
+
+/// Type of MySql column field
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+#[repr(u8)]
+pub enum ColumnType {
+    MYSQL_TYPE_DECIMAL = 0,
+    MYSQL_TYPE_TINY,
+    MYSQL_TYPE_SHORT,
+    MYSQL_TYPE_LONG,
+    MYSQL_TYPE_FLOAT,
+    MYSQL_TYPE_DOUBLE,
+    MYSQL_TYPE_NULL,
+    MYSQL_TYPE_TIMESTAMP,
+    MYSQL_TYPE_LONGLONG,
+    MYSQL_TYPE_INT24,
+    MYSQL_TYPE_DATE,
+    MYSQL_TYPE_TIME,
+    MYSQL_TYPE_DATETIME,
+    MYSQL_TYPE_YEAR,
+    MYSQL_TYPE_NEWDATE, // Internal to MySql
+    MYSQL_TYPE_VARCHAR,
+    MYSQL_TYPE_BIT,
+    MYSQL_TYPE_TIMESTAMP2,
+    MYSQL_TYPE_DATETIME2,
+    MYSQL_TYPE_TIME2,
+    MYSQL_TYPE_TYPED_ARRAY, // Used for replication only
+    MYSQL_TYPE_JSON = 245,
+    MYSQL_TYPE_NEWDECIMAL = 246,
+    MYSQL_TYPE_ENUM = 247,
+    MYSQL_TYPE_SET = 248,
+    MYSQL_TYPE_TINY_BLOB = 249,
+    MYSQL_TYPE_MEDIUM_BLOB = 250,
+    MYSQL_TYPE_LONG_BLOB = 251,
+    MYSQL_TYPE_BLOB = 252,
+    MYSQL_TYPE_VAR_STRING = 253,
+    MYSQL_TYPE_STRING = 254,
+    MYSQL_TYPE_GEOMETRY = 255,
+}
+
+impl ColumnType {
+    pub fn from_u8(x: u8) -> Option<ColumnType> {
+        use ColumnType::*;
+        let t = match x {
+            0x00 => MYSQL_TYPE_DECIMAL,
+            0x01 => MYSQL_TYPE_TINY,
+            0x02 => MYSQL_TYPE_SHORT,
+            0x03 => MYSQL_TYPE_LONG,
+            0x04 => MYSQL_TYPE_FLOAT,
+            0x05 => MYSQL_TYPE_DOUBLE,
+            0x06 => MYSQL_TYPE_NULL,
+            0x07 => MYSQL_TYPE_TIMESTAMP,
+            0x08 => MYSQL_TYPE_LONGLONG,
+            0x09 => MYSQL_TYPE_INT24,
+            0x0a => MYSQL_TYPE_DATE,
+            0x0b => MYSQL_TYPE_TIME,
+            0x0c => MYSQL_TYPE_DATETIME,
+            0x0d => MYSQL_TYPE_YEAR,
+            0x0e => MYSQL_TYPE_NEWDATE,
+            0x0f => MYSQL_TYPE_VARCHAR,
+            0x10 => MYSQL_TYPE_BIT,
+            0x11 => MYSQL_TYPE_TIMESTAMP2,
+            0x12 => MYSQL_TYPE_DATETIME2,
+            0x13 => MYSQL_TYPE_TIME2,
+            0x14 => MYSQL_TYPE_TYPED_ARRAY,
+            0xf5 => MYSQL_TYPE_JSON,
+            0xf6 => MYSQL_TYPE_NEWDECIMAL,
+            0xf7 => MYSQL_TYPE_ENUM,
+            0xf8 => MYSQL_TYPE_SET,
+            0xf9 => MYSQL_TYPE_TINY_BLOB,
+            0xfa => MYSQL_TYPE_MEDIUM_BLOB,
+            0xfb => MYSQL_TYPE_LONG_BLOB,
+            0xfc => MYSQL_TYPE_BLOB,
+            0xfd => MYSQL_TYPE_VAR_STRING,
+            0xfe => MYSQL_TYPE_STRING,
+            0xff => MYSQL_TYPE_GEOMETRY,
+            _ => return None,
+        };
+        Some(t)
+    }
+}
+
 /// MySql server commands
 pub mod command {
     pub const COM_SLEEP: u8 = 0;
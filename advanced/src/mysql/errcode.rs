@@ -1,2 +1,15 @@
 //Reference: https://github.com/siddontang/mixer/blob/master/mysql/errcode.go
 pub const ER_HANDSHAKE_ERROR: u16 = 1043;
+pub const ER_DBACCESS_DENIED_ERROR: u16 = 1044;
+pub const ER_ACCESS_DENIED_ERROR: u16 = 1045;
+pub const ER_NO_DB_ERROR: u16 = 1046;
+pub const ER_UNKNOWN_COM_ERROR: u16 = 1047;
+pub const ER_BAD_DB_ERROR: u16 = 1049;
+pub const ER_BAD_FIELD_ERROR: u16 = 1054;
+pub const ER_PARSE_ERROR: u16 = 1064;
+pub const ER_UNKNOWN_ERROR: u16 = 1105;
+pub const ER_WRONG_ARGUMENTS: u16 = 1210;
+pub const ER_NOT_SUPPORTED_YET: u16 = 1235;
+pub const ER_UNKNOWN_STMT_HANDLER: u16 = 1243;
+pub const ER_SP_DOES_NOT_EXIST: u16 = 1305;
+pub const ER_XA_RBROLLBACK: u16 = 1402;
@@ -11,6 +11,10 @@ pub enum MySQLError {
     ErrPacketWrongSize,
     ErrPacketILL,
     ErUnknownCmd,
+    EofPacketILL,
+    ColumnDefinitionILL,
+    RowPacketILL,
+    StmtPacketILL,
     IO(std::io::Error),
 }
 
@@ -30,6 +34,10 @@ impl std::error::Error for MySQLError {
             MySQLError::OkPacketWrongSize => None,
             MySQLError::ErrPacketWrongSize => None,
             MySQLError::ErrPacketILL => None,
+            MySQLError::EofPacketILL => None,
+            MySQLError::ColumnDefinitionILL => None,
+            MySQLError::RowPacketILL => None,
+            MySQLError::StmtPacketILL => None,
             MySQLError::IO(e) => e.source(),
         }
     }
@@ -45,6 +53,10 @@ impl std::fmt::Display for MySQLError {
             MySQLError::ErUnknownCmd => write!(f, "MysqlError::ErUnknownCmd!"),
             MySQLError::ErrPacketWrongSize => write!(f, "MysqlError::ErrPacketWrongSize!"),
             MySQLError::ErrPacketILL => write!(f, "MysqlError::ErrPacketILL!"),
+            MySQLError::EofPacketILL => write!(f, "MysqlError::EofPacketILL!"),
+            MySQLError::ColumnDefinitionILL => write!(f, "MysqlError::ColumnDefinitionILL!"),
+            MySQLError::RowPacketILL => write!(f, "MysqlError::RowPacketILL!"),
+            MySQLError::StmtPacketILL => write!(f, "MysqlError::StmtPacketILL!"),
             MySQLError::IO(e) => e.fmt(f),
         }
     }
@@ -1,7 +1,8 @@
 #![allow(dead_code)]
+use super::constants::{CapabilityFlags, ColumnFlags, ColumnType, StatusFlags};
 use super::errors::{MySQLError, MySQLResult};
 use super::utils;
-use super::{constants, errcode, sql_state};
+use super::{constants, sql_state};
 use byteorder::{ByteOrder, LittleEndian as LE};
 
 #[derive(Debug, Clone)]
@@ -38,13 +39,22 @@ impl OkPacket {
     //http://hutaow.com/blog/2013/11/06/mysql-protocol-analysis/#41
     //https://dev.mysql.com/doc/internals/en/packet-OK_Packet.html
     //https://github.com/blackbeam/rust_mysql_common/blob/master/src/packets.rs
-    #[allow(unused_assignments)]
     pub fn parse(data: &[u8]) -> MySQLResult<OkPacket> {
+        OkPacket::parse_with_header(data, constants::OK_PACKET_HEADER_MARK)
+    }
+    //with CLIENT_DEPRECATE_EOF, the EOF packet ending the rows of a result set is replaced
+    //by an OK packet with 0xFE as header mark.
+    //https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_basic_ok_packet.html
+    pub fn parse_eof(data: &[u8]) -> MySQLResult<OkPacket> {
+        OkPacket::parse_with_header(data, constants::EOF_PACKET_HEADER_MARK)
+    }
+    #[allow(unused_assignments)]
+    fn parse_with_header(data: &[u8], header: u8) -> MySQLResult<OkPacket> {
         if data.len() < 7 {
             return Err(MySQLError::OkPacketWrongSize);
         }
         let mut pos: usize = 0;
-        if data[pos] == constants::OK_PACKET_HEADER_MARK {
+        if data[pos] == header {
             pos += 1;
             let affected_rows = utils::read_length_encoded_int(&data[pos..]);
             pos += affected_rows.0;
@@ -65,9 +75,31 @@ impl OkPacket {
         }
         Err(MySQLError::OkPacketILL)
     }
+    #[inline]
+    pub fn status(&self) -> constants::StatusFlags {
+        self.status
+    }
+    #[inline]
+    pub fn affected_rows(&self) -> u64 {
+        self.affected_rows
+    }
+    #[inline]
+    pub fn last_insert_id(&self) -> u64 {
+        self.last_insert_id
+    }
+    #[inline]
+    pub fn warnings(&self) -> u16 {
+        self.warnings
+    }
     pub fn to_bits(&self) -> Vec<u8> {
+        self.to_bits_with_header(constants::OK_PACKET_HEADER_MARK)
+    }
+    pub fn to_eof_bits(&self) -> Vec<u8> {
+        self.to_bits_with_header(constants::EOF_PACKET_HEADER_MARK)
+    }
+    fn to_bits_with_header(&self, header: u8) -> Vec<u8> {
         let mut data: Vec<u8> = Vec::new();
-        data.push(constants::OK_PACKET_HEADER_MARK);
+        data.push(header);
         data.extend_from_slice(&utils::write_length_encoded_int(self.affected_rows));
         data.extend_from_slice(&utils::write_length_encoded_int(self.last_insert_id));
         let status_bits = self.status.bits();
@@ -91,14 +123,22 @@ pub struct ErrPacket {
 impl ErrPacket {
     pub fn new(err_code: u16, err_msg: String) -> ErrPacket {
         let sql_state: &str = sql_state::MY_SQLSTATE
-            .get(&errcode::ER_HANDSHAKE_ERROR)
-            .unwrap_or_else(|| &sql_state::DEFAULT_MYSQL_STATE);
+            .get(&err_code)
+            .unwrap_or(&sql_state::DEFAULT_MYSQL_STATE);
         ErrPacket {
             err_code,
             sql_state: sql_state.as_bytes().to_vec(),
             err_msg: err_msg.as_bytes().to_vec(),
         }
     }
+    #[inline]
+    pub fn err_code(&self) -> u16 {
+        self.err_code
+    }
+    #[inline]
+    pub fn err_msg(&self) -> &[u8] {
+        &self.err_msg
+    }
     #[allow(unused_assignments)]
     pub fn parse(data: &[u8]) -> MySQLResult<ErrPacket> {
         if data.len() < 3 {
@@ -132,3 +172,801 @@ impl ErrPacket {
         data
     }
 }
+//--------------------------
+//a EOF packet has 0xFE as header mark and is less than 9 bytes, which tells it from a
+//length encoded integer starting with 0xFE.
+//https://dev.mysql.com/doc/internals/en/packet-EOF_Packet.html
+#[inline]
+pub fn is_eof_packet(data: &[u8]) -> bool {
+    !data.is_empty() && data[0] == constants::EOF_PACKET_HEADER_MARK && data.len() < 9
+}
+
+//the packet which ends the rows of a result set, it depends on CLIENT_DEPRECATE_EOF.
+//with the flag, no EOF packet follows the column definitions either.
+pub fn is_result_set_end(data: &[u8], capability: CapabilityFlags) -> bool {
+    if capability.contains(CapabilityFlags::CLIENT_DEPRECATE_EOF) {
+        !data.is_empty()
+            && data[0] == constants::EOF_PACKET_HEADER_MARK
+            && data.len() < constants::MAX_PAYLOAD_LEN
+    } else {
+        is_eof_packet(data)
+    }
+}
+pub fn result_set_end_bits(
+    capability: CapabilityFlags,
+    status: StatusFlags,
+    warnings: u16,
+) -> Vec<u8> {
+    if capability.contains(CapabilityFlags::CLIENT_DEPRECATE_EOF) {
+        OkPacket::new(0, 0, status, warnings).to_eof_bits()
+    } else {
+        EofPacket::new(warnings, status).to_bits()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EofPacket {
+    //0xFE as header mark
+    warnings: u16,
+    status: StatusFlags,
+}
+
+impl EofPacket {
+    pub fn new(warnings: u16, status: StatusFlags) -> EofPacket {
+        EofPacket { warnings, status }
+    }
+    pub fn parse(data: &[u8]) -> MySQLResult<EofPacket> {
+        if !is_eof_packet(data) {
+            return Err(MySQLError::EofPacketILL);
+        }
+        //we always assume capability CLIENT_PROTOCOL_41
+        if data.len() < 5 {
+            return Ok(EofPacket::new(0, StatusFlags::empty()));
+        }
+        Ok(EofPacket {
+            warnings: LE::read_u16(&data[1..]),
+            status: StatusFlags::from_bits_truncate(LE::read_u16(&data[3..])),
+        })
+    }
+    #[inline]
+    pub fn status(&self) -> StatusFlags {
+        self.status
+    }
+    #[inline]
+    pub fn warnings(&self) -> u16 {
+        self.warnings
+    }
+    pub fn to_bits(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = vec![constants::EOF_PACKET_HEADER_MARK];
+        data.extend_from_slice(&self.warnings.to_le_bytes());
+        data.extend_from_slice(&self.status.bits().to_le_bytes());
+        data
+    }
+}
+//--------------------------
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_query_response_text_resultset_column_definition.html
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDefinition {
+    //catalog is always "def"
+    pub schema: String,
+    pub table: String,
+    pub org_table: String,
+    pub name: String,
+    pub org_name: String,
+    pub charset: u16,
+    pub column_length: u32,
+    pub column_type: ColumnType,
+    pub flags: ColumnFlags,
+    pub decimals: u8,
+    pub default_values: Option<Vec<u8>>, //only in the response of COM_FIELD_LIST.
+}
+
+impl ColumnDefinition {
+    //a column of a result set which the proxy builds itself.
+    pub fn new(name: &str, column_type: ColumnType) -> ColumnDefinition {
+        let (charset, column_length, flags) = match column_type {
+            ColumnType::MYSQL_TYPE_LONGLONG | ColumnType::MYSQL_TYPE_LONG => (
+                BINARY_CHARSET,
+                21,
+                ColumnFlags::BINARY_FLAG | ColumnFlags::NUM_FLAG,
+            ),
+            ColumnType::MYSQL_TYPE_DOUBLE | ColumnType::MYSQL_TYPE_NEWDECIMAL => (
+                BINARY_CHARSET,
+                22,
+                ColumnFlags::BINARY_FLAG | ColumnFlags::NUM_FLAG,
+            ),
+            _ => (
+                constants::UTF8MB4_GENERAL_CI as u16,
+                1024,
+                ColumnFlags::empty(),
+            ),
+        };
+        ColumnDefinition {
+            schema: String::new(),
+            table: String::new(),
+            org_table: String::new(),
+            name: name.to_string(),
+            org_name: name.to_string(),
+            charset,
+            column_length,
+            column_type,
+            flags,
+            decimals: 0,
+            default_values: None,
+        }
+    }
+    pub fn parse(data: &[u8]) -> MySQLResult<ColumnDefinition> {
+        let mut pos: usize = 0;
+        let mut strs: Vec<String> = Vec::with_capacity(6);
+        //catalog, schema, table, org_table, name, org_name
+        for _ in 0..6 {
+            let (n, s) = utils::read_length_encoded_string(&data[pos..]);
+            let s = s.ok_or(MySQLError::ColumnDefinitionILL)?;
+            strs.push(String::from_utf8_lossy(&s).to_string());
+            pos += n;
+        }
+        //length of the fixed length fields, always 0x0c
+        let (n, fixed_len) = utils::read_length_encoded_int(&data[pos..]);
+        pos += n;
+        if n == 0 || fixed_len < 10 || ((data.len() - pos) as u64) < fixed_len {
+            return Err(MySQLError::ColumnDefinitionILL);
+        }
+        let charset = LE::read_u16(&data[pos..]);
+        let column_length = LE::read_u32(&data[pos + 2..]);
+        let column_type =
+            ColumnType::from_u8(data[pos + 6]).ok_or(MySQLError::ColumnDefinitionILL)?;
+        let flags = ColumnFlags::from_bits_truncate(LE::read_u16(&data[pos + 7..]));
+        let decimals = data[pos + 9];
+        pos += fixed_len as usize;
+        let default_values = if pos < data.len() {
+            let (n, s) = utils::read_length_encoded_string(&data[pos..]);
+            if n == 0 {
+                return Err(MySQLError::ColumnDefinitionILL);
+            }
+            s
+        } else {
+            None
+        };
+        let mut strs = strs.into_iter().skip(1);
+        let mut next = || strs.next().unwrap_or_default();
+        Ok(ColumnDefinition {
+            schema: next(),
+            table: next(),
+            org_table: next(),
+            name: next(),
+            org_name: next(),
+            charset,
+            column_length,
+            column_type,
+            flags,
+            decimals,
+            default_values,
+        })
+    }
+    pub fn to_bits(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        for s in [
+            "def",
+            &self.schema,
+            &self.table,
+            &self.org_table,
+            &self.name,
+            &self.org_name,
+        ] {
+            data.extend_from_slice(&utils::write_length_encoded_string(Some(s.as_bytes())));
+        }
+        data.push(0x0c);
+        data.extend_from_slice(&self.charset.to_le_bytes());
+        data.extend_from_slice(&self.column_length.to_le_bytes());
+        data.push(self.column_type as u8);
+        data.extend_from_slice(&self.flags.bits().to_le_bytes());
+        data.push(self.decimals);
+        //filler [00] [00]
+        data.extend_from_slice(&[0u8; 2]);
+        if let Some(d) = &self.default_values {
+            data.extend_from_slice(&utils::write_length_encoded_string(Some(d)));
+        }
+        data
+    }
+    #[inline]
+    pub fn is_unsigned(&self) -> bool {
+        self.flags.contains(ColumnFlags::UNSIGNED_FLAG)
+    }
+}
+
+const BINARY_CHARSET: u16 = 63;
+//--------------------------
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_query_response_text_resultset_row.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextRow {
+    pub values: Vec<Option<Vec<u8>>>, //None is NULL.
+}
+
+impl TextRow {
+    pub fn new(values: Vec<Option<Vec<u8>>>) -> TextRow {
+        TextRow { values }
+    }
+    pub fn parse(data: &[u8], column_count: usize) -> MySQLResult<TextRow> {
+        let mut pos: usize = 0;
+        let mut values = Vec::with_capacity(column_count);
+        for _ in 0..column_count {
+            let (n, v) = utils::read_length_encoded_string(&data[pos..]);
+            if n == 0 {
+                return Err(MySQLError::RowPacketILL);
+            }
+            values.push(v);
+            pos += n;
+        }
+        if pos != data.len() {
+            return Err(MySQLError::RowPacketILL);
+        }
+        Ok(TextRow { values })
+    }
+    pub fn to_bits(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::new();
+        for v in self.values.iter() {
+            data.extend_from_slice(&utils::write_length_encoded_string(v.as_deref()));
+        }
+        data
+    }
+}
+//--------------------------
+//one value of a binary protocol row, it is typed by the column definition.
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_binary_resultset.html#sect_protocol_binary_resultset_row_value
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryValue {
+    Null,
+    Int(i64),
+    UInt(u64),
+    Float(f32),
+    Double(f64),
+    Bytes(Vec<u8>),
+    //year, month, day, hour, minute, second, micro second
+    Date(u16, u8, u8, u8, u8, u8, u32),
+    //is negative, days, hours, minutes, seconds, micro seconds
+    Time(bool, u32, u8, u8, u8, u32),
+}
+
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_binary_resultset.html#sect_protocol_binary_resultset_row
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryRow {
+    pub values: Vec<BinaryValue>,
+}
+
+impl BinaryRow {
+    pub fn new(values: Vec<BinaryValue>) -> BinaryRow {
+        BinaryRow { values }
+    }
+    //the null bitmap of a result set row has an offset of 2 bits.
+    fn null_bitmap_len(column_count: usize) -> usize {
+        (column_count + 7 + 2) / 8
+    }
+    pub fn parse(data: &[u8], columns: &[ColumnDefinition]) -> MySQLResult<BinaryRow> {
+        let bitmap_len = BinaryRow::null_bitmap_len(columns.len());
+        if data.len() < 1 + bitmap_len || data[0] != constants::OK_PACKET_HEADER_MARK {
+            return Err(MySQLError::RowPacketILL);
+        }
+        let bitmap = &data[1..1 + bitmap_len];
+        let mut pos = 1 + bitmap_len;
+        let mut values = Vec::with_capacity(columns.len());
+        for (i, c) in columns.iter().enumerate() {
+            let bit = i + 2;
+            if bitmap[bit / 8] & (1 << (bit % 8)) != 0 {
+                values.push(BinaryValue::Null);
+                continue;
+            }
+            let (n, v) = BinaryRow::parse_value(&data[pos..], c)?;
+            values.push(v);
+            pos += n;
+        }
+        Ok(BinaryRow { values })
+    }
+    fn parse_value(data: &[u8], column: &ColumnDefinition) -> MySQLResult<(usize, BinaryValue)> {
+        let fixed =
+            |n: usize| -> MySQLResult<&[u8]> { data.get(..n).ok_or(MySQLError::RowPacketILL) };
+        let int = |n: usize| -> MySQLResult<(usize, BinaryValue)> {
+            let b = fixed(n)?;
+            let v = if column.is_unsigned() {
+                BinaryValue::UInt(LE::read_uint(b, n))
+            } else {
+                BinaryValue::Int(LE::read_int(b, n))
+            };
+            Ok((n, v))
+        };
+        match column.column_type {
+            ColumnType::MYSQL_TYPE_NULL => Ok((0, BinaryValue::Null)),
+            ColumnType::MYSQL_TYPE_TINY => int(1),
+            ColumnType::MYSQL_TYPE_SHORT | ColumnType::MYSQL_TYPE_YEAR => int(2),
+            ColumnType::MYSQL_TYPE_LONG | ColumnType::MYSQL_TYPE_INT24 => int(4),
+            ColumnType::MYSQL_TYPE_LONGLONG => int(8),
+            ColumnType::MYSQL_TYPE_FLOAT => Ok((4, BinaryValue::Float(LE::read_f32(fixed(4)?)))),
+            ColumnType::MYSQL_TYPE_DOUBLE => Ok((8, BinaryValue::Double(LE::read_f64(fixed(8)?)))),
+            ColumnType::MYSQL_TYPE_DATE
+            | ColumnType::MYSQL_TYPE_DATETIME
+            | ColumnType::MYSQL_TYPE_TIMESTAMP => {
+                let len = *fixed(1)?.first().unwrap_or(&0) as usize;
+                let b = fixed(1 + len)?;
+                let (mut y, mut mo, mut d, mut h, mut mi, mut s, mut us) = (0, 0, 0, 0, 0, 0, 0);
+                if len >= 4 {
+                    y = LE::read_u16(&b[1..]);
+                    mo = b[3];
+                    d = b[4];
+                }
+                if len >= 7 {
+                    h = b[5];
+                    mi = b[6];
+                    s = b[7];
+                }
+                if len >= 11 {
+                    us = LE::read_u32(&b[8..]);
+                }
+                Ok((1 + len, BinaryValue::Date(y, mo, d, h, mi, s, us)))
+            }
+            ColumnType::MYSQL_TYPE_TIME => {
+                let len = *fixed(1)?.first().unwrap_or(&0) as usize;
+                let b = fixed(1 + len)?;
+                let (mut neg, mut days, mut h, mut mi, mut s, mut us) = (false, 0, 0, 0, 0, 0);
+                if len >= 8 {
+                    neg = b[1] == 1;
+                    days = LE::read_u32(&b[2..]);
+                    h = b[6];
+                    mi = b[7];
+                    s = b[8];
+                }
+                if len >= 12 {
+                    us = LE::read_u32(&b[9..]);
+                }
+                Ok((1 + len, BinaryValue::Time(neg, days, h, mi, s, us)))
+            }
+            _ => match utils::read_length_encoded_string(data) {
+                (0, _) => Err(MySQLError::RowPacketILL),
+                (n, v) => Ok((n, v.map(BinaryValue::Bytes).unwrap_or(BinaryValue::Null))),
+            },
+        }
+    }
+    pub fn to_bits(&self, columns: &[ColumnDefinition]) -> Vec<u8> {
+        let bitmap_len = BinaryRow::null_bitmap_len(columns.len());
+        let mut data: Vec<u8> = vec![constants::OK_PACKET_HEADER_MARK];
+        data.extend_from_slice(&vec![0u8; bitmap_len]);
+        for (i, (v, c)) in self.values.iter().zip(columns.iter()).enumerate() {
+            let width = match c.column_type {
+                ColumnType::MYSQL_TYPE_TINY => 1,
+                ColumnType::MYSQL_TYPE_SHORT | ColumnType::MYSQL_TYPE_YEAR => 2,
+                ColumnType::MYSQL_TYPE_LONG | ColumnType::MYSQL_TYPE_INT24 => 4,
+                _ => 8,
+            };
+            match v {
+                BinaryValue::Null => {
+                    let bit = i + 2;
+                    data[1 + bit / 8] |= 1 << (bit % 8);
+                }
+                BinaryValue::Int(x) => data.extend_from_slice(&x.to_le_bytes()[..width]),
+                BinaryValue::UInt(x) => data.extend_from_slice(&x.to_le_bytes()[..width]),
+                BinaryValue::Float(x) => data.extend_from_slice(&x.to_le_bytes()),
+                BinaryValue::Double(x) => data.extend_from_slice(&x.to_le_bytes()),
+                BinaryValue::Bytes(b) => {
+                    data.extend_from_slice(&utils::write_length_encoded_string(Some(b)))
+                }
+                BinaryValue::Date(y, mo, d, h, mi, s, us) => {
+                    let len: u8 = if *us != 0 {
+                        11
+                    } else if *h != 0 || *mi != 0 || *s != 0 {
+                        7
+                    } else if *y != 0 || *mo != 0 || *d != 0 {
+                        4
+                    } else {
+                        0
+                    };
+                    data.push(len);
+                    if len >= 4 {
+                        data.extend_from_slice(&y.to_le_bytes());
+                        data.extend_from_slice(&[*mo, *d]);
+                    }
+                    if len >= 7 {
+                        data.extend_from_slice(&[*h, *mi, *s]);
+                    }
+                    if len >= 11 {
+                        data.extend_from_slice(&us.to_le_bytes());
+                    }
+                }
+                BinaryValue::Time(neg, days, h, mi, s, us) => {
+                    let len: u8 = if *us != 0 {
+                        12
+                    } else if *days != 0 || *h != 0 || *mi != 0 || *s != 0 {
+                        8
+                    } else {
+                        0
+                    };
+                    data.push(len);
+                    if len >= 8 {
+                        data.push(*neg as u8);
+                        data.extend_from_slice(&days.to_le_bytes());
+                        data.extend_from_slice(&[*h, *mi, *s]);
+                    }
+                    if len >= 12 {
+                        data.extend_from_slice(&us.to_le_bytes());
+                    }
+                }
+            }
+        }
+        data
+    }
+}
+
+impl BinaryValue {
+    //the value as a sql literal, so that a bound statement can be routed like a COM_QUERY.
+    //a NaN or an infinite float has no literal in sql.
+    pub fn to_sql_literal(&self) -> Option<String> {
+        let literal = match self {
+            BinaryValue::Null => "NULL".to_string(),
+            BinaryValue::Int(x) => x.to_string(),
+            BinaryValue::UInt(x) => x.to_string(),
+            BinaryValue::Float(x) if x.is_finite() => x.to_string(),
+            BinaryValue::Double(x) if x.is_finite() => x.to_string(),
+            BinaryValue::Float(_) | BinaryValue::Double(_) => return None,
+            BinaryValue::Bytes(b) => utils::quote_sql_string(b),
+            BinaryValue::Date(y, mo, d, h, mi, s, us) => format!(
+                "'{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:06}'",
+                y, mo, d, h, mi, s, us
+            ),
+            BinaryValue::Time(neg, days, h, mi, s, us) => format!(
+                "'{}{:02}:{:02}:{:02}.{:06}'",
+                if *neg { "-" } else { "" },
+                //the days are the client's, in u64 they can not overflow.
+                *days as u64 * 24 + *h as u64,
+                mi,
+                s,
+                us
+            ),
+        };
+        Some(literal)
+    }
+    //a text protocol value typed by its column, a value which does not fit the type is NULL.
+    pub fn from_text(value: Option<&[u8]>, column: &ColumnDefinition) -> BinaryValue {
+        let text = match value {
+            Some(v) => v,
+            None => return BinaryValue::Null,
+        };
+        let s = String::from_utf8_lossy(text);
+        let parsed = match column.column_type {
+            ColumnType::MYSQL_TYPE_TINY
+            | ColumnType::MYSQL_TYPE_SHORT
+            | ColumnType::MYSQL_TYPE_YEAR
+            | ColumnType::MYSQL_TYPE_LONG
+            | ColumnType::MYSQL_TYPE_INT24
+            | ColumnType::MYSQL_TYPE_LONGLONG => {
+                if column.is_unsigned() {
+                    s.parse::<u64>().ok().map(BinaryValue::UInt)
+                } else {
+                    s.parse::<i64>().ok().map(BinaryValue::Int)
+                }
+            }
+            ColumnType::MYSQL_TYPE_FLOAT => s.parse::<f32>().ok().map(BinaryValue::Float),
+            ColumnType::MYSQL_TYPE_DOUBLE => s.parse::<f64>().ok().map(BinaryValue::Double),
+            ColumnType::MYSQL_TYPE_DATE
+            | ColumnType::MYSQL_TYPE_DATETIME
+            | ColumnType::MYSQL_TYPE_TIMESTAMP => parse_text_date(&s),
+            ColumnType::MYSQL_TYPE_TIME => parse_text_time(&s),
+            ColumnType::MYSQL_TYPE_NULL => Some(BinaryValue::Null),
+            _ => Some(BinaryValue::Bytes(text.to_vec())),
+        };
+        parsed.unwrap_or(BinaryValue::Null)
+    }
+}
+
+//`.ffffff` padded to micro seconds.
+fn parse_micros(frac: &str) -> Option<u32> {
+    if frac.is_empty() || frac.len() > 6 {
+        return None;
+    }
+    Some(frac.parse::<u32>().ok()? * 10u32.pow(6 - frac.len() as u32))
+}
+
+//`YYYY-MM-DD[ hh:mm:ss[.ffffff]]`
+fn parse_text_date(s: &str) -> Option<BinaryValue> {
+    let (date, time) = match s.split_once(' ') {
+        Some((d, t)) => (d, Some(t)),
+        None => (s, None),
+    };
+    let mut ymd = date.splitn(3, '-');
+    let y = ymd.next()?.parse().ok()?;
+    let mo = ymd.next()?.parse().ok()?;
+    let d = ymd.next()?.parse().ok()?;
+    let (mut h, mut mi, mut sec, mut us) = (0, 0, 0, 0);
+    if let Some(t) = time {
+        let (hms, frac) = match t.split_once('.') {
+            Some((hms, f)) => (hms, Some(f)),
+            None => (t, None),
+        };
+        let mut parts = hms.splitn(3, ':');
+        h = parts.next()?.parse().ok()?;
+        mi = parts.next()?.parse().ok()?;
+        sec = parts.next()?.parse().ok()?;
+        if let Some(f) = frac {
+            us = parse_micros(f)?;
+        }
+    }
+    Some(BinaryValue::Date(y, mo, d, h, mi, sec, us))
+}
+
+//`[-]hhh:mm:ss[.ffffff]`, the hours are split into days.
+fn parse_text_time(s: &str) -> Option<BinaryValue> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (hms, frac) = match s.split_once('.') {
+        Some((hms, f)) => (hms, Some(f)),
+        None => (s, None),
+    };
+    let mut parts = hms.splitn(3, ':');
+    let hours: u32 = parts.next()?.parse().ok()?;
+    let mi = parts.next()?.parse().ok()?;
+    let sec = parts.next()?.parse().ok()?;
+    let us = match frac {
+        Some(f) => parse_micros(f)?,
+        None => 0,
+    };
+    Some(BinaryValue::Time(
+        neg,
+        hours / 24,
+        (hours % 24) as u8,
+        mi,
+        sec,
+        us,
+    ))
+}
+//--------------------------
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_stmt_prepare.html#sect_protocol_com_stmt_prepare_response_ok
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StmtPrepareOk {
+    pub stmt_id: u32,
+    pub column_count: u16,
+    pub param_count: u16,
+    pub warnings: u16,
+}
+
+impl StmtPrepareOk {
+    pub fn parse(data: &[u8]) -> MySQLResult<StmtPrepareOk> {
+        if data.len() < 12 || data[0] != constants::OK_PACKET_HEADER_MARK {
+            return Err(MySQLError::StmtPacketILL);
+        }
+        Ok(StmtPrepareOk {
+            stmt_id: LE::read_u32(&data[1..5]),
+            column_count: LE::read_u16(&data[5..7]),
+            param_count: LE::read_u16(&data[7..9]),
+            warnings: LE::read_u16(&data[10..12]),
+        })
+    }
+    pub fn to_bits(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = vec![constants::OK_PACKET_HEADER_MARK];
+        data.extend_from_slice(&self.stmt_id.to_le_bytes());
+        data.extend_from_slice(&self.column_count.to_le_bytes());
+        data.extend_from_slice(&self.param_count.to_le_bytes());
+        data.push(0u8);
+        data.extend_from_slice(&self.warnings.to_le_bytes());
+        data
+    }
+}
+
+//the payload of COM_STMT_EXECUTE after the command byte, the parameter values are kept raw.
+//https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_stmt_execute.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StmtExecute {
+    pub stmt_id: u32,
+    pub flags: u8,
+    pub null_bitmap: Vec<u8>,
+    pub param_types: Vec<u8>, //2 bytes each, empty when the client did not bind new types.
+    pub values: Vec<u8>,
+}
+
+impl StmtExecute {
+    //the statement id, to look up how many parameters the rest of the packet has.
+    pub fn parse_stmt_id(data: &[u8]) -> MySQLResult<u32> {
+        data.get(..4)
+            .map(LE::read_u32)
+            .ok_or(MySQLError::StmtPacketILL)
+    }
+    pub fn parse(data: &[u8], param_count: usize) -> MySQLResult<StmtExecute> {
+        //stmt id, flags, iteration count
+        if data.len() < 9 {
+            return Err(MySQLError::StmtPacketILL);
+        }
+        let mut exec = StmtExecute {
+            stmt_id: LE::read_u32(&data[..4]),
+            flags: data[4],
+            null_bitmap: Vec::new(),
+            param_types: Vec::new(),
+            values: Vec::new(),
+        };
+        if param_count == 0 {
+            return Ok(exec);
+        }
+        let mut pos = 9;
+        let bitmap_len = param_count.div_ceil(8);
+        exec.null_bitmap = data
+            .get(pos..pos + bitmap_len)
+            .ok_or(MySQLError::StmtPacketILL)?
+            .to_vec();
+        pos += bitmap_len;
+        let new_params_bound = *data.get(pos).ok_or(MySQLError::StmtPacketILL)?;
+        pos += 1;
+        if new_params_bound == 1 {
+            exec.param_types = data
+                .get(pos..pos + 2 * param_count)
+                .ok_or(MySQLError::StmtPacketILL)?
+                .to_vec();
+            pos += 2 * param_count;
+        }
+        exec.values = data[pos..].to_vec();
+        Ok(exec)
+    }
+    //the types are always sent, a backend statement may see its first execute here.
+    pub fn to_bits(&self) -> Vec<u8> {
+        let mut data: Vec<u8> = Vec::with_capacity(16 + self.param_types.len() + self.values.len());
+        data.extend_from_slice(&self.stmt_id.to_le_bytes());
+        data.push(self.flags);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        if !self.null_bitmap.is_empty() {
+            data.extend_from_slice(&self.null_bitmap);
+            data.push(1u8);
+            data.extend_from_slice(&self.param_types);
+            data.extend_from_slice(&self.values);
+        }
+        data
+    }
+    //the parameter values, the ones sent by COM_STMT_SEND_LONG_DATA are left None.
+    pub fn params(&self, long_data: &[usize]) -> MySQLResult<Vec<Option<BinaryValue>>> {
+        let count = self.param_types.len() / 2;
+        let mut params = Vec::with_capacity(count);
+        let mut pos = 0;
+        for i in 0..count {
+            if self.null_bitmap[i / 8] & (1 << (i % 8)) != 0 {
+                params.push(Some(BinaryValue::Null));
+                continue;
+            }
+            if long_data.contains(&i) {
+                params.push(None);
+                continue;
+            }
+            let column_type =
+                ColumnType::from_u8(self.param_types[2 * i]).ok_or(MySQLError::StmtPacketILL)?;
+            let mut column = ColumnDefinition::new("?", column_type);
+            if self.param_types[2 * i + 1] & 0x80 != 0 {
+                column.flags |= ColumnFlags::UNSIGNED_FLAG;
+            }
+            let (n, v) = BinaryRow::parse_value(&self.values[pos..], &column)
+                .map_err(|_| MySQLError::StmtPacketILL)?;
+            params.push(Some(v));
+            pos += n;
+        }
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn result_set_codec() {
+        let mut column = ColumnDefinition::new("id", ColumnType::MYSQL_TYPE_LONGLONG);
+        column.schema = "db1".to_string();
+        column.table = "t".to_string();
+        column.flags |= ColumnFlags::UNSIGNED_FLAG;
+        assert_eq!(ColumnDefinition::parse(&column.to_bits()).unwrap(), column);
+        let name = ColumnDefinition::new("name", ColumnType::MYSQL_TYPE_VAR_STRING);
+        let born = ColumnDefinition::new("born", ColumnType::MYSQL_TYPE_DATETIME);
+        let columns = vec![column, name, born];
+
+        let eof = EofPacket::new(1, StatusFlags::SERVER_STATUS_AUTOCOMMIT);
+        assert_eq!(EofPacket::parse(&eof.to_bits()).unwrap(), eof);
+        let end = result_set_end_bits(
+            CapabilityFlags::CLIENT_DEPRECATE_EOF,
+            StatusFlags::SERVER_MORE_RESULTS_EXISTS,
+            0,
+        );
+        assert!(is_result_set_end(
+            &end,
+            CapabilityFlags::CLIENT_DEPRECATE_EOF
+        ));
+        assert_eq!(
+            OkPacket::parse_eof(&end).unwrap().status(),
+            StatusFlags::SERVER_MORE_RESULTS_EXISTS
+        );
+
+        //251 bytes long string needs a 0xfc length prefix, NULL is 0xfb.
+        let long = vec![b'x'; 251];
+        let row = TextRow::new(vec![Some(b"7".to_vec()), Some(long.clone()), None]);
+        let bits = row.to_bits();
+        assert_eq!(bits[2], 0xfc);
+        assert_eq!(*bits.last().unwrap(), 0xfb);
+        assert_eq!(TextRow::parse(&bits, 3).unwrap(), row);
+        assert!(TextRow::parse(&bits[..10], 3).is_err());
+
+        let row = BinaryRow::new(vec![
+            BinaryValue::UInt(u64::MAX),
+            BinaryValue::Null,
+            BinaryValue::Date(2022, 10, 1, 8, 30, 0, 0),
+        ]);
+        let bits = row.to_bits(&columns);
+        assert_eq!(bits[1], 0b0000_1000);
+        assert_eq!(BinaryRow::parse(&bits, &columns).unwrap(), row);
+        let row = BinaryRow::new(vec![
+            BinaryValue::UInt(1),
+            BinaryValue::Bytes(long),
+            BinaryValue::Null,
+        ]);
+        assert_eq!(
+            BinaryRow::parse(&row.to_bits(&columns), &columns).unwrap(),
+            row
+        );
+    }
+
+    #[test]
+    fn stmt_execute_params() {
+        //3 params: BIGINT UNSIGNED 7, NULL, then a string sent as long data.
+        let mut data = vec![1, 0, 0, 0, 0, 1, 0, 0, 0, 0b0000_0010, 1];
+        data.extend_from_slice(&[ColumnType::MYSQL_TYPE_LONGLONG as u8, 0x80]);
+        data.extend_from_slice(&[ColumnType::MYSQL_TYPE_NULL as u8, 0]);
+        data.extend_from_slice(&[ColumnType::MYSQL_TYPE_BLOB as u8, 0]);
+        data.extend_from_slice(&7u64.to_le_bytes());
+        let exec = StmtExecute::parse(&data, 3).unwrap();
+        assert_eq!(exec.to_bits(), data);
+        let params = exec.params(&[2]).unwrap();
+        assert_eq!(
+            params,
+            vec![Some(BinaryValue::UInt(7)), Some(BinaryValue::Null), None]
+        );
+        //the types are left out once bound, the proxy fills them back.
+        let mut again =
+            StmtExecute::parse(&[1, 0, 0, 0, 0, 1, 0, 0, 0, 0b0000_0010, 0], 3).unwrap();
+        assert!(again.param_types.is_empty());
+        again.param_types = exec.param_types.clone();
+        assert_eq!(again.params(&[0, 2]).unwrap()[0], None);
+
+        let literal = |v: BinaryValue| v.to_sql_literal();
+        assert_eq!(
+            literal(BinaryValue::Bytes(b"it's".to_vec())).unwrap(),
+            "'it''s'"
+        );
+        assert_eq!(
+            literal(BinaryValue::Bytes(b"a\\'b".to_vec())).unwrap(),
+            "X'615C2762'"
+        );
+        assert_eq!(
+            literal(BinaryValue::Date(2023, 1, 5, 0, 0, 0, 0)).unwrap(),
+            "'2023-01-05 00:00:00.000000'"
+        );
+        assert_eq!(
+            literal(BinaryValue::Time(true, u32::MAX, 23, 0, 0, 0)).unwrap(),
+            "'-103079215103:00:00.000000'"
+        );
+        assert_eq!(literal(BinaryValue::Double(0.5)).unwrap(), "0.5");
+        assert_eq!(literal(BinaryValue::Double(f64::NAN)), None);
+        assert_eq!(literal(BinaryValue::Float(f32::NEG_INFINITY)), None);
+        let born = ColumnDefinition::new("born", ColumnType::MYSQL_TYPE_DATETIME);
+        assert_eq!(
+            BinaryValue::from_text(Some(b"2023-01-05 10:20:30.5"), &born),
+            BinaryValue::Date(2023, 1, 5, 10, 20, 30, 500000)
+        );
+        let took = ColumnDefinition::new("took", ColumnType::MYSQL_TYPE_TIME);
+        assert_eq!(
+            BinaryValue::from_text(Some(b"-25:00:01"), &took),
+            BinaryValue::Time(true, 1, 1, 0, 1, 0)
+        );
+    }
+
+    #[test]
+    fn huge_length_is_ill() {
+        //a string param whose 0xfe length runs far past the packet.
+        let mut data = vec![1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 1];
+        data.extend_from_slice(&[ColumnType::MYSQL_TYPE_BLOB as u8, 0]);
+        data.extend_from_slice(&[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, b'x']);
+        let exec = StmtExecute::parse(&data, 1).unwrap();
+        assert!(exec.params(&[]).is_err());
+        let mut row = vec![0xfe];
+        row.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(TextRow::parse(&row, 1).is_err());
+    }
+}
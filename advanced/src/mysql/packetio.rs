@@ -23,6 +23,9 @@ impl PacketIO {
         //self.stream.shutdown();
         Ok(())
     }
+    pub async fn shutdown(&mut self) -> MySQLResult<()> {
+        self.stream.shutdown().await.map_err(MySQLError::IO)
+    }
 
     pub fn reset_seq(&mut self) {
         self.sequence = 0;
@@ -37,7 +40,7 @@ impl PacketIO {
                 return Err(MySQLError::MismatchPacketSequence);
             }
 
-            self.sequence += 1;
+            self.sequence = self.sequence.wrapping_add(1);
 
             //let payload_len = (header[0] as u32) |  ((header[1] as u32) << 8) | ((header[2]as u32) << 16) ;
             let mut rdr = Cursor::new(&header[..3]);
@@ -78,7 +81,7 @@ impl PacketIO {
             bufp[2] = 0xff;
             bufp[3] = self.sequence;
             self.stream.write_all(&bufp[..4 + MAX_PAYLOAD_LEN]).await?;
-            self.sequence += 1;
+            self.sequence = self.sequence.wrapping_add(1);
             data_len -= MAX_PAYLOAD_LEN;
             //原地拆包法
             bufp = &mut bufp[MAX_PAYLOAD_LEN..];
@@ -90,8 +93,8 @@ impl PacketIO {
         bufp[1] = wtr[1];
         bufp[2] = wtr[2];
         bufp[3] = self.sequence;
-        self.stream.write_all(&bufp).await?;
-        self.sequence += 1;
+        self.stream.write_all(bufp).await?;
+        self.sequence = self.sequence.wrapping_add(1);
         Ok(())
     }
 }
@@ -1,9 +1,23 @@
+#![allow(dead_code)]
+//the mysql protocol server of a listener: it accepts the client conns on its socket address, runs
+//the handshake and the command loop of each and leaves what the commands do to its executor.
+use crate::frontend::errors::{FrontendError, FrontendResult};
+use crate::mysql::constants::command;
+use crate::mysql::{constants, errcode, errors, packet, packetio, utils};
 use crate::proto::interface;
 use crate::proto::interface::Executor;
-use std::collections::HashMap;
+use async_trait::async_trait;
+use byteorder::{WriteBytesExt, LE};
+use mysql_common::scramble;
+use std::io;
+use std::io::Cursor;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
 
 pub struct ServerConfig {
     pub server_version: String,
+    pub address: String, //host:port
 }
 
 pub struct Listener<T>
@@ -11,50 +25,693 @@ where
     T: Executor,
 {
     config: ServerConfig,
-    listener: String, // TODO
-    executor: T,
-    conn_id: u32,
-    conn_read_buffer_size: u16,
-    capabilities: u32,
-    character_set: u8,
-    schema_name: String,
-    statement_id: u32,
-    stmts: HashMap<String, String>,
+    executor: Arc<T>,
+    closed: watch::Sender<bool>,
+    bound: std::sync::Mutex<Option<TcpListener>>, //a socket bound before listen, if any.
 }
 
-impl <T: Executor>interface::Listener for Listener<T> {
-    fn set_executor(&self, executor: Box<dyn Executor>) {
-        todo!()
+#[async_trait]
+impl<T: Executor> interface::Listener for Listener<T> {
+    fn address(&self) -> &str {
+        &self.config.address
     }
 
-    fn listen(&self) {
-        todo!()
+    async fn listen(&self) -> io::Result<()> {
+        let bound = self.bound.lock().unwrap().take();
+        let listener = match bound {
+            Some(listener) => listener,
+            None => TcpListener::bind(&self.config.address).await?,
+        };
+        log::info!("Listen on {}", listener.local_addr()?);
+        let mut closed = self.closed.subscribe();
+        loop {
+            tokio::select! {
+                _ = closed.wait_for(|closed| *closed) => return Ok(()),
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let executor = self.executor.clone();
+                        let version = self.config.server_version.clone();
+                        tokio::spawn(serve(executor, stream, utils::generate_id(), version));
+                    }
+                    Err(e) => log::warn!("Accepting on {} error: {}", &self.config.address, e),
+                },
+            }
+        }
     }
 
+    //no more conns are accepted, those accepted run till they end.
     fn close(&self) {
-        todo!()
+        self.closed.send_replace(true);
     }
 }
 
-impl <T: Executor>Listener<T> {
+impl<T: Executor> Listener<T> {
     pub fn new(executor: T, config: crate::config::Listener) -> Self {
+        let address = &config.socket_address;
         let config = ServerConfig {
             server_version: config.server_version,
+            address: format!("{}:{}", address.address, address.port),
         };
+        Listener::with(executor, config)
+    }
 
-        //TODO Listener
-
+    pub fn with(executor: T, config: ServerConfig) -> Self {
         Listener {
             config,
-            listener: "".to_string(),
-            executor,
-            conn_id: 0,
-            conn_read_buffer_size: 0,
-            capabilities: 0,
-            character_set: 0,
-            schema_name: "".to_string(),
-            statement_id: 0,
-            stmts: Default::default()
+            executor: Arc::new(executor),
+            closed: watch::channel(false).0,
+            bound: std::sync::Mutex::new(None),
+        }
+    }
+
+    //a listener on a socket bound already, the conns to it queue up till it listens.
+    pub fn bound(executor: T, server_version: String, listener: TcpListener) -> io::Result<Self> {
+        let config = ServerConfig {
+            server_version,
+            address: listener.local_addr()?.to_string(),
+        };
+        let listener = Listener {
+            bound: std::sync::Mutex::new(Some(listener)),
+            ..Listener::with(executor, config)
+        };
+        Ok(listener)
+    }
+
+    //the executor of the conns accepted from now on.
+    pub fn set_executor(&mut self, executor: T) {
+        self.executor = Arc::new(executor);
+    }
+}
+
+//what the client handshake response tells.
+#[derive(Debug, Clone, Default)]
+pub struct Login {
+    pub user: String, //as the client sent it.
+    pub auth: Vec<u8>,
+    pub db: String,
+}
+
+//the protocol state of a client conn.
+#[derive(Debug)]
+pub struct Session {
+    pub pkg: packetio::PacketIO,
+    pub conn_id: u32,
+    pub capability: constants::CapabilityFlags,
+    pub salt: Vec<u8>, //8 or 20 bytes.
+    pub collation_id: u8,
+    pub status: constants::StatusFlags,
+    pub server_version: String,
+    //---
+    pub db: String,
+    //---
+    pub quit_flag: bool,
+}
+
+impl Session {
+    pub fn new(tcp: TcpStream, conn_id: u32, server_version: String) -> FrontendResult<Session> {
+        Ok(Session {
+            pkg: packetio::PacketIO::new(tcp),
+            conn_id,
+            capability: constants::get_default_capability_flags(),
+            salt: utils::random_salt(20)?,
+            collation_id: constants::UTF8MB4_GENERAL_CI,
+            status: constants::StatusFlags::SERVER_STATUS_AUTOCOMMIT,
+            server_version,
+            db: String::new(),
+            quit_flag: false,
+        })
+    }
+    async fn write_initial_handshake(&mut self) -> FrontendResult<()> {
+        let mut data: Vec<u8> = Vec::new();
+        //min version 10
+        data.push(constants::MIN_PROTOCOL_VERSION);
+
+        //server version[00]
+        data.extend_from_slice(self.server_version.as_bytes());
+        data.push(0u8);
+
+        //connection id
+        data.write_u32::<LE>(self.conn_id)?;
+
+        //auth-plugin-data-part-1
+        data.extend_from_slice(&self.salt[0..8]);
+        //filter [00]
+        data.push(0u8);
+
+        //the lower 2 bytes of the capability
+        data.push(self.capability.bits() as u8);
+        data.push((self.capability.bits() >> 8) as u8);
+
+        //charset, utf-8 default
+        data.push(self.collation_id);
+
+        //status
+        data.push(self.status.bits() as u8);
+        data.push((self.status.bits() >> 8) as u8);
+
+        //capability flag upper 2 bytes, using default capability here
+        data.push((self.capability.bits() >> 16) as u8);
+        data.push((self.capability.bits() >> 24) as u8);
+        //filter [0x15], for wireshark dump, value is 0x15
+        data.push(0x15);
+        //reserved 10 [00]
+        data.extend_from_slice(&[0u8; 10]);
+        //auth-plugin-data-part-2
+        data.extend_from_slice(&self.salt[8..]);
+        data.push(0u8);
+
+        //server send first auth packet to client by tcp stream
+        self.pkg
+            .write_packet(&mut data)
+            .await
+            .map_err(FrontendError::MySQLErr)
+    }
+    // http://hutaow.com/blog/2013/11/06/mysql-protocol-analysis/#41
+    //https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_connection_phase_packets_protocol_handshake_response.html
+    async fn read_client_handshake(&mut self) -> FrontendResult<Login> {
+        let data = self.pkg.read_packet().await?;
+        let short = || FrontendError::MySQLErr(errors::MySQLError::PacketZeroPayload);
+        if data.len() < 32 {
+            return Err(short());
+        }
+
+        let mut pos: usize = 0;
+        //capability
+        let mut rdr = Cursor::new(&data[..4]);
+        self.capability &= constants::CapabilityFlags::from_bits_truncate(
+            byteorder::ReadBytesExt::read_u32::<LE>(&mut rdr).unwrap_or(0),
+        );
+        if !self
+            .capability
+            .contains(constants::CapabilityFlags::CLIENT_PROTOCOL_41)
+        {
+            log::info!(
+                "Too older than CapabilityFlags::CLIENT_PROTOCOL_41 : {:?}",
+                &self
+            );
+            return Err(FrontendError::ProxyAuthOldInClientProtocol41);
+        }
+        pos += 4;
+        //skip max packet size
+        pos += 4;
+        //charset, skip, if you want to use another charset, use set names
+        pos += 1;
+        //skip reserved 23[00]
+        pos += 23;
+        //user name
+        let user = null_terminated(&data[pos..]);
+        pos += user.len() + 1;
+        //auth length and auth
+        let auth_len = *data.get(pos).ok_or_else(short)? as usize;
+        pos += 1;
+        let auth = data.get(pos..(pos + auth_len)).ok_or_else(short)?.to_vec();
+        pos += auth_len;
+        log::info!("client auth user: {}, auth:{:?}", &user, &auth);
+
+        //init with db
+        let mut db = String::new();
+        if self
+            .capability
+            .contains(constants::CapabilityFlags::CLIENT_CONNECT_WITH_DB)
+            && pos < data.len()
+        {
+            db = null_terminated(&data[pos..]);
+        }
+        Ok(Login { user, auth, db })
+    }
+    //the login of a COM_CHANGE_USER, `data` is without the command byte.
+    //https://dev.mysql.com/doc/dev/mysql-server/latest/page_protocol_com_change_user.html
+    pub fn parse_change_user(&self, data: &[u8]) -> Login {
+        let user = null_terminated(data);
+        let mut pos = user.len() + 1;
+        let auth = if self
+            .capability
+            .contains(constants::CapabilityFlags::CLIENT_SECURE_CONNECTION)
+        {
+            let len = data.get(pos).copied().unwrap_or(0) as usize;
+            pos += 1;
+            let auth = data.get(pos..pos + len).unwrap_or_default().to_vec();
+            pos += len;
+            auth
+        } else {
+            let auth = data.get(pos..).map(null_terminated).unwrap_or_default();
+            pos += auth.len() + 1;
+            auth.into_bytes()
+        };
+        let db = data.get(pos..).map(null_terminated).unwrap_or_default();
+        Login { user, auth, db }
+    }
+    //whether the auth of a login is the scramble of `password` with the salt of the session.
+    pub fn check_password(&self, auth: &[u8], password: &str) -> bool {
+        let scramble = scramble::scramble_native(&self.salt, password.as_bytes())
+            .unwrap_or_default()
+            .to_vec();
+        scramble == auth
+    }
+    pub async fn write_err(&mut self, r: packet::ErrPacket) -> FrontendResult<()> {
+        self.pkg
+            .write_packet(r.to_bits().as_mut_slice())
+            .await
+            .map_err(FrontendError::MySQLErr)
+    }
+    //write ok packet to client.
+    pub async fn write_ok(&mut self, r: Option<packet::OkPacket>) -> FrontendResult<()> {
+        let ok_p = r.unwrap_or_else(|| packet::OkPacket::empty(self.status));
+        let mut data = ok_p.to_bits();
+        self.pkg
+            .write_packet(&mut data)
+            .await
+            .map_err(FrontendError::MySQLErr)
+    }
+    pub fn quit(&mut self) -> FrontendResult<()> {
+        self.quit_flag = true;
+        self.pkg.quit().map_err(FrontendError::MySQLErr)
+    }
+    //answer a handshake which failed.
+    async fn refuse(&mut self, e: FrontendError) {
+        let code = match e {
+            FrontendError::ProxyAuthDenied => errcode::ER_ACCESS_DENIED_ERROR,
+            FrontendError::ProxyCrossTenant(..) => errcode::ER_DBACCESS_DENIED_ERROR,
+            _ => errcode::ER_HANDSHAKE_ERROR,
+        };
+        if let Err(e) = self
+            .write_err(packet::ErrPacket::new(code, format!("{}", e)))
+            .await
+        {
+            log::info!("write err packet failed: {:?}", e);
+        }
+    }
+}
+
+fn null_terminated(data: &[u8]) -> String {
+    let end = data.iter().position(|&x| x == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).to_string()
+}
+
+//the io errors of a conn which is gone.
+fn gone(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
+//one client conn: the handshake, then its commands till it quits or goes away.
+async fn serve<T: Executor>(executor: Arc<T>, stream: TcpStream, id: u32, server_version: String) {
+    if let (Ok(local), Ok(peer)) = (stream.local_addr(), stream.peer_addr()) {
+        log::info!(
+            "Server listener: {}, Accepted from: {}, MySQL thread id: {}",
+            local,
+            peer,
+            id
+        );
+    }
+    let mut session = match Session::new(stream, id, server_version) {
+        Ok(session) => session,
+        Err(e) => {
+            log::warn!("Fail to process connection; error = {}", e);
+            return;
+        }
+    };
+    if let Err(e) = session.write_initial_handshake().await {
+        log::info!("handshake failed: {}", e);
+        return;
+    }
+    let login = match session.read_client_handshake().await {
+        Ok(login) => login,
+        Err(e) => return session.refuse(e).await,
+    };
+    let mut conn = executor.open(session);
+    if let Err(e) = executor.login(&mut conn, &login).await {
+        return conn.as_mut().refuse(e).await;
+    }
+    if conn.as_mut().write_ok(None).await.is_err() {
+        return;
+    }
+    conn.as_mut().pkg.reset_seq();
+    run_loop(executor.as_ref(), &mut conn).await;
+}
+
+async fn run_loop<T: Executor>(executor: &T, conn: &mut T::Conn) {
+    loop {
+        let data = match conn.as_mut().pkg.read_packet().await {
+            Ok(data) => data,
+            Err(errors::MySQLError::IO(e)) => {
+                log::info!("quit mysql connection: {}", e);
+                return executor.close(conn).await;
+            }
+            Err(e) => {
+                log::info!("read packet failed: {}", e);
+                conn.as_mut().pkg.reset_seq();
+                continue;
+            }
+        };
+        let rc = dispatch_mysql_cmd(executor, conn, &data).await;
+        log::info!("dispatch_mysql_cmd  result: {:?}", rc);
+        match rc {
+            Err(FrontendError::IO(o)) if gone(o.kind()) => {
+                return executor.close(conn).await;
+            }
+            Err(FrontendError::MySQLErr(errors::MySQLError::IO(o))) if gone(o.kind()) => {
+                return executor.close(conn).await;
+            }
+            Err(e) if !conn.as_mut().quit_flag => {
+                let err_p = packet::ErrPacket::new(errcode::ER_UNKNOWN_ERROR, format!("{}", e));
+                if let Err(e) = conn.as_mut().write_err(err_p).await {
+                    log::info!("write err packet failed: {:?}", e);
+                    return executor.close(conn).await;
+                }
+            }
+            _ => {}
+        }
+        if conn.as_mut().quit_flag {
+            return;
+        }
+        conn.as_mut().pkg.reset_seq();
+    } //end of loop
+}
+
+async fn dispatch_mysql_cmd<T: Executor>(
+    executor: &T,
+    conn: &mut T::Conn,
+    data: &[u8],
+) -> FrontendResult<()> {
+    log::info!("dispatch_mysql_cmd data: {:?}", data);
+    match data[0] {
+        command::COM_QUIT => {
+            executor.close(conn).await;
+            conn.as_mut().quit()
+        }
+        command::COM_QUERY => {
+            let sql = String::from_utf8_lossy(&data[1..]).to_string();
+            executor.query(conn, &sql).await
+        }
+        command::COM_INIT_DB => {
+            let db = String::from_utf8_lossy(&data[1..]).to_string();
+            executor.init_db(conn, &db).await
+        }
+        command::COM_FIELD_LIST => {
+            //the table[00], then the wildcard to the end.
+            let table = null_terminated(&data[1..]);
+            let rest = data.get(table.len() + 2..).unwrap_or_default();
+            executor
+                .field_list(conn, &table, &String::from_utf8_lossy(rest))
+                .await
+        }
+        command::COM_STMT_PREPARE => {
+            let sql = String::from_utf8_lossy(&data[1..]).to_string();
+            executor.prepare(conn, &sql).await
         }
+        command::COM_STMT_EXECUTE => executor.execute(conn, &data[1..]).await,
+        command::COM_STMT_SEND_LONG_DATA | command::COM_STMT_CLOSE | command::COM_STMT_RESET => {
+            executor.stmt(conn, data[0], &data[1..]).await
+        }
+        cmd => executor.command(conn, cmd, &data[1..]).await,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{Listener, Login, Session};
+    use crate::backend::merger::{self, ResultSet};
+    use crate::frontend::errors::{FrontendError, FrontendResult};
+    use crate::mysql::constants::{command, CapabilityFlags, ColumnType, StatusFlags};
+    use crate::mysql::{packet, packetio};
+    use crate::proto::interface::{Executor, Listener as _};
+    use crate::server::server::Server;
+    use async_trait::async_trait;
+    use mysql_common::scramble;
+    use std::io;
+    use std::sync::Arc;
+    use tokio::net::{TcpListener, TcpStream};
+
+    //lets `root` in with password `secret`, answers a query with its length as affected rows.
+    pub(crate) struct Echo;
+
+    pub(crate) struct EchoConn(Session);
+
+    impl AsMut<Session> for EchoConn {
+        fn as_mut(&mut self) -> &mut Session {
+            &mut self.0
+        }
+    }
+
+    impl EchoConn {
+        async fn ok(&mut self, rows: usize) -> FrontendResult<()> {
+            let ok = packet::OkPacket::new(rows as u64, 0, self.0.status, 0);
+            self.0.write_ok(Some(ok)).await
+        }
+    }
+
+    #[async_trait]
+    impl Executor for Echo {
+        type Conn = EchoConn;
+        fn open(&self, session: Session) -> EchoConn {
+            EchoConn(session)
+        }
+        async fn login(&self, conn: &mut EchoConn, login: &Login) -> FrontendResult<()> {
+            if login.user != "root" || !conn.0.check_password(&login.auth, "secret") {
+                return Err(FrontendError::ProxyAuthDenied);
+            }
+            conn.0.db = login.db.clone();
+            Ok(())
+        }
+        async fn init_db(&self, conn: &mut EchoConn, db: &str) -> FrontendResult<()> {
+            conn.0.db = db.to_string();
+            conn.ok(0).await
+        }
+        async fn query(&self, conn: &mut EchoConn, sql: &str) -> FrontendResult<()> {
+            conn.ok(sql.len()).await
+        }
+        async fn field_list(&self, conn: &mut EchoConn, t: &str, w: &str) -> FrontendResult<()> {
+            conn.ok(t.len() * 10 + w.len()).await
+        }
+        async fn prepare(&self, conn: &mut EchoConn, _: &str) -> FrontendResult<()> {
+            conn.ok(0).await
+        }
+        async fn execute(&self, conn: &mut EchoConn, _: &[u8]) -> FrontendResult<()> {
+            conn.ok(0).await
+        }
+        async fn stmt(&self, conn: &mut EchoConn, _: u8, _: &[u8]) -> FrontendResult<()> {
+            conn.ok(0).await
+        }
+        async fn close(&self, _: &mut EchoConn) {}
+    }
+
+    //what a stub backend answers a query with.
+    pub(crate) enum Answer {
+        Ok,
+        Rows(ResultSet),
+        Err(u16, &'static str),
+        Close, //the conn goes away without an answer.
+    }
+
+    //a mysql backend for the tests of the proxy: it lets anyone in and answers each query, and
+    //the table of a COM_FIELD_LIST as `FIELD LIST t`, by its function. the end of a conn is told
+    //to it as `QUIT`, its answer is left out.
+    pub(crate) struct Stub(pub Box<dyn Fn(&str) -> Answer + Send + Sync>);
+
+    pub(crate) struct StubConn(Session);
+
+    impl AsMut<Session> for StubConn {
+        fn as_mut(&mut self) -> &mut Session {
+            &mut self.0
+        }
+    }
+
+    impl Stub {
+        async fn answer(&self, conn: &mut Session, sql: &str) -> FrontendResult<()> {
+            match (self.0)(sql) {
+                Answer::Ok => conn.write_ok(None).await,
+                Answer::Rows(rs) => merger::write_result_set(&mut conn.pkg, &rs)
+                    .await
+                    .map_err(|e| FrontendError::IO(io::Error::other(e.to_string()))),
+                Answer::Err(code, msg) => {
+                    conn.write_err(packet::ErrPacket::new(code, msg.to_string()))
+                        .await
+                }
+                Answer::Close => Err(FrontendError::IO(io::ErrorKind::ConnectionReset.into())),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Executor for Stub {
+        type Conn = StubConn;
+        fn open(&self, session: Session) -> StubConn {
+            StubConn(session)
+        }
+        async fn login(&self, conn: &mut StubConn, login: &Login) -> FrontendResult<()> {
+            conn.0.db = login.db.clone();
+            Ok(())
+        }
+        async fn init_db(&self, conn: &mut StubConn, db: &str) -> FrontendResult<()> {
+            conn.0.db = db.to_string();
+            conn.0.write_ok(None).await
+        }
+        async fn query(&self, conn: &mut StubConn, sql: &str) -> FrontendResult<()> {
+            self.answer(&mut conn.0, sql).await
+        }
+        async fn field_list(&self, conn: &mut StubConn, t: &str, _: &str) -> FrontendResult<()> {
+            match (self.0)(&format!("FIELD LIST {}", t)) {
+                Answer::Rows(rs) => {
+                    for c in rs.columns.iter() {
+                        conn.0.pkg.write_packet(&mut c.to_bits()).await?;
+                    }
+                    let mut eof = packet::EofPacket::new(0, conn.0.status).to_bits();
+                    Ok(conn.0.pkg.write_packet(&mut eof).await?)
+                }
+                _ => self.answer(&mut conn.0, t).await,
+            }
+        }
+        async fn prepare(&self, conn: &mut StubConn, sql: &str) -> FrontendResult<()> {
+            self.answer(&mut conn.0, sql).await
+        }
+        async fn execute(&self, conn: &mut StubConn, _: &[u8]) -> FrontendResult<()> {
+            conn.0.write_ok(None).await
+        }
+        async fn stmt(&self, _: &mut StubConn, _: u8, _: &[u8]) -> FrontendResult<()> {
+            Ok(())
+        }
+        async fn command(&self, conn: &mut StubConn, _: u8, _: &[u8]) -> FrontendResult<()> {
+            conn.0.write_ok(None).await
+        }
+        async fn close(&self, _: &mut StubConn) {
+            (self.0)("QUIT");
+        }
+    }
+
+    //a result set of text columns.
+    pub(crate) fn rows(columns: &[&str], rows: &[&[&str]]) -> ResultSet {
+        let mut rs = ResultSet::new(StatusFlags::SERVER_STATUS_AUTOCOMMIT);
+        rs.columns = columns
+            .iter()
+            .map(|c| packet::ColumnDefinition::new(c, ColumnType::MYSQL_TYPE_VAR_STRING))
+            .collect();
+        rs.rows = rows
+            .iter()
+            .map(|r| packet::TextRow::new(r.iter().map(|v| Some(v.as_bytes().to_vec())).collect()))
+            .collect();
+        rs
+    }
+
+    //a client login, the first packet the server answers it with and the salt of the server.
+    pub(crate) async fn connect(
+        addr: &str,
+        user: &str,
+        password: &str,
+    ) -> (packetio::PacketIO, Vec<u8>, Vec<u8>) {
+        let mut pkg = packetio::PacketIO::new(TcpStream::connect(addr).await.unwrap());
+        let greeting = pkg.read_packet().await.unwrap();
+        let version_end = 1 + greeting[1..].iter().position(|&b| b == 0).unwrap();
+        let mut salt = greeting[version_end + 5..version_end + 13].to_vec();
+        salt.extend_from_slice(&greeting[version_end + 32..version_end + 44]);
+
+        let caps = CapabilityFlags::CLIENT_PROTOCOL_41
+            | CapabilityFlags::CLIENT_SECURE_CONNECTION
+            | CapabilityFlags::CLIENT_CONNECT_WITH_DB;
+        let mut data = caps.bits().to_le_bytes().to_vec();
+        data.extend_from_slice(&[0u8; 28]);
+        data.extend_from_slice(user.as_bytes());
+        data.push(0);
+        let auth = scramble::scramble_native(&salt, password.as_bytes()).unwrap();
+        data.push(auth.len() as u8);
+        data.extend_from_slice(&auth);
+        data.extend_from_slice(b"db1\0");
+        pkg.write_packet(&mut data).await.unwrap();
+        let answer = pkg.read_packet().await.unwrap();
+        (pkg, answer, salt)
+    }
+
+    //a listener of `executor` on a port of its own.
+    pub(crate) async fn bind<T: Executor>(executor: T) -> Listener<T> {
+        let bound = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        Listener::bound(executor, "5.7.0-test".to_string(), bound).unwrap()
+    }
+
+    //serve `executor` on a port of its own, whose address is returned.
+    pub(crate) async fn serve<T: Executor>(executor: T) -> String {
+        let listener = bind(executor).await;
+        let address = listener.address().to_string();
+        tokio::spawn(async move { listener.listen().await });
+        address
+    }
+
+    pub(crate) async fn command(pkg: &mut packetio::PacketIO, data: &[u8]) -> Vec<u8> {
+        pkg.reset_seq();
+        pkg.write_packet(&mut data.to_vec()).await.unwrap();
+        pkg.read_packet().await.unwrap()
+    }
+
+    //the result set of a COM_QUERY, or the OK or ERR packet it was answered with.
+    pub(crate) async fn query(
+        pkg: &mut packetio::PacketIO,
+        sql: &str,
+    ) -> Result<ResultSet, Vec<u8>> {
+        let mut data = vec![command::COM_QUERY];
+        data.extend_from_slice(sql.as_bytes());
+        let first = command(pkg, &data).await;
+        if first[0] == 0x00 || first[0] == 0xff {
+            return Err(first);
+        }
+        let mut rs = ResultSet::new(StatusFlags::empty());
+        for _ in 0..first[0] {
+            let column = pkg.read_packet().await.unwrap();
+            rs.columns
+                .push(packet::ColumnDefinition::parse(&column).unwrap());
+        }
+        pkg.read_packet().await.unwrap();
+        loop {
+            let row = pkg.read_packet().await.unwrap();
+            if packet::is_eof_packet(&row) {
+                return Ok(rs);
+            }
+            rs.rows
+                .push(packet::TextRow::parse(&row, rs.columns.len()).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn listener_serves_executor() {
+        let mut addrs = Vec::new();
+        let mut listeners: Vec<Box<dyn crate::proto::interface::Listener>> = Vec::new();
+        for _ in 0..2 {
+            let listener = bind(Echo).await;
+            addrs.push(listener.address().to_string());
+            listeners.push(Box::new(listener));
+        }
+        let server = Arc::new(Server::new(listeners));
+        let running = {
+            let server = server.clone();
+            tokio::spawn(async move { server.start().await })
+        };
+
+        let (_, refused, _) = connect(&addrs[0], "root", "wrong").await;
+        assert_eq!(refused[0], 0xff);
+        assert_eq!(u16::from_le_bytes([refused[1], refused[2]]), 1045);
+
+        for addr in addrs.iter() {
+            let (mut pkg, ok, _) = connect(addr, "root", "secret").await;
+            assert_eq!(ok[0], 0x00);
+            let mut query = vec![command::COM_QUERY];
+            query.extend_from_slice(b"select 1");
+            assert_eq!(command(&mut pkg, &query).await[..2], [0x00, 8]);
+            let field_list = [command::COM_FIELD_LIST, b't', 0, b'%'];
+            assert_eq!(command(&mut pkg, &field_list).await[..2], [0x00, 11]);
+            assert_eq!(command(&mut pkg, &[0x7f]).await[0], 0xff);
+            pkg.reset_seq();
+            pkg.write_packet(&mut [command::COM_QUIT]).await.unwrap();
+        }
+
+        server.close();
+        let rc = tokio::time::timeout(std::time::Duration::from_secs(5), running).await;
+        rc.unwrap().unwrap().unwrap();
+        assert_eq!(server.listeners[1].address(), addrs[1]);
     }
 }
@@ -7,6 +7,11 @@ lazy_static::lazy_static! {
    pub  static ref MY_SQLSTATE: HashMap<u16, &'static str> = {
         let mut m = HashMap::new();
         m.insert(ER_HANDSHAKE_ERROR, "08S01");
+        m.insert(ER_NO_DB_ERROR, "3D000");
+        m.insert(ER_UNKNOWN_COM_ERROR, "08S01");
+        m.insert(ER_BAD_DB_ERROR, "42000");
+        m.insert(ER_PARSE_ERROR, "42000");
+        m.insert(ER_NOT_SUPPORTED_YET, "42000");
         m
     };
 }
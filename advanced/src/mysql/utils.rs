@@ -5,6 +5,8 @@ use rand::{thread_rng, Rng};
 use sha1::Sha1;
 use std::sync::atomic::{AtomicU32, Ordering};
 
+const LENGTH_ENCODED_NULL: u8 = 0xfb;
+
 pub fn generate_id() -> u32 {
     static COUNTER: AtomicU32 = AtomicU32::new(1);
     COUNTER.fetch_add(1, Ordering::Relaxed)
@@ -101,3 +103,49 @@ pub fn read_length_encoded_int(data: &[u8]) -> (usize, u64) {
         (0, 0)
     }
 }
+// Reads MySql's length-encoded string, 0xfb is a NULL in text rows rather than the integer 251.
+// Zero bytes consumed means the data is truncated.
+pub fn read_length_encoded_string(data: &[u8]) -> (usize, Option<Vec<u8>>) {
+    if data.first() == Some(&LENGTH_ENCODED_NULL) {
+        return (1, None);
+    }
+    let (n, len) = read_length_encoded_int(data);
+    //a length of the client may be anything, the end must not overflow.
+    let end = usize::try_from(len).ok().and_then(|len| n.checked_add(len));
+    match end {
+        Some(end) if n > 0 && end <= data.len() => (end, Some(data[n..end].to_vec())),
+        _ => (0, None),
+    }
+}
+// Writes MySql's length-encoded string, None is written as NULL.
+pub fn write_length_encoded_string(s: Option<&[u8]>) -> Vec<u8> {
+    match s {
+        None => vec![LENGTH_ENCODED_NULL],
+        Some(s) => {
+            let mut encoded_s = write_length_encoded_int(s.len() as u64);
+            encoded_s.extend_from_slice(s);
+            encoded_s
+        }
+    }
+}
+// Quotes bytes as a sql string literal by doubling the quotes, which reads the same whether or not
+// NO_BACKSLASH_ESCAPES is set. the bytes which are not utf-8 or have a backslash are written as X'..'.
+pub fn quote_sql_string(s: &[u8]) -> String {
+    let s = match std::str::from_utf8(s) {
+        Ok(s) if !s.contains('\\') => s,
+        _ => {
+            let hex: String = s.iter().map(|b| format!("{:02X}", b)).collect();
+            return format!("X'{}'", hex);
+        }
+    };
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('\'');
+    for c in s.chars() {
+        if c == '\'' {
+            quoted.push('\'');
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
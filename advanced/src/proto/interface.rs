@@ -1,19 +1,86 @@
-use crate::config::Filter;
+#![allow(dead_code)]
+use crate::analyzer::plan::Plan;
+use crate::filter::{QueryContext, QueryOutcome};
+use crate::frontend::errors::{FrontendError, FrontendResult};
+use crate::mysql::errors::MySQLError;
+use crate::mysql::server::{Login, Session};
+use async_trait::async_trait;
 use std::error::Error;
 
-pub trait FilterFactory {
-    fn new_filter(&self, config: String) -> Result<Filter, Box<dyn Error>>;
+//makes the filter of a name of the registry, from the `config` string of its entry.
+pub trait FilterFactory: Send + Sync {
+    fn new_filter(&self, config: String) -> Result<Box<dyn QueryFilter>, Box<dyn Error>>;
 }
 
-pub trait Listener {
-    fn set_executor(&self, executor: Box<dyn Executor>);
+//the hooks of a filter around a COM_QUERY, a hook which is not there lets the query through.
+//an error rejects the query with it as the why.
+pub trait QueryFilter: std::fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+    //before the sql is parsed, it may be changed.
+    fn pre_parse(&self, _ctx: &QueryContext, _sql: &mut String) -> Result<(), String> {
+        Ok(())
+    }
+    //after the sql is routed, the sql of each shard may be changed.
+    fn post_route(&self, _ctx: &QueryContext, _plan: &mut Plan) -> Result<(), String> {
+        Ok(())
+    }
+    //after the answer is written to the client.
+    fn post_result(&self, _ctx: &QueryContext, _outcome: &QueryOutcome) {}
+}
+
+//a server of client conns on one socket address.
+#[async_trait]
+pub trait Listener: Send + Sync {
+    fn address(&self) -> &str;
 
-    fn listen(&self);
+    //accept client conns till it is closed.
+    async fn listen(&self) -> std::io::Result<()>;
 
     fn close(&self);
 }
 
-// Executor
-pub trait Executor {
-    // TODO
+//what the commands of a client conn do, once the listener has read them. the listener runs the
+//handshake and the command loop, the executor answers the commands on the session of the conn.
+#[async_trait]
+pub trait Executor: Send + Sync + 'static {
+    //the state of one client conn, it owns the session.
+    type Conn: AsMut<Session> + Send;
+
+    //the conn of a session whose handshake is read.
+    fn open(&self, session: Session) -> Self::Conn;
+
+    //check the login of the handshake, an error refuses it.
+    async fn login(&self, conn: &mut Self::Conn, login: &Login) -> FrontendResult<()>;
+
+    //COM_INIT_DB
+    async fn init_db(&self, conn: &mut Self::Conn, db: &str) -> FrontendResult<()>;
+
+    //COM_QUERY
+    async fn query(&self, conn: &mut Self::Conn, sql: &str) -> FrontendResult<()>;
+
+    //COM_FIELD_LIST, the columns of `table` whose names are like `wildcard`.
+    async fn field_list(
+        &self,
+        conn: &mut Self::Conn,
+        table: &str,
+        wildcard: &str,
+    ) -> FrontendResult<()>;
+
+    //COM_STMT_PREPARE
+    async fn prepare(&self, conn: &mut Self::Conn, sql: &str) -> FrontendResult<()>;
+
+    //COM_STMT_EXECUTE, `data` is the packet without the command byte.
+    async fn execute(&self, conn: &mut Self::Conn, data: &[u8]) -> FrontendResult<()>;
+
+    //COM_STMT_SEND_LONG_DATA, COM_STMT_CLOSE and COM_STMT_RESET.
+    async fn stmt(&self, conn: &mut Self::Conn, command: u8, data: &[u8]) -> FrontendResult<()>;
+
+    //any other command, such as COM_PING or COM_CHANGE_USER, `data` is without the command byte.
+    async fn command(&self, conn: &mut Self::Conn, command: u8, data: &[u8]) -> FrontendResult<()> {
+        let _ = (conn, command, data);
+        Err(FrontendError::MySQLErr(MySQLError::ErUnknownCmd))
+    }
+
+    //the client quit or went away.
+    async fn close(&self, conn: &mut Self::Conn);
 }
@@ -0,0 +1,355 @@
+#![allow(dead_code)]
+//the virtual table of a sharding rule: a logical table spread over physical dbs and tables by
+//the rules of its shard columns.
+use crate::config::config_model::{Rule, Table, Topology};
+use regex::Regex;
+use std::collections::HashMap;
+
+pub mod error;
+pub mod expr;
+pub mod hash;
+pub mod ring;
+use error::{RuleError, RuleResult};
+use expr::RuleExpr;
+use hash::HashFn;
+
+//`student_${0000...0031}`: the names of a prefix, a zero padded number in a range and a suffix,
+//or one name alone.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    prefix: String,
+    suffix: String,
+    range: Option<(u32, u32)>,
+    width: usize,
+}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> RuleResult<Pattern> {
+        let range_regex = Regex::new(r"^(.*)\$\{\s*(\d+)\s*\.{2,3}\s*(\d+)\s*\}(.*)$").unwrap();
+        let pattern = pattern.trim();
+        let captures = match range_regex.captures(pattern) {
+            Some(c) => c,
+            None if !pattern.is_empty() && !pattern.contains('$') => {
+                return Ok(Pattern {
+                    prefix: pattern.to_string(),
+                    suffix: String::new(),
+                    range: None,
+                    width: 0,
+                })
+            }
+            None => return Err(RuleError::PatternILL(pattern.to_string())),
+        };
+        let ill = || RuleError::PatternILL(pattern.to_string());
+        let start = captures[2].parse::<u32>().map_err(|_| ill())?;
+        let end = captures[3].parse::<u32>().map_err(|_| ill())?;
+        if start > end {
+            return Err(ill());
+        }
+        Ok(Pattern {
+            prefix: captures[1].to_string(),
+            suffix: captures[4].to_string(),
+            range: Some((start, end)),
+            width: captures[2].len(),
+        })
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self.range {
+            Some((start, end)) => (end - start) as usize + 1,
+            None => 1,
+        }
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+    //the number of the `n`th name, from 0.
+    #[inline]
+    pub fn number_at(&self, n: usize) -> Option<i128> {
+        match self.range {
+            Some((start, _)) if n < self.len() => Some(start as i128 + n as i128),
+            None if n == 0 => Some(0),
+            _ => None,
+        }
+    }
+    //the position of the name numbered `number`.
+    #[inline]
+    pub fn position(&self, number: i128) -> Option<usize> {
+        match self.range {
+            Some((start, end)) if number >= start as i128 && number <= end as i128 => {
+                Some((number - start as i128) as usize)
+            }
+            None => Some(0),
+            _ => None,
+        }
+    }
+    //the name of the position `n`.
+    pub fn name(&self, n: usize) -> Option<String> {
+        let number = self.number_at(n)?;
+        Some(match self.range {
+            Some(_) => format!(
+                "{}{:0width$}{}",
+                self.prefix,
+                number,
+                self.suffix,
+                width = self.width
+            ),
+            None => self.prefix.clone(),
+        })
+    }
+    pub fn names(&self) -> Vec<String> {
+        (0..self.len()).filter_map(|n| self.name(n)).collect()
+    }
+}
+
+//the physical tables of a virtual table, spread evenly over its dbs in order.
+#[derive(Debug, Clone)]
+pub struct VTopology {
+    dbs: Pattern,
+    tables: Pattern,
+}
+
+impl VTopology {
+    pub fn new(topology: &Topology) -> RuleResult<VTopology> {
+        let dbs = Pattern::parse(&topology.db_pattern)?;
+        let tables = Pattern::parse(&topology.tbl_pattern)?;
+        if tables.len() < dbs.len() {
+            return Err(RuleError::TopologyILL(format!(
+                "{} tables over {} dbs",
+                tables.len(),
+                dbs.len()
+            )));
+        }
+        Ok(VTopology { dbs, tables })
+    }
+    #[inline]
+    pub fn dbs(&self) -> &Pattern {
+        &self.dbs
+    }
+    #[inline]
+    pub fn tables(&self) -> &Pattern {
+        &self.tables
+    }
+    #[inline]
+    fn tables_per_db(&self) -> usize {
+        self.tables.len().div_ceil(self.dbs.len())
+    }
+    //the db position of the table at position `n`.
+    #[inline]
+    pub fn db_of_table(&self, n: usize) -> usize {
+        n / self.tables_per_db()
+    }
+    //(db, its tables) in order.
+    pub fn topology(&self) -> Vec<(String, Vec<String>)> {
+        let per_db = self.tables_per_db();
+        let tables = self.tables.names();
+        self.dbs
+            .names()
+            .into_iter()
+            .zip(tables.chunks(per_db).map(<[String]>::to_vec))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShardRule {
+    pub column: String,
+    pub expr: RuleExpr,
+}
+
+impl ShardRule {
+    pub fn new(rule: &Rule) -> RuleResult<ShardRule> {
+        Ok(ShardRule {
+            column: rule.column.clone(),
+            expr: RuleExpr::compile(&rule.expr)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VTable {
+    schema: String,
+    name: String,
+    allow_full_scan: bool,
+    db_rules: Vec<ShardRule>,
+    tbl_rules: Vec<ShardRule>,
+    topology: VTopology,
+    shadow_topology: Option<VTopology>,
+    attributes: HashMap<String, String>,
+}
+
+impl VTable {
+    //`schema.table` of the config, with its rules compiled and its patterns parsed.
+    pub fn new(table: &Table) -> RuleResult<VTable> {
+        let (schema, name) = match table.name.split_once('.') {
+            Some((s, n)) if !s.is_empty() && !n.is_empty() => (s, n),
+            _ => return Err(RuleError::TableNameILL(table.name.clone())),
+        };
+        //the `hash` attribute tells what hash() of the rules is, rules calling it must have one.
+        let hash = match table.attributes.get("hash") {
+            Some(h) => Some(HashFn::parse(h).ok_or_else(|| RuleError::HashILL(h.clone()))?),
+            None => None,
+        };
+        let compile = |rules: &Option<Vec<Rule>>| -> RuleResult<Vec<ShardRule>> {
+            rules
+                .iter()
+                .flatten()
+                .map(|r| {
+                    let mut rule = ShardRule::new(r)?;
+                    rule.expr = match hash {
+                        Some(h) => rule.expr.with_hash(h),
+                        None if rule.expr.uses_hash() => {
+                            return Err(RuleError::HashNotNamed(table.name.clone()))
+                        }
+                        None => rule.expr,
+                    };
+                    Ok(rule)
+                })
+                .collect()
+        };
+        Ok(VTable {
+            schema: schema.to_string(),
+            name: name.to_string(),
+            allow_full_scan: table.allow_full_scan,
+            db_rules: compile(&table.db_rules)?,
+            tbl_rules: compile(&table.tbl_rules)?,
+            topology: VTopology::new(&table.topology)?,
+            shadow_topology: match &table.shadow_topology {
+                Some(t) => Some(VTopology::new(t)?),
+                None => None,
+            },
+            attributes: table.attributes.clone(),
+        })
+    }
+    #[inline]
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    #[inline]
+    pub fn allow_full_scan(&self) -> bool {
+        self.allow_full_scan
+    }
+    #[inline]
+    pub fn db_rules(&self) -> &[ShardRule] {
+        &self.db_rules
+    }
+    #[inline]
+    pub fn tbl_rules(&self) -> &[ShardRule] {
+        &self.tbl_rules
+    }
+    #[inline]
+    pub fn topology(&self) -> &VTopology {
+        &self.topology
+    }
+    #[inline]
+    pub fn shadow_topology(&self) -> Option<&VTopology> {
+        self.shadow_topology.as_ref()
+    }
+    #[inline]
+    pub fn attributes(&self) -> &HashMap<String, String> {
+        &self.attributes
+    }
+    #[inline]
+    pub fn is_shard_column(&self, column: &str) -> bool {
+        self.db_rules
+            .iter()
+            .chain(self.tbl_rules.iter())
+            .any(|r| r.column.eq_ignore_ascii_case(column))
+    }
+    //(db, table) of the value of a shard column, the table rule gives the table number and the
+    //db rule, if any, the db number, else the db is the one the table is spread to.
+    //none when the column has no table rule.
+    pub fn shard(&self, column: &str, value: &str) -> RuleResult<Option<(String, String)>> {
+        let rule_of = |rules: &[ShardRule]| {
+            rules
+                .iter()
+                .find(|r| r.column.eq_ignore_ascii_case(column))
+                .cloned()
+        };
+        let tbl_rule = match rule_of(&self.tbl_rules) {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+        let out_of = |what: &str, n: i128| {
+            RuleError::ExprEvalErr(
+                tbl_rule.expr.source().to_string(),
+                format!("{} {} is out of the topology", what, n),
+            )
+        };
+        let tables = self.topology.tables();
+        let t_num = tbl_rule.expr.eval_int(value, &[])?;
+        let t_pos = tables
+            .position(t_num)
+            .ok_or_else(|| out_of("table", t_num))?;
+        let dbs = self.topology.dbs();
+        let d_pos = match rule_of(&self.db_rules) {
+            Some(r) => {
+                let d_num = r.expr.eval_int(value, &[])?;
+                dbs.position(d_num).ok_or_else(|| out_of("db", d_num))?
+            }
+            None => self.topology.db_of_table(t_pos),
+        };
+        match (dbs.name(d_pos), tables.name(t_pos)) {
+            (Some(db), Some(table)) => Ok(Some((db, table))),
+            _ => Err(out_of("db", d_pos as i128)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pattern, VTable};
+    use crate::config::config_model::Table;
+
+    #[test]
+    fn build_vtable() {
+        let p = Pattern::parse("student_${0000...0031}").unwrap();
+        assert_eq!(
+            (p.len(), p.name(3).unwrap()),
+            (32, "student_0003".to_string())
+        );
+        assert_eq!(
+            Pattern::parse("db").unwrap().names(),
+            vec!["db".to_string()]
+        );
+        assert!(Pattern::parse("t_${9...1}").is_err());
+
+        let table: Table = serde_yaml::from_str(
+            r#"
+name: employees.student
+allow_full_scan: true
+db_rules:
+  - column: uid
+    expr: $value % 32 / 8
+tbl_rules:
+  - column: uid
+    expr: lpad($value % 32, 4, '0')
+topology:
+  db_pattern: employees_${0000...0003}
+  tbl_pattern: student_${0000...0031}
+attributes:
+  sqlMaxLimit: "-1"
+"#,
+        )
+        .unwrap();
+        let vt = VTable::new(&table).unwrap();
+        assert_eq!((vt.schema(), vt.name()), ("employees", "student"));
+        let topology = vt.topology().topology();
+        assert_eq!(topology.len(), 4);
+        assert_eq!(topology[1].0, "employees_0001");
+        assert_eq!(topology[1].1[0], "student_0008");
+        assert_eq!(
+            vt.shard("UID", "77").unwrap(),
+            Some(("employees_0001".to_string(), "student_0013".to_string()))
+        );
+        assert_eq!(
+            vt.shard("uid", "-1").unwrap(),
+            Some(("employees_0003".to_string(), "student_0031".to_string()))
+        );
+        assert_eq!(vt.shard("name", "1").unwrap(), None);
+    }
+}
@@ -0,0 +1,41 @@
+#![allow(dead_code)]
+pub type RuleResult<T> = std::result::Result<T, RuleError>;
+
+#[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Clone)]
+pub enum RuleError {
+    ExprILL(String, String), //(expr, why)
+    ExprEvalErr(String, String),
+    HashILL(String),
+    HashNotNamed(String), //a table whose rules call hash() without naming the hash function.
+    PatternILL(String),
+    TableNameILL(String),
+    TopologyILL(String),
+}
+
+impl std::fmt::Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleError::ExprILL(expr, why) => {
+                write!(f, "RuleError::ExprILL: {:?}, {}", expr, why)
+            }
+            RuleError::ExprEvalErr(expr, why) => {
+                write!(f, "RuleError::ExprEvalErr: {:?}, {}", expr, why)
+            }
+            RuleError::HashILL(h) => write!(f, "RuleError::HashILL: {:?}", h),
+            RuleError::HashNotNamed(t) => write!(
+                f,
+                "RuleError::HashNotNamed: {:?} calls hash(), name it by hash = \"crc32\", \"murmur3\", \"xxhash\" or \"std\"",
+                t
+            ),
+            RuleError::PatternILL(p) => write!(f, "RuleError::PatternILL: {:?}", p),
+            RuleError::TableNameILL(t) => write!(f, "RuleError::TableNameILL: {:?}", t),
+            RuleError::TopologyILL(s) => write!(f, "RuleError::TopologyILL: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
@@ -0,0 +1,510 @@
+#![allow(dead_code)]
+//the expression of a sharding rule, such as `$value % 32` or `lpad(month($value), 2, '0')`:
+//integers, strings and a few functions over the value of the shard column, compiled once
+//when the rules are loaded, so a bad one never reaches routing.
+use super::error::{RuleError, RuleResult};
+use super::hash::{self, HashFn};
+use chrono::{format::Item, format::StrftimeItems, Datelike};
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use std::fmt::Write;
+
+//a shard value comes as its SQL literal, it is taken as an integer where one is needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Int(i128),
+    Str(String),
+}
+
+impl Value {
+    pub fn to_int(&self) -> Option<i128> {
+        match self {
+            Value::Int(i) => Some(*i),
+            Value::Str(s) => s.trim().parse::<i128>().ok(),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Str(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Func {
+    Hash,       //hash(x): the hash function of the table over the text of x.
+    Crc32,      //crc32(x)
+    Murmur3,    //murmur3(x)
+    XxHash,     //xxhash(x)
+    Substr,     //substr(s, pos[, len]): 1 based, a negative pos counts from the end.
+    Lpad,       //lpad(s, len, pad)
+    Concat,     //concat(x, ...)
+    Year,       //year(d), d is a DATE or DATETIME literal.
+    Month,      //month(d)
+    Day,        //day(d)
+    Hour,       //hour(d)
+    DateFormat, //date_format(d, fmt), fmt is of strftime.
+    Range,      //range(x, start, end, ...): the position of the [start, end) holding x.
+}
+
+impl Func {
+    //(func, min args, max args)
+    fn lookup(name: &str) -> Option<(Func, usize, usize)> {
+        let found = match name.to_ascii_lowercase().as_str() {
+            "hash" => (Func::Hash, 1, 1),
+            "crc32" => (Func::Crc32, 1, 1),
+            "murmur3" => (Func::Murmur3, 1, 1),
+            "xxhash" => (Func::XxHash, 1, 1),
+            "substr" | "substring" => (Func::Substr, 2, 3),
+            "lpad" => (Func::Lpad, 3, 3),
+            "concat" => (Func::Concat, 1, usize::MAX),
+            "year" => (Func::Year, 1, 1),
+            "month" => (Func::Month, 1, 1),
+            "day" => (Func::Day, 1, 1),
+            "hour" => (Func::Hour, 1, 1),
+            "date_format" => (Func::DateFormat, 2, 2),
+            "range" => (Func::Range, 3, usize::MAX),
+            _ => return None,
+        };
+        Some(found)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Var(usize), //0 is `$value`, then the extra variables in order.
+    Int(i128),
+    Str(String),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleExpr {
+    src: String,
+    expr: Expr,
+    hash: Option<HashFn>, //none until the table names one, hash() can not be evaluated then.
+}
+
+impl RuleExpr {
+    //only `$value` can be used.
+    pub fn compile(src: &str) -> RuleResult<RuleExpr> {
+        RuleExpr::compile_with(src, &[])
+    }
+    //`$value` and the extra variables, which eval_with is given in the same order.
+    pub fn compile_with(src: &str, vars: &[&str]) -> RuleResult<RuleExpr> {
+        let mut parser = Parser {
+            src,
+            bytes: src.as_bytes(),
+            pos: 0,
+            vars,
+        };
+        let expr = parser.expr()?;
+        parser.skip_spaces();
+        if parser.pos < parser.bytes.len() {
+            return Err(parser.err("unexpected trailing input"));
+        }
+        Ok(RuleExpr {
+            src: src.to_string(),
+            expr,
+            hash: None,
+        })
+    }
+    //what `hash()` stands for.
+    pub fn with_hash(mut self, hash: HashFn) -> RuleExpr {
+        self.hash = Some(hash);
+        self
+    }
+    #[inline]
+    pub fn hash_fn(&self) -> Option<HashFn> {
+        self.hash
+    }
+    //whether `hash()` is called, its table must name the hash function then.
+    pub fn uses_hash(&self) -> bool {
+        fn calls_hash(e: &Expr) -> bool {
+            match e {
+                Expr::Call(Func::Hash, _) => true,
+                Expr::Call(_, args) => args.iter().any(calls_hash),
+                Expr::Neg(e) => calls_hash(e),
+                Expr::Binary(_, l, r) => calls_hash(l) || calls_hash(r),
+                Expr::Var(_) | Expr::Int(_) | Expr::Str(_) => false,
+            }
+        }
+        calls_hash(&self.expr)
+    }
+    #[inline]
+    pub fn source(&self) -> &str {
+        &self.src
+    }
+    #[inline]
+    pub fn eval(&self, value: &str) -> RuleResult<Value> {
+        self.eval_with(value, &[])
+    }
+    pub fn eval_with(&self, value: &str, vars: &[i128]) -> RuleResult<Value> {
+        self.eval_expr(&self.expr, value, vars)
+    }
+    //the result as a shard number, a padded text such as `0003` is the number 3.
+    pub fn eval_int(&self, value: &str, vars: &[i128]) -> RuleResult<i128> {
+        let v = self.eval_with(value, vars)?;
+        v.to_int()
+            .ok_or_else(|| self.eval_err(format!("{:?} is not an integer", v.to_string())))
+    }
+
+    fn eval_err(&self, why: String) -> RuleError {
+        RuleError::ExprEvalErr(self.src.clone(), why)
+    }
+    fn int_of(&self, v: &Value) -> RuleResult<i128> {
+        v.to_int()
+            .ok_or_else(|| self.eval_err(format!("{:?} is not an integer", v.to_string())))
+    }
+    fn datetime_of(&self, v: &Value) -> RuleResult<NaiveDateTime> {
+        let s = v.to_string();
+        let s = s.trim();
+        for fmt in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+            if let Ok(dt) = NaiveDateTime::parse_from_str(s, fmt) {
+                return Ok(dt);
+            }
+        }
+        for fmt in ["%Y-%m-%d", "%Y%m%d"] {
+            if let Some(dt) = NaiveDate::parse_from_str(s, fmt)
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+            {
+                return Ok(dt);
+            }
+        }
+        Err(self.eval_err(format!("{:?} is not a date", s)))
+    }
+    fn eval_expr(&self, expr: &Expr, value: &str, vars: &[i128]) -> RuleResult<Value> {
+        let overflow = || self.eval_err("overflow".to_string());
+        match expr {
+            Expr::Var(0) => Ok(Value::Str(value.to_string())),
+            Expr::Var(i) => vars
+                .get(*i - 1)
+                .map(|v| Value::Int(*v))
+                .ok_or_else(|| self.eval_err(format!("variable {} is not given", i))),
+            Expr::Int(i) => Ok(Value::Int(*i)),
+            Expr::Str(s) => Ok(Value::Str(s.clone())),
+            Expr::Neg(e) => {
+                let v = self.eval_expr(e, value, vars)?;
+                Ok(Value::Int(
+                    self.int_of(&v)?.checked_neg().ok_or_else(overflow)?,
+                ))
+            }
+            Expr::Binary(op, l, r) => {
+                let l = self.int_of(&self.eval_expr(l, value, vars)?)?;
+                let r = self.int_of(&self.eval_expr(r, value, vars)?)?;
+                if r == 0 && matches!(op, Op::Div | Op::Mod) {
+                    return Err(self.eval_err("division by zero".to_string()));
+                }
+                //`%` and `/` are euclidean, a negative value still lands on a shard.
+                let v = match op {
+                    Op::Add => l.checked_add(r),
+                    Op::Sub => l.checked_sub(r),
+                    Op::Mul => l.checked_mul(r),
+                    Op::Div => l.checked_div_euclid(r),
+                    Op::Mod => l.checked_rem_euclid(r),
+                };
+                Ok(Value::Int(v.ok_or_else(overflow)?))
+            }
+            Expr::Call(func, args) => {
+                let args = args
+                    .iter()
+                    .map(|a| self.eval_expr(a, value, vars))
+                    .collect::<RuleResult<Vec<Value>>>()?;
+                self.call(*func, &args)
+            }
+        }
+    }
+    fn call(&self, func: Func, args: &[Value]) -> RuleResult<Value> {
+        Ok(match func {
+            Func::Hash => match self.hash {
+                Some(h) => Value::Int(h.hash(&args[0].to_string()) as i128),
+                None => return Err(self.eval_err("no hash function is named".to_string())),
+            },
+            Func::Crc32 => Value::Int(hash::crc32(args[0].to_string().as_bytes()) as i128),
+            Func::Murmur3 => Value::Int(HashFn::Murmur3.hash(&args[0].to_string()) as i128),
+            Func::XxHash => Value::Int(HashFn::XxHash.hash(&args[0].to_string()) as i128),
+            Func::Substr => {
+                let chars: Vec<char> = args[0].to_string().chars().collect();
+                let (pos, n) = (self.int_of(&args[1])?, chars.len() as i128);
+                let start = match pos {
+                    p if p > 0 => p - 1,
+                    p if p < 0 => n + p,
+                    _ => n,
+                };
+                let start = if (0..n).contains(&start) {
+                    start as usize
+                } else {
+                    chars.len()
+                };
+                let len = match args.get(2) {
+                    Some(l) => self.int_of(l)?.max(0) as usize,
+                    None => chars.len(),
+                };
+                Value::Str(chars[start..].iter().take(len).collect())
+            }
+            Func::Lpad => {
+                let s: Vec<char> = args[0].to_string().chars().collect();
+                let len = self.int_of(&args[1])?.max(0) as usize;
+                let pad: Vec<char> = args[2].to_string().chars().collect();
+                if s.len() >= len {
+                    Value::Str(s[..len].iter().collect())
+                } else if pad.is_empty() {
+                    return Err(self.eval_err("lpad with an empty pad".to_string()));
+                } else {
+                    let fill = len - s.len();
+                    let mut out: String = pad.iter().cycle().take(fill).collect();
+                    out.extend(s.iter());
+                    Value::Str(out)
+                }
+            }
+            Func::Concat => Value::Str(args.iter().map(Value::to_string).collect()),
+            Func::Year => Value::Int(self.datetime_of(&args[0])?.year() as i128),
+            Func::Month => Value::Int(self.datetime_of(&args[0])?.month() as i128),
+            Func::Day => Value::Int(self.datetime_of(&args[0])?.day() as i128),
+            Func::Hour => Value::Int(self.datetime_of(&args[0])?.hour() as i128),
+            Func::DateFormat => {
+                let dt = self.datetime_of(&args[0])?;
+                let mut out = String::new();
+                write!(out, "{}", dt.format(&args[1].to_string()))
+                    .map_err(|_| self.eval_err("illegal date format".to_string()))?;
+                Value::Str(out)
+            }
+            Func::Range => {
+                let x = self.int_of(&args[0])?;
+                let mut bounds = Vec::with_capacity(args.len() - 1);
+                for a in args[1..].iter() {
+                    bounds.push(self.int_of(a)?);
+                }
+                match bounds.chunks(2).position(|r| x >= r[0] && x < r[1]) {
+                    Some(pos) => Value::Int(pos as i128),
+                    None => return Err(self.eval_err(format!("{} is not in any range", x))),
+                }
+            }
+        })
+    }
+}
+
+//expr := term (('+' | '-') term)*, term := unary (('*' | '/' | '%') unary)*,
+//unary := '-' unary | atom,
+//atom := integer | 'string' | $var | func '(' expr (',' expr)* ')' | '(' expr ')'.
+struct Parser<'a> {
+    src: &'a str,
+    bytes: &'a [u8],
+    pos: usize,
+    vars: &'a [&'a str],
+}
+
+impl<'a> Parser<'a> {
+    fn err(&self, why: &str) -> RuleError {
+        RuleError::ExprILL(self.src.to_string(), format!("{} at {}", why, self.pos))
+    }
+    fn skip_spaces(&mut self) {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_spaces();
+        self.bytes.get(self.pos).copied()
+    }
+    fn ident(&mut self) -> &'a str {
+        let start = self.pos;
+        while self.pos < self.bytes.len()
+            && (self.bytes[self.pos].is_ascii_alphanumeric() || self.bytes[self.pos] == b'_')
+        {
+            self.pos += 1;
+        }
+        &self.src[start..self.pos]
+    }
+    fn expr(&mut self) -> RuleResult<Expr> {
+        let mut left = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some(b'+') => Op::Add,
+                Some(b'-') => Op::Sub,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+    }
+    fn term(&mut self) -> RuleResult<Expr> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(b'*') => Op::Mul,
+                Some(b'/') => Op::Div,
+                Some(b'%') => Op::Mod,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+    fn unary(&mut self) -> RuleResult<Expr> {
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+    fn atom(&mut self) -> RuleResult<Expr> {
+        match self.peek() {
+            Some(b'(') => {
+                self.pos += 1;
+                let e = self.expr()?;
+                self.expect(b')')?;
+                Ok(e)
+            }
+            Some(b'$') => {
+                self.pos += 1;
+                let name = self.ident();
+                if name == "value" {
+                    return Ok(Expr::Var(0));
+                }
+                match self.vars.iter().position(|v| *v == name) {
+                    Some(i) => Ok(Expr::Var(i + 1)),
+                    None => Err(self.err(&format!("unknown variable ${}", name))),
+                }
+            }
+            Some(b'\'') => {
+                //'' in a string is a quote.
+                self.pos += 1;
+                let mut s = String::new();
+                loop {
+                    match self.src[self.pos..].find('\'') {
+                        None => return Err(self.err("unterminated string")),
+                        Some(end) => {
+                            s.push_str(&self.src[self.pos..self.pos + end]);
+                            self.pos += end + 1;
+                            if self.bytes.get(self.pos) == Some(&b'\'') {
+                                s.push('\'');
+                                self.pos += 1;
+                            } else {
+                                return Ok(Expr::Str(s));
+                            }
+                        }
+                    }
+                }
+            }
+            Some(b) if b.is_ascii_digit() => {
+                let start = self.pos;
+                while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_digit() {
+                    self.pos += 1;
+                }
+                self.src[start..self.pos]
+                    .parse::<i128>()
+                    .map(Expr::Int)
+                    .map_err(|_| self.err("integer out of range"))
+            }
+            Some(b) if b.is_ascii_alphabetic() => {
+                let name = self.ident();
+                let (func, min, max) = Func::lookup(name)
+                    .ok_or_else(|| self.err(&format!("unknown function {}", name)))?;
+                self.expect(b'(')?;
+                let mut args = vec![self.expr()?];
+                while self.peek() == Some(b',') {
+                    self.pos += 1;
+                    args.push(self.expr()?);
+                }
+                self.expect(b')')?;
+                if args.len() < min || args.len() > max {
+                    return Err(self.err(&format!("wrong argument count of {}", name)));
+                }
+                self.check_args(func, &args)?;
+                Ok(Expr::Call(func, args))
+            }
+            _ => Err(self.err("expect an integer, a string, a variable, a function or '('")),
+        }
+    }
+    fn expect(&mut self, b: u8) -> RuleResult<()> {
+        if self.peek() != Some(b) {
+            return Err(self.err(&format!("expect '{}'", b as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+    //what can be told before any value comes.
+    fn check_args(&self, func: Func, args: &[Expr]) -> RuleResult<()> {
+        match (func, args) {
+            (Func::Range, args) if args.len() % 2 == 0 => {
+                Err(self.err("range needs pairs of start and end"))
+            }
+            (Func::DateFormat, [_, Expr::Str(fmt)])
+                if StrftimeItems::new(fmt).any(|i| matches!(i, Item::Error)) =>
+            {
+                Err(self.err(&format!("illegal date format {:?}", fmt)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HashFn, RuleExpr, Value};
+
+    #[test]
+    fn compile_and_eval() {
+        let eval = |src: &str, v: &str| RuleExpr::compile(src).unwrap().eval(v).unwrap();
+        assert_eq!(eval("$value % 32", "-1"), Value::Int(31));
+        assert_eq!(eval("($value + 3) * 2 / 4", "7"), Value::Int(5));
+        assert_eq!(eval("crc32($value)", "hello"), Value::Int(0x3610_a686));
+        assert_eq!(
+            eval("lpad($value % 32, 4, '0')", "77"),
+            Value::Str("0013".to_string())
+        );
+        assert_eq!(
+            eval("substr($value, -3, 2)", "abcdef"),
+            Value::Str("de".to_string())
+        );
+        assert_eq!(
+            eval("date_format($value, '%Y%m')", "2023-01-15 10:00:00"),
+            Value::Str("202301".to_string())
+        );
+        assert_eq!(eval("month($value) - 1", "2023-12-01"), Value::Int(11));
+        assert_eq!(eval("range($value, 0, 10, 10, 20)", "15"), Value::Int(1));
+
+        let tables = RuleExpr::compile_with("hash($value) % $tables", &["tables"]).unwrap();
+        assert!(tables.uses_hash());
+        assert!(tables.eval_with("k", &[7]).is_err());
+        let tables = tables.with_hash(HashFn::XxHash);
+        assert!(
+            matches!(tables.eval_with("k", &[7]).unwrap(), Value::Int(i) if (0..7).contains(&i))
+        );
+        assert!(!RuleExpr::compile("crc32($value) % 4").unwrap().uses_hash());
+        assert!(RuleExpr::compile("$value % 0").unwrap().eval("1").is_err());
+        assert!(RuleExpr::compile("$value + 'a'")
+            .unwrap()
+            .eval("1")
+            .is_err());
+        assert!(RuleExpr::compile("year($value)")
+            .unwrap()
+            .eval("x")
+            .is_err());
+        for bad in [
+            "$tables % 2",
+            "md5($value)",
+            "lpad($value, 4)",
+            "($value",
+            "1 2",
+        ] {
+            assert!(RuleExpr::compile(bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+#![allow(dead_code)]
+//a consistent-hash ring: each cluster owns `vnodes` points on it and a key goes to the owner of
+//the first point at or after its hash, so adding a cluster moves only the keys it takes over.
+use super::hash::HashFn;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashRing {
+    //(point, position of the cluster), sorted by point.
+    points: Vec<(u64, usize)>,
+    vnodes: u32,
+    hash: HashFn,
+}
+
+impl HashRing {
+    pub fn new(cluster_ids: &[String], vnodes: u32, hash: HashFn) -> HashRing {
+        let vnodes = vnodes.max(1);
+        let mut points: Vec<(u64, usize)> = cluster_ids
+            .iter()
+            .enumerate()
+            .flat_map(|(n, id)| (0..vnodes).map(move |v| (hash.hash(&format!("{}#{}", id, v)), n)))
+            .collect();
+        points.sort_unstable();
+        points.dedup_by_key(|p| p.0);
+        HashRing {
+            points,
+            vnodes,
+            hash,
+        }
+    }
+    #[inline]
+    pub fn vnodes(&self) -> u32 {
+        self.vnodes
+    }
+    #[inline]
+    pub fn hash_fn(&self) -> HashFn {
+        self.hash
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+    //the position of the cluster that owns the hash `point`.
+    pub fn locate(&self, point: u64) -> Option<usize> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let i = self.points.partition_point(|p| p.0 < point);
+        Some(self.points[i % self.points.len()].1)
+    }
+    //the position of the cluster that owns `key`.
+    #[inline]
+    pub fn locate_key(&self, key: &str) -> Option<usize> {
+        self.locate(self.hash.hash(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashRing;
+    use crate::proto::rule::hash::HashFn;
+
+    #[test]
+    fn ring_moves_few_keys() {
+        let ids = |n: usize| -> Vec<String> { (1..=n).map(|i| format!("cluster_{}", i)).collect() };
+        let keys: Vec<String> = (0..10_000).map(|k| k.to_string()).collect();
+        let four = HashRing::new(&ids(4), 160, HashFn::Murmur3);
+        let five = HashRing::new(&ids(5), 160, HashFn::Murmur3);
+
+        let mut counts = [0usize; 4];
+        for k in &keys {
+            counts[four.locate_key(k).unwrap()] += 1;
+        }
+        assert!(
+            counts.iter().all(|c| *c > 1_500 && *c < 3_500),
+            "{:?}",
+            counts
+        );
+
+        let moved = keys
+            .iter()
+            .filter(|k| four.locate_key(k) != five.locate_key(k))
+            .count();
+        //about a fifth moves to the new cluster, and nothing moves between the old ones.
+        assert!(moved > 1_000 && moved < 3_000, "{}", moved);
+        assert!(keys
+            .iter()
+            .filter(|k| four.locate_key(k) != five.locate_key(k))
+            .all(|k| five.locate_key(k) == Some(4)));
+        assert_eq!(HashRing::new(&[], 8, HashFn::Crc32).locate(1), None);
+    }
+}
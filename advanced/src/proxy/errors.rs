@@ -1,7 +1,5 @@
 use crate::frontend;
 
-pub type ProxyResult<T> = std::result::Result<T, ProxyError>;
-
 #[derive(Debug)]
 pub enum ProxyError {
     IO(std::io::Error),
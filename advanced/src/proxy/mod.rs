@@ -1,3 +1,4 @@
 pub mod server;
 pub use server::ProxyServer;
 pub mod errors;
+pub mod reload;
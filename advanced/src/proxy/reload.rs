@@ -0,0 +1,266 @@
+#![allow(dead_code)]
+//hot reload: a client conn runs with one snapshot of the config, its users, router and pool, from
+//its handshake to its end. a change of the config in its center or a SIGHUP builds a new snapshot
+//which the next conns get, the pool of the old one is closed once no conn holds it any more.
+use crate::backend::pool::P2MConnPool;
+use crate::config::{self, center, Config, ConfigCenter, ConfigShortcut, DistributedTxn, NodePool};
+use crate::filter::FilterChain;
+use crate::router::{self, Router};
+use crate::security::tenant::TenantManagerProvider;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time;
+
+pub type ReloadResult<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+//how often the config center is checked for a change.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+//how often a retired snapshot is checked for conns still holding it.
+const RETIRE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct Snapshot {
+    pub version: u64,
+    pub shortcut: ConfigShortcut,
+    pub router: Arc<Router>,
+    pub pool: Arc<P2MConnPool>,
+    pub distributed_txn: DistributedTxn,
+    pub tenants: TenantManagerProvider, //whom a login is resolved through.
+    pub filters: FilterChain,
+    pub bound_tenant: Option<String>, //the tenant of a login without `@tenant`.
+    node_pool: NodePool,
+    listen_addr: String,
+}
+
+impl Snapshot {
+    //a new router and users, the pool of `prev` is kept when the nodes and clusters are the same.
+    pub async fn build(cfg: &Config, prev: Option<&Snapshot>) -> ReloadResult<Snapshot> {
+        let router = router::build_router_with(cfg)?;
+        let shortcut = config::build_config_shortcut_with(cfg).map_err(|e| e.to_string())?;
+        let node_pool = cfg.query_node_pool();
+        let pool = match prev {
+            Some(p) if p.node_pool == node_pool && p.shortcut.same_backends(&shortcut) => {
+                p.pool.clone()
+            }
+            _ => Arc::new(P2MConnPool::build_pool_with(cfg).await?),
+        };
+        Ok(Snapshot {
+            version: prev.map(|p| p.version + 1).unwrap_or(0),
+            shortcut,
+            router,
+            pool,
+            distributed_txn: cfg.query_distributed_txn(),
+            tenants: TenantManagerProvider::build_with(cfg),
+            filters: FilterChain::build(
+                cfg.filter.as_deref().unwrap_or_default(),
+                cfg.load_sql_max_limits(),
+            )
+            .map_err(|e| e.to_string())?,
+            bound_tenant: cfg.query_proxy_tenant().map(str::to_string),
+            node_pool,
+            listen_addr: cfg.query_proxy_listen_addr().to_string(),
+        })
+    }
+    //the tenant, other than the one of `owner`, which has the db; `owner` may not use it.
+    pub fn foreign_tenant(&self, owner: &str, db: &str) -> Option<String> {
+        self.router.foreign_tenant(owner, db)
+    }
+}
+
+#[derive(Debug)]
+pub struct Reloader {
+    center: Arc<dyn ConfigCenter>,
+    current: RwLock<Arc<Snapshot>>,
+    reloading: Mutex<()>, //one reload at a time.
+}
+
+impl Reloader {
+    pub async fn new(center: Arc<dyn ConfigCenter>, cfg: &Config) -> ReloadResult<Arc<Reloader>> {
+        let snapshot = Snapshot::build(cfg, None).await?;
+        Ok(Arc::new(Reloader {
+            center,
+            current: RwLock::new(Arc::new(snapshot)),
+            reloading: Mutex::new(()),
+        }))
+    }
+    //the snapshot a new conn runs with.
+    #[inline]
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.current.read().unwrap().clone()
+    }
+    //read the config from the center again and swap in what it builds, a config which does not
+    //parse or build changes nothing. the version of the new snapshot is returned.
+    pub async fn reload(&self) -> ReloadResult<u64> {
+        let _guard = self.reloading.lock().await;
+        let center = self.center.clone();
+        let cfg = tokio::task::spawn_blocking(move || center.load()).await??;
+        let prev = self.snapshot();
+        let next = Arc::new(Snapshot::build(&cfg, Some(&prev)).await?);
+        if next.listen_addr != prev.listen_addr {
+            log::warn!(
+                "listen_addr {} is kept, a new one takes a restart",
+                prev.listen_addr
+            );
+        }
+        let version = next.version;
+        let shared_pool = Arc::ptr_eq(&next.pool, &prev.pool);
+        *self.current.write().unwrap() = next;
+        if !shared_pool {
+            tokio::spawn(retire(prev));
+        }
+        Ok(version)
+    }
+    async fn reload_and_log(&self, why: &str) {
+        match self.reload().await {
+            Ok(version) => log::info!("config reloaded on {}, version: {}", why, version),
+            Err(e) => log::warn!("config reload on {} failed, kept the old one: {}", why, e),
+        }
+    }
+    //reload when the config in the center changes, and on SIGHUP.
+    pub fn watch(self: &Arc<Self>, interval: Duration) {
+        let reloader = self.clone();
+        let mut changes = center::watch(self.center.clone(), interval);
+        tokio::spawn(async move {
+            while changes.changed().await.is_ok() {
+                reloader.reload_and_log("config change").await;
+            }
+        });
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let reloader = self.clone();
+            tokio::spawn(async move {
+                let mut hangup = match signal(SignalKind::hangup()) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        log::warn!("no config reload on SIGHUP: {}", e);
+                        return;
+                    }
+                };
+                while hangup.recv().await.is_some() {
+                    reloader.reload_and_log("SIGHUP").await;
+                }
+            });
+        }
+    }
+}
+
+//close the pool of an old snapshot after the last conn holding it ends.
+async fn retire(old: Arc<Snapshot>) {
+    while Arc::strong_count(&old) > 1 {
+        time::sleep(RETIRE_INTERVAL).await;
+    }
+    old.pool.quit().await;
+    log::info!("config version {} retired", old.version);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{Reloader, Snapshot};
+    use crate::config::center::FileCenter;
+    use crate::config::Config;
+    use crate::security::tenant::TenantManager;
+    use std::sync::Arc;
+
+    //the config of the proxy tests: one node and cluster, db1 with integer_table in two shards.
+    pub(crate) const CONFIG: &str = r#"
+[proxy]
+listen_addr = "127.0.0.1:9696"
+users = [{ user = "root", pwd = "root" }]
+
+[[node]]
+id = "mysql_1"
+listen_addr = "127.0.0.1:1"
+user = "root"
+pwd = "root"
+
+[[cluster]]
+id = "cluster_1"
+master_node_id = "mysql_1"
+
+[[schema]]
+owner = "root"
+[[schema.db]]
+db = "db1"
+cluster_ids = ["cluster_1"]
+[[schema.db.table]]
+table = "integer_table"
+shard_key = "id"
+shard_type = "integer"
+each_cluster_table_split_count = [2]
+"#;
+
+    //the config with its node at `node`, such as a stub backend.
+    pub(crate) fn config(node: &str) -> Config {
+        toml::from_str(&CONFIG.replace("127.0.0.1:1", node)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn reload_swaps_snapshots() {
+        let path = std::env::temp_dir().join(format!("reload_{}.toml", std::process::id()));
+        let path_str = path.to_str().unwrap().to_string();
+        std::fs::write(&path, CONFIG).unwrap();
+        let cfg: Config = toml::from_str(CONFIG).unwrap();
+        let center = Arc::new(FileCenter::new(path_str));
+        let reloader = Reloader::new(center, &cfg).await.unwrap();
+        let old = reloader.snapshot();
+        assert!(old
+            .tenants
+            .get_user(String::new(), "bob".to_string())
+            .is_none());
+
+        //a new user keeps the pool, a conn still holds the old snapshot.
+        let added = CONFIG.replace(
+            r#"users = [{ user = "root", pwd = "root" }]"#,
+            r#"users = [{ user = "root", pwd = "root" }, { user = "bob", pwd = "bob" }]"#,
+        );
+        std::fs::write(&path, &added).unwrap();
+        assert_eq!(reloader.reload().await.unwrap(), 1);
+        let new = reloader.snapshot();
+        assert!(new
+            .tenants
+            .get_user(String::new(), "bob".to_string())
+            .is_some());
+        assert!(old
+            .tenants
+            .get_user(String::new(), "bob".to_string())
+            .is_none());
+        assert!(Arc::ptr_eq(&old.pool, &new.pool));
+
+        //a rule which does not build changes nothing.
+        std::fs::write(&path, added.replace("\"integer\"", "\"unknown\"")).unwrap();
+        assert!(reloader.reload().await.is_err());
+        assert_eq!(reloader.snapshot().version, 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tenant_isolation() {
+        let tenants = CONFIG
+            .replace(
+                r#"users = [{ user = "root", pwd = "root" }]"#,
+                r#"tenant = "t1"
+users = [{ user = "root", pwd = "root", tenant = "t1" }, { user = "root", pwd = "other", tenant = "t2" }]"#,
+            )
+            .replace(r#"owner = "root""#, r#"owner = "root@t1""#);
+        let cfg: Config = toml::from_str(&tenants).unwrap();
+        assert!(cfg.validate().is_empty(), "{:?}", cfg.validate());
+        let snapshot = Snapshot::build(&cfg, None).await.unwrap();
+        let user = |t: &str| snapshot.tenants.get_user(t.to_string(), "root".to_string());
+        assert_eq!(user("t2").map(|u| u.password), Some("other".to_string()));
+        assert!(user("").is_none());
+        assert_eq!(snapshot.bound_tenant.as_deref(), Some("t1"));
+        assert_eq!(
+            snapshot.tenants.get_clusters("t1".to_string()),
+            Some(vec!["cluster_1".to_string()])
+        );
+        assert_eq!(snapshot.foreign_tenant("root@t1", "db1"), None);
+        assert_eq!(
+            snapshot.foreign_tenant("root@t2", "db1"),
+            Some("t1".to_string())
+        );
+        assert_eq!(snapshot.foreign_tenant("root@t2", "nodb"), None);
+        snapshot.pool.quit().await;
+    }
+}
@@ -1,60 +1,41 @@
-use crate::mysql::{errcode, packet, utils};
-use crate::proxy::errors::{ProxyError, ProxyResult};
-use crate::{frontend, router};
+use crate::config::ConfigCenter;
+use crate::frontend::executor::ShardingExecutor;
+use crate::mysql::constants;
+use crate::mysql::server::{Listener, ServerConfig};
+use crate::proxy::errors::ProxyError;
+use crate::proxy::reload::{Reloader, WATCH_INTERVAL};
+use crate::server::server::Server;
 use std::error::Error;
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
 
 #[derive(Debug)]
-pub struct ProxyServer {}
+pub struct ProxyServer {
+    center: Arc<dyn ConfigCenter>, //watched for hot reload.
+}
 
 impl ProxyServer {
-    pub fn new() -> ProxyServer {
-        ProxyServer {}
+    pub fn new(center: Arc<dyn ConfigCenter>) -> ProxyServer {
+        ProxyServer { center }
     }
 
     pub async fn run(&self) -> Result<(), Box<dyn Error>> {
         log::info!("Run sharding proxy server...");
-        let shard_r = router::build_router()?;
-        log::info!("Shard router module init ok! {:#?}", &shard_r);
-        let listen_address = crate::GLOBAL_CONFIG.query_proxy_listen_addr();
-        let listener = TcpListener::bind(listen_address).await?;
-        loop {
-            match listener.accept().await {
-                Ok((stream, _)) => {
-                    let client_router = shard_r.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = process(stream, utils::generate_id(), client_router).await {
-                            println!("Fail to process connection; error = {}", e);
-                        }
-                    });
-                }
-                Err(e) => println!("Accepting socket stream error; error = {:?}", e),
-            }
-        }
-    }
-}
-
-async fn process<'a>(
-    stream: TcpStream,
-    id: u32,
-    router: Arc<router::Router<'a>>,
-) -> ProxyResult<()> {
-    log::info!(
-        "Server listener: {}, Accepted from: {}, MySQL thread id: {}",
-        stream.local_addr()?,
-        stream.peer_addr()?,
-        id
-    );
-
-    let mut c2p = frontend::conn::C2PConn::build_c2p_conn(stream, id, router).await?;
-    if let Err(e) = c2p.s2c_handshake().await {
-        let err_p = packet::ErrPacket::new(errcode::ER_HANDSHAKE_ERROR, format!("{:?}", e));
-        return c2p
-            .write_err(err_p)
+        let reloader = Reloader::new(self.center.clone(), &crate::GLOBAL_CONFIG)
             .await
-            .map_err(|e| ProxyError::Other(Box::new(e)));
+            .map_err(ProxyError::Other)?;
+        log::info!(
+            "Shard router module and backend conn pool init ok! {:#?}",
+            &reloader.snapshot().router
+        );
+        reloader.watch(WATCH_INTERVAL);
+        let listener = Listener::with(
+            ShardingExecutor::new(reloader),
+            ServerConfig {
+                server_version: constants::SERVER_VERSION.to_string(),
+                address: crate::GLOBAL_CONFIG.query_proxy_listen_addr().to_string(),
+            },
+        );
+        Server::new(vec![Box::new(listener)]).start().await?;
+        Ok(())
     }
-    c2p.run_loop().await;
-    Ok(())
 }
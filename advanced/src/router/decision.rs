@@ -1,73 +1,152 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 use super::error::RouterError;
-use std::collections::hash_map::DefaultHasher;
+use crate::config::Config;
+use crate::config::TableSectionConfig;
+use crate::proto::rule::error::RuleError;
+use crate::proto::rule::expr::RuleExpr;
+use crate::proto::rule::hash::HashFn;
+use crate::proto::rule::ring::HashRing;
+use crate::proto::rule::Pattern;
+use crate::security::tenant::split_owner;
 use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
 use std::ops::Range;
 use std::result::Result;
 use std::sync::Arc;
 
 #[derive(Debug)]
-struct SchemaEntry<'a> {
+struct SchemaEntry {
     owner: String,
     //key :db name
-    db_entries: HashMap<&'a str, DBSectionEntry<'a>>,
+    db_entries: HashMap<String, DBSectionEntry>,
 }
 
 #[derive(Debug, Clone)]
-pub struct DBSectionEntry<'a> {
+pub struct DBSectionEntry {
     db: String,
     cluster_ids: Vec<String>,
-    tables: HashMap<&'a str, TableSectionEntry>,
+    tables: HashMap<String, TableSectionEntry>,
 }
 #[derive(Debug, Clone)]
 pub struct TableSectionEntry {
     table: String,
     shard_key: String,
-    shard_type: ShardType,
+    cluster_rule: RuleExpr, //shard value -> cluster position, with $clusters.
+    table_rule: RuleExpr,   //shard value -> table suffix in its cluster, with $clusters, $tables.
     cluster_pairs: Vec<(String, u16)>, //Vec<(cluster_id , table_split_count)>
     integer_range: Vec<Range<u128>>,
+    ring: Option<HashRing>, //consistent_hash: the cluster rule gives a point on the ring.
+    table_names: Option<Pattern>, //the physical tables numbered across the clusters in order.
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-enum ShardType {
-    IntegerRange,
-    Integer,
-    Hash,
-}
-
+//owns all it was built from, so that a reload can build a new one beside it.
 #[derive(Debug)]
-pub struct Router<'a> {
+pub struct Router {
     //key: proxy user , value: schema of the proxy.
-    schema_map: HashMap<&'a str, SchemaEntry<'a>>,
+    schema_map: HashMap<String, SchemaEntry>,
 }
-impl<'a> Router<'a> {
+impl Router {
     //proxy user , db name
     #[inline]
-    pub fn lookup_db(&self, user: &str, db: &str) -> Result<&'a DBSectionEntry, RouterError> {
+    pub fn lookup_db(&self, user: &str, db: &str) -> Result<&DBSectionEntry, RouterError> {
         self.schema_map
             .get(user)
-            .ok_or_else(|| RouterError::LookupErrSchemaNotExit)
+            .ok_or(RouterError::LookupErrSchemaNotExit)
             .and_then(|schema| {
                 schema
                     .db_entries
                     .get(db)
-                    .ok_or_else(|| RouterError::LookupErrDBNotExist)
+                    .ok_or(RouterError::LookupErrDBNotExist)
             })
     }
+    //the db name sorted first of the proxy user, used when no db is selected.
+    #[inline]
+    pub fn lookup_default_db(&self, user: &str) -> Result<&str, RouterError> {
+        self.schema_map
+            .get(user)
+            .ok_or(RouterError::LookupErrSchemaNotExit)
+            .and_then(|schema| {
+                schema
+                    .db_entries
+                    .keys()
+                    .min()
+                    .map(String::as_str)
+                    .ok_or(RouterError::LookupErrDBNotExist)
+            })
+    }
+    //the proxy users which have a db of the name, sorted.
+    pub fn list_owners_of_db(&self, db: &str) -> Vec<&str> {
+        let mut v: Vec<&str> = self
+            .schema_map
+            .iter()
+            .filter(|(_, schema)| schema.db_entries.contains_key(db))
+            .map(|(user, _)| user.as_str())
+            .collect();
+        v.sort_unstable();
+        v
+    }
+    //the tenant, other than the one of `owner`, which has the db; `owner` may not use it.
+    pub fn foreign_tenant(&self, owner: &str, db: &str) -> Option<String> {
+        if db.is_empty() || self.lookup_db(owner, db).is_ok() {
+            return None;
+        }
+        let (_, tenant) = split_owner(owner);
+        self.list_owners_of_db(db)
+            .into_iter()
+            .map(|o| split_owner(o).1)
+            .find(|t| *t != tenant)
+            .map(str::to_string)
+    }
+    //the schemas are owned by more than one tenant.
+    pub fn is_multi_tenant(&self) -> bool {
+        let mut tenants = self.schema_map.keys().map(|o| split_owner(o).1);
+        let first = tenants.next();
+        tenants.any(|t| Some(t) != first)
+    }
+    //the db names of the proxy user, sorted.
+    pub fn list_dbs(&self, user: &str) -> Vec<&str> {
+        let mut v: Vec<&str> = match self.schema_map.get(user) {
+            Some(schema) => schema.db_entries.keys().map(String::as_str).collect(),
+            None => Vec::new(),
+        };
+        v.sort_unstable();
+        v
+    }
+    //every (proxy user, db name, table) of the router, sorted by them.
+    pub fn list_tables(&self) -> Vec<(&str, &str, &TableSectionEntry)> {
+        let mut v: Vec<(&str, &str, &TableSectionEntry)> = self
+            .schema_map
+            .iter()
+            .flat_map(|(user, schema)| {
+                schema.db_entries.iter().flat_map(move |(db, entry)| {
+                    entry
+                        .tables
+                        .values()
+                        .map(move |t| (user.as_str(), db.as_str(), t))
+                })
+            })
+            .collect();
+        v.sort_by(|a, b| (a.0, a.1, a.2.get_table()).cmp(&(b.0, b.1, b.2.get_table())));
+        v
+    }
 }
-impl<'a> DBSectionEntry<'a> {
+impl DBSectionEntry {
     #[inline]
     pub fn load_cluster_ids(&self) -> &Vec<String> {
         &self.cluster_ids
     }
+    //the tables of the db, sorted by name.
+    pub fn list_tables(&self) -> Vec<&TableSectionEntry> {
+        let mut v: Vec<&TableSectionEntry> = self.tables.values().collect();
+        v.sort_by(|a, b| a.table.cmp(&b.table));
+        v
+    }
     //the result: (cluster_id, table_name)
     #[inline]
     pub fn lookup_table(&self, table: &str) -> Result<&TableSectionEntry, RouterError> {
         self.tables
             .get(table)
-            .ok_or_else(|| RouterError::LookupErrTableNotExist)
+            .ok_or(RouterError::LookupErrTableNotExist)
     }
 }
 impl TableSectionEntry {
@@ -75,6 +154,39 @@ impl TableSectionEntry {
     pub fn get_shard_key(&self) -> &str {
         &self.shard_key
     }
+    #[inline]
+    pub fn get_table(&self) -> &str {
+        &self.table
+    }
+    //the same shard value always lands on the same cluster and table suffix of both tables.
+    pub fn is_co_located(&self, other: &TableSectionEntry) -> bool {
+        self.cluster_rule.source() == other.cluster_rule.source()
+            && self.table_rule.source() == other.table_rule.source()
+            && self.cluster_pairs == other.cluster_pairs
+            && self.integer_range == other.integer_range
+            && self.table_rule.hash_fn() == other.table_rule.hash_fn()
+            && self.ring == other.ring
+            && self.table_names == other.table_names
+    }
+    //the physical name of the table `t_pos` of the cluster `c_pos`, none when it is not split:
+    //`{table}_{t_pos}`, or the name at its position across the clusters of table_pattern.
+    fn table_name(&self, c_pos: usize, t_pos: Option<usize>) -> String {
+        match &self.table_names {
+            Some(names) => {
+                let before: usize = self.cluster_pairs[..c_pos]
+                    .iter()
+                    .map(|p| p.1.max(1) as usize)
+                    .sum();
+                names
+                    .name(before + t_pos.unwrap_or(0))
+                    .unwrap_or_else(|| self.table.clone())
+            }
+            None => match t_pos {
+                Some(t_pos) => format!("{}_{}", self.table, t_pos),
+                None => self.table.clone(),
+            },
+        }
+    }
     //the result: (cluster_id, table_name ) list.
     #[inline]
     pub fn load_all_path(&self) -> Result<Vec<(&str, String)>, RouterError> {
@@ -82,95 +194,203 @@ impl TableSectionEntry {
             return Err(RouterError::LookupErrClusterPairsEmpty);
         }
         let mut v: Vec<(&str, String)> = Vec::new();
-        for pair in self.cluster_pairs.iter() {
+        for (c_pos, pair) in self.cluster_pairs.iter().enumerate() {
             if pair.1 > 1 {
                 for pos in 0..pair.1 {
-                    let table_final_name = format!("{}_{}", self.table, pos);
-                    v.push((&pair.0, table_final_name));
+                    v.push((&pair.0, self.table_name(c_pos, Some(pos as usize))));
                 }
             } else {
-                v.push((&pair.0, self.table.clone()));
+                v.push((&pair.0, self.table_name(c_pos, None)));
             }
         }
         Ok(v)
     }
-    //the result: (cluster_id, table_name)
-    #[inline]
-    pub fn lookup_one_path(&self, shard_val: &str) -> Result<(&str, String), RouterError> {
-        if shard_val.trim().is_empty() {
-            return Err(RouterError::LookupErrShardValueEmpty);
+    //the (cluster_id, table_name) list which may hold the shard values in [low, high].
+    //integer_range prunes the clusters, a short range is looked up value by value.
+    pub fn lookup_range_paths(
+        &self,
+        low: u128,
+        high: u128,
+    ) -> Result<Vec<(&str, String)>, RouterError> {
+        if self.cluster_pairs.is_empty() {
+            return Err(RouterError::LookupErrClusterPairsEmpty);
         }
-        match self.shard_type {
-            ShardType::Hash => {
-                let cluster_sum = self.cluster_pairs.len() as u64;
-                if cluster_sum > 0 {
-                    let mut s = DefaultHasher::new();
-                    shard_val.hash(&mut s);
-                    let shard_hash = s.finish(); //u64
-                    let cluster_idx = (shard_hash % cluster_sum) as usize;
-                    //table split count
-                    let tsc = self.cluster_pairs[cluster_idx].1 as u64;
-                    if tsc > 1 {
-                        let table_idx = shard_hash % tsc;
-                        let table_final_name = format!("{}_{}", self.table, table_idx);
-                        return Ok((&self.cluster_pairs[cluster_idx].0, table_final_name));
-                    } else {
-                        return Ok((&self.cluster_pairs[cluster_idx].0, self.table.clone()));
-                    }
+        let mut v: Vec<(&str, String)> = Vec::new();
+        if low > high {
+            return Ok(v);
+        }
+        if !self.integer_range.is_empty() {
+            for (pos, r) in self.integer_range.iter().enumerate() {
+                if r.is_empty() || high < r.start || low >= r.end {
+                    continue;
                 }
-                Err(RouterError::LookupErrClusterPairsEmpty)
-            }
-            ShardType::Integer => {
-                let cluster_sum = self.cluster_pairs.len() as u128;
-                if cluster_sum > 0 {
-                    let shard_u128 = u128::from_str_radix(shard_val, 10).map_err(|e| {
-                        RouterError::LookupErrShardValueILL(format!("illegal integer: {:?}", e))
-                    })?;
-                    let cluster_idx = (shard_u128 % cluster_sum) as usize;
-                    let tsc = self.cluster_pairs[cluster_idx].1 as u128;
-                    if tsc > 1 {
-                        let table_idx = shard_u128 % tsc;
-                        let table_final_name = format!("{}_{}", self.table, table_idx);
-                        return Ok((&self.cluster_pairs[cluster_idx].0, table_final_name));
-                    } else {
-                        return Ok((&self.cluster_pairs[cluster_idx].0, self.table.clone()));
+                let lo = std::cmp::max(low, r.start);
+                let hi = std::cmp::min(high, r.end - 1);
+                let (c_id, tsc) = (
+                    &self.cluster_pairs[pos].0,
+                    self.cluster_pairs[pos].1 as u128,
+                );
+                if tsc <= 1 {
+                    v.push((c_id, self.table_name(pos, None)));
+                } else if hi - lo >= tsc - 1 {
+                    for idx in 0..tsc {
+                        v.push((c_id, self.table_name(pos, Some(idx as usize))));
                     }
-                }
-                Err(RouterError::LookupErrClusterPairsEmpty)
-            }
-            ShardType::IntegerRange => {
-                let cluster_sum = self.cluster_pairs.len() as u128;
-                if cluster_sum > 0 {
-                    let shard_u128 = u128::from_str_radix(shard_val, 10).map_err(|e| {
-                        RouterError::LookupErrShardValueILL(format!("illegal integer: {:?}", e))
-                    })?;
-                    for (pos, r) in self.integer_range.iter().enumerate() {
-                        if r.contains(&shard_u128) {
-                            let tsc = self.cluster_pairs[pos].1 as u128;
-                            if tsc > 1 {
-                                let table_idx = shard_u128 % tsc;
-                                let table_final_name = format!("{}_{}", self.table, table_idx);
-                                return Ok((&self.cluster_pairs[pos].0, table_final_name));
-                            } else {
-                                return Ok((&self.cluster_pairs[pos].0, self.table.clone()));
-                            }
+                } else {
+                    for x in lo..=hi {
+                        let path = self.lookup_one_path(&x.to_string())?;
+                        if !v.contains(&path) {
+                            v.push(path);
                         }
                     }
-                    return Err(RouterError::LookupErrNotInIntegerRange(format!(
-                        "{:?} not in integer range",
-                        shard_val
-                    )));
                 }
-                Err(RouterError::LookupErrClusterPairsEmpty)
             }
+        } else {
+            let table_sum: u128 = self.cluster_pairs.iter().map(|p| p.1.max(1) as u128).sum();
+            if high - low >= table_sum - 1 {
+                return self.load_all_path();
+            }
+            for x in low..=high {
+                //a rule which can not take an integer may send it anywhere.
+                let path = match self.lookup_one_path(&x.to_string()) {
+                    Ok(path) => path,
+                    Err(_) => return self.load_all_path(),
+                };
+                if !v.contains(&path) {
+                    v.push(path);
+                }
+            }
+        }
+        Ok(v)
+    }
+    //the result: (cluster_id, table_name)
+    #[inline]
+    pub fn lookup_one_path(&self, shard_val: &str) -> Result<(&str, String), RouterError> {
+        if shard_val.trim().is_empty() {
+            return Err(RouterError::LookupErrShardValueEmpty);
         }
+        let cluster_sum = self.cluster_pairs.len();
+        if cluster_sum == 0 {
+            return Err(RouterError::LookupErrClusterPairsEmpty);
+        }
+        let ill = |e: RuleError| RouterError::LookupErrShardValueILL(e.to_string());
+        let c_pos = self
+            .cluster_rule
+            .eval_int(shard_val, &[cluster_sum as i128])
+            .map_err(ill)?;
+        let pos = match &self.ring {
+            Some(ring) => ring.locate(c_pos as u64),
+            None => usize::try_from(c_pos).ok(),
+        };
+        let (c_pos, (c_id, tsc)) = pos
+            .and_then(|pos| self.cluster_pairs.get(pos).map(|pair| (pos, pair)))
+            .ok_or_else(|| {
+                RouterError::LookupErrShardValueILL(format!(
+                    "cluster position {} of {:?} is out of {} clusters",
+                    c_pos, shard_val, cluster_sum
+                ))
+            })?;
+        if *tsc <= 1 {
+            return Ok((c_id, self.table_name(c_pos, None)));
+        }
+        let t_pos = self
+            .table_rule
+            .eval_int(shard_val, &[cluster_sum as i128, *tsc as i128])
+            .map_err(ill)?;
+        if t_pos < 0 || t_pos >= *tsc as i128 {
+            return Err(RouterError::LookupErrShardValueILL(format!(
+                "table suffix {} of {:?} is out of {} tables",
+                t_pos, shard_val, tsc
+            )));
+        }
+        Ok((c_id, self.table_name(c_pos, Some(t_pos as usize))))
     }
 }
+//the rules of a table: the ones of its shard type, which cluster_expr and table_expr override,
+//with `hash()` the hash function of the table, and the ring of consistent_hash.
+fn compile_rules(
+    table_sec: &TableSectionConfig,
+    cluster_ids: &[String],
+    integer_range: &[Range<u128>],
+) -> Result<(RuleExpr, RuleExpr, Option<HashRing>), RouterError> {
+    let shard_type = table_sec.shard_type.as_deref().map(str::trim);
+    let (cluster_expr, table_expr) = match shard_type {
+        Some("hash") => (
+            "hash($value) % $clusters".to_string(),
+            "hash($value) % $tables",
+        ),
+        Some("consistent_hash") => ("hash($value)".to_string(), "hash($value) % $tables"),
+        Some("integer") => ("$value % $clusters".to_string(), "$value % $tables"),
+        Some("integer_range") => {
+            let bounds: Vec<String> = integer_range
+                .iter()
+                .map(|r| format!("{}, {}", r.start, r.end))
+                .collect();
+            (
+                format!("range($value, {})", bounds.join(", ")),
+                "$value % $tables",
+            )
+        }
+        None if table_sec.cluster_expr.is_some() => (String::new(), "$value % $tables"),
+        _ => {
+            return Err(RouterError::ShardSchemaParameterILL(
+                "wrong shard type in TableSectionConfig".to_string(),
+            ))
+        }
+    };
+    let hash = match table_sec.hash.as_deref() {
+        Some(name) => Some(HashFn::parse(name).ok_or_else(|| {
+            RouterError::ShardSchemaParameterILL(format!(
+                "unknown hash {:?} in TableSectionConfig of {}",
+                name, table_sec.table
+            ))
+        })?),
+        None => None,
+    };
+    let cluster_expr = table_sec.cluster_expr.clone().unwrap_or(cluster_expr);
+    let table_expr = table_sec.table_expr.as_deref().unwrap_or(table_expr);
+    let ill = |e: RuleError| {
+        RouterError::ShardSchemaParameterILL(format!(
+            "{} in TableSectionConfig of {}",
+            e, table_sec.table
+        ))
+    };
+    let mut cluster_rule = RuleExpr::compile_with(&cluster_expr, &["clusters"]).map_err(ill)?;
+    let mut table_rule =
+        RuleExpr::compile_with(table_expr, &["clusters", "tables"]).map_err(ill)?;
+    //a default hash could change where the rows are, so a table hashing must name its own.
+    let ring = shard_type == Some("consistent_hash");
+    let hash = match hash {
+        Some(hash) => hash,
+        None if ring || cluster_rule.uses_hash() || table_rule.uses_hash() => {
+            return Err(ill(RuleError::HashNotNamed(table_sec.table.clone())))
+        }
+        None => return Ok((cluster_rule, table_rule, None)),
+    };
+    if hash == HashFn::Std {
+        log::warn!(
+            "table {} hashes with the std hasher, which may change with the toolchain, set hash = \"murmur3\" or another stable one",
+            table_sec.table
+        );
+    }
+    cluster_rule = cluster_rule.with_hash(hash);
+    table_rule = table_rule.with_hash(hash);
+    let ring = ring.then(|| {
+        HashRing::new(
+            cluster_ids,
+            table_sec.vnodes.unwrap_or(DEFAULT_VNODES),
+            hash,
+        )
+    });
+    Ok((cluster_rule, table_rule, ring))
+}
+const DEFAULT_VNODES: u32 = 160;
+
 //not allow panic, just return Error
-pub fn build_router<'a>() -> Result<Arc<Router<'a>>, RouterError> {
-    let mut schema_map: HashMap<&'a str, SchemaEntry<'a>> = HashMap::new();
-    for schema in crate::GLOBAL_CONFIG.schema.iter() {
-        let mut db_entries: HashMap<&'a str, DBSectionEntry<'a>> = HashMap::new();
+pub fn build_router_with(cfg: &Config) -> Result<Arc<Router>, RouterError> {
+    let mut schema_map: HashMap<String, SchemaEntry> = HashMap::new();
+    for schema in cfg.schema.iter() {
+        let mut db_entries: HashMap<String, DBSectionEntry> = HashMap::new();
         for db in schema.db.iter() {
             let db_name = if !db.db.trim().is_empty() {
                 db.db.trim()
@@ -193,112 +413,136 @@ pub fn build_router<'a>() -> Result<Arc<Router<'a>>, RouterError> {
                     "zero len table list  in TableSectionConfig".to_string(),
                 ));
             }
-            let mut table_map: HashMap<&'a str, TableSectionEntry> = HashMap::new();
+            let mut table_map: HashMap<String, TableSectionEntry> = HashMap::new();
             for table_sec in db.table.iter() {
-                //1. create TableSectionEntry
-                let table_name = if !table_sec.table.trim().is_empty() {
-                    table_sec.table.trim()
-                } else {
-                    return Err(RouterError::ShardSchemaParameterILL(
-                        "table name is empty in TableSectionConfig".to_string(),
-                    ));
-                };
-                //---
-                let shard_key = if !table_sec.shard_key.trim().is_empty() {
-                    table_sec.shard_key.trim()
-                } else {
-                    return Err(RouterError::ShardSchemaParameterILL(
-                        "shard key is empty in TableSectionConfig".to_string(),
-                    ));
-                };
-
-                let shard_type: ShardType = match table_sec.shard_type.trim() {
-                    "hash" => ShardType::Hash,
-                    "integer_range" => ShardType::IntegerRange,
-                    "integer" => ShardType::Integer,
-                    _ => {
-                        return Err(RouterError::ShardSchemaParameterILL(
-                            "wrong shard type in TableSectionConfig".to_string(),
-                        ))
-                    }
-                };
-
-                let integer_range: Vec<Range<u128>> = if shard_type == ShardType::IntegerRange {
-                    let ir = table_sec.integer_range.as_ref().ok_or_else(|| {
-                        RouterError::ShardSchemaParameterILL(
-                            "no integer_range  in TableSectionConfig".to_string(),
-                        )
-                    })?;
-                    if ir.is_empty() {
-                        return Err(RouterError::ShardSchemaParameterILL(
-                            " integer_range is empty in TableSectionConfig".to_string(),
-                        ));
-                    }
-                    let mut v: Vec<Range<u128>> = Vec::new();
-                    let range_sum = ir.len() / 2;
-                    let cluster_sum = cluster_ids.len();
-                    if range_sum != cluster_sum {
-                        return Err(RouterError::ShardSchemaParameterILL(
-                            "(integer_range.len() / 2) != cluster_ids.len()  in TableSectionConfig"
-                                .to_string(),
-                        ));
-                    }
-                    let mut parsed_fail = false;
-                    for pair in ir.chunks(2) {
-                        let start = u128::from_str_radix(&pair[0], 10).unwrap_or_else(|_| {
-                            parsed_fail = true;
-                            0
-                        });
-                        let end = u128::from_str_radix(&pair[1], 10).unwrap_or_else(|_| {
-                            parsed_fail = true;
-                            0
-                        });
-                        if parsed_fail {
-                            return Err(RouterError::ShardSchemaParameterILL(
-                                "Illegal integer in integer_range in TableSectionConfig"
-                                    .to_string(),
-                            ));
-                        }
-                        if start > end {
-                            return Err(RouterError::ShardSchemaParameterILL(
-                                "Wrong case : start > end in integer_range".to_string(),
-                            ));
-                        }
-                        v.push(Range { start, end });
-                    }
-                    v
-                } else {
-                    Vec::new()
-                };
-                let cluster_pairs: Vec<(String, u16)> = {
-                    if table_sec.each_cluster_table_split_count.len() != cluster_ids.len() {
-                        return Err(RouterError::ShardSchemaParameterILL("Wrong case : each_cluster_table_split_count.len() != cluster_ids.len() in TableSectionConfig".to_string()));
-                    }
-                    let mut cparis: Vec<(String, u16)> = Vec::new();
-                    for (pos, val) in table_sec.each_cluster_table_split_count.iter().enumerate() {
-                        cparis.push((cluster_ids[pos].to_string(), *val));
-                    }
-                    cparis
-                };
-                //2. insert it into tables hashmap
-                table_map.entry(table_name).or_insert(TableSectionEntry {
-                    table: table_name.to_string(),
-                    shard_key: shard_key.to_string(),
-                    shard_type,
-                    cluster_pairs,
-                    integer_range,
-                });
+                let entry = build_table(table_sec, cluster_ids)?;
+                table_map.entry(entry.table.clone()).or_insert(entry);
             }
-            db_entries.entry(db_name).or_insert(DBSectionEntry {
-                db: db_name.to_string(),
-                cluster_ids: cluster_ids.to_vec(),
-                tables: table_map,
-            });
+            db_entries
+                .entry(db_name.to_string())
+                .or_insert(DBSectionEntry {
+                    db: db_name.to_string(),
+                    cluster_ids: cluster_ids.to_vec(),
+                    tables: table_map,
+                });
         }
-        schema_map.entry(&schema.owner).or_insert(SchemaEntry {
-            owner: schema.owner.clone(),
-            db_entries,
-        });
+        schema_map
+            .entry(schema.owner.clone())
+            .or_insert(SchemaEntry {
+                owner: schema.owner.clone(),
+                db_entries,
+            });
     }
     Ok(Arc::new(Router { schema_map }))
 }
+
+//the entry of one table section over the clusters of its db section.
+pub fn build_table(
+    table_sec: &TableSectionConfig,
+    cluster_ids: &[String],
+) -> Result<TableSectionEntry, RouterError> {
+    let table_name = if !table_sec.table.trim().is_empty() {
+        table_sec.table.trim()
+    } else {
+        return Err(RouterError::ShardSchemaParameterILL(
+            "table name is empty in TableSectionConfig".to_string(),
+        ));
+    };
+    //---
+    let shard_key = if !table_sec.shard_key.trim().is_empty() {
+        table_sec.shard_key.trim()
+    } else {
+        return Err(RouterError::ShardSchemaParameterILL(
+            "shard key is empty in TableSectionConfig".to_string(),
+        ));
+    };
+
+    let is_range = table_sec.shard_type.as_deref().map(str::trim) == Some("integer_range");
+    let integer_range: Vec<Range<u128>> = if is_range {
+        let ir = table_sec.integer_range.as_ref().ok_or_else(|| {
+            RouterError::ShardSchemaParameterILL(
+                "no integer_range  in TableSectionConfig".to_string(),
+            )
+        })?;
+        if ir.is_empty() {
+            return Err(RouterError::ShardSchemaParameterILL(
+                " integer_range is empty in TableSectionConfig".to_string(),
+            ));
+        }
+        let mut v: Vec<Range<u128>> = Vec::new();
+        let range_sum = ir.len() / 2;
+        let cluster_sum = cluster_ids.len();
+        if range_sum != cluster_sum {
+            return Err(RouterError::ShardSchemaParameterILL(
+                "(integer_range.len() / 2) != cluster_ids.len()  in TableSectionConfig".to_string(),
+            ));
+        }
+        let mut parsed_fail = false;
+        for pair in ir.chunks(2) {
+            let start = pair[0].parse::<u128>().unwrap_or_else(|_| {
+                parsed_fail = true;
+                0
+            });
+            let end = pair[1].parse::<u128>().unwrap_or_else(|_| {
+                parsed_fail = true;
+                0
+            });
+            if parsed_fail {
+                return Err(RouterError::ShardSchemaParameterILL(
+                    "Illegal integer in integer_range in TableSectionConfig".to_string(),
+                ));
+            }
+            if start > end {
+                return Err(RouterError::ShardSchemaParameterILL(
+                    "Wrong case : start > end in integer_range".to_string(),
+                ));
+            }
+            v.push(Range { start, end });
+        }
+        v
+    } else {
+        Vec::new()
+    };
+    let cluster_pairs: Vec<(String, u16)> = {
+        if table_sec.each_cluster_table_split_count.len() != cluster_ids.len() {
+            return Err(RouterError::ShardSchemaParameterILL("Wrong case : each_cluster_table_split_count.len() != cluster_ids.len() in TableSectionConfig".to_string()));
+        }
+        let mut cparis: Vec<(String, u16)> = Vec::new();
+        for (pos, val) in table_sec.each_cluster_table_split_count.iter().enumerate() {
+            cparis.push((cluster_ids[pos].to_string(), *val));
+        }
+        cparis
+    };
+    let (cluster_rule, table_rule, ring) = compile_rules(table_sec, cluster_ids, &integer_range)?;
+    let table_names = match table_sec.table_pattern.as_deref() {
+        Some(p) => {
+            let pattern = Pattern::parse(p).map_err(|e| {
+                RouterError::ShardSchemaParameterILL(format!(
+                    "{} in TableSectionConfig of {}",
+                    e, table_name
+                ))
+            })?;
+            let table_sum: usize = cluster_pairs.iter().map(|p| p.1.max(1) as usize).sum();
+            if pattern.len() != table_sum {
+                return Err(RouterError::ShardSchemaParameterILL(format!(
+                    "table_pattern names {} tables, the clusters split {} in TableSectionConfig of {}",
+                    pattern.len(),
+                    table_sum,
+                    table_name
+                )));
+            }
+            Some(pattern)
+        }
+        None => None,
+    };
+    Ok(TableSectionEntry {
+        table: table_name.to_string(),
+        shard_key: shard_key.to_string(),
+        cluster_rule,
+        table_rule,
+        cluster_pairs,
+        integer_range,
+        ring,
+        table_names,
+    })
+}
@@ -1,5 +1,5 @@
 mod decision;
 mod error;
-pub use decision::build_router;
-pub use decision::Router;
+pub use decision::{build_router_with, build_table};
+pub use decision::{Router, TableSectionEntry};
 pub use error::RouterError;
@@ -1,4 +1,3 @@
 pub mod tenant;
 
 pub use tenant::TenantManagerProvider;
-pub use tenant::Tenant;
\ No newline at end of file
@@ -1,26 +1,45 @@
 use crate::config::config_model::User;
-use mysql_common::frunk::labelled::chars::u;
-use std::borrow::{Borrow, BorrowMut};
+use crate::config::Config;
 use std::collections::HashMap;
 use std::sync::RwLock;
 
+//the tenant of the proxy users which have none.
+pub const DEFAULT_TENANT: &str = "";
+
+//the name a user of a tenant owns its schema by, `user@tenant`, or `user` for the default tenant.
+pub fn qualify(user: &str, tenant: &str) -> String {
+    if tenant == DEFAULT_TENANT {
+        user.to_string()
+    } else {
+        format!("{}@{}", user, tenant)
+    }
+}
+
+//the (user, tenant) of a schema owner.
+pub fn split_owner(owner: &str) -> (&str, &str) {
+    owner.rsplit_once('@').unwrap_or((owner, DEFAULT_TENANT))
+}
+
+//the (user, tenant) of a login, `user@tenant` or `user` of the tenant the listener is bound to.
+//a login of another tenant than that of the listener is the tenant as the error.
+pub fn split_login(login: &str, bound: Option<&str>) -> Result<(String, String), String> {
+    let (user, tenant) = split_owner(login);
+    match bound {
+        Some(b) if tenant == DEFAULT_TENANT => Ok((user.to_string(), b.to_string())),
+        Some(b) if tenant != b => Err(tenant.to_string()),
+        _ => Ok((user.to_string(), tenant.to_string())),
+    }
+}
+
 pub trait TenantManager {
-    // GetTenants returns all tenants.
-    fn get_tenants(&self) -> Vec<String>;
     // GetUser returns user by tenant and username.
     fn get_user(&self, tenant: String, username: String) -> Option<User>;
     // GetClusters returns cluster names.
     fn get_clusters(&self, tenant: String) -> Option<Vec<String>>;
-    // GetTenantOfCluster returns the tenant of cluster.
-    fn get_tenant_cluster(&self, cluster: String) -> Option<String>;
     // PutUser puts a user into tenant.
     fn put_user(&self, tenant: String, user: crate::config::config_model::User);
-    // RemoveUser removes a user from tenant.
-    fn remove_user(&self, tenant: String, username: String);
     // PutCluster puts a cluster into tenant.
     fn put_cluster(&self, tenant: String, cluster: String);
-    // RemoveCluster removes a cluster from tenant.
-    fn remove_cluster(&self, tenant: String, cluster: String);
 }
 
 #[derive(Debug, Clone)]
@@ -29,6 +48,7 @@ pub struct Tenant {
     users: HashMap<String, User>,
 }
 
+#[derive(Debug)]
 pub struct TenantManagerProvider {
     tenants: RwLock<HashMap<String, Tenant>>,
 }
@@ -39,55 +59,43 @@ impl TenantManagerProvider {
             tenants: RwLock::new(HashMap::new()),
         }
     }
-}
-
-impl TenantManager for TenantManagerProvider {
-    fn get_tenants(&self) -> Vec<String> {
-        let tenants_map = &*(self.tenants.read().unwrap());
-        let mut result: Vec<String> = Vec::new();
-        for (k, _) in tenants_map {
-            result.push(k.clone());
+    //the users of the config by their tenants, each tenant with the clusters its users' schemas
+    //are on.
+    pub fn build_with(cfg: &Config) -> TenantManagerProvider {
+        let provider = TenantManagerProvider::new();
+        for (tenant, user) in cfg.load_tenant_users() {
+            provider.put_user(tenant, user);
+        }
+        for schema in cfg.schema.iter() {
+            let (_, tenant) = split_owner(schema.owner.trim());
+            for db in schema.db.iter() {
+                for c_id in db.cluster_ids.iter() {
+                    provider.put_cluster(tenant.to_string(), c_id.clone());
+                }
+            }
         }
-        result
+        provider
     }
+}
 
+impl TenantManager for TenantManagerProvider {
     fn get_user(&self, tenant: String, username: String) -> Option<User> {
         let tenants_map = self.tenants.read().unwrap();
-        if tenants_map.contains_key(tenant.as_str()) {
-            return None;
-        }
-        let tenant = tenants_map.get(tenant.as_str()).unwrap();
-        let user = tenant.users.get(username.as_str()).unwrap();
-        Some(user.to_owned())
+        let tenant = tenants_map.get(tenant.as_str())?;
+        tenant.users.get(username.as_str()).cloned()
     }
 
     fn get_clusters(&self, tenant: String) -> Option<Vec<String>> {
         let tenants_map = self.tenants.read().unwrap();
-        if tenants_map.contains_key(tenant.as_str()) {
-            return None;
-        }
-        let tenant = tenants_map.get(tenant.as_str()).unwrap();
-        let mut result: Vec<String> = Vec::new();
-        for (k, _) in tenant.clone().cluster {
-            result.push(k);
-        }
+        let tenant = tenants_map.get(tenant.as_str())?;
+        let mut result: Vec<String> = tenant.cluster.keys().cloned().collect();
+        result.sort();
         Some(result)
     }
 
-    fn get_tenant_cluster(&self, cluster: String) -> Option<String> {
-        let tenants_map = &*self.tenants.read().unwrap();
-        for (_, tenant) in tenants_map {
-            if tenant.cluster.contains_key(cluster.as_str()) {
-                let c = tenant.cluster.get(cluster.as_str()).unwrap();
-                return Some(c.to_string());
-            }
-        }
-        None
-    }
-
     fn put_user(&self, tenant: String, user: User) {
         let mut tenants_map = self.tenants.write().unwrap();
-        let mut current = match tenants_map.get_mut(tenant.as_str()) {
+        let current = match tenants_map.get_mut(tenant.as_str()) {
             Some(t) => t,
             None => {
                 let new_tenant = Tenant {
@@ -102,18 +110,6 @@ impl TenantManager for TenantManagerProvider {
         users.insert(user.username.clone(), user);
     }
 
-    fn remove_user(&self, tenant: String, username: String) {
-        let mut tenant_map = self.tenants.write().unwrap();
-        if !tenant_map.contains_key(tenant.as_str()) {
-            return;
-        }
-        let tenant = tenant_map.get_mut(tenant.as_str()).unwrap();
-        if !tenant.users.contains_key(username.as_str()) {
-            return;
-        }
-        tenant.users.remove(username.as_str());
-    }
-
     fn put_cluster(&self, tenant: String, cluster: String) {
         let mut tenants_map = self.tenants.write().unwrap();
         let current = match tenants_map.get_mut(tenant.as_str()) {
@@ -127,19 +123,48 @@ impl TenantManager for TenantManagerProvider {
                 tenants_map.get_mut(tenant.as_str()).unwrap()
             }
         };
-        let mut clusters = &mut current.cluster;
+        let clusters = &mut current.cluster;
         clusters.insert(cluster, String::new());
     }
+}
 
-    fn remove_cluster(&self, tenant: String, cluster: String) {
-        let mut tenant_map = self.tenants.write().unwrap();
-        if !tenant_map.contains_key(tenant.as_str()) {
-            return;
-        }
-        let tenant = tenant_map.get_mut(tenant.as_str()).unwrap();
-        if !tenant.cluster.contains_key(cluster.as_str()) {
-            return;
-        }
-        tenant.cluster.remove(cluster.as_str());
+#[cfg(test)]
+mod tests {
+    use super::{qualify, split_login, TenantManager, TenantManagerProvider};
+    use crate::config::config_model::User;
+
+    #[test]
+    fn tenant_users() {
+        let provider = TenantManagerProvider::new();
+        assert!(provider
+            .get_user("t1".to_string(), "bob".to_string())
+            .is_none());
+        let bob = User {
+            username: "bob".to_string(),
+            password: "pwd".to_string(),
+        };
+        provider.put_user("t1".to_string(), bob);
+        provider.put_cluster("t1".to_string(), "c1".to_string());
+        let user = provider.get_user("t1".to_string(), "bob".to_string());
+        assert_eq!(user.map(|u| u.password), Some("pwd".to_string()));
+        assert!(provider
+            .get_user("t2".to_string(), "bob".to_string())
+            .is_none());
+        assert!(provider
+            .get_user("t1".to_string(), "eve".to_string())
+            .is_none());
+        assert_eq!(
+            provider.get_clusters("t1".to_string()),
+            Some(vec!["c1".to_string()])
+        );
+        assert_eq!(provider.get_clusters("t2".to_string()), None);
+
+        let login = |l: &str, b: Option<&str>| split_login(l, b);
+        assert_eq!(login("bob@t1", None), Ok(("bob".into(), "t1".into())));
+        assert_eq!(login("bob", Some("t1")), Ok(("bob".into(), "t1".into())));
+        assert_eq!(login("bob", None), Ok(("bob".into(), "".into())));
+        assert_eq!(login("bob@t2", Some("t1")), Err("t2".to_string()));
+        assert_eq!(qualify("bob", "t1"), "bob@t1");
+        assert_eq!(qualify("bob", ""), "bob");
     }
 }
@@ -1,27 +1,48 @@
-use std::error::Error;
-use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
+#![allow(dead_code)]
 use crate::proto::interface::Listener;
-use crate::proxy::errors::ProxyResult;
-use crate::{frontend, proxy, router};
+use std::io;
+use std::sync::Arc;
+use tokio::task::JoinSet;
 
 pub struct Server {
-    pub listeners: Vec<Box<dyn Listener>>,
+    pub listeners: Vec<Arc<dyn Listener>>,
 }
 
 impl Server {
     pub fn new(listeners: Vec<Box<dyn Listener>>) -> Self {
-        return Server { listeners };
+        Server {
+            listeners: listeners.into_iter().map(Arc::from).collect(),
+        }
     }
 
-    pub fn add_listener(mut self, listener: Box<dyn Listener>) {
-        self.listeners.push(listener);
+    pub fn add_listener(&mut self, listener: Box<dyn Listener>) {
+        self.listeners.push(Arc::from(listener));
+    }
+
+    //run every listener till they are all closed. a listener which fails, such as one whose
+    //address is taken, closes the others.
+    pub async fn start(&self) -> io::Result<()> {
+        let mut running = JoinSet::new();
+        for each in self.listeners.iter() {
+            let listener = each.clone();
+            running.spawn(async move {
+                let rc = listener.listen().await;
+                rc.map_err(|e| io::Error::new(e.kind(), format!("{}: {}", listener.address(), e)))
+            });
+        }
+        while let Some(rc) = running.join_next().await {
+            let rc = rc.map_err(io::Error::other);
+            if let Err(e) = rc.and_then(|rc| rc) {
+                self.close();
+                return Err(e);
+            }
+        }
+        Ok(())
     }
 
-    pub fn start(&self) {
-        for each in &self.listeners {
-            each.listen();
-            // proxy::ProxyServer::new().run()?;
+    pub fn close(&self) {
+        for each in self.listeners.iter() {
+            each.close();
         }
     }
 }